@@ -0,0 +1,500 @@
+use serde::Serialize;
+
+use crate::analyzer::bitstream::BitReader;
+use crate::analyzer::extract::{read_object_type, AAC_SAMPLE_RATES};
+
+/// Full AudioSpecificConfig of an AAC stream, including SBR/PS signalling (ISO/IEC 14496-3
+/// 1.6.2.1)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AacConfig {
+    /// Core audio object type
+    pub object_type: u8,
+    pub sample_rate: u32,
+    /// Output sampling rate of the SBR tool, when signalled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_sample_rate: Option<u32>,
+    pub channel_config: u8,
+    /// Spectral lines per frame: 1024, or 960 with frameLengthFlag
+    pub frame_length: u32,
+    /// SBR signalled explicitly (AOT 5/29) or by a sync extension
+    pub sbr: bool,
+    pub ps: bool,
+}
+
+impl AacConfig {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut bits = BitReader::new(data);
+        let mut object_type = read_object_type(&mut bits)?;
+        let sample_rate = read_sample_rate(&mut bits)?;
+        let channel_config = bits.read(4)? as u8;
+        let mut config = Self {
+            object_type,
+            sample_rate,
+            extension_sample_rate: None,
+            channel_config,
+            frame_length: 1024,
+            sbr: false,
+            ps: false,
+        };
+        if object_type == 5 || object_type == 29 {
+            config.sbr = true;
+            config.ps = object_type == 29;
+            config.extension_sample_rate = Some(read_sample_rate(&mut bits)?);
+            object_type = read_object_type(&mut bits)?;
+            config.object_type = object_type;
+            if object_type == 22 {
+                bits.skip(4)?; // extensionChannelConfiguration
+            }
+        }
+        if !matches!(object_type, 1..=4 | 6 | 7 | 17 | 19..=23) {
+            return Some(config);
+        }
+
+        // GASpecificConfig
+        if bits.read_flag()? {
+            config.frame_length = 960;
+        }
+        if bits.read_flag()? {
+            bits.skip(14)?; // coreCoderDelay
+        }
+        let extension_flag = bits.read_flag()?;
+        if channel_config == 0 {
+            // program_config_element 之后的同步扩展不再解析
+            return Some(config);
+        }
+        if object_type == 6 || object_type == 20 {
+            bits.skip(3)?; // layerNr
+        }
+        if extension_flag {
+            match object_type {
+                22 => bits.skip(16)?,
+                17 | 19 | 20 | 23 => bits.skip(3)?,
+                _ => {}
+            }
+            bits.skip(1)?; // extensionFlag3
+        }
+        if matches!(object_type, 17 | 19..=23) {
+            bits.skip(2)?; // epConfig
+        }
+
+        // 向后兼容的隐式信令: syncExtensionType 0x2b7 与 0x548
+        if !config.sbr
+            && data.len() * 8 >= bits.position() + 16
+            && bits.read(11) == Some(0x2b7)
+            && read_object_type(&mut bits) == Some(5)
+        {
+            config.sbr = bits.read_flag()?;
+            if config.sbr {
+                config.extension_sample_rate = Some(read_sample_rate(&mut bits)?);
+                if data.len() * 8 >= bits.position() + 12 && bits.read(11) == Some(0x548) {
+                    config.ps = bits.read_flag()?;
+                }
+            }
+        }
+        Some(config)
+    }
+
+    /// Name of the object type, with the SBR/PS extension
+    pub fn profile(&self) -> String {
+        let core = match self.object_type {
+            1 => "AAC Main",
+            2 => "AAC LC",
+            3 => "AAC SSR",
+            4 => "AAC LTP",
+            17 => "ER AAC LC",
+            19 => "ER AAC LTP",
+            23 => "ER AAC LD",
+            39 => "ER AAC ELD",
+            _ => "AAC",
+        };
+        match (self.sbr, self.ps) {
+            (_, true) => format!("{} + SBR + PS (HE-AAC v2)", core),
+            (true, false) => format!("{} + SBR (HE-AAC)", core),
+            _ => core.to_string(),
+        }
+    }
+}
+
+fn read_sample_rate(bits: &mut BitReader) -> Option<u32> {
+    match bits.read(4)? {
+        15 => bits.read(24),
+        index => AAC_SAMPLE_RATES.get(index as usize).copied(),
+    }
+}
+
+/// Fixed and variable header of an ADTS frame (ISO/IEC 13818-7 6.2)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AdtsHeader {
+    /// Audio object type (profile + 1)
+    pub object_type: u8,
+    pub sample_rate: u32,
+    pub channel_config: u8,
+    pub frame_length: u32,
+    pub raw_data_blocks: u8,
+    pub crc: bool,
+}
+
+impl AdtsHeader {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let header = data.get(..7)?;
+        if header[0] != 0xff || header[1] & 0xf6 != 0xf0 {
+            return None;
+        }
+        Some(Self {
+            object_type: (header[2] >> 6) + 1,
+            sample_rate: *AAC_SAMPLE_RATES.get(((header[2] >> 2) & 0x0f) as usize)?,
+            channel_config: (header[2] & 0x01) << 2 | header[3] >> 6,
+            frame_length: ((header[3] as u32 & 0x03) << 11)
+                | (header[4] as u32) << 3
+                | (header[5] as u32) >> 5,
+            raw_data_blocks: (header[6] & 0x03) + 1,
+            crc: header[1] & 0x01 == 0,
+        })
+    }
+
+    /// Bytes before the first raw_data_block
+    pub fn header_length(&self) -> usize {
+        // 单个原始数据块时 CRC 紧跟在头之后
+        if self.crc && self.raw_data_blocks == 1 {
+            9
+        } else {
+            7
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ElementType {
+    Sce,
+    Cpe,
+    Cce,
+    Lfe,
+    Dse,
+    Pce,
+    Fil,
+    End,
+}
+
+impl ElementType {
+    fn from_id(id: u32) -> Self {
+        match id {
+            0 => ElementType::Sce,
+            1 => ElementType::Cpe,
+            2 => ElementType::Cce,
+            3 => ElementType::Lfe,
+            4 => ElementType::Dse,
+            5 => ElementType::Pce,
+            6 => ElementType::Fil,
+            _ => ElementType::End,
+        }
+    }
+
+    /// Elements of a raw_data_block for a channel configuration (table 1.19)
+    pub fn layout(channel_config: u8) -> &'static [ElementType] {
+        use ElementType::*;
+        match channel_config {
+            1 => &[Sce],
+            2 => &[Cpe],
+            3 => &[Sce, Cpe],
+            4 => &[Sce, Cpe, Sce],
+            5 => &[Sce, Cpe, Cpe],
+            6 => &[Sce, Cpe, Cpe, Lfe],
+            7 => &[Sce, Cpe, Cpe, Cpe, Lfe],
+            _ => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for ElementType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ElementType::Sce => "SCE",
+            ElementType::Cpe => "CPE",
+            ElementType::Cce => "CCE",
+            ElementType::Lfe => "LFE",
+            ElementType::Dse => "DSE",
+            ElementType::Pce => "PCE",
+            ElementType::Fil => "FIL",
+            ElementType::End => "END",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowSequence {
+    OnlyLong,
+    LongStart,
+    EightShort,
+    LongStop,
+}
+
+impl std::fmt::Display for WindowSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WindowSequence::OnlyLong => "long",
+            WindowSequence::LongStart => "start",
+            WindowSequence::EightShort => "short",
+            WindowSequence::LongStop => "stop",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Header fields of the first audio element (SCE/CPE/LFE/CCE) of a raw_data_block
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AudioElement {
+    pub element: ElementType,
+    pub instance_tag: u8,
+    /// Global gain of the first channel; the second channel of a CPE follows the spectral
+    /// data of the first one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub global_gain: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_sequence: Option<WindowSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_shape: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sfb: Option<u8>,
+    /// CPE only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub common_window: Option<bool>,
+}
+
+/// Fill element found at the end of a raw_data_block
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FillElement {
+    pub extension: &'static str,
+    pub bytes: u32,
+}
+
+/// What can be read of a raw_data_block without decoding the spectral data: the elements
+/// before the first audio element, that element's header and a fill element before END
+#[derive(Debug, Clone, Serialize)]
+pub struct RawDataBlock {
+    /// DSE/FIL elements before the first audio element, then that element's type
+    pub leading: Vec<ElementType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioElement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_fill: Option<FillElement>,
+    /// The block ends with an END element and zero byte alignment
+    pub terminated: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+}
+
+impl RawDataBlock {
+    /// Parses a raw_data_block of a stream with the given core object type
+    pub fn parse(data: &[u8], object_type: u8) -> Self {
+        let mut block = Self {
+            leading: Vec::new(),
+            audio: None,
+            trailing_fill: None,
+            terminated: false,
+            problems: Vec::new(),
+        };
+        if object_type >= 17 {
+            // 错误恢复 (ER) 语法没有元素 ID
+            block
+                .problems
+                .push("error resilient raw data blocks are not parsed".to_string());
+            return block;
+        }
+        block.terminated = end_position(data).is_some();
+        if !block.terminated {
+            block
+                .problems
+                .push("no END element before the byte alignment".to_string());
+        } else {
+            block.trailing_fill = trailing_fill(data);
+        }
+
+        let mut r = BitReader::new(data);
+        loop {
+            let Some(id) = r.read(3) else {
+                block.problems.push("empty raw_data_block".to_string());
+                break;
+            };
+            let element = ElementType::from_id(id);
+            block.leading.push(element);
+            let skipped = match element {
+                ElementType::Dse => skip_data_stream(&mut r),
+                ElementType::Fil => skip_fill(&mut r),
+                ElementType::End => break,
+                ElementType::Pce => break,
+                _ => {
+                    match audio_element(&mut r, element) {
+                        Some(audio) => block.audio = Some(audio),
+                        None => block
+                            .problems
+                            .push(format!("truncated {} element header", element)),
+                    }
+                    break;
+                }
+            };
+            if skipped.is_none() {
+                block
+                    .problems
+                    .push(format!("truncated {} element", element));
+                break;
+            }
+        }
+        block
+    }
+}
+
+fn skip_data_stream(r: &mut BitReader) -> Option<()> {
+    r.skip(4)?; // element_instance_tag
+    let align = r.read_flag()?;
+    let mut count = r.read(8)? as usize;
+    if count == 255 {
+        count += r.read(8)? as usize;
+    }
+    if align {
+        let position = r.position();
+        r.skip((8 - position % 8) % 8)?;
+    }
+    r.skip(count * 8)
+}
+
+fn skip_fill(r: &mut BitReader) -> Option<()> {
+    let mut count = r.read(4)? as usize;
+    if count == 15 {
+        count += r.read(8)? as usize - 1;
+    }
+    r.skip(count * 8)
+}
+
+fn audio_element(r: &mut BitReader, element: ElementType) -> Option<AudioElement> {
+    let mut audio = AudioElement {
+        element,
+        instance_tag: r.read(4)? as u8,
+        global_gain: None,
+        window_sequence: None,
+        window_shape: None,
+        max_sfb: None,
+        common_window: None,
+    };
+    match element {
+        ElementType::Cpe => {
+            let common_window = r.read_flag()?;
+            audio.common_window = Some(common_window);
+            if common_window {
+                let (groups, predictor) = ics_info(r, &mut audio)?;
+                if predictor {
+                    // 预测数据 (Main/LTP) 的长度取决于 profile, 不再继续
+                    return Some(audio);
+                }
+                if r.read(2)? == 1 {
+                    r.skip(groups * audio.max_sfb.unwrap_or(0) as usize)?; // ms_used
+                }
+                audio.global_gain = Some(r.read(8)? as u8);
+            } else {
+                audio.global_gain = Some(r.read(8)? as u8);
+                ics_info(r, &mut audio)?;
+            }
+        }
+        // 耦合声道: 目标列表之后才是 individual_channel_stream
+        ElementType::Cce => {}
+        _ => {
+            audio.global_gain = Some(r.read(8)? as u8);
+            ics_info(r, &mut audio)?;
+        }
+    }
+    Some(audio)
+}
+
+/// ics_info (4.4.2.1); returns the number of window groups and predictor_data_present
+fn ics_info(r: &mut BitReader, audio: &mut AudioElement) -> Option<(usize, bool)> {
+    r.skip(1)?; // ics_reserved_bit
+    let window_sequence = match r.read(2)? {
+        0 => WindowSequence::OnlyLong,
+        1 => WindowSequence::LongStart,
+        2 => WindowSequence::EightShort,
+        _ => WindowSequence::LongStop,
+    };
+    audio.window_sequence = Some(window_sequence);
+    audio.window_shape = Some(r.read(1)? as u8);
+    if window_sequence == WindowSequence::EightShort {
+        audio.max_sfb = Some(r.read(4)? as u8);
+        let grouping = r.read(7)?;
+        Some((
+            1 + (0..7).filter(|bit| grouping & (1 << bit) == 0).count(),
+            false,
+        ))
+    } else {
+        audio.max_sfb = Some(r.read(6)? as u8);
+        Some((1, r.read_flag()?))
+    }
+}
+
+/// Bit position of the END element when the block ends with END and zero alignment bits
+fn end_position(data: &[u8]) -> Option<usize> {
+    let total = data.len() * 8;
+    (0..8).find_map(|padding| {
+        let end = total.checked_sub(3 + padding)?;
+        let mut r = BitReader::new(data);
+        r.skip(end)?;
+        (r.read(3)? == 7 && r.read(padding as u32)? == 0).then_some(end)
+    })
+}
+
+/// Looks for a fill element that ends right before the END element. Which bytes belong to
+/// it cannot be known without decoding the spectral data, so a candidate only counts when
+/// its extension payload is consistent (SBR, dynamic range or 0xa5 fill bytes).
+fn trailing_fill(data: &[u8]) -> Option<FillElement> {
+    let end = end_position(data)?;
+    for bytes in 1..=269usize {
+        // ID_FIL 与 count 共 7 位, 15 字节起多一个 esc_count 字节
+        let header = if bytes >= 15 { 15 } else { 7 };
+        let Some(start) = end.checked_sub(header + bytes * 8) else {
+            break;
+        };
+        let mut r = BitReader::new(data);
+        r.skip(start)?;
+        if r.read(3)? != 6 {
+            continue;
+        }
+        let count = r.read(4)? as usize;
+        let count = if count == 15 {
+            count + r.read(8)? as usize - 1
+        } else {
+            count
+        };
+        if count != bytes {
+            continue;
+        }
+        let payload_start = r.position();
+        let extension = match r.read(4)? {
+            0xd | 0xe => "sbr",
+            0xb => "dynamic_range",
+            0x2 => "data_element",
+            0xc => "sac",
+            kind @ (0x0 | 0x1) => {
+                // fill_nibble 为 0, 其余字节为 0xa5
+                if r.read(4)? != 0 {
+                    continue;
+                }
+                let mut fill = BitReader::new(data);
+                fill.skip(payload_start + 8)?;
+                if !(1..bytes).all(|_| fill.read(8) == Some(0xa5)) {
+                    continue;
+                }
+                if kind == 0 {
+                    "fill"
+                } else {
+                    "fill_data"
+                }
+            }
+            _ => continue,
+        };
+        return Some(FillElement {
+            extension,
+            bytes: bytes as u32,
+        });
+    }
+    None
+}
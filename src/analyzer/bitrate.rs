@@ -0,0 +1,257 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::analyzer::detector::{DetectionStrategy, FileFormat, FormatDetector};
+use crate::analyzer::isobmff;
+use crate::analyzer::rmff::RmffAnalyzer;
+use crate::error::MediaError;
+
+/// One sample (ISOBMFF) or packet (RealMedia) of a stream, in seconds and bytes
+#[derive(Debug, Clone, Copy)]
+pub struct TimedUnit {
+    pub time: f64,
+    pub duration: f64,
+    pub bytes: u64,
+}
+
+/// Bitrate at the end of one unit
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BitratePoint {
+    pub time: f64,
+    /// Size of the unit over its duration, in bit/s
+    pub instantaneous: f64,
+    /// Bitrate over the first configured window ending here, in bit/s
+    pub windowed: f64,
+}
+
+/// Highest bitrate over any window of `window` seconds
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PeakBitrate {
+    pub window: f64,
+    pub bitrate: f64,
+    /// End of the window with the peak
+    pub at: f64,
+}
+
+/// Bit rates a container declares for a stream
+#[derive(Debug, Clone, Serialize)]
+pub struct DeclaredBitrate {
+    /// `btrt`, `esds` or `MDPR`
+    pub source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<u32>,
+}
+
+/// Bitrate over time of one track or RealMedia stream
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackBitrate {
+    /// Track ID, or the stream number of a RealMedia stream
+    pub track_id: u32,
+    pub codec: String,
+    pub units: u64,
+    pub bytes: u64,
+    pub duration: f64,
+    pub average: f64,
+    pub peaks: Vec<PeakBitrate>,
+    pub declared: Vec<DeclaredBitrate>,
+    pub series: Vec<BitratePoint>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BitrateReport {
+    pub format: String,
+    /// Window lengths of the peaks, in seconds; the first one is used for the series
+    pub windows: Vec<f64>,
+    pub tracks: Vec<TrackBitrate>,
+}
+
+impl TrackBitrate {
+    /// Computes the series and peaks of `units` and compares them with the declared rates:
+    /// the 1-second peak against a declared maximum, the average against a declared average
+    pub fn new(
+        track_id: u32,
+        codec: String,
+        mut units: Vec<TimedUnit>,
+        windows: &[f64],
+        declared: Vec<DeclaredBitrate>,
+    ) -> Self {
+        units.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let bytes: u64 = units.iter().map(|u| u.bytes).sum();
+        let duration = match (units.first(), units.last()) {
+            (Some(first), Some(last)) => (last.time + last.duration - first.time).max(0.0),
+            _ => 0.0,
+        };
+        let average = if duration > 0.0 {
+            bytes as f64 * 8.0 / duration
+        } else {
+            0.0
+        };
+
+        let series_window = windows.first().copied().unwrap_or(1.0);
+        let windowed = sliding(&units, series_window);
+        let series = units
+            .iter()
+            .zip(&windowed)
+            .map(|(unit, &windowed)| BitratePoint {
+                time: unit.time + unit.duration,
+                instantaneous: if unit.duration > 0.0 {
+                    unit.bytes as f64 * 8.0 / unit.duration
+                } else {
+                    0.0
+                },
+                windowed,
+            })
+            .collect();
+        let peaks: Vec<PeakBitrate> = windows.iter().map(|&w| peak(&units, w)).collect();
+
+        let mut warnings = Vec::new();
+        // btrt/esds 的 maxBitrate 按任意 1 秒窗口定义
+        let one_second = peaks
+            .iter()
+            .find(|p| p.window == 1.0)
+            .copied()
+            .unwrap_or_else(|| peak(&units, 1.0));
+        for declared in &declared {
+            if let Some(max) = declared.max.filter(|&max| max > 0) {
+                if one_second.bitrate > max as f64 * 1.01 {
+                    warnings.push(format!(
+                        "1 s peak of {:.0} kbit/s at {:.3} s exceeds the {} maximum of {:.0} kbit/s",
+                        one_second.bitrate / 1000.0,
+                        one_second.at,
+                        declared.source,
+                        max as f64 / 1000.0
+                    ));
+                }
+            }
+            if let Some(declared_average) = declared.average.filter(|&avg| avg > 0) {
+                let deviation = (average - declared_average as f64) / declared_average as f64;
+                if deviation.abs() > 0.1 {
+                    warnings.push(format!(
+                        "average of {:.0} kbit/s differs from the {} average of {:.0} kbit/s by {:+.0}%",
+                        average / 1000.0,
+                        declared.source,
+                        declared_average as f64 / 1000.0,
+                        deviation * 100.0
+                    ));
+                }
+            }
+        }
+
+        Self {
+            track_id,
+            codec,
+            units: units.len() as u64,
+            bytes,
+            duration,
+            average,
+            peaks,
+            declared,
+            series,
+            warnings,
+        }
+    }
+}
+
+/// Bytes of the units that start within the `window` seconds ending with each unit
+fn window_bytes(units: &[TimedUnit], window: f64) -> Vec<u64> {
+    let mut result = Vec::with_capacity(units.len());
+    let mut start = 0;
+    let mut sum = 0u64;
+    for (i, unit) in units.iter().enumerate() {
+        let end = unit.time + unit.duration;
+        sum += unit.bytes;
+        while start <= i && units[start].time < end - window {
+            sum -= units[start].bytes;
+            start += 1;
+        }
+        result.push(sum);
+    }
+    result
+}
+
+/// Bitrate of the window of `window` seconds ending with each unit; at the start of the
+/// stream the window is shortened to the elapsed time
+fn sliding(units: &[TimedUnit], window: f64) -> Vec<f64> {
+    let Some(first) = units.first() else {
+        return Vec::new();
+    };
+    window_bytes(units, window)
+        .into_iter()
+        .zip(units)
+        .map(|(bytes, unit)| {
+            let span = window.min(unit.time + unit.duration - first.time);
+            if span > 0.0 {
+                bytes as f64 * 8.0 / span
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Highest bitrate over a full window; a stream shorter than the window counts as one
+/// window holding all of it
+fn peak(units: &[TimedUnit], window: f64) -> PeakBitrate {
+    window_bytes(units, window)
+        .into_iter()
+        .zip(units)
+        .map(|(bytes, u)| PeakBitrate {
+            window,
+            bitrate: bytes as f64 * 8.0 / window,
+            at: u.time + u.duration,
+        })
+        .fold(
+            PeakBitrate {
+                window,
+                bitrate: 0.0,
+                at: 0.0,
+            },
+            |best, p| if p.bitrate > best.bitrate { p } else { best },
+        )
+}
+
+/// Bitrate over time of every track (ISOBMFF) or stream (RealMedia) of a file
+pub fn analyze_bitrate(
+    path: &str,
+    strategy: DetectionStrategy,
+    windows: &[f64],
+) -> Result<BitrateReport, MediaError> {
+    let (format, tracks) = match FormatDetector::new(strategy).detect_format(path)? {
+        FileFormat::Isobmff => (
+            "ISOBMFF",
+            isobmff::bitrate::track_bitrates(std::path::Path::new(path), windows)?,
+        ),
+        FileFormat::RealMedia => {
+            let mut analyzer = RmffAnalyzer::new(path)?;
+            analyzer.set_debug(false);
+            ("RealMedia", analyzer.bitrates(windows)?)
+        }
+    };
+    Ok(BitrateReport {
+        format: format.to_string(),
+        windows: windows.to_vec(),
+        tracks,
+    })
+}
+
+/// Writes the series of every track as CSV
+pub fn write_csv(report: &BitrateReport, out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "track_id,time,instantaneous_bps,windowed_bps")?;
+    for track in &report.tracks {
+        for point in &track.series {
+            writeln!(
+                out,
+                "{},{:.6},{:.0},{:.0}",
+                track.track_id, point.time, point.instantaneous, point.windowed
+            )?;
+        }
+    }
+    Ok(())
+}
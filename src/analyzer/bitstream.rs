@@ -0,0 +1,546 @@
+use serde::Serialize;
+
+/// MSB-first bit reader with the Exp-Golomb codes of H.264/H.265
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..bits {
+            value = value << 1 | self.read_bit()? as u32;
+        }
+        Some(value)
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        let byte = *self.data.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Some(bit == 1)
+    }
+
+    pub fn read_flag(&mut self) -> Option<bool> {
+        self.read_bit()
+    }
+
+    /// Bits read or skipped so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn skip(&mut self, bits: usize) -> Option<()> {
+        self.position += bits;
+        (self.position <= self.data.len() * 8).then_some(())
+    }
+
+    /// ue(v)
+    pub fn read_ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while !self.read_bit()? {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some(((1u64 << zeros) - 1 + self.read(zeros)? as u64) as u32)
+    }
+
+    /// se(v)
+    pub fn read_se(&mut self) -> Option<i32> {
+        let code = self.read_ue()? as i64;
+        Some(if code % 2 == 1 {
+            ((code + 1) / 2) as i32
+        } else {
+            -(code / 2) as i32
+        })
+    }
+}
+
+/// Removes the emulation prevention bytes (00 00 03) of a NAL unit payload
+pub fn rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        out.push(byte);
+    }
+    out
+}
+
+/// Fields of an H.264 sequence parameter set (ITU-T H.264 7.3.2.1.1)
+#[derive(Debug, Clone)]
+pub struct AvcSps {
+    pub profile_idc: u8,
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+    pub sps_id: u32,
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    pub bit_depth_luma: u32,
+    pub bit_depth_chroma: u32,
+    pub log2_max_frame_num: u32,
+    pub pic_order_cnt_type: u32,
+    pub log2_max_pic_order_cnt_lsb: u32,
+    pub frame_mbs_only: bool,
+    pub width: u32,
+    pub height: u32,
+    /// num_units_in_tick and time_scale of the VUI timing info
+    pub timing: Option<(u32, u32)>,
+    /// Field sizes of the picture timing SEI; None without a readable VUI
+    pub pic_timing: Option<AvcPicTimingLayout>,
+}
+
+/// Field sizes of the H.264 picture timing SEI, from the VUI and its HRD parameters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AvcPicTimingLayout {
+    /// cpb_removal_delay and dpb_output_delay lengths, when an HRD is present
+    pub delay_lengths: Option<(u32, u32)>,
+    pub pic_struct_present: bool,
+    pub time_offset_length: u32,
+}
+
+impl AvcSps {
+    /// Parses a complete SPS NAL unit, header byte included
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        let data = rbsp(nal.get(1..)?);
+        let mut r = BitReader::new(&data);
+        let profile_idc = r.read(8)? as u8;
+        let constraint_flags = r.read(8)? as u8;
+        let level_idc = r.read(8)? as u8;
+        let sps_id = r.read_ue()?;
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane = false;
+        let mut bit_depth_luma = 8;
+        let mut bit_depth_chroma = 8;
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            chroma_format_idc = r.read_ue()?;
+            if chroma_format_idc == 3 {
+                separate_colour_plane = r.read_flag()?;
+            }
+            bit_depth_luma = r.read_ue()? + 8;
+            bit_depth_chroma = r.read_ue()? + 8;
+            r.read_flag()?; // qpprime_y_zero_transform_bypass_flag
+            if r.read_flag()? {
+                let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..lists {
+                    if r.read_flag()? {
+                        skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+        let log2_max_frame_num = r.read_ue()? + 4;
+        let pic_order_cnt_type = r.read_ue()?;
+        let mut log2_max_pic_order_cnt_lsb = 0;
+        match pic_order_cnt_type {
+            0 => log2_max_pic_order_cnt_lsb = r.read_ue()? + 4,
+            1 => {
+                r.read_flag()?;
+                r.read_se()?;
+                r.read_se()?;
+                for _ in 0..r.read_ue()? {
+                    r.read_se()?;
+                }
+            }
+            _ => {}
+        }
+        r.read_ue()?; // max_num_ref_frames
+        r.read_flag()?; // gaps_in_frame_num_value_allowed_flag
+        let width_in_mbs = r.read_ue()? + 1;
+        let height_in_map_units = r.read_ue()? + 1;
+        let frame_mbs_only = r.read_flag()?;
+        if !frame_mbs_only {
+            r.read_flag()?;
+        }
+        r.read_flag()?; // direct_8x8_inference_flag
+        let mut crop = [0u32; 4];
+        if r.read_flag()? {
+            for value in &mut crop {
+                *value = r.read_ue()?;
+            }
+        }
+        let field_factor = if frame_mbs_only { 1 } else { 2 };
+        let (crop_x, crop_y) = match (chroma_format_idc, separate_colour_plane) {
+            (0, _) | (3, true) => (1, field_factor),
+            (1, _) => (2, 2 * field_factor),
+            (2, _) => (2, field_factor),
+            _ => (1, field_factor),
+        };
+        let width = (width_in_mbs * 16).saturating_sub(crop_x * (crop[0] + crop[1]));
+        let height =
+            (field_factor * height_in_map_units * 16).saturating_sub(crop_y * (crop[2] + crop[3]));
+        let (timing, pic_timing) = if r.read_flag().unwrap_or(false) {
+            match avc_vui_timing(&mut r) {
+                Some(timing) => (timing, avc_pic_timing_layout(&mut r)),
+                None => (None, None),
+            }
+        } else {
+            (None, None)
+        };
+        Some(Self {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            sps_id,
+            chroma_format_idc,
+            separate_colour_plane,
+            bit_depth_luma,
+            bit_depth_chroma,
+            log2_max_frame_num,
+            pic_order_cnt_type,
+            log2_max_pic_order_cnt_lsb,
+            frame_mbs_only,
+            width,
+            height,
+            timing,
+            pic_timing,
+        })
+    }
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Option<()> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.read_se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+/// timing_info of the VUI, if present (the fields before it are skipped); None when the VUI
+/// cannot be read
+fn avc_vui_timing(r: &mut BitReader) -> Option<Option<(u32, u32)>> {
+    if r.read_flag()? && r.read(8)? == 255 {
+        r.skip(32)?; // sar_width, sar_height
+    }
+    if r.read_flag()? {
+        r.read_flag()?; // overscan_appropriate_flag
+    }
+    if r.read_flag()? {
+        r.skip(4)?;
+        if r.read_flag()? {
+            r.skip(24)?; // colour primaries, transfer, matrix
+        }
+    }
+    if r.read_flag()? {
+        r.read_ue()?;
+        r.read_ue()?;
+    }
+    if !r.read_flag()? {
+        return Some(None);
+    }
+    let num_units_in_tick = r.read(32)?;
+    let time_scale = r.read(32)?;
+    r.read_flag()?; // fixed_frame_rate_flag
+    Some((num_units_in_tick > 0 && time_scale > 0).then_some((num_units_in_tick, time_scale)))
+}
+
+/// The VUI fields after timing_info (E.1.1)
+fn avc_pic_timing_layout(r: &mut BitReader) -> Option<AvcPicTimingLayout> {
+    let mut layout = AvcPicTimingLayout::default();
+    let mut hrd = false;
+    // nal_hrd_parameters 与 vcl_hrd_parameters 中的延迟字段长度相同
+    for _ in 0..2 {
+        if r.read_flag()? {
+            hrd = true;
+            let cpb_count = r.read_ue()? + 1;
+            r.skip(8)?; // bit_rate_scale, cpb_size_scale
+            for _ in 0..cpb_count {
+                r.read_ue()?; // bit_rate_value_minus1
+                r.read_ue()?; // cpb_size_value_minus1
+                r.read_flag()?; // cbr_flag
+            }
+            r.skip(5)?; // initial_cpb_removal_delay_length_minus1
+            let cpb_removal_delay_length = r.read(5)? + 1;
+            let dpb_output_delay_length = r.read(5)? + 1;
+            layout.delay_lengths = Some((cpb_removal_delay_length, dpb_output_delay_length));
+            layout.time_offset_length = r.read(5)?;
+        }
+    }
+    if hrd {
+        r.read_flag()?; // low_delay_hrd_flag
+    }
+    layout.pic_struct_present = r.read_flag()?;
+    Some(layout)
+}
+
+/// The start of an H.264 picture parameter set
+#[derive(Debug, Clone, Copy)]
+pub struct AvcPps {
+    pub pps_id: u32,
+    pub sps_id: u32,
+}
+
+impl AvcPps {
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        let data = rbsp(nal.get(1..)?);
+        let mut r = BitReader::new(&data);
+        Some(Self {
+            pps_id: r.read_ue()?,
+            sps_id: r.read_ue()?,
+        })
+    }
+}
+
+/// Coding type of a slice, ordered from intra to bi-predicted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum SliceType {
+    I,
+    SI,
+    P,
+    SP,
+    B,
+}
+
+impl SliceType {
+    /// H.264 slice_type (7.4.3); 5-9 repeat 0-4 for pictures whose slices share the type
+    pub fn from_avc(value: u32) -> Option<Self> {
+        match value {
+            0 | 5 => Some(Self::P),
+            1 | 6 => Some(Self::B),
+            2 | 7 => Some(Self::I),
+            3 | 8 => Some(Self::SP),
+            4 | 9 => Some(Self::SI),
+            _ => None,
+        }
+    }
+
+    /// H.265 slice_type (7.4.7.1)
+    pub fn from_hevc(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::B),
+            1 => Some(Self::P),
+            2 => Some(Self::I),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SliceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::I => "I",
+            Self::SI => "SI",
+            Self::P => "P",
+            Self::SP => "SP",
+            Self::B => "B",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The leading fields of an H.264 slice header, up to pic_order_cnt_lsb
+#[derive(Debug, Clone, Copy)]
+pub struct AvcSliceHeader {
+    pub slice_type: u32,
+    pub pps_id: u32,
+    pub frame_num: u32,
+    pub pic_order_cnt_lsb: u32,
+}
+
+impl AvcSliceHeader {
+    /// `sps` resolves the SPS of the slice's PPS
+    pub fn parse<'s>(nal: &[u8], sps: impl Fn(u32) -> Option<&'s AvcSps>) -> Option<Self> {
+        let idr = nal.first()? & 0x1f == 5;
+        let data = rbsp(nal.get(1..nal.len().min(64))?);
+        let mut r = BitReader::new(&data);
+        r.read_ue()?; // first_mb_in_slice
+        let slice_type = r.read_ue()?;
+        let pps_id = r.read_ue()?;
+        let sps = sps(pps_id)?;
+        if sps.separate_colour_plane {
+            r.skip(2)?;
+        }
+        let frame_num = r.read(sps.log2_max_frame_num)?;
+        if !sps.frame_mbs_only && r.read_flag()? {
+            r.read_flag()?; // bottom_field_flag
+        }
+        if idr {
+            r.read_ue()?; // idr_pic_id
+        }
+        let pic_order_cnt_lsb = if sps.pic_order_cnt_type == 0 {
+            r.read(sps.log2_max_pic_order_cnt_lsb)?
+        } else {
+            0
+        };
+        Some(Self {
+            slice_type,
+            pps_id,
+            frame_num,
+            pic_order_cnt_lsb,
+        })
+    }
+}
+
+/// Fields of an H.265 sequence parameter set (ITU-T H.265 7.3.2.2), up to
+/// log2_max_pic_order_cnt_lsb
+#[derive(Debug, Clone)]
+pub struct HevcSps {
+    pub sps_id: u32,
+    pub max_sub_layers: u32,
+    pub temporal_id_nesting: bool,
+    /// general_profile_space .. general_level_idc, as stored
+    pub profile_tier_level: [u8; 12],
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth_luma: u32,
+    pub bit_depth_chroma: u32,
+    pub log2_max_pic_order_cnt_lsb: u32,
+}
+
+impl HevcSps {
+    /// Parses a complete SPS NAL unit, 2-byte header included
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        let data = rbsp(nal.get(2..)?);
+        let mut r = BitReader::new(&data);
+        r.skip(4)?; // sps_video_parameter_set_id
+        let max_sub_layers = r.read(3)? + 1;
+        let temporal_id_nesting = r.read_flag()?;
+        let profile_tier_level: [u8; 12] = data.get(1..13)?.try_into().ok()?;
+        r.skip(96)?;
+        let mut sub_layer_flags = Vec::new();
+        for _ in 1..max_sub_layers {
+            sub_layer_flags.push((r.read_flag()?, r.read_flag()?));
+        }
+        if max_sub_layers > 1 {
+            r.skip(2 * (9 - max_sub_layers as usize))?;
+        }
+        for (profile_present, level_present) in sub_layer_flags {
+            if profile_present {
+                r.skip(88)?;
+            }
+            if level_present {
+                r.skip(8)?;
+            }
+        }
+        let sps_id = r.read_ue()?;
+        let chroma_format_idc = r.read_ue()?;
+        let mut separate_colour_plane = false;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = r.read_flag()?;
+        }
+        let mut width = r.read_ue()?;
+        let mut height = r.read_ue()?;
+        if r.read_flag()? {
+            let (sub_width, sub_height) = match (chroma_format_idc, separate_colour_plane) {
+                (1, _) => (2, 2),
+                (2, _) => (2, 1),
+                _ => (1, 1),
+            };
+            let (left, right, top, bottom) =
+                (r.read_ue()?, r.read_ue()?, r.read_ue()?, r.read_ue()?);
+            width = width.saturating_sub(sub_width * (left + right));
+            height = height.saturating_sub(sub_height * (top + bottom));
+        }
+        let bit_depth_luma = r.read_ue()? + 8;
+        let bit_depth_chroma = r.read_ue()? + 8;
+        let log2_max_pic_order_cnt_lsb = r.read_ue()? + 4;
+        Some(Self {
+            sps_id,
+            max_sub_layers,
+            temporal_id_nesting,
+            profile_tier_level,
+            chroma_format_idc,
+            separate_colour_plane,
+            width,
+            height,
+            bit_depth_luma,
+            bit_depth_chroma,
+            log2_max_pic_order_cnt_lsb,
+        })
+    }
+}
+
+/// The start of an H.265 picture parameter set
+#[derive(Debug, Clone, Copy)]
+pub struct HevcPps {
+    pub pps_id: u32,
+    pub sps_id: u32,
+    pub output_flag_present: bool,
+    pub num_extra_slice_header_bits: u32,
+}
+
+impl HevcPps {
+    pub fn parse(nal: &[u8]) -> Option<Self> {
+        let data = rbsp(nal.get(2..)?);
+        let mut r = BitReader::new(&data);
+        let pps_id = r.read_ue()?;
+        let sps_id = r.read_ue()?;
+        r.read_flag()?; // dependent_slice_segments_enabled_flag
+        Some(Self {
+            pps_id,
+            sps_id,
+            output_flag_present: r.read_flag()?,
+            num_extra_slice_header_bits: r.read(3)?,
+        })
+    }
+}
+
+/// The leading fields of an H.265 slice segment header, up to slice_pic_order_cnt_lsb
+#[derive(Debug, Clone, Copy)]
+pub struct HevcSliceHeader {
+    pub pps_id: u32,
+    /// 0 B, 1 P, 2 I; None for dependent slice segments
+    pub slice_type: Option<u32>,
+    /// Absent (0) in IDR pictures
+    pub pic_order_cnt_lsb: u32,
+}
+
+impl HevcSliceHeader {
+    /// `parameter_sets` resolves a PPS id to the PPS and its SPS
+    pub fn parse<'s>(
+        nal: &[u8],
+        parameter_sets: impl Fn(u32) -> Option<(&'s HevcPps, &'s HevcSps)>,
+    ) -> Option<Self> {
+        let nal_type = (nal.first()? >> 1) & 0x3f;
+        let data = rbsp(nal.get(2..nal.len().min(64))?);
+        let mut r = BitReader::new(&data);
+        let first_slice_segment_in_pic = r.read_flag()?;
+        if (16..=23).contains(&nal_type) {
+            r.read_flag()?; // no_output_of_prior_pics_flag
+        }
+        let pps_id = r.read_ue()?;
+        let mut header = Self {
+            pps_id,
+            slice_type: None,
+            pic_order_cnt_lsb: 0,
+        };
+        // 非首个 slice segment 的地址长度取决于图像尺寸, 这里不再继续解析
+        if !first_slice_segment_in_pic {
+            return Some(header);
+        }
+        let (pps, sps) = parameter_sets(pps_id)?;
+        r.skip(pps.num_extra_slice_header_bits as usize)?;
+        header.slice_type = Some(r.read_ue()?);
+        if pps.output_flag_present {
+            r.read_flag()?;
+        }
+        if sps.separate_colour_plane {
+            r.skip(2)?;
+        }
+        if !matches!(nal_type, 19 | 20) {
+            header.pic_order_cnt_lsb = r.read(sps.log2_max_pic_order_cnt_lsb)?;
+        }
+        Some(header)
+    }
+}
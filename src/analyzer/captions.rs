@@ -0,0 +1,717 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use serde::Serialize;
+
+/// One valid cc_data triplet (CEA-708 4.4): cc_type 0/1 carry CEA-608 field 1/2 byte pairs,
+/// 2/3 carry DTVCC packet data/start
+#[derive(Debug, Clone, Copy)]
+pub struct CcData {
+    pub cc_type: u8,
+    pub data: [u8; 2],
+}
+
+/// cc_data of an ATSC A/53 "GA94" user_data_registered_itu_t_t35 payload
+pub fn a53_cc_data(payload: &[u8]) -> Vec<CcData> {
+    // country_code 0xb5, provider 0x0031, "GA94", user_data_type_code 3
+    let Some(rest) = payload.strip_prefix(&[0xb5, 0x00, 0x31, b'G', b'A', b'9', b'4', 0x03]) else {
+        return Vec::new();
+    };
+    let Some(&flags) = rest.first() else {
+        return Vec::new();
+    };
+    if flags & 0x40 == 0 {
+        return Vec::new();
+    }
+    let count = (flags & 0x1f) as usize;
+    rest.get(2..)
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .take(count)
+        .filter(|triplet| triplet[0] & 0x04 != 0)
+        .map(|triplet| CcData {
+            cc_type: triplet[0] & 0x03,
+            data: [triplet[1], triplet[2]],
+        })
+        .collect()
+}
+
+/// Byte pairs of a QuickTime `c608` sample: `cdat` atoms carry field 1, `cdt2` field 2
+pub fn c608_cc_data(sample: &[u8]) -> Vec<CcData> {
+    let mut result = Vec::new();
+    let mut at = 0;
+    while let Some(header) = sample.get(at..at + 8) {
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if size < 8 {
+            break;
+        }
+        let cc_type = match &header[4..8] {
+            b"cdat" => Some(0),
+            b"cdt2" => Some(1),
+            _ => None,
+        };
+        let body = sample.get(at + 8..at + size).unwrap_or_default();
+        if let Some(cc_type) = cc_type {
+            result.extend(body.chunks_exact(2).map(|pair| CcData {
+                cc_type,
+                data: [pair[0], pair[1]],
+            }));
+        }
+        at += size;
+    }
+    result
+}
+
+/// Text shown from `start` to `end` (seconds on the presentation timeline)
+#[derive(Debug, Clone, Serialize)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Captions of one CEA-608 channel (CC1-CC4) or CEA-708 service (SERVICE1-63)
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionChannel {
+    pub name: String,
+    pub cues: Vec<Cue>,
+}
+
+/// Runs the CEA-608 and CEA-708 decoders over cc_data given in presentation order
+pub struct CaptionDecoder {
+    fields: [FieldState; 2],
+    cc608: [Cea608Channel; 4],
+    dtvcc_packet: Vec<u8>,
+    services: BTreeMap<u8, Cea708Service>,
+    /// Raw field 1 byte pairs (parity included) other than padding, for SCC
+    pub field1: Vec<(f64, [u8; 2])>,
+    last_time: f64,
+}
+
+#[derive(Default)]
+struct FieldState {
+    /// Data channel selected by the last control code
+    channel: usize,
+    last_control: Option<[u8; 2]>,
+    /// Inside an XDS packet (field 2 only)
+    xds: bool,
+}
+
+impl Default for CaptionDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaptionDecoder {
+    pub fn new() -> Self {
+        Self {
+            fields: Default::default(),
+            cc608: Default::default(),
+            dtvcc_packet: Vec::new(),
+            services: BTreeMap::new(),
+            field1: Vec::new(),
+            last_time: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, time: f64, cc: CcData) {
+        self.last_time = self.last_time.max(time);
+        match cc.cc_type {
+            0 | 1 => self.push_608(time, cc.cc_type as usize, cc.data),
+            3 => {
+                self.flush_dtvcc(time);
+                self.dtvcc_packet = cc.data.to_vec();
+                self.try_dtvcc(time);
+            }
+            _ => {
+                if !self.dtvcc_packet.is_empty() {
+                    self.dtvcc_packet.extend_from_slice(&cc.data);
+                    self.try_dtvcc(time);
+                }
+            }
+        }
+    }
+
+    /// Closes the cues still on screen at `end` and returns the channels that have cues
+    pub fn finish(mut self, end: f64) -> Vec<CaptionChannel> {
+        let end = end.max(self.last_time);
+        self.flush_dtvcc(end);
+        let mut channels = Vec::new();
+        for (i, channel) in self.cc608.into_iter().enumerate() {
+            let cues = channel.cues.finish(end);
+            if !cues.is_empty() {
+                channels.push(CaptionChannel {
+                    name: format!("CC{}", i + 1),
+                    cues,
+                });
+            }
+        }
+        for (number, service) in self.services {
+            let cues = service.cues.finish(end);
+            if !cues.is_empty() {
+                channels.push(CaptionChannel {
+                    name: format!("SERVICE{}", number),
+                    cues,
+                });
+            }
+        }
+        channels
+    }
+
+    fn push_608(&mut self, time: f64, field: usize, data: [u8; 2]) {
+        let (b1, b2) = (data[0] & 0x7f, data[1] & 0x7f);
+        if b1 == 0 && b2 == 0 {
+            return;
+        }
+        if field == 0 {
+            self.field1.push((time, data));
+        }
+        let state = &mut self.fields[field];
+        if (0x01..=0x0f).contains(&b1) {
+            // XDS 数据包, 0x0f 为结束码
+            state.xds = b1 != 0x0f;
+            return;
+        }
+        if (0x10..=0x1f).contains(&b1) {
+            state.xds = false;
+            // 控制码通常重复发送两次, 只执行一次
+            if state.last_control == Some([b1, b2]) {
+                state.last_control = None;
+                return;
+            }
+            state.last_control = Some([b1, b2]);
+            state.channel = ((b1 >> 3) & 1) as usize;
+            let channel = &mut self.cc608[field * 2 + state.channel];
+            channel.control(b1 & 0x17, b2);
+            channel.update(time);
+            return;
+        }
+        state.last_control = None;
+        if state.xds {
+            return;
+        }
+        let channel = &mut self.cc608[field * 2 + state.channel];
+        for byte in [b1, b2] {
+            if byte >= 0x20 {
+                channel.write(basic_char(byte));
+            }
+        }
+    }
+
+    /// Decodes the buffered DTVCC packet once all of its bytes have arrived
+    fn try_dtvcc(&mut self, time: f64) {
+        let Some(&header) = self.dtvcc_packet.first() else {
+            return;
+        };
+        let size = match header & 0x3f {
+            0 => 128,
+            code => code as usize * 2,
+        };
+        if self.dtvcc_packet.len() >= size {
+            self.dtvcc_packet.truncate(size);
+            self.flush_dtvcc(time);
+        }
+    }
+
+    /// Decodes the service blocks of the buffered DTVCC packet, complete or not
+    fn flush_dtvcc(&mut self, time: f64) {
+        let packet = std::mem::take(&mut self.dtvcc_packet);
+        let mut at = 1;
+        while let Some(&header) = packet.get(at) {
+            let mut number = header >> 5;
+            let size = (header & 0x1f) as usize;
+            at += 1;
+            if number == 7 {
+                let Some(&extended) = packet.get(at) else {
+                    break;
+                };
+                number = extended & 0x3f;
+                at += 1;
+            }
+            if number == 0 || size == 0 {
+                // 空服务块表示包内剩余部分为填充
+                break;
+            }
+            let end = (at + size).min(packet.len());
+            let service = self.services.entry(number).or_default();
+            service.block(&packet[at..end]);
+            service.update(time);
+            at = end;
+        }
+    }
+}
+
+/// Follows the cues of one caption channel: a cue ends whenever the shown text changes
+#[derive(Default)]
+struct CueTrack {
+    cues: Vec<Cue>,
+    open: Option<(f64, String)>,
+}
+
+impl CueTrack {
+    fn show(&mut self, time: f64, text: String) {
+        if self.open.as_ref().map(|(_, shown)| shown) == Some(&text) {
+            return;
+        }
+        if let Some((start, shown)) = self.open.take() {
+            if time > start {
+                self.cues.push(Cue {
+                    start,
+                    end: time,
+                    text: shown,
+                });
+            }
+        }
+        if !text.is_empty() {
+            self.open = Some((time, text));
+        }
+    }
+
+    fn finish(mut self, end: f64) -> Vec<Cue> {
+        self.show(end, String::new());
+        self.cues
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Mode {
+    #[default]
+    PopOn,
+    PaintOn,
+    /// Roll-up with the given number of rows
+    RollUp(usize),
+    /// Text and XDS-like data of the T1/T2 channels, not shown
+    Text,
+}
+
+type Grid = [[char; 32]; 15];
+
+/// CEA-608 caption channel: displayed and non-displayed memory of 15 rows by 32 columns
+struct Cea608Channel {
+    mode: Mode,
+    displayed: Grid,
+    hidden: Grid,
+    row: usize,
+    column: usize,
+    cues: CueTrack,
+}
+
+impl Default for Cea608Channel {
+    fn default() -> Self {
+        Self {
+            mode: Mode::PopOn,
+            displayed: [['\0'; 32]; 15],
+            hidden: [['\0'; 32]; 15],
+            row: 14,
+            column: 0,
+            cues: CueTrack::default(),
+        }
+    }
+}
+
+impl Cea608Channel {
+    /// The memory text is written to in the current mode
+    fn target(&mut self) -> Option<&mut Grid> {
+        match self.mode {
+            Mode::PopOn => Some(&mut self.hidden),
+            Mode::PaintOn | Mode::RollUp(_) => Some(&mut self.displayed),
+            Mode::Text => None,
+        }
+    }
+
+    fn write(&mut self, c: char) {
+        let (row, column) = (self.row, self.column);
+        if let Some(grid) = self.target() {
+            grid[row][column] = c;
+        }
+        self.column = (self.column + 1).min(31);
+    }
+
+    fn backspace(&mut self) {
+        self.column = self.column.saturating_sub(1);
+        let (row, column) = (self.row, self.column);
+        if let Some(grid) = self.target() {
+            grid[row][column] = '\0';
+        }
+    }
+
+    /// A control code with the channel bit cleared (b1 in 0x10..=0x17)
+    fn control(&mut self, b1: u8, b2: u8) {
+        match (b1, b2) {
+            // 前导地址码 (PAC): 行号与缩进
+            (_, 0x40..=0x7f) => {
+                const ROWS: [usize; 8] = [11, 1, 3, 12, 14, 5, 7, 9];
+                let row = ROWS[(b1 & 0x07) as usize] + usize::from(b2 & 0x20 != 0 && b1 != 0x10);
+                self.row = row.clamp(1, 15) - 1;
+                self.column = if b2 & 0x10 != 0 {
+                    ((b2 & 0x0e) >> 1) as usize * 4
+                } else {
+                    0
+                };
+            }
+            // 行中码显示为空格
+            (0x11, 0x20..=0x2f) => self.write(' '),
+            (0x11, 0x30..=0x3f) => self.write(SPECIAL_CHARS[(b2 - 0x30) as usize]),
+            (0x12, 0x20..=0x3f) => {
+                self.backspace();
+                self.write(EXTENDED_CHARS_1[(b2 - 0x20) as usize]);
+            }
+            (0x13, 0x20..=0x3f) => {
+                self.backspace();
+                self.write(EXTENDED_CHARS_2[(b2 - 0x20) as usize]);
+            }
+            (0x17, 0x21..=0x23) => self.column = (self.column + (b2 - 0x20) as usize).min(31),
+            (0x14 | 0x15, _) => self.misc_control(b2),
+            _ => {}
+        }
+    }
+
+    fn misc_control(&mut self, b2: u8) {
+        match b2 {
+            0x20 => self.mode = Mode::PopOn,
+            0x21 => self.backspace(),
+            0x24 => {
+                let (row, column) = (self.row, self.column);
+                if let Some(grid) = self.target() {
+                    grid[row][column..].fill('\0');
+                }
+            }
+            0x25..=0x27 => {
+                if !matches!(self.mode, Mode::RollUp(_)) {
+                    self.displayed = [['\0'; 32]; 15];
+                    self.hidden = [['\0'; 32]; 15];
+                }
+                self.mode = Mode::RollUp((b2 - 0x23) as usize);
+                self.column = 0;
+            }
+            0x29 => self.mode = Mode::PaintOn,
+            0x2a | 0x2b => self.mode = Mode::Text,
+            0x2c => self.displayed = [['\0'; 32]; 15],
+            0x2d => {
+                if let Mode::RollUp(rows) = self.mode {
+                    let top = (self.row + 1).saturating_sub(rows);
+                    for row in top..self.row {
+                        self.displayed[row] = self.displayed[row + 1];
+                    }
+                    self.displayed[self.row] = ['\0'; 32];
+                    for row in 0..top {
+                        self.displayed[row] = ['\0'; 32];
+                    }
+                }
+                self.column = 0;
+            }
+            0x2e => self.hidden = [['\0'; 32]; 15],
+            0x2f => {
+                std::mem::swap(&mut self.displayed, &mut self.hidden);
+                self.mode = Mode::PopOn;
+            }
+            _ => {}
+        }
+    }
+
+    /// Publishes the displayed memory; called after control codes only, so roll-up and
+    /// paint-on text appears at the next carriage return or command rather than per letter
+    fn update(&mut self, time: f64) {
+        let text = grid_text(&self.displayed);
+        self.cues.show(time, text);
+    }
+}
+
+fn grid_text(grid: &Grid) -> String {
+    grid.iter()
+        .map(|row| {
+            row.iter()
+                .map(|&c| if c == '\0' { ' ' } else { c })
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .filter(|row| !row.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// CEA-608 basic character set, with its differences from ASCII
+fn basic_char(byte: u8) -> char {
+    match byte {
+        0x2a => 'á',
+        0x5c => 'é',
+        0x5e => 'í',
+        0x5f => 'ó',
+        0x60 => 'ú',
+        0x7b => 'ç',
+        0x7c => '÷',
+        0x7d => 'Ñ',
+        0x7e => 'ñ',
+        0x7f => '█',
+        _ => byte as char,
+    }
+}
+
+const SPECIAL_CHARS: [char; 16] = [
+    '®', '°', '½', '¿', '™', '¢', '£', '♪', 'à', ' ', 'è', 'â', 'ê', 'î', 'ô', 'û',
+];
+
+/// Spanish, miscellaneous and French extended characters
+const EXTENDED_CHARS_1: [char; 32] = [
+    'Á', 'É', 'Ó', 'Ú', 'Ü', 'ü', '‘', '¡', '*', '\'', '—', '©', '℠', '•', '“', '”', 'À', 'Â', 'Ç',
+    'È', 'Ê', 'Ë', 'ë', 'Î', 'Ï', 'ï', 'Ô', 'Ù', 'ù', 'Û', '«', '»',
+];
+
+/// Portuguese, German and Danish extended characters
+const EXTENDED_CHARS_2: [char; 32] = [
+    'Ã', 'ã', 'Í', 'Ì', 'ì', 'Ò', 'ò', 'Õ', 'õ', '{', '}', '\\', '^', '_', '|', '~', 'Ä', 'ä', 'Ö',
+    'ö', 'ß', '¥', '¤', '│', 'Å', 'å', 'Ø', 'ø', '┌', '┐', '└', '┘',
+];
+
+/// One CEA-708 window: its rows of text and whether it is shown
+#[derive(Default, Clone)]
+struct Window {
+    defined: bool,
+    visible: bool,
+    rows: Vec<String>,
+}
+
+impl Window {
+    fn clear(&mut self) {
+        self.rows.clear();
+    }
+}
+
+/// A CEA-708 caption service with its eight windows
+#[derive(Default)]
+struct Cea708Service {
+    windows: [Window; 8],
+    current: usize,
+    /// A command that changes what is shown was decoded since the last update
+    changed: bool,
+    cues: CueTrack,
+}
+
+impl Cea708Service {
+    /// Decodes one service block (CEA-708 7.1)
+    fn block(&mut self, data: &[u8]) {
+        let mut at = 0;
+        while let Some(&code) = data.get(at) {
+            at += 1;
+            match code {
+                // ETX, CR, HCR
+                0x03 => self.changed = true,
+                0x0d => {
+                    self.window().rows.push(String::new());
+                    self.changed = true;
+                }
+                0x0e => {
+                    if let Some(row) = self.window().rows.last_mut() {
+                        row.clear();
+                    }
+                    self.changed = true;
+                }
+                0x08 => {
+                    if let Some(row) = self.window().rows.last_mut() {
+                        row.pop();
+                    }
+                }
+                0x0c => {
+                    self.window().clear();
+                    self.changed = true;
+                }
+                0x10 => {
+                    let Some(&extended) = data.get(at) else {
+                        break;
+                    };
+                    at += 1;
+                    at += match extended {
+                        0x00..=0x07 => 0,
+                        0x08..=0x0f => 1,
+                        0x10..=0x17 => 2,
+                        0x18..=0x1f => 3,
+                        0x20..=0x7f => {
+                            if let Some(c) = g2_char(extended) {
+                                self.write(c);
+                            }
+                            0
+                        }
+                        0x80..=0x87 => 4,
+                        0x88..=0x8f => 5,
+                        0x90..=0x9f => data.get(at).map_or(0, |&len| 1 + (len & 0x1f) as usize),
+                        // G3: 只定义了 [CC] 图标
+                        0xa0..=0xff => 0,
+                    };
+                }
+                0x11..=0x17 => at += 1,
+                0x18..=0x1f => at += 2,
+                0x20..=0x7e => self.write(code as char),
+                0x7f => self.write('♪'),
+                0x80..=0x87 => self.current = (code - 0x80) as usize,
+                0x88..=0x8c => {
+                    let Some(&bitmap) = data.get(at) else {
+                        break;
+                    };
+                    at += 1;
+                    for (i, window) in self.windows.iter_mut().enumerate() {
+                        if bitmap & (1 << i) == 0 {
+                            continue;
+                        }
+                        match code {
+                            0x88 => window.clear(),
+                            0x89 => window.visible = true,
+                            0x8a => window.visible = false,
+                            0x8b => window.visible = !window.visible,
+                            _ => *window = Window::default(),
+                        }
+                    }
+                    self.changed = true;
+                }
+                0x8d => at += 1,
+                0x8f => {
+                    self.windows = Default::default();
+                    self.changed = true;
+                }
+                0x90 | 0x92 => at += 2,
+                0x91 => at += 3,
+                0x97 => at += 4,
+                0x98..=0x9f => {
+                    let id = (code - 0x98) as usize;
+                    let Some(&parameters) = data.get(at) else {
+                        break;
+                    };
+                    at += 6;
+                    let window = &mut self.windows[id];
+                    window.defined = true;
+                    window.visible = parameters & 0x20 != 0;
+                    self.current = id;
+                    self.changed = true;
+                }
+                0xa0..=0xff => self.write(char::from(code)),
+                _ => {}
+            }
+        }
+    }
+
+    fn window(&mut self) -> &mut Window {
+        &mut self.windows[self.current]
+    }
+
+    fn write(&mut self, c: char) {
+        let window = self.window();
+        match window.rows.last_mut() {
+            Some(row) => row.push(c),
+            None => window.rows.push(c.to_string()),
+        }
+    }
+
+    /// Publishes the visible windows after a command that changed the display; text written
+    /// into a visible window shows at the next such command
+    fn update(&mut self, time: f64) {
+        if !std::mem::take(&mut self.changed) {
+            return;
+        }
+        let text = self
+            .windows
+            .iter()
+            .filter(|window| window.defined && window.visible)
+            .flat_map(|window| window.rows.iter())
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.cues.show(time, text);
+    }
+}
+
+/// CEA-708 G2 characters that have a Unicode equivalent
+fn g2_char(code: u8) -> Option<char> {
+    Some(match code {
+        0x20 | 0x21 => ' ',
+        0x25 => '…',
+        0x2a => 'Š',
+        0x2c => 'Œ',
+        0x30 => '█',
+        0x31 => '‘',
+        0x32 => '’',
+        0x33 => '“',
+        0x34 => '”',
+        0x35 => '•',
+        0x39 => '™',
+        0x3a => 'š',
+        0x3c => 'œ',
+        0x3d => '℠',
+        0x3f => 'Ÿ',
+        0x76 => '⅛',
+        0x77 => '⅜',
+        0x78 => '⅝',
+        0x79 => '⅞',
+        0x7a => '│',
+        0x7b => '┐',
+        0x7c => '└',
+        0x7d => '─',
+        0x7e => '┘',
+        0x7f => '┌',
+        _ => return None,
+    })
+}
+
+/// Writes cues as SubRip
+pub fn write_srt(cues: &[Cue], out: &mut impl Write) -> std::io::Result<()> {
+    for (i, cue) in cues.iter().enumerate() {
+        writeln!(
+            out,
+            "{}\n{} --> {}\n{}\n",
+            i + 1,
+            srt_time(cue.start),
+            srt_time(cue.end),
+            cue.text
+        )?;
+    }
+    Ok(())
+}
+
+fn srt_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Writes field 1 byte pairs as Scenarist SCC; pairs of consecutive frames share a line.
+/// SCC is defined at 29.97 fps, so the timecodes are always drop-frame whatever the video
+/// frame rate; a pair that falls on an already written frame moves to the next one.
+pub fn write_scc(pairs: &[(f64, [u8; 2])], out: &mut impl Write) -> std::io::Result<()> {
+    write!(out, "Scenarist_SCC V1.0")?;
+    let frame = |time: f64| (time.max(0.0) * SCC_FRAME_RATE).round() as u64;
+    let mut next_frame = None;
+    for &(time, data) in pairs {
+        let number = frame(time).max(next_frame.unwrap_or(0));
+        if next_frame != Some(number) {
+            write!(out, "\n\n{}\t", scc_timecode(number))?;
+        } else {
+            write!(out, " ")?;
+        }
+        write!(out, "{:02x}{:02x}", data[0], data[1])?;
+        next_frame = Some(number + 1);
+    }
+    writeln!(out)
+}
+
+const SCC_FRAME_RATE: f64 = 30000.0 / 1001.0;
+
+/// SMPTE drop-frame timecode of a 29.97 fps frame number: frame numbers 0 and 1 of every
+/// minute not divisible by 10 are skipped
+fn scc_timecode(frame: u64) -> String {
+    let ten_minutes = frame / 17982;
+    let rest = frame % 17982;
+    let frame = frame + 18 * ten_minutes + if rest < 2 { 0 } else { 2 * ((rest - 2) / 1798) };
+    format!(
+        "{:02}:{:02}:{:02};{:02}",
+        frame / (30 * 3600),
+        frame / (30 * 60) % 60,
+        frame / 30 % 60,
+        frame % 30
+    )
+}
@@ -55,7 +55,8 @@ impl FormatDetector {
             .map(|s| s.to_lowercase());
 
         match extension.as_deref() {
-            Some("mp4") | Some("mov") | Some("m4a") | Some("m4v") => Ok(FileFormat::Isobmff),
+            Some("mp4") | Some("mov") | Some("m4a") | Some("m4v") | Some("3gp") | Some("heic")
+            | Some("heif") | Some("avif") | Some("m4s") => Ok(FileFormat::Isobmff),
             Some("rm") | Some("rmvb") | Some("ra") => Ok(FileFormat::RealMedia),
             _ => Err(MediaError::UnsupportedFormat(
                 "Unknown file extension".to_string(),
@@ -65,20 +66,22 @@ impl FormatDetector {
 
     fn detect_by_content(&self, path: impl AsRef<Path>) -> Result<FileFormat, MediaError> {
         let mut file = File::open(path)?;
-        let mut magic = [0u8; 4];
+        let mut magic = [0u8; 8];
         file.read_exact(&mut magic)?;
 
         // 读完后重置文件指针位置
         file.seek(SeekFrom::Start(0))?;
 
-        match &magic {
-            b".RMF" => Ok(FileFormat::RealMedia),
-            b"ftyp" | // MP4
-            b"moov" | // MOV
-            b"mdat" | // MOV/MP4
-            b"free" | // MOV/MP4
-            b"wide" | // MOV/MP4
-            b"skip" => Ok(FileFormat::Isobmff),
+        // ISOBMFF 的 4CC 在 size 字段之后
+        match (&magic[..4], &magic[4..]) {
+            (b".RMF", _) => Ok(FileFormat::RealMedia),
+            (_, b"ftyp") | // MP4
+            (_, b"moov") | // MOV
+            (_, b"styp") | // DASH/HLS media segment
+            (_, b"mdat") | // MOV/MP4
+            (_, b"free") | // MOV/MP4
+            (_, b"wide") | // MOV/MP4
+            (_, b"skip") => Ok(FileFormat::Isobmff),
             _ => Err(MediaError::UnsupportedFormat(format!(
                 "Unknown magic number: {:?}",
                 magic
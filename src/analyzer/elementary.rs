@@ -0,0 +1,999 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::analyzer::bitstream::{
+    AvcPps, AvcSliceHeader, AvcSps, BitReader, HevcPps, HevcSliceHeader, HevcSps, SliceType,
+};
+use crate::analyzer::extract::AAC_SAMPLE_RATES;
+use crate::error::MediaError;
+
+/// Codec of an elementary stream file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamKind {
+    /// Annex B H.264
+    H264,
+    /// Annex B H.265
+    H265,
+    /// ADTS AAC
+    Aac,
+    /// AC-3 sync frames
+    Ac3,
+    /// Opus in Ogg
+    Opus,
+}
+
+impl std::fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StreamKind::H264 => "H.264",
+            StreamKind::H265 => "H.265",
+            StreamKind::Aac => "AAC",
+            StreamKind::Ac3 => "AC-3",
+            StreamKind::Opus => "Opus",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One access unit or audio frame, in decoding order, as it is stored in an MP4 sample
+#[derive(Debug, Clone)]
+pub struct EsSample {
+    pub data: Vec<u8>,
+    pub duration: u32,
+    /// CTS - DTS, in timescale units
+    pub composition_offset: i64,
+    pub is_sync: bool,
+}
+
+/// Codec parameters of a video or audio stream
+#[derive(Debug, Clone, Copy)]
+pub enum StreamFormat {
+    Video { width: u32, height: u32 },
+    Audio { channels: u16, sample_rate: u32 },
+}
+
+/// An elementary stream split into samples, with what its MP4 sample entry needs
+#[derive(Debug, Clone)]
+pub struct ElementaryStream {
+    pub kind: StreamKind,
+    /// Sample entry 4CC (avc1, avc3, hvc1, hev1, mp4a, ac-3, Opus)
+    pub sample_entry: &'static str,
+    /// Child of the sample entry: box type and payload (avcC, hvcC, esds, dac3, dOps)
+    pub config: ([u8; 4], Vec<u8>),
+    pub format: StreamFormat,
+    pub timescale: u32,
+    pub samples: Vec<EsSample>,
+    /// Media time of the first presented sample: reordering delay or decoder pre-skip
+    pub presentation_start: u64,
+    /// Presented media duration when the stream signals its end (Opus final granule)
+    pub presentation_duration: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+impl ElementaryStream {
+    /// Reads an elementary stream file. The codec is recognised from its first bytes; Annex B
+    /// streams are H.265 when the extension says so or the first NAL unit has an H.265 header.
+    /// `frame_rate` overrides the timing of video streams (H.264 VUI, otherwise 25 fps).
+    pub fn read(path: &Path, frame_rate: Option<f64>) -> Result<Self, MediaError> {
+        let data = std::fs::read(path)?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if data.starts_with(b"OggS") {
+            read_opus(&data)
+        } else if data.starts_with(&[0x0b, 0x77]) {
+            read_ac3(&data)
+        } else if data.len() > 1 && data[0] == 0xff && data[1] & 0xf6 == 0xf0 {
+            read_adts(&data)
+        } else if data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1]) {
+            let nals = annex_b_units(&data);
+            let hevc = matches!(extension.as_str(), "h265" | "265" | "hevc")
+                || (!matches!(extension.as_str(), "h264" | "264" | "avc" | "26l")
+                    && nals.first().is_some_and(|nal| looks_like_hevc(nal)));
+            read_video(&nals, hevc, frame_rate)
+        } else {
+            Err(MediaError::UnsupportedFormat(format!(
+                "{} is not an Annex B, ADTS, AC-3 or Ogg Opus stream",
+                path.display()
+            )))
+        }
+    }
+}
+
+/// NAL units of an Annex B byte stream, without start codes or trailing zero bytes
+pub fn annex_b_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let mut units = Vec::with_capacity(starts.len());
+    for (n, &start) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).map_or(data.len(), |&next| next - 3);
+        while end > start && data[end - 1] == 0 {
+            end -= 1;
+        }
+        if end > start {
+            units.push(&data[start..end]);
+        }
+    }
+    units
+}
+
+/// H.265 VPS/SPS/PPS/AUD/SEI headers (layer 0, temporal id 0) as the first unit of a stream
+fn looks_like_hevc(nal: &[u8]) -> bool {
+    nal.len() > 1 && matches!(nal[0], 0x40 | 0x42 | 0x44 | 0x46 | 0x4e) && nal[1] == 0x01
+}
+
+/// How a NAL unit takes part in building access units
+#[derive(Debug, Clone, Copy)]
+pub struct NalRole {
+    /// Coded slice (segment) of a picture
+    pub vcl: bool,
+    /// Starts a new access unit when the current one already has a picture (H.264 7.4.1.2.3,
+    /// H.265 7.4.2.4.4)
+    pub boundary: bool,
+    /// IDR, or IRAP for H.265
+    pub random_access: bool,
+    /// Access unit delimiter or filler data, which MP4 samples leave out
+    pub dropped: bool,
+}
+
+/// Classifies a NAL unit (header included) of an H.264 or H.265 stream
+pub fn nal_role(nal: &[u8], hevc: bool) -> NalRole {
+    let header = nal.first().copied().unwrap_or(0);
+    if hevc {
+        let t = (header >> 1) & 0x3f;
+        let first_slice = t < 32 && nal.get(2).is_some_and(|b| b & 0x80 != 0);
+        NalRole {
+            vcl: t < 32,
+            boundary: matches!(t, 32..=35 | 39 | 41..=44 | 48..=55) || first_slice,
+            random_access: (16..=21).contains(&t),
+            dropped: matches!(t, 35 | 38),
+        }
+    } else {
+        let t = header & 0x1f;
+        // first_mb_in_slice == 0 时 ue(v) 编码为单个 1 比特
+        let first_slice = matches!(t, 1 | 5) && nal.get(1).is_some_and(|b| b & 0x80 != 0);
+        NalRole {
+            vcl: matches!(t, 1 | 5),
+            boundary: matches!(t, 6..=9 | 14..=18) || first_slice,
+            random_access: t == 5,
+            dropped: matches!(t, 9 | 12),
+        }
+    }
+}
+
+/// Picture being assembled from NAL units
+struct Picture {
+    nals: Vec<Vec<u8>>,
+    has_vcl: bool,
+    is_sync: bool,
+    /// POC, and whether the POC counting restarts at this picture
+    order: Option<(i64, bool)>,
+}
+
+impl Picture {
+    fn new() -> Self {
+        Self {
+            nals: Vec::new(),
+            has_vcl: false,
+            is_sync: false,
+            order: None,
+        }
+    }
+}
+
+/// Parameter sets seen in the stream, keyed by type and id
+#[derive(Default)]
+struct ParameterSets {
+    sets: Vec<(u8, u32, Vec<u8>)>,
+    /// The same id was sent with different content
+    changed: bool,
+}
+
+impl ParameterSets {
+    fn add(&mut self, kind: u8, id: u32, nal: &[u8]) {
+        match self.sets.iter().find(|(k, i, _)| *k == kind && *i == id) {
+            Some((_, _, stored)) if stored.as_slice() == nal => {}
+            Some(_) => self.changed = true,
+            None => self.sets.push((kind, id, nal.to_vec())),
+        }
+    }
+
+    fn of_kind(&self, kind: u8) -> Vec<&[u8]> {
+        self.sets
+            .iter()
+            .filter(|(k, _, _)| *k == kind)
+            .map(|(_, _, nal)| nal.as_slice())
+            .collect()
+    }
+}
+
+fn read_video(
+    nals: &[&[u8]],
+    hevc: bool,
+    frame_rate: Option<f64>,
+) -> Result<ElementaryStream, MediaError> {
+    let mut warnings = Vec::new();
+    let mut parameter_sets = ParameterSets::default();
+    let mut tracker = PocTracker::new(hevc);
+    let mut pictures: Vec<Picture> = Vec::new();
+    let mut current = Picture::new();
+
+    for &nal in nals {
+        if nal.is_empty() {
+            continue;
+        }
+        let role = nal_role(nal, hevc);
+        if role.boundary && current.has_vcl {
+            pictures.push(std::mem::replace(&mut current, Picture::new()));
+        }
+
+        if let Some((kind, id)) = tracker.parameter_set(nal) {
+            parameter_sets.add(kind, id, nal);
+        }
+        if role.vcl && !current.has_vcl {
+            current.has_vcl = true;
+            current.is_sync = role.random_access;
+            current.order = tracker.picture(nal);
+        }
+        if !role.dropped {
+            current.nals.push(nal.to_vec());
+        }
+    }
+    if current.has_vcl {
+        pictures.push(current);
+    } else if !current.nals.is_empty() {
+        warnings.push(format!(
+            "{} NAL units after the last picture were dropped",
+            current.nals.len()
+        ));
+    }
+    if pictures.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "the stream contains no coded pictures".to_string(),
+        ));
+    }
+
+    // 参数集: 内容不变时放进配置记录并从样本中去掉, 否则保留在码流中 (avc3/hev1)
+    let in_band = parameter_sets.changed;
+    if in_band {
+        warnings.push(
+            "parameter sets change within the stream; they are kept in band (avc3/hev1)"
+                .to_string(),
+        );
+    }
+    let is_parameter_set = |nal: &Vec<u8>| {
+        if hevc {
+            matches!((nal[0] >> 1) & 0x3f, 32..=34)
+        } else {
+            matches!(nal[0] & 0x1f, 7 | 8)
+        }
+    };
+
+    let (config, sample_entry, width, height, timing) = if hevc {
+        let sps = parameter_sets
+            .of_kind(33)
+            .first()
+            .and_then(|nal| HevcSps::parse(nal))
+            .ok_or_else(|| MediaError::InvalidFormat("the stream has no usable SPS".to_string()))?;
+        let record = hvcc(&sps, &parameter_sets);
+        let entry = if in_band { "hev1" } else { "hvc1" };
+        ((*b"hvcC", record), entry, sps.width, sps.height, None)
+    } else {
+        let sps = parameter_sets
+            .of_kind(7)
+            .first()
+            .and_then(|nal| AvcSps::parse(nal))
+            .ok_or_else(|| MediaError::InvalidFormat("the stream has no usable SPS".to_string()))?;
+        let record = avcc(&sps, &parameter_sets);
+        let entry = if in_band { "avc3" } else { "avc1" };
+        ((*b"avcC", record), entry, sps.width, sps.height, sps.timing)
+    };
+    if parameter_sets.of_kind(if hevc { 34 } else { 8 }).is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "the stream has no PPS".to_string(),
+        ));
+    }
+
+    let (timescale, duration) = match (frame_rate, timing) {
+        (Some(rate), _) => frame_timing(rate)?,
+        // VUI 的 time_scale 以场计
+        (None, Some((num_units_in_tick, time_scale))) => (time_scale, 2 * num_units_in_tick),
+        (None, None) => {
+            warnings.push("the stream has no timing information; 25 fps was assumed".to_string());
+            (25000, 1000)
+        }
+    };
+    if pictures.iter().any(|p| p.order.is_none()) {
+        warnings.push(
+            "some slice headers could not be read; those pictures keep their decoding order"
+                .to_string(),
+        );
+    }
+
+    let orders: Vec<Option<(i64, bool)>> = pictures.iter().map(|p| p.order).collect();
+    let (presentation, delay) = presentation_ranks(&orders);
+
+    let samples = pictures
+        .into_iter()
+        .enumerate()
+        .map(|(i, picture)| {
+            let mut data = Vec::new();
+            for nal in picture
+                .nals
+                .iter()
+                .filter(|nal| in_band || !is_parameter_set(nal))
+            {
+                data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                data.extend_from_slice(nal);
+            }
+            EsSample {
+                data,
+                duration,
+                composition_offset: (presentation[i] - i as i64 + delay) * duration as i64,
+                is_sync: picture.is_sync,
+            }
+        })
+        .collect();
+    Ok(ElementaryStream {
+        kind: if hevc {
+            StreamKind::H265
+        } else {
+            StreamKind::H264
+        },
+        sample_entry,
+        config,
+        format: StreamFormat::Video { width, height },
+        timescale,
+        samples,
+        presentation_start: delay as u64 * duration as u64,
+        presentation_duration: None,
+        warnings,
+    })
+}
+
+/// Derives the picture order count of each picture from its first slice header, following the
+/// parameter sets seen so far (H.264 8.2.1, H.265 8.3.1)
+pub struct PocTracker {
+    hevc: bool,
+    avc_sps: HashMap<u32, AvcSps>,
+    avc_pps: HashMap<u32, AvcPps>,
+    hevc_sps: HashMap<u32, HevcSps>,
+    hevc_pps: HashMap<u32, HevcPps>,
+    // POC 推导状态: (prevPicOrderCntMsb, prevPicOrderCntLsb)
+    previous: (i64, i64),
+    pictures: usize,
+}
+
+impl PocTracker {
+    pub fn new(hevc: bool) -> Self {
+        Self {
+            hevc,
+            avc_sps: HashMap::new(),
+            avc_pps: HashMap::new(),
+            hevc_sps: HashMap::new(),
+            hevc_pps: HashMap::new(),
+            previous: (0, 0),
+            pictures: 0,
+        }
+    }
+
+    /// Records a VPS, SPS or PPS NAL unit and returns its (NAL unit type, id); other NAL units
+    /// and unreadable parameter sets give None
+    pub fn parameter_set(&mut self, nal: &[u8]) -> Option<(u8, u32)> {
+        let header = *nal.first()?;
+        if self.hevc {
+            match (header >> 1) & 0x3f {
+                32 => Some((32, (*nal.get(2)? >> 4) as u32)),
+                33 => {
+                    let sps = HevcSps::parse(nal)?;
+                    let id = sps.sps_id;
+                    self.hevc_sps.insert(id, sps);
+                    Some((33, id))
+                }
+                34 => {
+                    let pps = HevcPps::parse(nal)?;
+                    let id = pps.pps_id;
+                    self.hevc_pps.insert(id, pps);
+                    Some((34, id))
+                }
+                _ => None,
+            }
+        } else {
+            match header & 0x1f {
+                7 => {
+                    let sps = AvcSps::parse(nal)?;
+                    let id = sps.sps_id;
+                    self.avc_sps.insert(id, sps);
+                    Some((7, id))
+                }
+                8 => {
+                    let pps = AvcPps::parse(nal)?;
+                    let id = pps.pps_id;
+                    self.avc_pps.insert(id, pps);
+                    Some((8, id))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// POC of the picture whose first VCL NAL unit is `nal`, and whether the counting restarts
+    /// there; None when the slice header or its parameter sets cannot be read
+    pub fn picture(&mut self, nal: &[u8]) -> Option<(i64, bool)> {
+        let first_picture = self.pictures == 0;
+        self.pictures += 1;
+        let header = *nal.first()?;
+        if self.hevc {
+            let nal_type = (header >> 1) & 0x3f;
+            let slice = HevcSliceHeader::parse(nal, |id| {
+                let pps = self.hevc_pps.get(&id)?;
+                Some((pps, self.hevc_sps.get(&pps.sps_id)?))
+            })?;
+            let sps = self
+                .hevc_pps
+                .get(&slice.pps_id)
+                .and_then(|pps| self.hevc_sps.get(&pps.sps_id))?;
+            // IDR/BLA 以及码流中的第一个 CRA 重新开始计数
+            let reset = matches!(nal_type, 16..=20) || (nal_type == 21 && first_picture);
+            let lsb = slice.pic_order_cnt_lsb as i64;
+            let msb = if reset {
+                0
+            } else {
+                poc_msb(self.previous, lsb, 1i64 << sps.log2_max_pic_order_cnt_lsb)
+            };
+            let temporal_id = nal.get(1).map_or(0, |b| (b & 0x07).saturating_sub(1));
+            // prevTid0Pic: TemporalId 0, 且不是 RASL/RADL/子层非参考图像
+            if temporal_id == 0
+                && !((nal_type <= 14 && nal_type % 2 == 0) || (6..=9).contains(&nal_type))
+            {
+                self.previous = (msb, lsb);
+            }
+            Some((msb + lsb, reset))
+        } else {
+            let idr = header & 0x1f == 5;
+            let slice =
+                AvcSliceHeader::parse(nal, |id| self.avc_sps.get(&self.avc_pps.get(&id)?.sps_id))?;
+            let sps = self
+                .avc_pps
+                .get(&slice.pps_id)
+                .and_then(|pps| self.avc_sps.get(&pps.sps_id))?;
+            if idr {
+                self.previous = (0, 0);
+            }
+            let order = match sps.pic_order_cnt_type {
+                0 => {
+                    let lsb = slice.pic_order_cnt_lsb as i64;
+                    let msb = poc_msb(self.previous, lsb, 1i64 << sps.log2_max_pic_order_cnt_lsb);
+                    if header & 0x60 != 0 {
+                        self.previous = (msb, lsb);
+                    }
+                    msb + lsb
+                }
+                // 类型 1/2 的输出顺序与解码顺序一致 (类型 1 近似处理)
+                _ => self.pictures as i64 - 1,
+            };
+            Some((order, idr))
+        }
+    }
+
+    /// Slice type and, for H.264, frame_num of a VCL NAL unit; None when the slice header or
+    /// its parameter sets cannot be read, and for dependent H.265 slice segments
+    pub fn slice(&self, nal: &[u8]) -> Option<(SliceType, Option<u32>)> {
+        if self.hevc {
+            let slice = HevcSliceHeader::parse(nal, |id| {
+                let pps = self.hevc_pps.get(&id)?;
+                Some((pps, self.hevc_sps.get(&pps.sps_id)?))
+            })?;
+            Some((SliceType::from_hevc(slice.slice_type?)?, None))
+        } else {
+            let slice =
+                AvcSliceHeader::parse(nal, |id| self.avc_sps.get(&self.avc_pps.get(&id)?.sps_id))?;
+            Some((
+                SliceType::from_avc(slice.slice_type)?,
+                Some(slice.frame_num),
+            ))
+        }
+    }
+}
+
+/// Display position of each picture from its (POC, reset) in decoding order, plus the largest
+/// reordering delay in frames. Pictures without a POC keep their decoding position.
+pub fn presentation_ranks(orders: &[Option<(i64, bool)>]) -> (Vec<i64>, i64) {
+    // 在每个 POC 重置区间内按 POC 排序得到显示顺序
+    let mut presentation = vec![0i64; orders.len()];
+    let mut start = 0;
+    while start < orders.len() {
+        let mut end = start + 1;
+        while end < orders.len() && !matches!(orders[end], Some((_, true))) {
+            end += 1;
+        }
+        let mut order: Vec<usize> = (start..end).collect();
+        order.sort_by_key(|&i| (orders[i].map_or(i as i64, |(poc, _)| poc), i));
+        for (rank, i) in order.into_iter().enumerate() {
+            presentation[i] = (start + rank) as i64;
+        }
+        start = end;
+    }
+    let delay = (0..orders.len())
+        .map(|i| i as i64 - presentation[i])
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    (presentation, delay)
+}
+
+/// PicOrderCntMsb from the previous reference picture (H.264 8.2.1.1, H.265 8.3.1)
+fn poc_msb((prev_msb, prev_lsb): (i64, i64), lsb: i64, max_lsb: i64) -> i64 {
+    if lsb < prev_lsb && prev_lsb - lsb >= max_lsb / 2 {
+        prev_msb + max_lsb
+    } else if lsb > prev_lsb && lsb - prev_lsb > max_lsb / 2 {
+        prev_msb - max_lsb
+    } else {
+        prev_msb
+    }
+}
+
+/// Timescale and frame duration for a frame rate; NTSC rates use a 1001 denominator
+fn frame_timing(rate: f64) -> Result<(u32, u32), MediaError> {
+    if !rate.is_finite() || rate <= 0.0 || rate > 1000.0 {
+        return Err(MediaError::InvalidFormat(format!(
+            "invalid frame rate {}",
+            rate
+        )));
+    }
+    // 29.97, 23.976 等 NTSC 帧率按 N*1000/1001 处理
+    let nominal = (rate * 1.001).round();
+    if rate.fract() > 1e-6 && (rate * 1.001 - nominal).abs() < 0.005 {
+        Ok((nominal as u32 * 1000, 1001))
+    } else {
+        Ok(((rate * 1000.0).round() as u32, 1000))
+    }
+}
+
+/// AVCDecoderConfigurationRecord (ISO/IEC 14496-15 5.3.3.1)
+fn avcc(sps: &AvcSps, sets: &ParameterSets) -> Vec<u8> {
+    let mut record = vec![
+        1,
+        sps.profile_idc,
+        sps.constraint_flags,
+        sps.level_idc,
+        0xff, // lengthSizeMinusOne = 3
+    ];
+    for (kind, prefix) in [(7, 0xe0u8), (8, 0)] {
+        let nals = sets.of_kind(kind);
+        record.push(prefix | nals.len() as u8);
+        for nal in nals {
+            record.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+            record.extend_from_slice(nal);
+        }
+    }
+    if matches!(sps.profile_idc, 100 | 110 | 122 | 144) {
+        record.push(0xfc | sps.chroma_format_idc as u8);
+        record.push(0xf8 | (sps.bit_depth_luma - 8) as u8);
+        record.push(0xf8 | (sps.bit_depth_chroma - 8) as u8);
+        record.push(0); // numOfSequenceParameterSetExt
+    }
+    record
+}
+
+/// HEVCDecoderConfigurationRecord (ISO/IEC 14496-15 8.3.3.1)
+fn hvcc(sps: &HevcSps, sets: &ParameterSets) -> Vec<u8> {
+    let mut record = vec![1];
+    record.extend_from_slice(&sps.profile_tier_level);
+    record.extend_from_slice(&[
+        0xf0,
+        0x00, // min_spatial_segmentation_idc
+        0xfc, // parallelismType
+        0xfc | sps.chroma_format_idc as u8,
+        0xf8 | (sps.bit_depth_luma - 8) as u8,
+        0xf8 | (sps.bit_depth_chroma - 8) as u8,
+        0x00,
+        0x00, // avgFrameRate
+        ((sps.max_sub_layers as u8) << 3) | ((sps.temporal_id_nesting as u8) << 2) | 0x03,
+    ]);
+    let kinds = [32, 33, 34];
+    record.push(
+        kinds
+            .iter()
+            .filter(|&&k| !sets.of_kind(k).is_empty())
+            .count() as u8,
+    );
+    for kind in kinds {
+        let nals = sets.of_kind(kind);
+        if nals.is_empty() {
+            continue;
+        }
+        record.push(0x80 | kind); // array_completeness
+        record.extend_from_slice(&(nals.len() as u16).to_be_bytes());
+        for nal in nals {
+            record.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+            record.extend_from_slice(nal);
+        }
+    }
+    record
+}
+
+fn read_adts(data: &[u8]) -> Result<ElementaryStream, MediaError> {
+    let mut warnings = Vec::new();
+    let mut samples = Vec::new();
+    let mut config: Option<(u8, u8, u8)> = None;
+    let mut skipped = 0u64;
+    let mut at = 0;
+    while at + 7 <= data.len() {
+        let header = &data[at..at + 7];
+        if header[0] != 0xff || header[1] & 0xf6 != 0xf0 {
+            skipped += 1;
+            at += 1;
+            continue;
+        }
+        let protection_absent = header[1] & 0x01 == 1;
+        let profile = header[2] >> 6;
+        let sampling_index = (header[2] >> 2) & 0x0f;
+        let channels = (header[2] & 0x01) << 2 | header[3] >> 6;
+        let frame_len = ((header[3] as usize & 0x03) << 11)
+            | (header[4] as usize) << 3
+            | (header[5] as usize) >> 5;
+        let blocks = (header[6] & 0x03) + 1;
+        let header_len = if protection_absent { 7 } else { 9 };
+        if frame_len < header_len || at + frame_len > data.len() {
+            warnings.push(format!("truncated ADTS frame at offset {} was dropped", at));
+            break;
+        }
+        match config {
+            None => config = Some((profile, sampling_index, channels)),
+            Some(c) if c != (profile, sampling_index, channels) => {
+                warnings.push(format!(
+                    "the AAC configuration changes at offset {}; the rest of the stream was dropped",
+                    at
+                ));
+                break;
+            }
+            _ => {}
+        }
+        if blocks != 1 {
+            return Err(MediaError::UnsupportedFormat(
+                "ADTS frames with several raw data blocks are not supported".to_string(),
+            ));
+        }
+        samples.push(EsSample {
+            data: data[at + header_len..at + frame_len].to_vec(),
+            duration: 1024,
+            composition_offset: 0,
+            is_sync: true,
+        });
+        at += frame_len;
+    }
+    if skipped > 0 {
+        warnings.push(format!(
+            "{} bytes outside ADTS frames were skipped",
+            skipped
+        ));
+    }
+    let (profile, sampling_index, channels) = config
+        .ok_or_else(|| MediaError::InvalidFormat("the stream has no ADTS frames".to_string()))?;
+    let sample_rate = *AAC_SAMPLE_RATES
+        .get(sampling_index as usize)
+        .ok_or_else(|| {
+            MediaError::InvalidFormat(format!(
+                "invalid sampling frequency index {}",
+                sampling_index
+            ))
+        })?;
+    if channels == 0 {
+        return Err(MediaError::UnsupportedFormat(
+            "AAC streams with an in-band channel configuration (PCE) are not supported".to_string(),
+        ));
+    }
+    let asc = [
+        (profile + 1) << 3 | sampling_index >> 1,
+        (sampling_index & 0x01) << 7 | channels << 3,
+    ];
+    let total: u64 = samples.iter().map(|s| s.data.len() as u64).sum();
+    let seconds = samples.len() as f64 * 1024.0 / sample_rate as f64;
+    let average = if seconds > 0.0 {
+        (total as f64 * 8.0 / seconds) as u32
+    } else {
+        0
+    };
+    // 最大码率按 1 秒窗口统计
+    let window = (sample_rate as usize).div_ceil(1024).max(1);
+    let maximum = samples
+        .windows(window.min(samples.len()).max(1))
+        .map(|frames| frames.iter().map(|s| s.data.len() as u64).sum::<u64>() * 8)
+        .max()
+        .unwrap_or(0) as u32;
+    let buffer = samples.iter().map(|s| s.data.len()).max().unwrap_or(0) as u32;
+    Ok(ElementaryStream {
+        kind: StreamKind::Aac,
+        sample_entry: "mp4a",
+        config: (*b"esds", esds(&asc, buffer, maximum, average)),
+        format: StreamFormat::Audio {
+            channels: if channels == 7 { 8 } else { channels as u16 },
+            sample_rate,
+        },
+        timescale: sample_rate,
+        samples,
+        presentation_start: 0,
+        presentation_duration: None,
+        warnings,
+    })
+}
+
+/// esds payload: ES_Descriptor with a DecoderConfigDescriptor for AAC and an SLConfigDescriptor
+fn esds(asc: &[u8], buffer_size: u32, max_bitrate: u32, avg_bitrate: u32) -> Vec<u8> {
+    fn descriptor(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        // 固定使用 4 字节长度编码, 与常见封装器一致
+        let len = body.len() as u32;
+        out.extend_from_slice(&[
+            0x80 | (len >> 21) as u8 & 0x7f,
+            0x80 | (len >> 14) as u8 & 0x7f,
+            0x80 | (len >> 7) as u8 & 0x7f,
+            len as u8 & 0x7f,
+        ]);
+        out.extend_from_slice(body);
+        out
+    }
+    let mut decoder_config = vec![0x40, 0x15]; // MPEG-4 audio, audio stream
+    decoder_config.extend_from_slice(&buffer_size.to_be_bytes()[1..]);
+    decoder_config.extend_from_slice(&max_bitrate.to_be_bytes());
+    decoder_config.extend_from_slice(&avg_bitrate.to_be_bytes());
+    decoder_config.extend_from_slice(&descriptor(0x05, asc));
+    let mut es = vec![0, 0, 0]; // ES_ID, flags
+    es.extend_from_slice(&descriptor(0x04, &decoder_config));
+    es.extend_from_slice(&descriptor(0x06, &[0x02]));
+    let mut payload = vec![0; 4]; // version, flags
+    payload.extend_from_slice(&descriptor(0x03, &es));
+    payload
+}
+
+/// AC-3 frame sizes in 16-bit words by frmsizecod, for 48, 44.1 and 32 kHz
+const AC3_FRAME_WORDS: [[u16; 38]; 3] = [
+    [
+        64, 64, 80, 80, 96, 96, 112, 112, 128, 128, 160, 160, 192, 192, 224, 224, 256, 256, 320,
+        320, 384, 384, 448, 448, 512, 512, 640, 640, 768, 768, 896, 896, 1024, 1024, 1152, 1152,
+        1280, 1280,
+    ],
+    [
+        69, 70, 87, 88, 104, 105, 121, 122, 139, 140, 174, 175, 208, 209, 243, 244, 278, 279, 348,
+        349, 417, 418, 487, 488, 557, 558, 696, 697, 835, 836, 975, 976, 1114, 1115, 1253, 1254,
+        1393, 1394,
+    ],
+    [
+        96, 96, 120, 120, 144, 144, 168, 168, 192, 192, 240, 240, 288, 288, 336, 336, 384, 384,
+        480, 480, 576, 576, 672, 672, 768, 768, 960, 960, 1152, 1152, 1344, 1344, 1536, 1536, 1728,
+        1728, 1920, 1920,
+    ],
+];
+
+fn read_ac3(data: &[u8]) -> Result<ElementaryStream, MediaError> {
+    let mut warnings = Vec::new();
+    let mut samples = Vec::new();
+    let mut dac3 = None;
+    let mut format = None;
+    let mut at = 0;
+    while at + 8 <= data.len() {
+        if data[at] != 0x0b || data[at + 1] != 0x77 {
+            warnings.push(format!(
+                "lost AC-3 sync at offset {}; the rest of the stream was dropped",
+                at
+            ));
+            break;
+        }
+        let fscod = data[at + 4] >> 6;
+        let frmsizecod = data[at + 4] & 0x3f;
+        let bsid = data[at + 5] >> 3;
+        if bsid > 10 {
+            return Err(MediaError::UnsupportedFormat(
+                "E-AC-3 streams are not supported".to_string(),
+            ));
+        }
+        let Some(words) = AC3_FRAME_WORDS
+            .get(fscod as usize)
+            .and_then(|sizes| sizes.get(frmsizecod as usize))
+        else {
+            return Err(MediaError::InvalidFormat(format!(
+                "invalid AC-3 frame header at offset {}",
+                at
+            )));
+        };
+        let frame_len = *words as usize * 2;
+        if at + frame_len > data.len() {
+            warnings.push(format!("truncated AC-3 frame at offset {} was dropped", at));
+            break;
+        }
+        if dac3.is_none() {
+            let mut r = BitReader::new(&data[at + 5..at + 8]);
+            r.skip(5); // bsid
+            let bsmod = r.read(3).unwrap_or(0);
+            let acmod = r.read(3).unwrap_or(0);
+            if acmod & 0x01 != 0 && acmod != 1 {
+                r.skip(2); // cmixlev
+            }
+            if acmod & 0x04 != 0 {
+                r.skip(2); // surmixlev
+            }
+            if acmod == 2 {
+                r.skip(2); // dsurmod
+            }
+            let lfeon = r.read(1).unwrap_or(0);
+            let bits = (fscod as u32) << 22
+                | (bsid as u32) << 17
+                | bsmod << 14
+                | acmod << 11
+                | lfeon << 10
+                | ((frmsizecod >> 1) as u32) << 5;
+            dac3 = Some(bits.to_be_bytes()[1..].to_vec());
+            let channels = [2, 1, 2, 3, 3, 4, 4, 5][acmod as usize] + lfeon as u16;
+            format = Some((channels, [48000, 44100, 32000][fscod as usize]));
+        }
+        samples.push(EsSample {
+            data: data[at..at + frame_len].to_vec(),
+            duration: 1536,
+            composition_offset: 0,
+            is_sync: true,
+        });
+        at += frame_len;
+    }
+    let (Some(dac3), Some((channels, sample_rate))) = (dac3, format) else {
+        return Err(MediaError::InvalidFormat(
+            "the stream has no AC-3 frames".to_string(),
+        ));
+    };
+    Ok(ElementaryStream {
+        kind: StreamKind::Ac3,
+        sample_entry: "ac-3",
+        config: (*b"dac3", dac3),
+        format: StreamFormat::Audio {
+            channels,
+            sample_rate,
+        },
+        timescale: sample_rate,
+        samples,
+        presentation_start: 0,
+        presentation_duration: None,
+        warnings,
+    })
+}
+
+fn read_opus(data: &[u8]) -> Result<ElementaryStream, MediaError> {
+    let mut warnings = Vec::new();
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut partial: Vec<u8> = Vec::new();
+    let mut serial = None;
+    let mut final_granule = None;
+    let mut at = 0;
+    while at + 27 <= data.len() {
+        if &data[at..at + 4] != b"OggS" {
+            warnings.push(format!(
+                "lost Ogg sync at offset {}; the rest of the file was dropped",
+                at
+            ));
+            break;
+        }
+        let granule = i64::from_le_bytes(data[at + 6..at + 14].try_into().unwrap());
+        let page_serial = u32::from_le_bytes(data[at + 14..at + 18].try_into().unwrap());
+        let segments = data[at + 26] as usize;
+        let Some(lacing) = data.get(at + 27..at + 27 + segments) else {
+            break;
+        };
+        let body_len: usize = lacing.iter().map(|&l| l as usize).sum();
+        let mut body = at + 27 + segments;
+        if body + body_len > data.len() {
+            warnings.push(format!("truncated Ogg page at offset {} was dropped", at));
+            break;
+        }
+        let next = body + body_len;
+        // 只取第一个逻辑流
+        if *serial.get_or_insert(page_serial) != page_serial {
+            if !warnings
+                .iter()
+                .any(|w: &String| w.starts_with("other logical"))
+            {
+                warnings.push("other logical streams in the Ogg file were ignored".to_string());
+            }
+            at = next;
+            continue;
+        }
+        for &len in lacing {
+            partial.extend_from_slice(&data[body..body + len as usize]);
+            body += len as usize;
+            if len < 255 {
+                packets.push(std::mem::take(&mut partial));
+            }
+        }
+        if granule >= 0 {
+            final_granule = Some(granule as u64);
+        }
+        at = next;
+    }
+
+    let head = packets
+        .first()
+        .filter(|p| p.starts_with(b"OpusHead") && p.len() >= 19)
+        .ok_or_else(|| {
+            MediaError::UnsupportedFormat("the Ogg stream does not carry Opus".to_string())
+        })?;
+    let channels = head[9];
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]);
+    let input_rate = u32::from_le_bytes(head[12..16].try_into().unwrap());
+    let gain = i16::from_le_bytes([head[16], head[17]]);
+    let family = head[18];
+    let mut dops = vec![0, channels];
+    dops.extend_from_slice(&pre_skip.to_be_bytes());
+    dops.extend_from_slice(&input_rate.to_be_bytes());
+    dops.extend_from_slice(&gain.to_be_bytes());
+    dops.push(family);
+    if family != 0 {
+        let table = head.get(19..21 + channels as usize).ok_or_else(|| {
+            MediaError::InvalidFormat("OpusHead channel mapping table is truncated".to_string())
+        })?;
+        dops.extend_from_slice(table);
+    }
+
+    let mut samples = Vec::new();
+    for packet in packets.iter().skip(2) {
+        match opus_packet_duration(packet) {
+            Some(duration) => samples.push(EsSample {
+                data: packet.clone(),
+                duration,
+                composition_offset: 0,
+                is_sync: true,
+            }),
+            None => warnings.push(format!(
+                "invalid Opus packet of {} bytes was dropped",
+                packet.len()
+            )),
+        }
+    }
+    let total: u64 = samples.iter().map(|s| s.duration as u64).sum();
+    // 最终 granule 之后的样本为填充, 由编辑列表截掉
+    let presentation_duration = final_granule
+        .filter(|&granule| granule < total && granule > pre_skip as u64)
+        .map(|granule| granule - pre_skip as u64);
+    Ok(ElementaryStream {
+        kind: StreamKind::Opus,
+        sample_entry: "Opus",
+        config: (*b"dOps", dops),
+        format: StreamFormat::Audio {
+            channels: channels as u16,
+            sample_rate: 48000,
+        },
+        timescale: 48000,
+        samples,
+        presentation_start: pre_skip as u64,
+        presentation_duration,
+        warnings,
+    })
+}
+
+/// Samples at 48 kHz of an Opus packet, from its TOC byte (RFC 6716 3.1)
+fn opus_packet_duration(packet: &[u8]) -> Option<u32> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+    // 帧长, 单位 1/400 秒 (2.5 ms)
+    let frame = match config {
+        0..=11 => [4, 8, 16, 24][config as usize % 4],
+        12..=15 => [4, 8][config as usize % 2],
+        _ => [1, 2, 4, 8][config as usize % 4],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3f) as u32,
+    };
+    (frames > 0).then_some(frames * frame * 120)
+}
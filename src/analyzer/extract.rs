@@ -0,0 +1,487 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::analyzer::bitstream::BitReader;
+use crate::error::MediaError;
+
+/// Elementary stream layout written by an extraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamFormat {
+    /// H.264/H.265 with start codes
+    AnnexB,
+    /// AAC frames with ADTS headers
+    Adts,
+    /// AV1 temporal units in an IVF file
+    Ivf,
+    /// AV1 low-overhead bitstream (temporal delimiter + OBUs)
+    Obu,
+    /// Opus in an Ogg stream
+    Ogg,
+}
+
+impl std::fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StreamFormat::AnnexB => "Annex B",
+            StreamFormat::Adts => "ADTS",
+            StreamFormat::Ivf => "IVF",
+            StreamFormat::Obu => "OBU",
+            StreamFormat::Ogg => "Ogg",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Outcome of writing one track (or RealMedia stream) as an elementary stream
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractReport {
+    /// Track ID, or the stream number of a RealMedia stream
+    pub track_id: u32,
+    pub codec: String,
+    pub format: StreamFormat,
+    /// Samples (access units, audio frames) written
+    pub samples: u64,
+    /// Keyframes that received the avcC/hvcC parameter sets
+    #[serde(skip_serializing_if = "is_zero")]
+    pub parameter_sets_inserted: u64,
+    pub bytes_written: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Length-prefixed NAL unit layout and parameter sets of an avcC or hvcC record
+#[derive(Debug, Clone)]
+pub struct NalConfig {
+    pub length_size: usize,
+    pub parameter_sets: Vec<Vec<u8>>,
+    pub hevc: bool,
+}
+
+impl NalConfig {
+    /// AVCDecoderConfigurationRecord (ISO/IEC 14496-15 5.3.3)
+    pub fn from_avcc(data: &[u8]) -> Option<Self> {
+        let length_size = (*data.get(4)? & 0x03) as usize + 1;
+        let mut at = 5;
+        let mut parameter_sets = Vec::new();
+        // SPS 数量占低 5 位, PPS 数量占整个字节
+        for mask in [0x1f, 0xff] {
+            let count = *data.get(at)? & mask;
+            at += 1;
+            for _ in 0..count {
+                let len = u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]) as usize;
+                parameter_sets.push(data.get(at + 2..at + 2 + len)?.to_vec());
+                at += 2 + len;
+            }
+        }
+        Some(Self {
+            length_size,
+            parameter_sets,
+            hevc: false,
+        })
+    }
+
+    /// HEVCDecoderConfigurationRecord (ISO/IEC 14496-15 8.3.3)
+    pub fn from_hvcc(data: &[u8]) -> Option<Self> {
+        let length_size = (*data.get(21)? & 0x03) as usize + 1;
+        let arrays = *data.get(22)?;
+        let mut at = 23;
+        let mut parameter_sets = Vec::new();
+        for _ in 0..arrays {
+            let count = u16::from_be_bytes([*data.get(at + 1)?, *data.get(at + 2)?]);
+            at += 3;
+            for _ in 0..count {
+                let len = u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]) as usize;
+                parameter_sets.push(data.get(at + 2..at + 2 + len)?.to_vec());
+                at += 2 + len;
+            }
+        }
+        Some(Self {
+            length_size,
+            parameter_sets,
+            hevc: true,
+        })
+    }
+
+    /// VPS/SPS/PPS (H.265) or SPS/PPS (H.264)
+    fn is_parameter_set(&self, nal: &[u8]) -> bool {
+        match (self.hevc, nal.first()) {
+            (true, Some(header)) => matches!((header >> 1) & 0x3f, 32..=34),
+            (false, Some(header)) => matches!(header & 0x1f, 7 | 8),
+            (_, None) => false,
+        }
+    }
+
+    /// Splits a length-prefixed sample into its NAL units
+    pub fn nal_units<'a>(&self, sample: &'a [u8]) -> Result<Vec<&'a [u8]>, MediaError> {
+        let mut units = Vec::new();
+        let mut at = 0;
+        while at < sample.len() {
+            let prefix = sample
+                .get(at..at + self.length_size)
+                .ok_or_else(|| MediaError::InvalidFormat("truncated NAL length".to_string()))?;
+            let len = prefix
+                .iter()
+                .fold(0usize, |len, &byte| len << 8 | byte as usize);
+            at += self.length_size;
+            let unit = sample.get(at..at + len).ok_or_else(|| {
+                MediaError::InvalidFormat(format!(
+                    "NAL unit of {} bytes exceeds the sample ({} bytes left)",
+                    len,
+                    sample.len() - at
+                ))
+            })?;
+            units.push(unit);
+            at += len;
+        }
+        Ok(units)
+    }
+}
+
+/// Converts length-prefixed samples to an Annex B byte stream
+pub struct AnnexBWriter<W: Write> {
+    out: W,
+    config: NalConfig,
+    pub parameter_sets_inserted: u64,
+    pub bytes_written: u64,
+}
+
+impl<W: Write> AnnexBWriter<W> {
+    pub fn new(out: W, config: NalConfig) -> Self {
+        Self {
+            out,
+            config,
+            parameter_sets_inserted: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Writes one access unit; keyframes without in-band parameter sets get the ones of the
+    /// configuration record first
+    pub fn write_sample(&mut self, sample: &[u8], keyframe: bool) -> Result<(), MediaError> {
+        let units = self.config.nal_units(sample)?;
+        if keyframe
+            && !self.config.parameter_sets.is_empty()
+            && !units.iter().any(|unit| self.config.is_parameter_set(unit))
+        {
+            for set in &self.config.parameter_sets {
+                self.out.write_all(&[0, 0, 0, 1])?;
+                self.out.write_all(set)?;
+                self.bytes_written += 4 + set.len() as u64;
+            }
+            self.parameter_sets_inserted += 1;
+        }
+        for unit in units {
+            self.out.write_all(&[0, 0, 0, 1])?;
+            self.out.write_all(unit)?;
+            self.bytes_written += 4 + unit.len() as u64;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), MediaError> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Sampling frequencies addressed by the 4-bit sampling_frequency_index
+pub const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// The fields of an AudioSpecificConfig that an ADTS header can carry
+#[derive(Debug, Clone, Copy)]
+pub struct AudioSpecificConfig {
+    /// Core audio object type (SBR/PS signalling resolved)
+    pub object_type: u8,
+    pub sampling_index: u8,
+    pub channel_config: u8,
+}
+
+impl AudioSpecificConfig {
+    /// ISO/IEC 14496-3 1.6.2.1
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut bits = BitReader::new(data);
+        let mut object_type = read_object_type(&mut bits)?;
+        let mut sampling_index = bits.read(4)? as u8;
+        if sampling_index == 15 {
+            sampling_index = sample_rate_index(bits.read(24)?)?;
+        }
+        let channel_config = bits.read(4)? as u8;
+        // 显式 SBR/PS: 之后是扩展采样率和核心编码类型, ADTS 只能描述核心层
+        if object_type == 5 || object_type == 29 {
+            if bits.read(4)? == 15 {
+                bits.read(24)?;
+            }
+            object_type = read_object_type(&mut bits)?;
+        }
+        Some(Self {
+            object_type,
+            sampling_index,
+            channel_config,
+        })
+    }
+
+    /// AudioSpecificConfig bytes of an esds box payload (ES_Descriptor → DecoderConfigDescriptor →
+    /// DecoderSpecificInfo)
+    pub fn from_esds(esds: &[u8]) -> Option<Vec<u8>> {
+        esds_descriptor(esds, 0x05).map(|body| body.to_vec())
+    }
+
+    /// Whether an ADTS header can describe the stream (AAC Main, LC, SSR or LTP)
+    pub fn fits_adts(&self) -> bool {
+        (1..=4).contains(&self.object_type) && self.sampling_index < 13
+    }
+
+    /// 7-byte ADTS header (no CRC) for a raw frame of `frame_len` bytes
+    pub fn adts_header(&self, frame_len: usize) -> [u8; 7] {
+        let len = frame_len + 7;
+        let profile = self.object_type - 1;
+        [
+            0xff,
+            0xf1,
+            profile << 6 | self.sampling_index << 2 | (self.channel_config >> 2) & 0x01,
+            (self.channel_config & 0x03) << 6 | ((len >> 11) & 0x03) as u8,
+            (len >> 3) as u8,
+            ((len & 0x07) as u8) << 5 | 0x1f,
+            0xfc,
+        ]
+    }
+}
+
+/// GetAudioObjectType() of ISO/IEC 14496-3 1.6.2.1
+pub fn read_object_type(bits: &mut BitReader) -> Option<u8> {
+    let object_type = bits.read(5)? as u8;
+    if object_type == 31 {
+        Some(32 + bits.read(6)? as u8)
+    } else {
+        Some(object_type)
+    }
+}
+
+fn sample_rate_index(rate: u32) -> Option<u8> {
+    AAC_SAMPLE_RATES
+        .iter()
+        .position(|&r| r == rate)
+        .map(|index| index as u8)
+}
+
+/// Body of the descriptor with `wanted` tag in an esds box payload, descending through the
+/// ES_Descriptor (0x03) and DecoderConfigDescriptor (0x04)
+fn esds_descriptor(esds: &[u8], wanted: u8) -> Option<&[u8]> {
+    let mut at = 4; // version + flags
+    let mut end = esds.len();
+    loop {
+        let tag = *esds.get(at)?;
+        let (len, header) = descriptor_length(esds.get(at + 1..end)?)?;
+        let body = at + 1 + header;
+        let body_end = (body + len).min(end);
+        if tag == wanted {
+            return esds.get(body..body_end);
+        }
+        match tag {
+            0x03 => {
+                // ES_ID, flags 以及可选字段
+                let flags = *esds.get(body + 2)?;
+                let mut next = body + 3;
+                if flags & 0x80 != 0 {
+                    next += 2;
+                }
+                if flags & 0x40 != 0 {
+                    next += 1 + *esds.get(next)? as usize;
+                }
+                if flags & 0x20 != 0 {
+                    next += 2;
+                }
+                at = next;
+                end = body_end;
+            }
+            0x04 => {
+                at = body + 13;
+                end = body_end;
+            }
+            _ => at = body_end,
+        }
+        if at >= end {
+            return None;
+        }
+    }
+}
+
+/// bufferSizeDB, maxBitrate and avgBitrate of the DecoderConfigDescriptor of an esds box payload
+pub fn esds_bitrates(esds: &[u8]) -> Option<(u32, u32, u32)> {
+    let config = esds_descriptor(esds, 0x04)?;
+    let field = |at: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(config.get(at..at + 4)?.try_into().ok()?))
+    };
+    Some((field(1)? & 0x00ff_ffff, field(5)?, field(9)?))
+}
+
+/// Expandable descriptor size: up to four bytes of 7 bits. Returns (length, bytes used).
+fn descriptor_length(data: &[u8]) -> Option<(usize, usize)> {
+    let mut len = 0;
+    for (i, &byte) in data.iter().take(4).enumerate() {
+        len = len << 7 | (byte & 0x7f) as usize;
+        if byte & 0x80 == 0 {
+            return Some((len, i + 1));
+        }
+    }
+    None
+}
+
+/// IVF container: 32-byte file header and a 12-byte header per frame
+pub struct IvfWriter<W: Write> {
+    out: W,
+    pub bytes_written: u64,
+}
+
+impl<W: Write> IvfWriter<W> {
+    pub fn new(
+        mut out: W,
+        fourcc: &[u8; 4],
+        (width, height): (u16, u16),
+        timescale: u32,
+        frame_count: u32,
+    ) -> Result<Self, MediaError> {
+        out.write_all(b"DKIF")?;
+        out.write_all(&0u16.to_le_bytes())?;
+        out.write_all(&32u16.to_le_bytes())?;
+        out.write_all(fourcc)?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        out.write_all(&timescale.to_le_bytes())?;
+        out.write_all(&1u32.to_le_bytes())?;
+        out.write_all(&frame_count.to_le_bytes())?;
+        out.write_all(&[0; 4])?;
+        Ok(Self {
+            out,
+            bytes_written: 32,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8], pts: u64) -> Result<(), MediaError> {
+        self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&pts.to_le_bytes())?;
+        self.out.write_all(frame)?;
+        self.bytes_written += 12 + frame.len() as u64;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), MediaError> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// AV1 temporal delimiter OBU (type 2, has_size_field, empty payload)
+pub const AV1_TEMPORAL_DELIMITER: [u8; 2] = [0x12, 0x00];
+
+/// OpusHead packet (RFC 7845 5.1) built from the big-endian fields of a dOps box
+pub fn opus_head_from_dops(dops: &[u8]) -> Option<Vec<u8>> {
+    let channels = *dops.get(1)?;
+    let pre_skip = u16::from_be_bytes([*dops.get(2)?, *dops.get(3)?]);
+    let input_rate = u32::from_be_bytes(dops.get(4..8)?.try_into().ok()?);
+    let gain = i16::from_be_bytes([*dops.get(8)?, *dops.get(9)?]);
+    let family = *dops.get(10)?;
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&gain.to_le_bytes());
+    head.push(family);
+    if family != 0 {
+        // stream count, coupled count, channel mapping
+        head.extend_from_slice(dops.get(11..13 + channels as usize)?);
+    }
+    Some(head)
+}
+
+/// Ogg bitstream with one packet per page (RFC 3533)
+pub struct OggWriter<W: Write> {
+    out: W,
+    serial: u32,
+    sequence: u32,
+    pub bytes_written: u64,
+}
+
+impl<W: Write> OggWriter<W> {
+    pub fn new(out: W, serial: u32) -> Self {
+        Self {
+            out,
+            serial,
+            sequence: 0,
+            bytes_written: 0,
+        }
+    }
+
+    /// Writes `packet` as a page: header_type 0x02 starts the stream, 0x04 ends it
+    pub fn write_page(
+        &mut self,
+        packet: &[u8],
+        header_type: u8,
+        granule: u64,
+    ) -> Result<(), MediaError> {
+        let mut lacing = vec![255u8; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        if lacing.len() > 255 {
+            return Err(MediaError::InvalidFormat(format!(
+                "packet of {} bytes does not fit in an Ogg page",
+                packet.len()
+            )));
+        }
+        let mut page = b"OggS".to_vec();
+        page.push(0);
+        page.push(header_type);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(packet);
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        self.out.write_all(&page)?;
+        self.bytes_written += page.len() as u64;
+        self.sequence += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), MediaError> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// CRC-32 with polynomial 0x04c11db7, no reflection, zero initial value
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                crc << 1 ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// OpusTags packet with only the vendor string
+pub fn opus_tags() -> Vec<u8> {
+    let vendor = b"media-inspector";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
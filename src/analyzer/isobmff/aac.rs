@@ -0,0 +1,226 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::faststart::read_bytes;
+use super::sample_index::TrackSampleIndex;
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::aac::{AacConfig, AdtsHeader, ElementType, RawDataBlock, WindowSequence};
+use crate::analyzer::extract::AudioSpecificConfig;
+use crate::error::MediaError;
+
+/// One AAC access unit
+#[derive(Debug, Clone, Serialize)]
+pub struct AacFrame {
+    pub sample: u32,
+    pub dts: u64,
+    pub duration: u32,
+    pub size: u32,
+    /// The sample starts with an ADTS header, which does not belong in an MP4 sample
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adts: Option<AdtsHeader>,
+    pub block: RawDataBlock,
+    /// A trailing fill element carries SBR data
+    pub sbr: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowSequenceCount {
+    pub window_sequence: WindowSequence,
+    pub count: u32,
+}
+
+/// Frame-level structure of an `mp4a` track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackAacFrames {
+    pub track_id: u32,
+    pub timescale: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<AacConfig>,
+    pub profile: String,
+    /// Elements each raw_data_block should hold for the channel configuration
+    pub expected_elements: Vec<ElementType>,
+    /// stts duration of a frame: frame length scaled from the core sampling rate to the
+    /// media timescale
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_duration: Option<u32>,
+    pub frames: Vec<AacFrame>,
+    pub window_sequences: Vec<WindowSequenceCount>,
+    /// Frames whose fill element carries SBR data (implicit or explicit HE-AAC)
+    pub sbr_frames: u32,
+    pub adts_frames: u32,
+    /// Samples, other than the last one, whose stts duration is not `expected_duration`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duration_mismatches: Vec<u32>,
+    /// Samples with a problem of any kind
+    pub frames_with_problems: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AacReport {
+    pub tracks: Vec<TrackAacFrames>,
+}
+
+/// Parses the raw_data_block (or ADTS) header of every sample of the `mp4a` tracks: element
+/// types, global gain, window sequence and SBR fill elements, and checks the sample durations
+/// against the frame length
+pub fn analyze_aac_frames(input: &Path) -> Result<AacReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let mut source = File::open(input)?;
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        let Some(entry) = track.sample_entry() else {
+            continue;
+        };
+        if entry.entry_type() != "mp4a" {
+            continue;
+        }
+        let mut warnings = index.warnings.clone();
+        let config = entry
+            .child_box("esds")
+            .and_then(AudioSpecificConfig::from_esds)
+            .and_then(|asc| AacConfig::parse(&asc));
+        if config.is_none() {
+            warnings.push("esds has no readable AudioSpecificConfig".to_string());
+        }
+        let expected_duration = config.and_then(|config| {
+            let scaled = config.frame_length as u64 * index.timescale as u64;
+            (config.sample_rate > 0 && scaled.is_multiple_of(config.sample_rate as u64))
+                .then(|| (scaled / config.sample_rate as u64) as u32)
+        });
+        if config.is_some() && expected_duration.is_none() {
+            warnings.push(format!(
+                "the media timescale {} is not a multiple of the AAC sampling rate",
+                index.timescale
+            ));
+        }
+        let object_type = config.map_or(2, |config| config.object_type);
+        let expected_elements =
+            ElementType::layout(config.map_or(0, |config| config.channel_config)).to_vec();
+
+        let mut result = TrackAacFrames {
+            track_id: track.track_id(),
+            timescale: index.timescale,
+            config,
+            profile: config.map_or("unknown".to_string(), |config| config.profile()),
+            expected_elements,
+            expected_duration,
+            frames: Vec::with_capacity(index.samples.len()),
+            window_sequences: Vec::new(),
+            sbr_frames: 0,
+            adts_frames: 0,
+            duration_mismatches: Vec::new(),
+            frames_with_problems: 0,
+            warnings,
+        };
+        let mut previous_window = None;
+        for (i, sample) in index.samples.iter().enumerate() {
+            let data = read_bytes(&mut source, sample.offset, sample.size as u64)?;
+            let adts = AdtsHeader::parse(&data).filter(|adts| adts.frame_length == sample.size);
+            let mut problems = Vec::new();
+            let payload = match adts {
+                Some(adts) => {
+                    problems.push("sample starts with an ADTS header".to_string());
+                    if adts.raw_data_blocks > 1 {
+                        problems.push(format!(
+                            "ADTS frame holds {} raw data blocks; only the first one was parsed",
+                            adts.raw_data_blocks
+                        ));
+                    }
+                    data.get(adts.header_length()..).unwrap_or_default()
+                }
+                None => &data[..],
+            };
+            let block = RawDataBlock::parse(payload, object_type);
+
+            if let (Some(audio), Some(&first)) = (&block.audio, result.expected_elements.first()) {
+                if audio.element != first {
+                    problems.push(format!(
+                        "first audio element is {}, the channel configuration expects {}",
+                        audio.element, first
+                    ));
+                }
+            }
+            if let Some(window) = block.audio.and_then(|audio| audio.window_sequence) {
+                if let Some(previous) = previous_window {
+                    if !window_follows(previous, window) {
+                        problems.push(format!("window sequence {} follows {}", window, previous));
+                    }
+                }
+                previous_window = Some(window);
+                match result
+                    .window_sequences
+                    .iter_mut()
+                    .find(|count| count.window_sequence == window)
+                {
+                    Some(count) => count.count += 1,
+                    None => result.window_sequences.push(WindowSequenceCount {
+                        window_sequence: window,
+                        count: 1,
+                    }),
+                }
+            }
+            if let Some(expected) = expected_duration {
+                if sample.duration != expected && i + 1 < index.samples.len() {
+                    result.duration_mismatches.push(sample.number);
+                    problems.push(format!(
+                        "stts duration {} instead of {}",
+                        sample.duration, expected
+                    ));
+                }
+            }
+
+            let sbr = block
+                .trailing_fill
+                .is_some_and(|fill| fill.extension == "sbr");
+            result.sbr_frames += sbr as u32;
+            result.adts_frames += adts.is_some() as u32;
+            if !problems.is_empty() || !block.problems.is_empty() {
+                result.frames_with_problems += 1;
+            }
+            result.frames.push(AacFrame {
+                sample: sample.number,
+                dts: sample.dts,
+                duration: sample.duration,
+                size: sample.size,
+                adts,
+                block,
+                sbr,
+                problems,
+            });
+        }
+        if result.sbr_frames > 0 && config.is_some_and(|config| !config.sbr) {
+            result.warnings.push(
+                "SBR data found in the frames but not signalled in the AudioSpecificConfig (implicit HE-AAC)"
+                    .to_string(),
+            );
+        }
+        tracks.push(result);
+    }
+    if tracks.is_empty() {
+        return Err(MediaError::UnsupportedFormat(
+            "the file has no mp4a track".to_string(),
+        ));
+    }
+    Ok(AacReport { tracks })
+}
+
+/// Window sequence transitions allowed by ISO/IEC 14496-3 4.6.11.3.1
+fn window_follows(previous: WindowSequence, next: WindowSequence) -> bool {
+    use WindowSequence::*;
+    matches!(
+        (previous, next),
+        (OnlyLong | LongStop, OnlyLong | LongStart)
+            | (LongStart | EightShort, EightShort | LongStop)
+    )
+}
@@ -0,0 +1,328 @@
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo, SampleGroupEntry};
+use super::sample_index::TrackSampleIndex;
+use super::timeline::TrackTimeline;
+use super::track::{self, Track};
+
+/// ITU-R BT.1359 detectability thresholds, in milliseconds
+const AUDIO_LEAD_DETECTABLE_MS: f64 = 45.0;
+const AUDIO_LAG_DETECTABLE_MS: f64 = 125.0;
+/// ITU-R BT.1359 acceptability thresholds, in milliseconds
+const AUDIO_LEAD_ACCEPTABLE_MS: f64 = 90.0;
+const AUDIO_LAG_ACCEPTABLE_MS: f64 = 185.0;
+
+/// Where the first decoding time of a track comes from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeTimeSource {
+    /// Sample table of the moov (stts starts at 0)
+    Stts,
+    /// tfdt of the first track fragment
+    Tfdt,
+    /// Neither the sample table nor a fragment has samples
+    None,
+}
+
+/// Encoder delay/padding from the iTunSMPB freeform metadata item
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ITunSmpb {
+    pub encoder_delay: u32,
+    pub end_padding: u32,
+    pub original_sample_count: u64,
+}
+
+impl ITunSmpb {
+    /// Parses " 00000000 00000840 000001CA 00000000003F31F6 ..." (hex fields)
+    pub fn parse(text: &str) -> Option<ITunSmpb> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() < 4 {
+            return None;
+        }
+        Some(ITunSmpb {
+            encoder_delay: u32::from_str_radix(fields[1], 16).ok()?,
+            end_padding: u32::from_str_radix(fields[2], 16).ok()?,
+            original_sample_count: u64::from_str_radix(fields[3], 16).ok()?,
+        })
+    }
+
+    /// Formats the value as iTunes writes it: the three counts between zero fields
+    pub fn to_text(self) -> String {
+        format!(
+            " 00000000 {:08X} {:08X} {:016X}{}",
+            self.encoder_delay,
+            self.end_padding,
+            self.original_sample_count,
+            " 00000000".repeat(8)
+        )
+    }
+}
+
+/// Audio priming as signalled by the different mechanisms
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioPriming {
+    /// Negative roll distance of the 'roll' sample group (pre-roll in frames)
+    pub roll_distance: Option<i16>,
+    pub itunsmpb: Option<ITunSmpb>,
+    /// Samples skipped by the first non-empty edit (media_time)
+    pub edit_skip: Option<i64>,
+    /// Priming actually removed from the presentation, in seconds
+    pub compensated: f64,
+}
+
+/// Start of one track on the movie timeline
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackStart {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub timescale: u32,
+    /// DTS of the first sample, in seconds
+    pub first_dts: f64,
+    pub first_dts_source: DecodeTimeSource,
+    /// Earliest composition time of the media, in seconds
+    pub first_cts: f64,
+    /// Earliest CTS minus first DTS (reordering delay), in seconds
+    pub cts_shift: f64,
+    /// Leading empty edits, in seconds
+    pub edit_delay: f64,
+    /// Media time at which presentation starts (first non-empty edit), in seconds
+    pub edit_media_start: f64,
+    /// First presented timestamp on the movie timeline, in seconds
+    pub first_presented: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priming: Option<AudioPriming>,
+}
+
+/// How noticeable an audio/video offset is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncVerdict {
+    InSync,
+    Detectable,
+    Unacceptable,
+}
+
+/// Start offset between one audio and one video track
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncPair {
+    pub video_track_id: u32,
+    pub audio_track_id: u32,
+    /// Audio start minus video start in milliseconds (positive = audio late)
+    pub offset_ms: f64,
+    pub verdict: SyncVerdict,
+}
+
+/// Cross-track start offsets and lip-sync assessment
+#[derive(Debug, Clone, Serialize)]
+pub struct AvSyncReport {
+    pub tracks: Vec<TrackStart>,
+    pub pairs: Vec<SyncPair>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl SyncVerdict {
+    pub fn classify(offset_ms: f64) -> SyncVerdict {
+        // 音频提前为负, 落后为正
+        let (detectable, acceptable) = if offset_ms < 0.0 {
+            (AUDIO_LEAD_DETECTABLE_MS, AUDIO_LEAD_ACCEPTABLE_MS)
+        } else {
+            (AUDIO_LAG_DETECTABLE_MS, AUDIO_LAG_ACCEPTABLE_MS)
+        };
+        if offset_ms.abs() > acceptable {
+            SyncVerdict::Unacceptable
+        } else if offset_ms.abs() > detectable {
+            SyncVerdict::Detectable
+        } else {
+            SyncVerdict::InSync
+        }
+    }
+}
+
+impl AvSyncReport {
+    /// Builds the report from the box tree, the sample indexes and the timelines
+    pub fn build(
+        boxes: &[Box],
+        indexes: &[TrackSampleIndex],
+        timelines: &[TrackTimeline],
+    ) -> AvSyncReport {
+        let mut warnings = Vec::new();
+        let itunsmpb = track::movie_item_list(boxes)
+            .and_then(|ilst| ilst.item("----:com.apple.iTunes:iTunSMPB"))
+            .and_then(|item| item.text())
+            .and_then(|text| ITunSmpb::parse(&text));
+        let mut itunsmpb_used = false;
+
+        let mut tracks = Vec::new();
+        for track in Track::all(boxes) {
+            let track_id = track.track_id();
+            let timescale = track.timescale();
+            let seconds = |value: i64| -> f64 {
+                if timescale == 0 {
+                    0.0
+                } else {
+                    value as f64 / timescale as f64
+                }
+            };
+
+            let index = indexes.iter().find(|index| index.track_id == track_id);
+            let (first_dts, first_cts, source) = match index {
+                Some(index) if !index.samples.is_empty() => (
+                    index.samples[0].dts as i64,
+                    index.samples.iter().map(|s| s.cts).min().unwrap_or(0),
+                    DecodeTimeSource::Stts,
+                ),
+                _ => match first_fragment_times(boxes, track_id) {
+                    Some((dts, cts)) => (dts, cts, DecodeTimeSource::Tfdt),
+                    None => (0, 0, DecodeTimeSource::None),
+                },
+            };
+
+            let timeline = timelines.iter().find(|t| t.track_id == track_id);
+            let edit_delay = timeline.map(|t| t.presentation_start).unwrap_or(0.0);
+            let edit_media_start = timeline.map(|t| t.media_start).unwrap_or(0.0);
+            let first_presented = edit_delay + (seconds(first_cts) - edit_media_start).max(0.0);
+
+            let handler_type = track.handler_type();
+            let priming = if handler_type == "soun" {
+                let edit_skip = track.edit_list().and_then(|elst| {
+                    elst.entries()
+                        .iter()
+                        .find(|entry| entry.media_time >= 0)
+                        .map(|entry| entry.media_time)
+                });
+                let priming = AudioPriming {
+                    roll_distance: roll_distance(&track),
+                    // iTunSMPB 是整部影片的元数据, 归属于第一条音轨
+                    itunsmpb: if itunsmpb_used { None } else { itunsmpb },
+                    edit_skip,
+                    compensated: edit_media_start,
+                };
+                itunsmpb_used = true;
+                check_priming(track_id, &priming, &mut warnings);
+                Some(priming)
+            } else {
+                None
+            };
+
+            tracks.push(TrackStart {
+                track_id,
+                handler_type,
+                timescale,
+                first_dts: seconds(first_dts),
+                first_dts_source: source,
+                first_cts: seconds(first_cts),
+                cts_shift: seconds(first_cts - first_dts),
+                edit_delay,
+                edit_media_start,
+                first_presented,
+                priming,
+            });
+        }
+
+        for track in &tracks {
+            if track.handler_type == "vide"
+                && track.cts_shift > 0.0
+                && track.edit_media_start == 0.0
+            {
+                warnings.push(format!(
+                    "track {}: composition offsets delay the first frame by {:.1} ms and no edit list compensates",
+                    track.track_id,
+                    track.cts_shift * 1000.0
+                ));
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for video in tracks.iter().filter(|t| t.handler_type == "vide") {
+            for audio in tracks.iter().filter(|t| t.handler_type == "soun") {
+                let offset_ms = (audio.first_presented - video.first_presented) * 1000.0;
+                let verdict = SyncVerdict::classify(offset_ms);
+                if verdict != SyncVerdict::InSync {
+                    warnings.push(format!(
+                        "audio track {} {} video track {} by {:.1} ms ({})",
+                        audio.track_id,
+                        if offset_ms < 0.0 { "leads" } else { "lags" },
+                        video.track_id,
+                        offset_ms.abs(),
+                        if verdict == SyncVerdict::Unacceptable {
+                            "likely lip-sync problem"
+                        } else {
+                            "may be noticeable"
+                        }
+                    ));
+                }
+                pairs.push(SyncPair {
+                    video_track_id: video.track_id,
+                    audio_track_id: audio.track_id,
+                    offset_ms,
+                    verdict,
+                });
+            }
+        }
+
+        AvSyncReport {
+            tracks,
+            pairs,
+            warnings,
+        }
+    }
+}
+
+fn check_priming(track_id: u32, priming: &AudioPriming, warnings: &mut Vec<String>) {
+    let Some(smpb) = priming.itunsmpb else {
+        return;
+    };
+    match priming.edit_skip {
+        Some(skip) if skip != smpb.encoder_delay as i64 => warnings.push(format!(
+            "track {}: edit list skips {} samples but iTunSMPB declares an encoder delay of {}",
+            track_id, skip, smpb.encoder_delay
+        )),
+        None if smpb.encoder_delay > 0 => warnings.push(format!(
+            "track {}: {} priming samples are only signalled by iTunSMPB, players ignoring it start late",
+            track_id, smpb.encoder_delay
+        )),
+        _ => {}
+    }
+}
+
+/// Roll distance of the first 'roll' sample group description of the track
+fn roll_distance(track: &Track) -> Option<i16> {
+    let stbl = track.sample_table()?;
+    stbl.children().iter().find_map(|child| match child.data() {
+        BoxData::SampleGroupDescription(sgpd) if sgpd.grouping_type() == "roll" => {
+            match sgpd.entry(1) {
+                Some(SampleGroupEntry::Roll { roll_distance }) => Some(*roll_distance),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// (tfdt, tfdt + first composition offset) of the first fragment of a track
+fn first_fragment_times(boxes: &[Box], track_id: u32) -> Option<(i64, i64)> {
+    let traf = boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "moof")
+        .flat_map(|moof| moof.children())
+        .filter(|child| child.box_type().as_str() == "traf")
+        .find(|traf| match traf.child("tfhd").map(|b| b.data()) {
+            Some(BoxData::TrackFragmentHeader(tfhd)) => tfhd.track_id() == track_id,
+            _ => false,
+        })?;
+
+    let dts = match traf.child("tfdt")?.data() {
+        BoxData::TrackFragmentDecodeTime(tfdt) => tfdt.base_media_decode_time() as i64,
+        _ => return None,
+    };
+    let offset = match traf.child("trun").map(|b| b.data()) {
+        Some(BoxData::TrackRun(trun)) => trun
+            .entries()
+            .first()
+            .and_then(|entry| entry.composition_time_offset)
+            .unwrap_or(0),
+        _ => 0,
+    };
+    Some((dts, dts + offset))
+}
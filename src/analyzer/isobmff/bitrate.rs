@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use super::sample_index::TrackSampleIndex;
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::bitrate::{DeclaredBitrate, TimedUnit, TrackBitrate};
+use crate::analyzer::extract::esds_bitrates;
+use crate::error::MediaError;
+
+/// Bitrate over time of every track from its sample sizes and decoding times, with the rates
+/// declared in the `btrt` and `esds` boxes of its sample entry
+pub fn track_bitrates(input: &Path, windows: &[f64]) -> Result<Vec<TrackBitrate>, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        let entry = track.sample_entry();
+        let codec = entry.map_or(index.handler_type.clone(), |entry| {
+            entry.entry_type().to_string()
+        });
+        let mut declared = Vec::new();
+        if let Some(btrt) = entry.and_then(|entry| entry.child_box("btrt")) {
+            let field = |at: usize| {
+                btrt.get(at..at + 4)
+                    .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            };
+            declared.push(DeclaredBitrate {
+                source: "btrt",
+                buffer_size: field(0),
+                max: field(4).map(u64::from),
+                average: field(8).map(u64::from),
+            });
+        }
+        if let Some((buffer_size, max, average)) = entry
+            .and_then(|entry| entry.child_box("esds"))
+            .and_then(esds_bitrates)
+        {
+            declared.push(DeclaredBitrate {
+                source: "esds",
+                buffer_size: Some(buffer_size),
+                max: Some(max as u64),
+                average: Some(average as u64),
+            });
+        }
+
+        let timescale = index.timescale.max(1) as f64;
+        let units = index
+            .samples
+            .iter()
+            .map(|sample| TimedUnit {
+                time: sample.dts as f64 / timescale,
+                duration: sample.duration as f64 / timescale,
+                bytes: sample.size as u64,
+            })
+            .collect();
+        let mut result = TrackBitrate::new(index.track_id, codec, units, windows, declared);
+        result.warnings.splice(0..0, index.warnings.iter().cloned());
+        tracks.push(result);
+    }
+    Ok(tracks)
+}
@@ -1,16 +1,27 @@
 use super::boxes::{
-    Box, BoxData, BoxInfo, ChunkOffsetBox, DataEntryBox, DataInformationBox, DataReferenceBox,
-    EditBox, EditListBox, EditListEntry, FileTypeBox, GenericBox, HandlerBox, MediaBox,
-    MediaDataBox, MediaHeaderBox, MediaInfoBox, MovieBox, MovieHeaderBox, SampleDescriptionBox,
-    SampleEntry, SampleSizeBox, SampleTableBox, SampleToChunkBox, SampleToChunkEntry,
-    SoundMediaHeaderBox, TimeToSampleBox, TimeToSampleEntry, TrackBox, TrackHeaderBox,
-    VideoMediaHeaderBox,
+    Box, BoxData, BoxInfo, ChunkOffsetBox, CompositionOffsetBox, CompositionOffsetEntry,
+    DataEntryBox, DataInformationBox, DataReferenceBox, EditBox, EditListBox, EditListEntry,
+    FileTypeBox, GenericBox, HandlerBox, ItemListBox, MediaBox, MediaDataBox, MediaHeaderBox,
+    MediaInfoBox, MetaBox, MetadataItem, MetadataValue, MovieBox, MovieExtendsBox,
+    MovieExtendsHeaderBox, MovieFragmentBox, MovieFragmentHeaderBox, MovieHeaderBox, RawBytes,
+    SampleDescriptionBox, SampleEntry, SampleGroupDescriptionBox, SampleGroupEntry, SampleSizeBox,
+    SampleTableBox, SampleToChunkBox, SampleToChunkEntry, SampleToGroupBox, SampleToGroupEntry,
+    SegmentIndexBox, SegmentReference, SoundMediaHeaderBox, SyncSampleBox, TimeToSampleBox,
+    TimeToSampleEntry, TrackBox, TrackExtendsBox, TrackFragmentBox, TrackFragmentDecodeTimeBox,
+    TrackFragmentHeaderBox, TrackHeaderBox, TrackRunBox, TrackRunEntry, UserDataBox,
+    VideoMediaHeaderBox, TFHD_BASE_DATA_OFFSET, TFHD_DEFAULT_SAMPLE_DURATION,
+    TFHD_DEFAULT_SAMPLE_FLAGS, TFHD_DEFAULT_SAMPLE_SIZE, TFHD_SAMPLE_DESCRIPTION_INDEX,
+    TRUN_DATA_OFFSET, TRUN_FIRST_SAMPLE_FLAGS, TRUN_SAMPLE_COMPOSITION_TIME_OFFSET,
+    TRUN_SAMPLE_DURATION, TRUN_SAMPLE_FLAGS, TRUN_SAMPLE_SIZE,
 };
 use super::types::{BoxType, Fixed16_16, Matrix, Mp4DateTime};
 use crate::error::MediaError;
 use crate::reader::Reader;
 use std::io::SeekFrom;
 
+/// Unparsed bytes up to this size are loaded, larger runs stay in the file
+const MAX_RETAINED_BYTES: u64 = 16 * 1024 * 1024;
+
 pub struct BoxParser {
     reader: Reader,
     debug: bool,
@@ -28,15 +39,41 @@ impl BoxParser {
         self.debug = debug;
     }
 
+    pub fn file_size(&mut self) -> Result<u64, MediaError> {
+        Ok(self.reader.get_file_size()?)
+    }
+
+    /// Reads `len` bytes at an absolute file offset
+    pub fn read_at(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, MediaError> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0u8; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Keeps `len` bytes verbatim so the box can be written back unchanged
+    fn read_raw(&mut self, len: u64) -> Result<RawBytes, MediaError> {
+        let offset = self.reader.position();
+        if len > MAX_RETAINED_BYTES || offset + len > self.reader.get_file_size()? {
+            self.reader.seek(SeekFrom::Start(offset + len))?;
+            return Ok(RawBytes::InFile { offset, len });
+        }
+        let mut bytes = vec![0u8; len as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(RawBytes::Loaded(bytes))
+    }
+
     pub fn parse_boxes(&mut self) -> Result<Vec<Box>, MediaError> {
         let mut boxes = Vec::new();
-        while let Ok(box_info) = self.parse_box() {
+        while let Ok(box_info) = self.parse_box(false) {
             boxes.push(box_info);
         }
         Ok(boxes)
     }
 
-    fn parse_box(&mut self) -> Result<Box, MediaError> {
+    /// Parses one box; `any_type` accepts 4CCs outside the known list, as user data and meta
+    /// boxes hold arbitrary (e.g. `©xyz`) children
+    fn parse_box(&mut self, any_type: bool) -> Result<Box, MediaError> {
         let offset = self.reader.position();
 
         // Read box size
@@ -57,6 +94,11 @@ impl BoxParser {
             (size as u64, 8)
         };
 
+        // 在解析 box 时会验证 box 大小和类型
+        if actual_size < header_size {
+            return Err(MediaError::InvalidBoxSize);
+        }
+
         if self.debug {
             println!(
                 "Parsing box: type={}, offset={}, size={}, header_size={}, data_size={}",
@@ -68,18 +110,13 @@ impl BoxParser {
             );
         }
 
-        // 在解析 box 时会验证 box 大小和类型
-        if size < 8 {
-            return Err(MediaError::InvalidBoxSize);
-        }
-
-        if !is_valid_box_type(&box_type) {
+        if !any_type && !is_valid_box_type(&box_type) {
             return Err(MediaError::InvalidBoxType(format!("{:?}", box_type)));
         }
 
         // Parse box data based on type
         let data = match box_type.as_str() {
-            "ftyp" => self.parse_ftyp(actual_size - header_size)?,
+            "ftyp" | "styp" => self.parse_ftyp(actual_size - header_size)?,
             "moov" => BoxData::Movie(self.parse_moov(actual_size - header_size)?),
             "mvhd" => self.parse_mvhd(actual_size - header_size)?,
             "trak" => BoxData::Track(self.parse_trak(actual_size - header_size)?),
@@ -98,9 +135,27 @@ impl BoxParser {
             "stts" => self.parse_stts(actual_size - header_size)?,
             "stsc" => self.parse_stsc(actual_size - header_size)?,
             "stsz" => self.parse_stsz(actual_size - header_size)?,
-            "stco" => self.parse_stco(actual_size - header_size)?,
+            "stco" => self.parse_stco(actual_size - header_size, false)?,
+            "co64" => self.parse_stco(actual_size - header_size, true)?,
+            "ctts" => self.parse_ctts(actual_size - header_size)?,
+            "stss" => self.parse_stss(actual_size - header_size)?,
+            "sbgp" => self.parse_sbgp(actual_size - header_size)?,
+            "sgpd" => self.parse_sgpd(actual_size - header_size)?,
             "dinf" => BoxData::DataInformation(self.parse_dinf(actual_size - header_size)?),
             "dref" => BoxData::DataReference(self.parse_dref(actual_size - header_size)?),
+            "moof" => BoxData::MovieFragment(MovieFragmentBox::new()),
+            "mfhd" => self.parse_mfhd(actual_size - header_size)?,
+            "traf" => BoxData::TrackFragment(TrackFragmentBox::new()),
+            "tfhd" => self.parse_tfhd(actual_size - header_size)?,
+            "tfdt" => self.parse_tfdt(actual_size - header_size)?,
+            "trun" => self.parse_trun(actual_size - header_size)?,
+            "udta" => BoxData::UserData(UserDataBox::new()),
+            "meta" => self.parse_meta()?,
+            "ilst" => self.parse_ilst(actual_size - header_size)?,
+            "mvex" => BoxData::MovieExtends(MovieExtendsBox::new()),
+            "mehd" => self.parse_mehd(actual_size - header_size)?,
+            "trex" => self.parse_trex(actual_size - header_size)?,
+            "sidx" => self.parse_sidx(actual_size - header_size)?,
             _ => {
                 if self.debug {
                     println!(
@@ -110,18 +165,19 @@ impl BoxParser {
                         actual_size
                     );
                 }
-                // Skip the data content but still create a box with the original 4CC type
-                self.reader.skip(actual_size - header_size)?;
-                BoxData::Generic(GenericBox::new(box_type.clone()))
+                // Keep the data content untouched and create a box with the original 4CC type
+                let payload = self.read_raw(actual_size - header_size)?;
+                BoxData::Generic(GenericBox::new(box_type.clone(), payload))
             }
         };
 
-        let mut box_info = Box::new(box_type, actual_size, offset, data);
+        let mut box_info = Box::new(box_type, actual_size, offset, header_size, data);
+        let end_offset = offset + actual_size;
 
         // Parse children for container boxes
         match box_info.box_type().as_str() {
-            "moov" | "trak" | "mdia" | "minf" | "stbl" | "dinf" | "edts" => {
-                let end_offset = offset + actual_size;
+            "moov" | "trak" | "mdia" | "minf" | "stbl" | "dinf" | "edts" | "moof" | "traf"
+            | "udta" | "meta" | "mvex" => {
                 if self.debug {
                     println!(
                         "Parsing container box: type={}, start={}, end={}",
@@ -130,23 +186,34 @@ impl BoxParser {
                         end_offset
                     );
                 }
+                let any_type = matches!(box_info.box_type().as_str(), "udta" | "meta");
                 while self.reader.position() < end_offset {
-                    if let Ok(child) = self.parse_box() {
+                    let child_offset = self.reader.position();
+                    if let Ok(child) = self.parse_box(any_type) {
                         box_info.add_child(child);
                     } else {
                         if self.debug {
                             println!(
                                 "Error parsing child box at offset {}, skipping to {}",
-                                self.reader.position(),
-                                end_offset
+                                child_offset, end_offset
                             );
                         }
-                        self.reader.seek(SeekFrom::Start(end_offset))?;
+                        // 保留无法解析的剩余字节, 写回时原样输出
+                        self.reader.seek(SeekFrom::Start(child_offset))?;
+                        box_info.set_trailing(self.read_raw(end_offset - child_offset)?);
                         break;
                     }
                 }
             }
-            _ => {}
+            _ => {
+                // Keep bytes the parser did not consume, and never read into the next box
+                let position = self.reader.position();
+                if position < end_offset {
+                    box_info.set_trailing(self.read_raw(end_offset - position)?);
+                } else if position > end_offset {
+                    self.reader.seek(SeekFrom::Start(end_offset))?;
+                }
+            }
         }
 
         if self.debug {
@@ -231,32 +298,25 @@ impl BoxParser {
         }
         let matrix = Matrix::new(matrix_values);
 
-        let _preview_time = self.reader.read_u32()?;
-        let _preview_duration = self.reader.read_u32()?;
-        let _poster_time = self.reader.read_u32()?;
-        let _selection_time = self.reader.read_u32()?;
-        let _selection_duration = self.reader.read_u32()?;
-        let _current_time = self.reader.read_u32()?;
+        // pre_defined, used by QuickTime for preview/poster/selection/current time
+        let mut pre_defined = [0u32; 6];
+        for value in &mut pre_defined {
+            *value = self.reader.read_u32()?;
+        }
 
         // Read next_track_id
         let next_track_id = self.reader.read_u32()?;
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: mvhd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: mvhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
-        Ok(BoxData::MovieHeader(MovieHeaderBox::new(
+        let mut header = MovieHeaderBox::new(
             version,
             flags,
             creation_time,
@@ -267,13 +327,19 @@ impl BoxParser {
             volume,
             matrix,
             next_track_id,
-        )))
+        );
+        header.set_pre_defined(pre_defined);
+        Ok(BoxData::MovieHeader(header))
     }
 
     fn parse_mdat(&mut self, size: u64) -> Result<MediaDataBox, MediaError> {
-        // Just skip the data content
+        // Just skip the data content, it is copied from the file when written back
+        let offset = self.reader.position();
         self.reader.skip(size)?;
-        Ok(MediaDataBox::new(size))
+        Ok(MediaDataBox::new(
+            size,
+            RawBytes::InFile { offset, len: size },
+        ))
     }
 
     fn parse_tkhd(&mut self, size: u64) -> Result<BoxData, MediaError> {
@@ -335,17 +401,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: tkhd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: tkhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::TrackHeader(TrackHeaderBox::new(
@@ -386,12 +446,12 @@ impl BoxParser {
         // Read entries
         for _ in 0..entry_count {
             let (segment_duration, media_time) = if version == 1 {
+                (self.reader.read_u64()?, self.reader.read_i64()?)
+            } else {
                 (
-                    self.reader.read_u64()? as u32,
-                    self.reader.read_i64()? as i32,
+                    self.reader.read_u32()? as u64,
+                    self.reader.read_i32()? as i64,
                 )
-            } else {
-                (self.reader.read_u32()?, self.reader.read_i32()?)
             };
 
             let media_rate = self.reader.read_i16()?;
@@ -407,17 +467,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: elst box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: elst box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(EditListBox::new(version, flags, entries))
@@ -451,31 +505,19 @@ impl BoxParser {
             )
         };
 
-        // Read language (ISO-639-2/T language code)
-        let lang_packed = self.reader.read_u16()?;
-        let language = format!(
-            "{}{}{}",
-            ((lang_packed >> 10) & 0x1F + 0x60) as u8 as char,
-            ((lang_packed >> 5) & 0x1F + 0x60) as u8 as char,
-            (lang_packed & 0x1F + 0x60) as u8 as char
-        );
+        // Read language (packed ISO-639-2/T language code)
+        let language = self.reader.read_u16()?;
 
         // Skip pre_defined
         self.reader.skip(2)?;
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: mdhd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: mdhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::MediaHeader(MediaHeaderBox::new(
@@ -496,14 +538,17 @@ impl BoxParser {
         let version = self.reader.read_u8()?;
         let flags = self.reader.read_u24()?;
 
-        // Skip pre_defined
-        self.reader.skip(4)?;
+        // Read pre_defined (QuickTime component type)
+        let pre_defined = self.reader.read_u32()?;
 
         // Read handler type
         let handler_type = self.reader.read_fixed_string(4)?;
 
-        // Skip reserved[3]
-        self.reader.skip(12)?;
+        // Read reserved[3] (QuickTime component manufacturer, flags and mask)
+        let mut reserved = [0u32; 3];
+        for value in &mut reserved {
+            *value = self.reader.read_u32()?;
+        }
 
         // Read name (remaining bytes, null-terminated string)
         let bytes_read = self.reader.position() - start_pos;
@@ -511,17 +556,13 @@ impl BoxParser {
         let mut name_bytes = vec![0u8; remaining_size as usize];
         self.reader.read_exact(&mut name_bytes)?;
 
-        // Remove trailing null bytes
-        while name_bytes.last() == Some(&0) {
-            name_bytes.pop();
-        }
-        let name = String::from_utf8_lossy(&name_bytes).to_string();
-
         Ok(BoxData::Handler(HandlerBox::new(
             version,
             flags,
+            pre_defined,
             handler_type,
-            name,
+            reserved,
+            name_bytes,
         )))
     }
 
@@ -547,17 +588,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: vmhd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: vmhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::VideoMediaHeader(VideoMediaHeaderBox::new(
@@ -583,17 +618,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: smhd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: smhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::SoundMediaHeader(SoundMediaHeaderBox::new(
@@ -641,17 +670,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: stsd box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: stsd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::SampleDescription(SampleDescriptionBox::new(
@@ -682,17 +705,11 @@ impl BoxParser {
 
         // Verify we read exactly the right number of bytes
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: stts box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            // Adjust position if necessary
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: stts box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::TimeToSample(TimeToSampleBox::new(
@@ -725,16 +742,11 @@ impl BoxParser {
 
         // Verify read size
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: stsc box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: stsc box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::SampleToChunk(SampleToChunkBox::new(
@@ -766,16 +778,11 @@ impl BoxParser {
 
         // Verify read size
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: stsz box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: stsz box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
         }
 
         Ok(BoxData::SampleSize(SampleSizeBox::new(
@@ -787,7 +794,7 @@ impl BoxParser {
         )))
     }
 
-    fn parse_stco(&mut self, size: u64) -> Result<BoxData, MediaError> {
+    fn parse_stco(&mut self, size: u64, large: bool) -> Result<BoxData, MediaError> {
         let start_pos = self.reader.position();
 
         // Read version and flags
@@ -798,30 +805,225 @@ impl BoxParser {
         let entry_count = self.reader.read_u32()?;
         let mut offsets = Vec::with_capacity(entry_count as usize);
 
-        // Read offsets
+        // Read offsets, co64 stores them as 64-bit values
         for _ in 0..entry_count {
-            offsets.push(self.reader.read_u32()? as u64);
+            if large {
+                offsets.push(self.reader.read_u64()?);
+            } else {
+                offsets.push(self.reader.read_u32()? as u64);
+            }
         }
 
         // Verify read size
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: stco box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
-            }
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
-            }
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: {} box size mismatch. Expected {} bytes, read {} bytes",
+                if large { "co64" } else { "stco" },
+                size,
+                bytes_read
+            );
         }
 
         Ok(BoxData::ChunkOffset(ChunkOffsetBox::new(
-            version, flags, offsets,
+            version, flags, offsets, large,
         )))
     }
 
+    fn parse_ctts(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        // Read entry count
+        let entry_count = self.reader.read_u32()?;
+        let mut entries = Vec::with_capacity(entry_count as usize);
+
+        // Read entries
+        for _ in 0..entry_count {
+            let sample_count = self.reader.read_u32()?;
+            let sample_offset = self.reader.read_i32()?;
+            entries.push(CompositionOffsetEntry::new(sample_count, sample_offset));
+        }
+
+        // Verify read size
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: ctts box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::CompositionOffset(CompositionOffsetBox::new(
+            version, flags, entries,
+        )))
+    }
+
+    fn parse_stss(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        // Read entry count
+        let entry_count = self.reader.read_u32()?;
+        let mut sample_numbers = Vec::with_capacity(entry_count as usize);
+
+        // Read sync sample numbers
+        for _ in 0..entry_count {
+            sample_numbers.push(self.reader.read_u32()?);
+        }
+
+        // Verify read size
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: stss box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::SyncSample(SyncSampleBox::new(
+            version,
+            flags,
+            sample_numbers,
+        )))
+    }
+
+    fn parse_sbgp(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        // Read grouping type and optional parameter
+        let grouping_type = self.reader.read_fixed_string(4)?;
+        let grouping_type_parameter = if version == 1 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+
+        // Read entry count
+        let entry_count = self.reader.read_u32()?;
+        let mut entries = Vec::with_capacity(entry_count as usize);
+
+        // Read entries
+        for _ in 0..entry_count {
+            let sample_count = self.reader.read_u32()?;
+            let group_description_index = self.reader.read_u32()?;
+            entries.push(SampleToGroupEntry::new(
+                sample_count,
+                group_description_index,
+            ));
+        }
+
+        // Verify read size
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: sbgp box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::SampleToGroup(SampleToGroupBox::new(
+            version,
+            flags,
+            grouping_type,
+            grouping_type_parameter,
+            entries,
+        )))
+    }
+
+    fn parse_sgpd(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        // Read grouping type
+        let grouping_type = self.reader.read_fixed_string(4)?;
+
+        // Version 1 and later carry a default entry length, version 2 and later also a
+        // default group index
+        let default_length = if version >= 1 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_description_index = if version >= 2 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+
+        // Read entry count
+        let entry_count = self.reader.read_u32()?;
+        let mut entries = Vec::with_capacity(entry_count.min(1024) as usize);
+
+        // Read entries, stopping once the box is used up
+        let end = start_pos + size;
+        for _ in 0..entry_count {
+            let remaining = end.saturating_sub(self.reader.position());
+            let entry_size = match default_length {
+                Some(0) if remaining < 4 => {
+                    if self.debug {
+                        println!("Warning: sgpd entry length overruns the box, stopping");
+                    }
+                    break;
+                }
+                Some(0) => self.reader.read_u32()? as u64,
+                Some(length) => length as u64,
+                // Version 0 entries have a size implied by the grouping type
+                None => match SampleGroupEntry::fixed_size(&grouping_type) {
+                    Some(length) => length as u64,
+                    None => remaining,
+                },
+            };
+
+            if entry_size > end.saturating_sub(self.reader.position()) {
+                if self.debug {
+                    println!(
+                        "Warning: sgpd entry of {} bytes overruns the box, stopping",
+                        entry_size
+                    );
+                }
+                break;
+            }
+
+            let mut data = vec![0u8; entry_size as usize];
+            self.reader.read_exact(&mut data)?;
+            entries.push(data);
+        }
+
+        // Verify read size
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: sgpd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::SampleGroupDescription(
+            SampleGroupDescriptionBox::new(
+                version,
+                flags,
+                grouping_type,
+                default_length,
+                default_sample_description_index,
+                entries,
+            ),
+        ))
+    }
+
     fn parse_dinf(&mut self, _size: u64) -> Result<DataInformationBox, MediaError> {
         Ok(DataInformationBox::new())
     }
@@ -841,12 +1043,13 @@ impl BoxParser {
         for _ in 0..entry_count {
             let entry_start = self.reader.position();
             let entry_size = self.reader.read_u32()? as u64;
-            let entry_type = self.reader.read_fixed_string(4)?;
+            let mut entry_type = [0u8; 4];
+            self.reader.read_exact(&mut entry_type)?;
             let entry_version = self.reader.read_u8()?;
             let entry_flags = self.reader.read_u24()?;
 
-            let entry = match entry_type.as_str() {
-                "url " => {
+            let entry = match &entry_type {
+                b"url " => {
                     let location = if entry_flags & 0x000001 == 0 {
                         // 如果没有设置 self_contained 标志，读取 URL
                         let remaining = entry_size - (self.reader.position() - entry_start);
@@ -860,7 +1063,7 @@ impl BoxParser {
                         location,
                     }
                 }
-                "urn " => {
+                b"urn " => {
                     let name = self.reader.read_string_until_null()?;
                     let location = self.reader.read_string_until_null()?;
                     DataEntryBox::Urn {
@@ -871,10 +1074,11 @@ impl BoxParser {
                     }
                 }
                 _ => {
-                    // Skip unknown entry type
-                    let remaining = entry_size - (self.reader.position() - entry_start);
-                    self.reader.skip(remaining)?;
-                    continue;
+                    // Keep unknown entry types as stored
+                    self.reader.seek(SeekFrom::Start(entry_start + 8))?;
+                    let mut data = vec![0u8; entry_size.saturating_sub(8) as usize];
+                    self.reader.read_exact(&mut data)?;
+                    DataEntryBox::Other { entry_type, data }
                 }
             };
 
@@ -883,19 +1087,428 @@ impl BoxParser {
 
         // Verify read size
         let bytes_read = self.reader.position() - start_pos;
-        if bytes_read != size {
-            if self.debug {
-                println!(
-                    "Warning: dref box size mismatch. Expected {} bytes, read {} bytes",
-                    size, bytes_read
-                );
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: dref box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(DataReferenceBox::new(version, flags, entries))
+    }
+
+    fn parse_mfhd(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let sequence_number = self.reader.read_u32()?;
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: mfhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::MovieFragmentHeader(MovieFragmentHeaderBox::new(
+            version,
+            flags,
+            sequence_number,
+        )))
+    }
+
+    fn parse_tfhd(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let track_id = self.reader.read_u32()?;
+
+        // Optional fields, present according to tf_flags
+        let base_data_offset = if flags & TFHD_BASE_DATA_OFFSET != 0 {
+            Some(self.reader.read_u64()?)
+        } else {
+            None
+        };
+        let sample_description_index = if flags & TFHD_SAMPLE_DESCRIPTION_INDEX != 0 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_duration = if flags & TFHD_DEFAULT_SAMPLE_DURATION != 0 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_size = if flags & TFHD_DEFAULT_SAMPLE_SIZE != 0 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_flags = if flags & TFHD_DEFAULT_SAMPLE_FLAGS != 0 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: tfhd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::TrackFragmentHeader(TrackFragmentHeaderBox::new(
+            version,
+            flags,
+            track_id,
+            base_data_offset,
+            sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags,
+        )))
+    }
+
+    fn parse_tfdt(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let base_media_decode_time = if version == 1 {
+            self.reader.read_u64()?
+        } else {
+            self.reader.read_u32()? as u64
+        };
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: tfdt box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::TrackFragmentDecodeTime(
+            TrackFragmentDecodeTimeBox::new(version, flags, base_media_decode_time),
+        ))
+    }
+
+    fn parse_trun(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let sample_count = self.reader.read_u32()?;
+        let data_offset = if flags & TRUN_DATA_OFFSET != 0 {
+            Some(self.reader.read_i32()?)
+        } else {
+            None
+        };
+        let first_sample_flags = if flags & TRUN_FIRST_SAMPLE_FLAGS != 0 {
+            Some(self.reader.read_u32()?)
+        } else {
+            None
+        };
+
+        // 每个样本的字段由 tr_flags 决定, 用剩余大小限制条目数
+        let has_sample_fields = flags & 0x000f00 != 0;
+        let mut entries = Vec::new();
+        for _ in 0..sample_count {
+            if has_sample_fields && self.reader.position() - start_pos >= size {
+                break;
+            }
+            let duration = if flags & TRUN_SAMPLE_DURATION != 0 {
+                Some(self.reader.read_u32()?)
+            } else {
+                None
+            };
+            let sample_size = if flags & TRUN_SAMPLE_SIZE != 0 {
+                Some(self.reader.read_u32()?)
+            } else {
+                None
+            };
+            let sample_flags = if flags & TRUN_SAMPLE_FLAGS != 0 {
+                Some(self.reader.read_u32()?)
+            } else {
+                None
+            };
+            let composition_time_offset = if flags & TRUN_SAMPLE_COMPOSITION_TIME_OFFSET != 0 {
+                if version == 0 {
+                    Some(self.reader.read_u32()? as i64)
+                } else {
+                    Some(self.reader.read_i32()? as i64)
+                }
+            } else {
+                None
+            };
+            entries.push(TrackRunEntry {
+                duration,
+                size: sample_size,
+                flags: sample_flags,
+                composition_time_offset,
+            });
+        }
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: trun box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::TrackRun(TrackRunBox::new(
+            version,
+            flags,
+            data_offset,
+            first_sample_flags,
+            entries,
+        )))
+    }
+
+    fn parse_meta(&mut self) -> Result<BoxData, MediaError> {
+        // ISO meta 是 full box; QuickTime 的 meta 直接以子 box 开始
+        let start_pos = self.reader.position();
+        let first = self.reader.read_u32()?;
+        if first == 0 {
+            return Ok(BoxData::Meta(MetaBox::new(Some((0, 0)))));
+        }
+        let mut type_buf = [0u8; 4];
+        self.reader.read_exact(&mut type_buf)?;
+        self.reader.seek(SeekFrom::Start(start_pos))?;
+        if first >= 8 && is_valid_box_type(&BoxType::new(type_buf)) {
+            Ok(BoxData::Meta(MetaBox::new(None)))
+        } else {
+            self.reader.skip(4)?;
+            Ok(BoxData::Meta(MetaBox::new(Some((
+                (first >> 24) as u8,
+                first & 0x00ff_ffff,
+            )))))
+        }
+    }
+
+    fn parse_ilst(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let end = self.reader.position() + size;
+        let mut items = Vec::new();
+
+        while self.reader.position() + 8 <= end {
+            let item_start = self.reader.position();
+            let item_size = self.reader.read_u32()? as u64;
+            let mut item_type = [0u8; 4];
+            self.reader.read_exact(&mut item_type)?;
+            if item_size < 8 || item_start + item_size > end {
+                if self.debug {
+                    println!(
+                        "Warning: invalid ilst item size {} at offset {}",
+                        item_size, item_start
+                    );
+                }
+                self.reader.seek(SeekFrom::Start(item_start))?;
+                break;
             }
-            if bytes_read < size {
-                self.reader.skip(size - bytes_read)?;
+
+            // 保留原始负载, 重写时原样写回未解析的子盒
+            let item_end = item_start + item_size;
+            let mut raw = vec![0u8; (item_size - 8) as usize];
+            self.reader.read_exact(&mut raw)?;
+            self.reader.seek(SeekFrom::Start(item_start + 8))?;
+
+            // Item children: mean/name (freeform items) and one or more data boxes
+            let mut mean = None;
+            let mut name = None;
+            let mut values = Vec::new();
+            let mut unparsed = false;
+            while self.reader.position() < item_end {
+                let child_start = self.reader.position();
+                if child_start + 8 > item_end {
+                    unparsed = true;
+                    break;
+                }
+                let child_size = self.reader.read_u32()? as u64;
+                let child_type = self.reader.read_fixed_string(4)?;
+                if child_size < 8 || child_start + child_size > item_end {
+                    unparsed = true;
+                    break;
+                }
+                let payload = child_size - 8;
+                match child_type.as_str() {
+                    "mean" | "name" if payload >= 4 => {
+                        self.reader.skip(4)?; // version + flags
+                        let text = self.reader.read_string(payload - 4)?;
+                        if child_type == "mean" {
+                            mean = Some(text);
+                        } else {
+                            name = Some(text);
+                        }
+                    }
+                    "data" if payload >= 8 => {
+                        let type_word = self.reader.read_u32()?;
+                        let locale = self.reader.read_u32()?;
+                        let mut data = vec![0u8; (payload - 8) as usize];
+                        self.reader.read_exact(&mut data)?;
+                        values.push(MetadataValue {
+                            version: (type_word >> 24) as u8,
+                            data_type: type_word & 0x00ff_ffff,
+                            locale,
+                            data,
+                        });
+                    }
+                    _ => {
+                        unparsed = true;
+                        self.reader.skip(payload)?;
+                    }
+                }
             }
+
+            self.reader.seek(SeekFrom::Start(item_end))?;
+            items.push(MetadataItem::parsed(
+                item_type, mean, name, values, raw, unparsed,
+            ));
         }
 
-        Ok(DataReferenceBox::new(version, flags, entries))
+        // 无效条目及其后的字节原样保留
+        let mut remainder = vec![0u8; end.saturating_sub(self.reader.position()) as usize];
+        self.reader.read_exact(&mut remainder)?;
+        self.reader.seek(SeekFrom::Start(end))?;
+        Ok(BoxData::ItemList(ItemListBox::with_remainder(
+            items, remainder,
+        )))
+    }
+
+    fn parse_mehd(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let fragment_duration = if version == 1 {
+            self.reader.read_u64()?
+        } else {
+            self.reader.read_u32()? as u64
+        };
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: mehd box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::MovieExtendsHeader(MovieExtendsHeaderBox::new(
+            version,
+            flags,
+            fragment_duration,
+        )))
+    }
+
+    fn parse_sidx(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let reference_id = self.reader.read_u32()?;
+        let timescale = self.reader.read_u32()?;
+        let (earliest_presentation_time, first_offset) = if version == 0 {
+            (
+                self.reader.read_u32()? as u64,
+                self.reader.read_u32()? as u64,
+            )
+        } else {
+            (self.reader.read_u64()?, self.reader.read_u64()?)
+        };
+        self.reader.read_u16()?; // reserved
+        let reference_count = self.reader.read_u16()?;
+
+        let mut references = Vec::with_capacity(reference_count as usize);
+        for _ in 0..reference_count {
+            if self.reader.position() + 12 > start_pos + size {
+                break;
+            }
+            let reference = self.reader.read_u32()?;
+            let subsegment_duration = self.reader.read_u32()?;
+            let sap = self.reader.read_u32()?;
+            references.push(SegmentReference {
+                reference_type: reference >> 31 == 1,
+                referenced_size: reference & 0x7fff_ffff,
+                subsegment_duration,
+                starts_with_sap: sap >> 31 == 1,
+                sap_type: ((sap >> 28) & 0x07) as u8,
+                sap_delta_time: sap & 0x0fff_ffff,
+            });
+        }
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: sidx box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::SegmentIndex(SegmentIndexBox::new(
+            version,
+            flags,
+            reference_id,
+            timescale,
+            earliest_presentation_time,
+            first_offset,
+            references,
+        )))
+    }
+
+    fn parse_trex(&mut self, size: u64) -> Result<BoxData, MediaError> {
+        let start_pos = self.reader.position();
+
+        // Read version and flags
+        let version = self.reader.read_u8()?;
+        let flags = self.reader.read_u24()?;
+
+        let track_id = self.reader.read_u32()?;
+        let default_sample_description_index = self.reader.read_u32()?;
+        let default_sample_duration = self.reader.read_u32()?;
+        let default_sample_size = self.reader.read_u32()?;
+        let default_sample_flags = self.reader.read_u32()?;
+
+        // Verify we read exactly the right number of bytes
+        let bytes_read = self.reader.position() - start_pos;
+        if bytes_read != size && self.debug {
+            println!(
+                "Warning: trex box size mismatch. Expected {} bytes, read {} bytes",
+                size, bytes_read
+            );
+        }
+
+        Ok(BoxData::TrackExtends(TrackExtendsBox::new(
+            version,
+            flags,
+            track_id,
+            default_sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags,
+        )))
     }
 }
 
@@ -922,11 +1535,51 @@ fn is_valid_box_type(box_type: &BoxType) -> bool {
             | "stsc"
             | "stsz"
             | "stco"
+            | "co64"
+            | "ctts"
+            | "stss"
+            | "sbgp"
+            | "sgpd"
             | "mdat"
             | "free"
             | "skip"
             | "wide"
             | "edts"
             | "elst"
+            | "moof"
+            | "mfhd"
+            | "traf"
+            | "tfhd"
+            | "tfdt"
+            | "trun"
+            | "styp"
+            | "sidx"
+            | "mfra"
+            | "udta"
+            | "meta"
+            | "ilst"
+            | "mvex"
+            | "mehd"
+            | "trex"
+            | "pdin"
+            | "uuid"
+            | "iods"
+            | "tref"
+            | "nmhd"
+            | "hmhd"
+            | "sthd"
+            | "gmhd"
+            | "stz2"
+            | "cslg"
+            | "sdtp"
+            | "subs"
+            | "saiz"
+            | "saio"
+            | "senc"
+            | "pssh"
+            | "mfro"
+            | "tfra"
+            | "prft"
+            | "emsg"
     )
 }
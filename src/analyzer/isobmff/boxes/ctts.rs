@@ -0,0 +1,103 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+#[derive(Debug)]
+pub struct CompositionOffsetBox {
+    version: u8,
+    flags: u32,
+    entries: Vec<CompositionOffsetEntry>,
+}
+
+#[derive(Debug)]
+pub struct CompositionOffsetEntry {
+    sample_count: u32,  // Number of consecutive samples with this offset
+    sample_offset: i32, // CTS - DTS, unsigned in version 0 but signed in practice
+}
+
+impl CompositionOffsetBox {
+    pub fn new(version: u8, flags: u32, entries: Vec<CompositionOffsetEntry>) -> Self {
+        Self {
+            version,
+            flags,
+            entries,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            writer.write_u32(entry.sample_count);
+            writer.write_i32(entry.sample_offset);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Composition Time To Sample Box"
+    }
+
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        8 + self.entries.len() as u64 * 8
+    }
+
+    pub fn entries(&self) -> &[CompositionOffsetEntry] {
+        &self.entries
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new(
+            "version",
+            self.version.to_string(),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "entry_count",
+            self.entries.len().to_string(),
+            None::<String>,
+        ));
+
+        // 只显示前几个条目
+        for (i, entry) in self.entries.iter().take(5).enumerate() {
+            properties.push(Property::new(
+                &format!("entry[{}].sample_count", i),
+                entry.sample_count.to_string(),
+                None::<String>,
+            ));
+            properties.push(Property::new(
+                &format!("entry[{}].sample_offset", i),
+                entry.sample_offset.to_string(),
+                None::<String>,
+            ));
+        }
+
+        if self.entries.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more entries", self.entries.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
+
+impl CompositionOffsetEntry {
+    pub fn new(sample_count: u32, sample_offset: i32) -> Self {
+        Self {
+            sample_count,
+            sample_offset,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn sample_offset(&self) -> i32 {
+        self.sample_offset
+    }
+}
@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 
 #[derive(Debug)]
 pub enum DataEntryBox {
@@ -13,6 +14,8 @@ pub enum DataEntryBox {
         name: String,
         location: String,
     },
+    /// Entry type this parser does not decode, kept as stored
+    Other { entry_type: [u8; 4], data: Vec<u8> },
 }
 
 #[derive(Debug)]
@@ -31,6 +34,48 @@ impl DataReferenceBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            let start = writer.position();
+            writer.write_u32(0); // size, patched below
+            match entry {
+                DataEntryBox::Url {
+                    version,
+                    flags,
+                    location,
+                } => {
+                    writer.write_fixed_string("url ", 4);
+                    writer.write_version_flags(*version, *flags);
+                    if !location.is_empty() {
+                        writer.write_bytes(location.as_bytes());
+                        writer.write_u8(0);
+                    }
+                }
+                DataEntryBox::Urn {
+                    version,
+                    flags,
+                    name,
+                    location,
+                } => {
+                    writer.write_fixed_string("urn ", 4);
+                    writer.write_version_flags(*version, *flags);
+                    writer.write_bytes(name.as_bytes());
+                    writer.write_u8(0);
+                    writer.write_bytes(location.as_bytes());
+                    writer.write_u8(0);
+                }
+                DataEntryBox::Other { entry_type, data } => {
+                    writer.write_bytes(entry_type);
+                    writer.write_bytes(data);
+                }
+            }
+            let size = writer.position() - start;
+            writer.patch_u32(start, size as u32);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Data Reference Box"
     }
@@ -72,6 +117,13 @@ impl DataReferenceBox {
                         Some(format!("URN: {} -> {}", name, location)),
                     ));
                 }
+                DataEntryBox::Other { entry_type, data } => {
+                    properties.push(Property::new(
+                        &format!("{}[{}]", String::from_utf8_lossy(entry_type), i),
+                        format!("{} bytes", data.len()),
+                        None::<String>,
+                    ));
+                }
             }
         }
 
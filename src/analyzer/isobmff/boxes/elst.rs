@@ -1,17 +1,18 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 
 #[derive(Debug)]
 pub struct EditListEntry {
-    pub segment_duration: u32,
-    pub media_time: i32,
+    pub segment_duration: u64, // In movie timescale units
+    pub media_time: i64,       // In media timescale units, -1 = empty edit
     pub media_rate: i16,
     pub media_rate_fraction: i16,
 }
 
 impl EditListEntry {
     pub fn new(
-        segment_duration: u32,
-        media_time: i32,
+        segment_duration: u64,
+        media_time: i64,
         media_rate: i16,
         media_rate_fraction: i16,
     ) -> Self {
@@ -40,10 +41,36 @@ impl EditListBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            if self.version == 1 {
+                writer.write_u64(entry.segment_duration);
+                writer.write_i64(entry.media_time);
+            } else {
+                writer.write_u32(entry.segment_duration as u32);
+                writer.write_i32(entry.media_time as i32);
+            }
+            writer.write_i16(entry.media_rate);
+            writer.write_i16(entry.media_rate_fraction);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Edit List Box"
     }
 
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        let entry_size = if self.version == 1 { 20 } else { 12 };
+        8 + self.entries.len() as u64 * entry_size
+    }
+
+    pub fn entries(&self) -> &[EditListEntry] {
+        &self.entries
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new("version", self.version, None::<String>));
         properties.push(Property::new(
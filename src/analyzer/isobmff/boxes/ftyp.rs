@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct FileTypeBox {
     major_brand: String,
@@ -15,10 +16,24 @@ impl FileTypeBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_fixed_string(&self.major_brand, 4);
+        writer.write_u32(self.minor_version);
+        for brand in &self.compatible_brands {
+            writer.write_fixed_string(brand, 4);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "File Type Box"
     }
 
+    pub fn add_compatible_brand(&mut self, brand: &str) {
+        if !self.compatible_brands.iter().any(|b| b == brand) {
+            self.compatible_brands.push(brand.to_string());
+        }
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "major brand",
@@ -1,3 +1,4 @@
+use super::RawBytes;
 use crate::analyzer::isobmff::types::BoxType;
 use crate::analyzer::Property;
 
@@ -6,12 +7,18 @@ use crate::analyzer::Property;
 pub struct GenericBox {
     /// The box type (4CC code)
     box_type: BoxType,
+    /// Payload passed through untouched when the box is written back
+    payload: RawBytes,
 }
 
 impl GenericBox {
     /// Creates a new generic box with the specified type
-    pub fn new(box_type: BoxType) -> Self {
-        Self { box_type }
+    pub fn new(box_type: BoxType, payload: RawBytes) -> Self {
+        Self { box_type, payload }
+    }
+
+    pub fn payload(&self) -> &RawBytes {
+        &self.payload
     }
 
     /// Returns the box type
@@ -1,27 +1,60 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 
 #[derive(Debug)]
 pub struct HandlerBox {
     version: u8,
     flags: u32,
+    pre_defined: u32,     // QuickTime component type, e.g. 'mhlr'
     handler_type: String, // 4 characters
+    reserved: [u32; 3],   // QuickTime component manufacturer/flags/mask
     name: String,         // Handler name
+    raw_name: Vec<u8>,    // Name bytes as stored (NUL terminated or a Pascal string)
 }
 
 impl HandlerBox {
-    pub fn new(version: u8, flags: u32, handler_type: String, name: String) -> Self {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        pre_defined: u32,
+        handler_type: String,
+        reserved: [u32; 3],
+        raw_name: Vec<u8>,
+    ) -> Self {
+        // Remove trailing null bytes
+        let mut name_bytes = raw_name.clone();
+        while name_bytes.last() == Some(&0) {
+            name_bytes.pop();
+        }
         Self {
             version,
             flags,
+            pre_defined,
             handler_type,
-            name,
+            reserved,
+            name: String::from_utf8_lossy(&name_bytes).to_string(),
+            raw_name,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.pre_defined);
+        writer.write_fixed_string(&self.handler_type, 4);
+        for &value in &self.reserved {
+            writer.write_u32(value);
         }
+        writer.write_bytes(&self.raw_name);
     }
 
     pub fn description(&self) -> &str {
         "Handler Reference Box"
     }
 
+    pub fn handler_type(&self) -> &str {
+        &self.handler_type
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
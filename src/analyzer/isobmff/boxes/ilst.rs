@@ -0,0 +1,213 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+/// iTunes-style metadata item list (moov/udta/meta/ilst)
+#[derive(Debug)]
+pub struct ItemListBox {
+    items: Vec<MetadataItem>,
+    remainder: Vec<u8>, // Bytes from the first invalid item on, kept as read
+}
+
+/// One ilst entry, e.g. `©nam` or a freeform `----` item
+#[derive(Debug, Clone)]
+pub struct MetadataItem {
+    item_type: [u8; 4],
+    mean: Option<String>, // Freeform items only, e.g. "com.apple.iTunes"
+    name: Option<String>, // Freeform items only, e.g. "iTunSMPB"
+    values: Vec<MetadataValue>,
+    // Item payload as read from the file; written back verbatim so that children
+    // this parser does not understand survive a rewrite. None for new items
+    raw: Option<Vec<u8>>,
+    unparsed_children: bool,
+}
+
+/// Payload of a `data` box inside an item
+#[derive(Debug, Clone)]
+pub struct MetadataValue {
+    pub version: u8,    // Type indicator set, normally 0
+    pub data_type: u32, // Well-known type: 1 = UTF-8, 13 = JPEG, 21 = signed int, ...
+    pub locale: u32,
+    pub data: Vec<u8>,
+}
+
+impl ItemListBox {
+    pub fn new(items: Vec<MetadataItem>) -> Self {
+        Self {
+            items,
+            remainder: Vec::new(),
+        }
+    }
+
+    pub fn with_remainder(items: Vec<MetadataItem>, remainder: Vec<u8>) -> Self {
+        Self { items, remainder }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        for item in &self.items {
+            let item_start = writer.position();
+            writer.write_u32(0); // size, patched below
+            writer.write_bytes(&item.item_type);
+            if let Some(raw) = &item.raw {
+                writer.write_bytes(raw);
+                writer.patch_u32(item_start, 8 + raw.len() as u32);
+                continue;
+            }
+            for (child_type, text) in [("mean", &item.mean), ("name", &item.name)] {
+                if let Some(text) = text {
+                    writer.write_u32(12 + text.len() as u32);
+                    writer.write_fixed_string(child_type, 4);
+                    writer.write_u32(0); // version + flags
+                    writer.write_bytes(text.as_bytes());
+                }
+            }
+            for value in &item.values {
+                writer.write_u32(16 + value.data.len() as u32);
+                writer.write_fixed_string("data", 4);
+                writer.write_u32((value.version as u32) << 24 | value.data_type);
+                writer.write_u32(value.locale);
+                writer.write_bytes(&value.data);
+            }
+            let size = writer.position() - item_start;
+            writer.patch_u32(item_start, size as u32);
+        }
+        writer.write_bytes(&self.remainder);
+    }
+
+    pub fn description(&self) -> &str {
+        "Metadata Item List Box"
+    }
+
+    /// Finds an item by its key as returned by [`MetadataItem::key`]
+    pub fn item(&self, key: &str) -> Option<&MetadataItem> {
+        self.items.iter().find(|item| item.key() == key)
+    }
+
+    pub fn items(&self) -> &[MetadataItem] {
+        &self.items
+    }
+
+    /// Bytes after an item that could not be parsed; items appended behind them
+    /// would be unreachable for readers that stop at the invalid item
+    pub fn remainder(&self) -> &[u8] {
+        &self.remainder
+    }
+
+    /// Replaces the item with the same key, or appends it
+    pub fn set_item(&mut self, item: MetadataItem) {
+        let key = item.key();
+        match self.items.iter_mut().find(|existing| existing.key() == key) {
+            Some(existing) => *existing = item,
+            None => self.items.push(item),
+        }
+    }
+
+    /// Removes the item with the given key, returns whether it existed
+    pub fn remove_item(&mut self, key: &str) -> bool {
+        let count = self.items.len();
+        self.items.retain(|item| item.key() != key);
+        self.items.len() != count
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new(
+            "item_count",
+            self.items.len(),
+            None::<String>,
+        ));
+        for item in &self.items {
+            let Some(value) = item.values.first() else {
+                properties.push(Property::new(&item.key(), "", None::<String>));
+                continue;
+            };
+            // 非默认语言环境时附带显示
+            let readable =
+                (value.locale != 0).then(|| format!("{} (locale {})", value, value.locale));
+            properties.push(Property::new(&item.key(), value, readable));
+        }
+    }
+}
+
+impl MetadataItem {
+    pub fn new(
+        item_type: [u8; 4],
+        mean: Option<String>,
+        name: Option<String>,
+        values: Vec<MetadataValue>,
+    ) -> Self {
+        Self {
+            item_type,
+            mean,
+            name,
+            values,
+            raw: None,
+            unparsed_children: false,
+        }
+    }
+
+    /// An item read from a file, `raw` is its payload after the 8-byte header
+    pub fn parsed(
+        item_type: [u8; 4],
+        mean: Option<String>,
+        name: Option<String>,
+        values: Vec<MetadataValue>,
+        raw: Vec<u8>,
+        unparsed_children: bool,
+    ) -> Self {
+        Self {
+            raw: Some(raw),
+            unparsed_children,
+            ..Self::new(item_type, mean, name, values)
+        }
+    }
+
+    /// Item key: the 4CC (`©` decoded as Latin-1) or `----:mean:name` for freeform items
+    pub fn key(&self) -> String {
+        let four_cc: String = self.item_type.iter().map(|&b| b as char).collect();
+        match (&self.mean, &self.name) {
+            (Some(mean), Some(name)) => format!("{}:{}:{}", four_cc, mean, name),
+            (None, Some(name)) => format!("{}:{}", four_cc, name),
+            _ => four_cc,
+        }
+    }
+
+    /// The item had children other than mean/name/data, or invalid ones
+    pub fn has_unparsed_children(&self) -> bool {
+        self.unparsed_children
+    }
+
+    pub fn values(&self) -> &[MetadataValue] {
+        &self.values
+    }
+
+    /// First value decoded as text, if it is a UTF-8 value
+    pub fn text(&self) -> Option<String> {
+        self.values
+            .iter()
+            .find(|value| value.data_type == 1)
+            .map(|value| String::from_utf8_lossy(&value.data).to_string())
+    }
+}
+
+impl std::fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.data_type {
+            1 => write!(f, "{}", String::from_utf8_lossy(&self.data)),
+            21 | 22 if !self.data.is_empty() && self.data.len() <= 8 => {
+                let mut value = 0u64;
+                for &byte in &self.data {
+                    value = (value << 8) | byte as u64;
+                }
+                if self.data_type == 21 {
+                    // 符号扩展
+                    let shift = 64 - self.data.len() * 8;
+                    write!(f, "{}", ((value << shift) as i64) >> shift)
+                } else {
+                    write!(f, "{}", value)
+                }
+            }
+            13 => write!(f, "JPEG image, {} bytes", self.data.len()),
+            14 => write!(f, "PNG image, {} bytes", self.data.len()),
+            _ => write!(f, "{} bytes (type {})", self.data.len(), self.data_type),
+        }
+    }
+}
@@ -1,13 +1,19 @@
+use super::RawBytes;
 use crate::analyzer::Property;
 
 #[derive(Debug)]
 pub struct MediaDataBox {
     data_size: u64,
+    payload: RawBytes, // Left in the source file, copied when written back
 }
 
 impl MediaDataBox {
-    pub fn new(data_size: u64) -> Self {
-        Self { data_size }
+    pub fn new(data_size: u64, payload: RawBytes) -> Self {
+        Self { data_size, payload }
+    }
+
+    pub fn payload(&self) -> &RawBytes {
+        &self.payload
     }
 
     pub fn description(&self) -> &str {
@@ -1,5 +1,6 @@
 use crate::analyzer::isobmff::types::Mp4DateTime;
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct MediaHeaderBox {
     version: u8,
@@ -8,7 +9,7 @@ pub struct MediaHeaderBox {
     modification_time: Mp4DateTime,
     timescale: u32,
     duration: u64,
-    language: String, // ISO-639-2/T language code
+    language: u16, // Packed ISO-639-2/T language code
 }
 
 impl MediaHeaderBox {
@@ -19,7 +20,7 @@ impl MediaHeaderBox {
         modification_time: Mp4DateTime,
         timescale: u32,
         duration: u64,
-        language: String,
+        language: u16,
     ) -> Self {
         Self {
             version,
@@ -32,10 +33,51 @@ impl MediaHeaderBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        if self.version == 1 {
+            writer.write_u64(self.creation_time.as_secs());
+            writer.write_u64(self.modification_time.as_secs());
+            writer.write_u32(self.timescale);
+            writer.write_u64(self.duration);
+        } else {
+            writer.write_u32(self.creation_time.as_secs() as u32);
+            writer.write_u32(self.modification_time.as_secs() as u32);
+            writer.write_u32(self.timescale);
+            writer.write_u32(self.duration as u32);
+        }
+        writer.write_u16(self.language);
+        writer.write_u16(0); // pre_defined
+    }
+
     pub fn description(&self) -> &str {
         "Media Header Box"
     }
 
+    /// Three lowercase letters, each stored as 5 bits offset by 0x60
+    pub fn language(&self) -> String {
+        [10, 5, 0]
+            .iter()
+            .map(|shift| (((self.language >> shift) & 0x1f) as u8 + 0x60) as char)
+            .collect()
+    }
+
+    pub fn timescale(&self) -> u32 {
+        self.timescale
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn set_duration(&mut self, duration: u64) {
+        self.duration = duration;
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -67,10 +109,6 @@ impl MediaHeaderBox {
             self.duration.to_string(),
             None::<String>,
         ));
-        properties.push(Property::new(
-            "language",
-            self.language.clone(),
-            None::<String>,
-        ));
+        properties.push(Property::new("language", self.language(), None::<String>));
     }
 }
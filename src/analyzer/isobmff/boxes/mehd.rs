@@ -0,0 +1,54 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct MovieExtendsHeaderBox {
+    version: u8,
+    flags: u32,
+    fragment_duration: u64, // Whole fragmented movie, in movie timescale units
+}
+
+impl MovieExtendsHeaderBox {
+    pub fn new(version: u8, flags: u32, fragment_duration: u64) -> Self {
+        Self {
+            version,
+            flags,
+            fragment_duration,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        if self.version == 1 {
+            writer.write_u64(self.fragment_duration);
+        } else {
+            writer.write_u32(self.fragment_duration as u32);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Movie Extends Header Box"
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn fragment_duration(&self) -> u64 {
+        self.fragment_duration
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "fragment_duration",
+            self.fragment_duration,
+            None::<String>,
+        ));
+    }
+}
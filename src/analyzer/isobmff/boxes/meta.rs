@@ -0,0 +1,44 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct MetaBox {
+    // ISO meta is a full box; QuickTime writes it without version/flags
+    version_flags: Option<(u8, u32)>,
+}
+
+impl MetaBox {
+    pub fn new(version_flags: Option<(u8, u32)>) -> Self {
+        Self { version_flags }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        if let Some((version, flags)) = self.version_flags {
+            writer.write_version_flags(version, flags);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Meta Box"
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        match self.version_flags {
+            Some((version, flags)) => {
+                properties.push(Property::new("version", version, None::<String>));
+                properties.push(Property::new(
+                    "flags",
+                    format!("0x{:06x}", flags),
+                    None::<String>,
+                ));
+            }
+            None => {
+                properties.push(Property::new(
+                    "layout",
+                    "quicktime",
+                    Some("QuickTime style (no version/flags)"),
+                ));
+            }
+        }
+    }
+}
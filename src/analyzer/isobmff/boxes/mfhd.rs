@@ -0,0 +1,42 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct MovieFragmentHeaderBox {
+    version: u8,
+    flags: u32,
+    sequence_number: u32,
+}
+
+impl MovieFragmentHeaderBox {
+    pub fn new(version: u8, flags: u32, sequence_number: u32) -> Self {
+        Self {
+            version,
+            flags,
+            sequence_number,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.sequence_number);
+    }
+
+    pub fn description(&self) -> &str {
+        "Movie Fragment Header Box"
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "sequence_number",
+            self.sequence_number,
+            None::<String>,
+        ));
+    }
+}
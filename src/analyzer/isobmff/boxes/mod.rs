@@ -1,3 +1,4 @@
+mod ctts;
 mod dinf;
 mod dref;
 mod edts;
@@ -5,25 +6,42 @@ mod elst;
 mod ftyp;
 mod generic;
 mod hdlr;
+mod ilst;
 mod mdat;
 mod mdhd;
 mod mdia;
+mod mehd;
+mod meta;
+mod mfhd;
 mod minf;
+mod moof;
 mod moov;
+mod mvex;
 mod mvhd;
+mod sbgp;
+mod sgpd;
+mod sidx;
 mod smhd;
 mod stbl;
 mod stco;
 mod stsc;
 mod stsd;
+mod stss;
 mod stsz;
 mod stts;
+mod tfdt;
+mod tfhd;
 mod tkhd;
+mod traf;
 mod trak;
+mod trex;
+mod trun;
+mod udta;
 mod vmhd;
 
 use crate::analyzer::isobmff::types::BoxType;
 use crate::analyzer::Property;
+use crate::writer::Writer;
 /// Common interface for all box types
 pub trait BoxInfo {
     fn box_type(&self) -> BoxType;
@@ -40,24 +58,95 @@ pub struct Box {
     box_type: BoxType,
     size: u64,
     offset: u64,
+    header_size: u64, // 8, or 16 when the 64-bit largesize field is used
     data: BoxData,
     children: Vec<Box>,
+    trailing: RawBytes, // Bytes after the parsed fields / last child that the parser did not decode
+}
+
+/// Box bytes kept verbatim, either loaded or left in the source file when too large
+#[derive(Debug, Clone)]
+pub enum RawBytes {
+    Loaded(Vec<u8>),
+    InFile { offset: u64, len: u64 },
+}
+
+impl RawBytes {
+    pub fn len(&self) -> u64 {
+        match self {
+            RawBytes::Loaded(bytes) => bytes.len() as u64,
+            RawBytes::InFile { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for RawBytes {
+    fn default() -> Self {
+        RawBytes::Loaded(Vec::new())
+    }
 }
 
 impl Box {
-    pub fn new(box_type: BoxType, size: u64, offset: u64, data: BoxData) -> Self {
+    pub fn new(box_type: BoxType, size: u64, offset: u64, header_size: u64, data: BoxData) -> Self {
         Self {
             box_type,
             size,
             offset,
+            header_size,
             data,
             children: Vec::new(),
+            trailing: RawBytes::default(),
         }
     }
 
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    pub fn trailing(&self) -> &RawBytes {
+        &self.trailing
+    }
+
+    pub fn set_trailing(&mut self, trailing: RawBytes) {
+        self.trailing = trailing;
+    }
+
     pub fn add_child(&mut self, child: Box) {
         self.children.push(child);
     }
+
+    pub fn data(&self) -> &BoxData {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut BoxData {
+        &mut self.data
+    }
+
+    pub fn set_box_type(&mut self, box_type: BoxType) {
+        self.box_type = box_type;
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<Box> {
+        &mut self.children
+    }
+
+    pub fn child_mut(&mut self, box_type: &str) -> Option<&mut Box> {
+        self.children
+            .iter_mut()
+            .find(|child| child.box_type.as_str() == box_type)
+    }
+
+    /// Returns the first direct child with the given 4CC type
+    pub fn child(&self, box_type: &str) -> Option<&Box> {
+        self.children
+            .iter()
+            .find(|child| child.box_type.as_str() == box_type)
+    }
 }
 
 impl BoxInfo for Box {
@@ -86,6 +175,7 @@ impl BoxInfo for Box {
     }
 }
 
+pub use ctts::{CompositionOffsetBox, CompositionOffsetEntry};
 pub use dinf::DataInformationBox;
 pub use dref::DataEntryBox;
 pub use dref::DataReferenceBox;
@@ -94,12 +184,21 @@ pub use elst::{EditListBox, EditListEntry};
 pub use ftyp::FileTypeBox;
 pub use generic::GenericBox;
 pub use hdlr::HandlerBox;
+pub use ilst::{ItemListBox, MetadataItem, MetadataValue};
 pub use mdat::MediaDataBox;
 pub use mdhd::MediaHeaderBox;
 pub use mdia::MediaBox;
+pub use mehd::MovieExtendsHeaderBox;
+pub use meta::MetaBox;
+pub use mfhd::MovieFragmentHeaderBox;
 pub use minf::MediaInfoBox;
+pub use moof::MovieFragmentBox;
 pub use moov::MovieBox;
+pub use mvex::MovieExtendsBox;
 pub use mvhd::MovieHeaderBox;
+pub use sbgp::{SampleToGroupBox, SampleToGroupEntry};
+pub use sgpd::{grouping_type_name, SampleGroupDescriptionBox, SampleGroupEntry};
+pub use sidx::{SegmentIndexBox, SegmentReference};
 pub use smhd::SoundMediaHeaderBox;
 pub use stbl::SampleTableBox;
 pub use stco::ChunkOffsetBox;
@@ -107,11 +206,25 @@ pub use stsc::SampleToChunkBox;
 pub use stsc::SampleToChunkEntry;
 pub use stsd::SampleDescriptionBox;
 pub use stsd::SampleEntry;
+pub use stss::SyncSampleBox;
 pub use stsz::SampleSizeBox;
 pub use stts::TimeToSampleBox;
 pub use stts::TimeToSampleEntry;
+pub use tfdt::TrackFragmentDecodeTimeBox;
+pub use tfhd::{
+    TrackFragmentHeaderBox, TFHD_BASE_DATA_OFFSET, TFHD_DEFAULT_BASE_IS_MOOF,
+    TFHD_DEFAULT_SAMPLE_DURATION, TFHD_DEFAULT_SAMPLE_FLAGS, TFHD_DEFAULT_SAMPLE_SIZE,
+    TFHD_SAMPLE_DESCRIPTION_INDEX,
+};
 pub use tkhd::TrackHeaderBox;
+pub use traf::TrackFragmentBox;
 pub use trak::TrackBox;
+pub use trex::TrackExtendsBox;
+pub use trun::{
+    TrackRunBox, TrackRunEntry, TRUN_DATA_OFFSET, TRUN_FIRST_SAMPLE_FLAGS,
+    TRUN_SAMPLE_COMPOSITION_TIME_OFFSET, TRUN_SAMPLE_DURATION, TRUN_SAMPLE_FLAGS, TRUN_SAMPLE_SIZE,
+};
+pub use udta::UserDataBox;
 pub use vmhd::VideoMediaHeaderBox;
 
 #[derive(Debug)]
@@ -136,8 +249,25 @@ pub enum BoxData {
     SampleToChunk(SampleToChunkBox),
     SampleSize(SampleSizeBox),
     ChunkOffset(ChunkOffsetBox),
+    CompositionOffset(CompositionOffsetBox),
+    SyncSample(SyncSampleBox),
+    SampleToGroup(SampleToGroupBox),
+    SampleGroupDescription(SampleGroupDescriptionBox),
     DataInformation(DataInformationBox),
     DataReference(DataReferenceBox),
+    MovieFragment(MovieFragmentBox),
+    MovieFragmentHeader(MovieFragmentHeaderBox),
+    TrackFragment(TrackFragmentBox),
+    TrackFragmentHeader(TrackFragmentHeaderBox),
+    TrackFragmentDecodeTime(TrackFragmentDecodeTimeBox),
+    TrackRun(TrackRunBox),
+    UserData(UserDataBox),
+    Meta(MetaBox),
+    ItemList(ItemListBox),
+    MovieExtends(MovieExtendsBox),
+    MovieExtendsHeader(MovieExtendsHeaderBox),
+    TrackExtends(TrackExtendsBox),
+    SegmentIndex(SegmentIndexBox),
     Generic(GenericBox),
     Unknown,
 }
@@ -165,8 +295,25 @@ impl BoxData {
             BoxData::SampleToChunk(b) => b.description(),
             BoxData::SampleSize(b) => b.description(),
             BoxData::ChunkOffset(b) => b.description(),
+            BoxData::CompositionOffset(b) => b.description(),
+            BoxData::SyncSample(b) => b.description(),
+            BoxData::SampleToGroup(b) => b.description(),
+            BoxData::SampleGroupDescription(b) => b.description(),
             BoxData::DataInformation(b) => b.description(),
             BoxData::DataReference(b) => b.description(),
+            BoxData::MovieFragment(b) => b.description(),
+            BoxData::MovieFragmentHeader(b) => b.description(),
+            BoxData::TrackFragment(b) => b.description(),
+            BoxData::TrackFragmentHeader(b) => b.description(),
+            BoxData::TrackFragmentDecodeTime(b) => b.description(),
+            BoxData::TrackRun(b) => b.description(),
+            BoxData::UserData(b) => b.description(),
+            BoxData::Meta(b) => b.description(),
+            BoxData::ItemList(b) => b.description(),
+            BoxData::MovieExtends(b) => b.description(),
+            BoxData::MovieExtendsHeader(b) => b.description(),
+            BoxData::TrackExtends(b) => b.description(),
+            BoxData::SegmentIndex(b) => b.description(),
             BoxData::Generic(b) => b.description(),
             BoxData::Unknown => "Unknown box type",
         }
@@ -194,10 +341,71 @@ impl BoxData {
             BoxData::SampleToChunk(b) => b.fill_properties(properties),
             BoxData::SampleSize(b) => b.fill_properties(properties),
             BoxData::ChunkOffset(b) => b.fill_properties(properties),
+            BoxData::CompositionOffset(b) => b.fill_properties(properties),
+            BoxData::SyncSample(b) => b.fill_properties(properties),
+            BoxData::SampleToGroup(b) => b.fill_properties(properties),
+            BoxData::SampleGroupDescription(b) => b.fill_properties(properties),
             BoxData::DataInformation(b) => b.fill_properties(properties),
             BoxData::DataReference(b) => b.fill_properties(properties),
+            BoxData::MovieFragment(b) => b.fill_properties(properties),
+            BoxData::MovieFragmentHeader(b) => b.fill_properties(properties),
+            BoxData::TrackFragment(b) => b.fill_properties(properties),
+            BoxData::TrackFragmentHeader(b) => b.fill_properties(properties),
+            BoxData::TrackFragmentDecodeTime(b) => b.fill_properties(properties),
+            BoxData::TrackRun(b) => b.fill_properties(properties),
+            BoxData::UserData(b) => b.fill_properties(properties),
+            BoxData::Meta(b) => b.fill_properties(properties),
+            BoxData::ItemList(b) => b.fill_properties(properties),
+            BoxData::MovieExtends(b) => b.fill_properties(properties),
+            BoxData::MovieExtendsHeader(b) => b.fill_properties(properties),
+            BoxData::TrackExtends(b) => b.fill_properties(properties),
+            BoxData::SegmentIndex(b) => b.fill_properties(properties),
             BoxData::Generic(b) => b.fill_properties(properties),
             BoxData::Unknown => {}
         }
     }
+
+    /// Serializes the decoded fields of the box, without header, children or trailing bytes
+    pub fn write(&self, writer: &mut Writer) {
+        match self {
+            BoxData::FileType(b) => b.write(writer),
+            BoxData::MovieHeader(b) => b.write(writer),
+            BoxData::TrackHeader(b) => b.write(writer),
+            BoxData::EditList(b) => b.write(writer),
+            BoxData::MediaHeader(b) => b.write(writer),
+            BoxData::Handler(b) => b.write(writer),
+            BoxData::VideoMediaHeader(b) => b.write(writer),
+            BoxData::SoundMediaHeader(b) => b.write(writer),
+            BoxData::SampleDescription(b) => b.write(writer),
+            BoxData::TimeToSample(b) => b.write(writer),
+            BoxData::SampleToChunk(b) => b.write(writer),
+            BoxData::SampleSize(b) => b.write(writer),
+            BoxData::ChunkOffset(b) => b.write(writer),
+            BoxData::CompositionOffset(b) => b.write(writer),
+            BoxData::SyncSample(b) => b.write(writer),
+            BoxData::SampleToGroup(b) => b.write(writer),
+            BoxData::SampleGroupDescription(b) => b.write(writer),
+            BoxData::DataReference(b) => b.write(writer),
+            BoxData::MovieFragmentHeader(b) => b.write(writer),
+            BoxData::TrackFragmentHeader(b) => b.write(writer),
+            BoxData::TrackFragmentDecodeTime(b) => b.write(writer),
+            BoxData::TrackRun(b) => b.write(writer),
+            BoxData::Meta(b) => b.write(writer),
+            BoxData::ItemList(b) => b.write(writer),
+            BoxData::MovieExtendsHeader(b) => b.write(writer),
+            BoxData::TrackExtends(b) => b.write(writer),
+            BoxData::SegmentIndex(b) => b.write(writer),
+            // Containers carry no fields of their own; mdat and generic payloads are raw bytes
+            _ => {}
+        }
+    }
+
+    /// Payload kept verbatim instead of decoded (mdat and generic boxes)
+    pub fn raw_payload(&self) -> Option<&RawBytes> {
+        match self {
+            BoxData::MediaData(b) => Some(b.payload()),
+            BoxData::Generic(b) => Some(b.payload()),
+            _ => None,
+        }
+    }
 }
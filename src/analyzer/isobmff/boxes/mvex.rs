@@ -0,0 +1,18 @@
+use crate::analyzer::Property;
+
+#[derive(Debug, Default)]
+pub struct MovieExtendsBox;
+
+impl MovieExtendsBox {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn description(&self) -> &str {
+        "Movie Extends Box"
+    }
+
+    pub fn fill_properties(&self, _properties: &mut Vec<Property>) {
+        // Movie extends box itself doesn't have properties, its children contain the actual data
+    }
+}
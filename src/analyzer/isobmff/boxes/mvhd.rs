@@ -1,5 +1,6 @@
 use crate::analyzer::isobmff::types::{Fixed16_16, Matrix, Mp4DateTime};
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct MovieHeaderBox {
     version: u8,
@@ -12,6 +13,7 @@ pub struct MovieHeaderBox {
     volume: Fixed16_16,
     matrix: Matrix,
     next_track_id: u32,
+    pre_defined: [u32; 6], // QuickTime preview/poster/selection/current time
 }
 
 impl MovieHeaderBox {
@@ -38,13 +40,60 @@ impl MovieHeaderBox {
             volume,
             matrix,
             next_track_id,
+            pre_defined: [0; 6],
         }
     }
 
+    pub fn set_pre_defined(&mut self, pre_defined: [u32; 6]) {
+        self.pre_defined = pre_defined;
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        if self.version == 1 {
+            writer.write_u64(self.creation_time.as_secs());
+            writer.write_u64(self.modification_time.as_secs());
+            writer.write_u32(self.timescale);
+            writer.write_u64(self.duration);
+        } else {
+            writer.write_u32(self.creation_time.as_secs() as u32);
+            writer.write_u32(self.modification_time.as_secs() as u32);
+            writer.write_u32(self.timescale);
+            writer.write_u32(self.duration as u32);
+        }
+        writer.write_i32(self.rate.raw());
+        writer.write_u16((self.volume.raw() >> 16) as u16);
+        writer.write_bytes(&[0; 10]); // reserved
+        for &value in &self.matrix.values {
+            writer.write_i32(value);
+        }
+        for &value in &self.pre_defined {
+            writer.write_u32(value);
+        }
+        writer.write_u32(self.next_track_id);
+    }
+
     pub fn description(&self) -> &str {
         "Movie Header Box"
     }
 
+    pub fn timescale(&self) -> u32 {
+        self.timescale
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn set_duration(&mut self, duration: u64) {
+        self.duration = duration;
+    }
+
+    /// Movie duration in movie timescale units
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
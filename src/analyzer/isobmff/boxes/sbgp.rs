@@ -0,0 +1,173 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct SampleToGroupBox {
+    version: u8,
+    flags: u32,
+    grouping_type: String,
+    grouping_type_parameter: Option<u32>, // Only present in version 1
+    entries: Vec<SampleToGroupEntry>,
+}
+
+#[derive(Debug)]
+pub struct SampleToGroupEntry {
+    sample_count: u32,            // Number of consecutive samples in this run
+    group_description_index: u32, // 1-based index into sgpd, 0 = not in any group
+}
+
+impl SampleToGroupBox {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        grouping_type: String,
+        grouping_type_parameter: Option<u32>,
+        entries: Vec<SampleToGroupEntry>,
+    ) -> Self {
+        Self {
+            version,
+            flags,
+            grouping_type,
+            grouping_type_parameter,
+            entries,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_fixed_string(&self.grouping_type, 4);
+        if let Some(parameter) = self.grouping_type_parameter {
+            writer.write_u32(parameter);
+        }
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            writer.write_u32(entry.sample_count);
+            writer.write_u32(entry.group_description_index);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Sample To Group Box"
+    }
+
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        let parameter_size = if self.grouping_type_parameter.is_some() {
+            4
+        } else {
+            0
+        };
+        12 + parameter_size + self.entries.len() as u64 * 8
+    }
+
+    /// The same grouping restricted to `count` samples starting at 0-based sample `first`
+    pub fn slice(&self, first: u32, count: u32) -> SampleToGroupBox {
+        let (end, mut sample) = (first as u64 + count as u64, 0u64);
+        let mut entries: Vec<SampleToGroupEntry> = Vec::new();
+        for entry in &self.entries {
+            let run_end = sample + entry.sample_count as u64;
+            let kept = run_end.min(end).saturating_sub(sample.max(first as u64));
+            sample = run_end;
+            if kept == 0 {
+                continue;
+            }
+            match entries.last_mut() {
+                Some(last) if last.group_description_index == entry.group_description_index => {
+                    last.sample_count += kept as u32
+                }
+                _ => entries.push(SampleToGroupEntry::new(
+                    kept as u32,
+                    entry.group_description_index,
+                )),
+            }
+        }
+        SampleToGroupBox::new(
+            self.version,
+            self.flags,
+            self.grouping_type.clone(),
+            self.grouping_type_parameter,
+            entries,
+        )
+    }
+
+    pub fn grouping_type(&self) -> &str {
+        &self.grouping_type
+    }
+
+    pub fn entries(&self) -> &[SampleToGroupEntry] {
+        &self.entries
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "grouping_type",
+            &self.grouping_type,
+            None::<String>,
+        ));
+        if let Some(parameter) = self.grouping_type_parameter {
+            properties.push(Property::new(
+                "grouping_type_parameter",
+                parameter,
+                Some(format!("0x{:08x}", parameter)),
+            ));
+        }
+        properties.push(Property::new(
+            "entry_count",
+            self.entries.len().to_string(),
+            None::<String>,
+        ));
+
+        // 只显示前几个条目, 样本编号从 1 开始
+        let mut first_sample = 1u64;
+        for (i, entry) in self.entries.iter().enumerate() {
+            let last_sample = first_sample + entry.sample_count as u64 - 1;
+            if i < 5 {
+                let group = match entry.group_description_index {
+                    0 => "no group".to_string(),
+                    index if index > 0x10000 => format!("fragment group {}", index - 0x10000),
+                    index => format!("group {}", index),
+                };
+                properties.push(Property::new(
+                    &format!("entry[{}]", i),
+                    format!("{} x {}", entry.sample_count, entry.group_description_index),
+                    Some(format!(
+                        "samples {}-{} -> {}",
+                        first_sample, last_sample, group
+                    )),
+                ));
+            }
+            first_sample = last_sample + 1;
+        }
+
+        if self.entries.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more entries", self.entries.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
+
+impl SampleToGroupEntry {
+    pub fn new(sample_count: u32, group_description_index: u32) -> Self {
+        Self {
+            sample_count,
+            group_description_index,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn group_description_index(&self) -> u32 {
+        self.group_description_index
+    }
+}
@@ -0,0 +1,306 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+use serde::Serialize;
+
+#[derive(Debug)]
+pub struct SampleGroupDescriptionBox {
+    version: u8,
+    flags: u32,
+    grouping_type: String,
+    default_length: Option<u32>, // Only present in version >= 1
+    default_sample_description_index: Option<u32>, // Only present in version >= 2
+    entries: Vec<SampleGroupEntry>,
+    entry_data: Vec<Vec<u8>>, // Undecoded entries, written back as stored
+}
+
+/// Decoded sample group description entry, keyed by grouping type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SampleGroupEntry {
+    /// 'roll' (audio/visual roll recovery) and 'prol' (audio pre-roll)
+    Roll { roll_distance: i16 },
+    /// 'rap ' random access point
+    RandomAccess {
+        num_leading_samples_known: bool,
+        num_leading_samples: u8,
+    },
+    /// 'sync' sync sample NAL unit type (HEVC/VVC)
+    Sync { nal_unit_type: u8 },
+    /// 'sap ' stream access point
+    StreamAccessPoint { dependent_flag: bool, sap_type: u8 },
+    /// 'tele' temporal level
+    TemporalLevel { level_independently_decodable: bool },
+    /// 'seig' CENC sample encryption information (key rotation)
+    CencKey {
+        crypt_byte_block: u8,
+        skip_byte_block: u8,
+        is_protected: bool,
+        per_sample_iv_size: u8,
+        kid: [u8; 16],
+        constant_iv: Vec<u8>,
+    },
+    /// 'av1f' AV1 forward key frame
+    Av1ForwardKeyFrame { fwd_distance: u8 },
+    /// 'av1m' AV1 multi-frame, 'av1s' AV1 switch frame (no payload)
+    Av1Marker,
+    /// Grouping type this parser does not decode
+    Unknown { data: Vec<u8> },
+}
+
+impl SampleGroupDescriptionBox {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        grouping_type: String,
+        default_length: Option<u32>,
+        default_sample_description_index: Option<u32>,
+        entry_data: Vec<Vec<u8>>,
+    ) -> Self {
+        let entries = entry_data
+            .iter()
+            .map(|data| SampleGroupEntry::parse(&grouping_type, data))
+            .collect();
+        Self {
+            version,
+            flags,
+            grouping_type,
+            default_length,
+            default_sample_description_index,
+            entries,
+            entry_data,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_fixed_string(&self.grouping_type, 4);
+        if let Some(default_length) = self.default_length {
+            writer.write_u32(default_length);
+        }
+        if let Some(index) = self.default_sample_description_index {
+            writer.write_u32(index);
+        }
+        writer.write_u32(self.entry_data.len() as u32);
+        for data in &self.entry_data {
+            if self.default_length == Some(0) {
+                writer.write_u32(data.len() as u32);
+            }
+            writer.write_bytes(data);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Sample Group Description Box"
+    }
+
+    pub fn grouping_type(&self) -> &str {
+        &self.grouping_type
+    }
+
+    pub fn default_sample_description_index(&self) -> Option<u32> {
+        self.default_sample_description_index
+    }
+
+    /// Looks up an entry by its 1-based group_description_index
+    pub fn entry(&self, group_description_index: u32) -> Option<&SampleGroupEntry> {
+        let index = group_description_index.checked_sub(1)? as usize;
+        self.entries.get(index)
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "grouping_type",
+            &self.grouping_type,
+            Some(format!(
+                "{} ({})",
+                self.grouping_type,
+                grouping_type_name(&self.grouping_type)
+            )),
+        ));
+        if let Some(default_length) = self.default_length {
+            properties.push(Property::new(
+                "default_length",
+                default_length,
+                None::<String>,
+            ));
+        }
+        if let Some(index) = self.default_sample_description_index {
+            properties.push(Property::new(
+                "default_sample_description_index",
+                index,
+                None::<String>,
+            ));
+        }
+        properties.push(Property::new(
+            "entry_count",
+            self.entries.len().to_string(),
+            None::<String>,
+        ));
+
+        // 只显示前几个条目
+        for (i, entry) in self.entries.iter().take(5).enumerate() {
+            properties.push(Property::new(
+                &format!("entry[{}]", i),
+                entry.to_string(),
+                None::<String>,
+            ));
+        }
+
+        if self.entries.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more entries", self.entries.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
+
+impl SampleGroupEntry {
+    /// Size in bytes of a version 0 entry, where the box carries no length field
+    pub fn fixed_size(grouping_type: &str) -> Option<u32> {
+        match grouping_type {
+            "roll" | "prol" => Some(2),
+            "rap " | "sync" | "sap " | "tele" | "av1f" => Some(1),
+            "av1m" | "av1s" => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Decodes one entry payload for the given grouping type
+    pub fn parse(grouping_type: &str, data: &[u8]) -> Self {
+        let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+
+        match grouping_type {
+            "roll" | "prol" if data.len() >= 2 => SampleGroupEntry::Roll {
+                roll_distance: i16::from_be_bytes([data[0], data[1]]),
+            },
+            "rap " if !data.is_empty() => SampleGroupEntry::RandomAccess {
+                num_leading_samples_known: byte(0) & 0x80 != 0,
+                num_leading_samples: byte(0) & 0x7f,
+            },
+            "sync" if !data.is_empty() => SampleGroupEntry::Sync {
+                nal_unit_type: byte(0) & 0x3f,
+            },
+            "sap " if !data.is_empty() => SampleGroupEntry::StreamAccessPoint {
+                dependent_flag: byte(0) & 0x80 != 0,
+                sap_type: byte(0) & 0x0f,
+            },
+            "tele" if !data.is_empty() => SampleGroupEntry::TemporalLevel {
+                level_independently_decodable: byte(0) & 0x80 != 0,
+            },
+            "av1f" if !data.is_empty() => SampleGroupEntry::Av1ForwardKeyFrame {
+                fwd_distance: byte(0),
+            },
+            "av1m" | "av1s" => SampleGroupEntry::Av1Marker,
+            "seig" if data.len() >= 20 => {
+                let is_protected = byte(2) == 1;
+                let per_sample_iv_size = byte(3);
+                let mut kid = [0u8; 16];
+                kid.copy_from_slice(&data[4..20]);
+
+                // 只有在没有逐样本 IV 时才携带常量 IV
+                let constant_iv = if is_protected && per_sample_iv_size == 0 {
+                    let iv_size = byte(20) as usize;
+                    data.get(21..21 + iv_size).unwrap_or_default().to_vec()
+                } else {
+                    Vec::new()
+                };
+
+                SampleGroupEntry::CencKey {
+                    crypt_byte_block: byte(1) >> 4,
+                    skip_byte_block: byte(1) & 0x0f,
+                    is_protected,
+                    per_sample_iv_size,
+                    kid,
+                    constant_iv,
+                }
+            }
+            _ => SampleGroupEntry::Unknown {
+                data: data.to_vec(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for SampleGroupEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleGroupEntry::Roll { roll_distance } => {
+                write!(f, "roll_distance={}", roll_distance)
+            }
+            SampleGroupEntry::RandomAccess {
+                num_leading_samples_known,
+                num_leading_samples,
+            } => {
+                if *num_leading_samples_known {
+                    write!(f, "num_leading_samples={}", num_leading_samples)
+                } else {
+                    write!(f, "num_leading_samples=unknown")
+                }
+            }
+            SampleGroupEntry::Sync { nal_unit_type } => {
+                write!(f, "nal_unit_type={}", nal_unit_type)
+            }
+            SampleGroupEntry::StreamAccessPoint {
+                dependent_flag,
+                sap_type,
+            } => write!(f, "sap_type={} dependent={}", sap_type, dependent_flag),
+            SampleGroupEntry::TemporalLevel {
+                level_independently_decodable,
+            } => write!(
+                f,
+                "level_independently_decodable={}",
+                level_independently_decodable
+            ),
+            SampleGroupEntry::CencKey {
+                crypt_byte_block,
+                skip_byte_block,
+                is_protected,
+                per_sample_iv_size,
+                kid,
+                constant_iv,
+            } => {
+                let kid_hex: String = kid.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(
+                    f,
+                    "protected={} kid={} iv_size={} pattern={}:{}",
+                    is_protected, kid_hex, per_sample_iv_size, crypt_byte_block, skip_byte_block
+                )?;
+                if !constant_iv.is_empty() {
+                    let iv_hex: String = constant_iv.iter().map(|b| format!("{:02x}", b)).collect();
+                    write!(f, " constant_iv={}", iv_hex)?;
+                }
+                Ok(())
+            }
+            SampleGroupEntry::Av1ForwardKeyFrame { fwd_distance } => {
+                write!(f, "fwd_distance={}", fwd_distance)
+            }
+            SampleGroupEntry::Av1Marker => write!(f, "marker"),
+            SampleGroupEntry::Unknown { data } => write!(f, "{} bytes", data.len()),
+        }
+    }
+}
+
+/// Human readable name of a sample grouping type
+pub fn grouping_type_name(grouping_type: &str) -> &'static str {
+    match grouping_type {
+        "roll" => "Roll Recovery",
+        "prol" => "Audio Pre-Roll",
+        "rap " => "Random Access Point",
+        "sync" => "Sync Sample",
+        "sap " => "Stream Access Point",
+        "tele" => "Temporal Level",
+        "seig" => "CENC Key Rotation",
+        "av1f" => "AV1 Forward Key Frame",
+        "av1m" => "AV1 Multi-Frame",
+        "av1s" => "AV1 Switch Frame",
+        _ => "Unknown",
+    }
+}
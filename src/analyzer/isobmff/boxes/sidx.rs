@@ -0,0 +1,127 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct SegmentIndexBox {
+    version: u8,
+    flags: u32,
+    reference_id: u32,
+    timescale: u32,
+    earliest_presentation_time: u64,
+    first_offset: u64, // From the end of this box to the first referenced byte
+    references: Vec<SegmentReference>,
+}
+
+/// One subsegment (or nested sidx) indexed by a sidx
+#[derive(Debug, Clone)]
+pub struct SegmentReference {
+    pub reference_type: bool, // true = points at another sidx
+    pub referenced_size: u32,
+    pub subsegment_duration: u32, // In sidx timescale units
+    pub starts_with_sap: bool,
+    pub sap_type: u8,
+    pub sap_delta_time: u32,
+}
+
+impl SegmentIndexBox {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        reference_id: u32,
+        timescale: u32,
+        earliest_presentation_time: u64,
+        first_offset: u64,
+        references: Vec<SegmentReference>,
+    ) -> Self {
+        Self {
+            version,
+            flags,
+            reference_id,
+            timescale,
+            earliest_presentation_time,
+            first_offset,
+            references,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.reference_id);
+        writer.write_u32(self.timescale);
+        if self.version == 0 {
+            writer.write_u32(self.earliest_presentation_time as u32);
+            writer.write_u32(self.first_offset as u32);
+        } else {
+            writer.write_u64(self.earliest_presentation_time);
+            writer.write_u64(self.first_offset);
+        }
+        writer.write_u16(0); // reserved
+        writer.write_u16(self.references.len() as u16);
+        for reference in &self.references {
+            writer.write_u32(
+                (reference.reference_type as u32) << 31 | (reference.referenced_size & 0x7fff_ffff),
+            );
+            writer.write_u32(reference.subsegment_duration);
+            writer.write_u32(
+                (reference.starts_with_sap as u32) << 31
+                    | ((reference.sap_type & 0x07) as u32) << 28
+                    | (reference.sap_delta_time & 0x0fff_ffff),
+            );
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Segment Index Box"
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "reference_id",
+            self.reference_id,
+            None::<String>,
+        ));
+        properties.push(Property::new("timescale", self.timescale, None::<String>));
+        properties.push(Property::new(
+            "earliest_presentation_time",
+            self.earliest_presentation_time,
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "first_offset",
+            self.first_offset,
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "reference_count",
+            self.references.len(),
+            None::<String>,
+        ));
+        for (i, reference) in self.references.iter().enumerate() {
+            properties.push(Property::new(
+                &format!("reference[{}]", i),
+                format!(
+                    "{} {} bytes, duration {}, sap {}",
+                    if reference.reference_type {
+                        "sidx"
+                    } else {
+                        "media"
+                    },
+                    reference.referenced_size,
+                    reference.subsegment_duration,
+                    if reference.starts_with_sap {
+                        format!("type {}", reference.sap_type)
+                    } else {
+                        "none".to_string()
+                    }
+                ),
+                None::<String>,
+            ));
+        }
+    }
+}
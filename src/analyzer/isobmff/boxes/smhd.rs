@@ -1,5 +1,6 @@
 use crate::analyzer::isobmff::types::Fixed16_16;
 use crate::analyzer::Property;
+use crate::writer::Writer;
 
 #[derive(Debug)]
 pub struct SoundMediaHeaderBox {
@@ -17,6 +18,12 @@ impl SoundMediaHeaderBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_i16(self.balance.raw() as i16);
+        writer.write_u16(0); // reserved
+    }
+
     pub fn description(&self) -> &str {
         "Sound Media Header Box"
     }
@@ -1,18 +1,33 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 
 #[derive(Debug)]
 pub struct ChunkOffsetBox {
     version: u8,
     flags: u32,
     offsets: Vec<u64>, // u32 for stco, u64 for co64
+    large: bool,       // Stored as co64
 }
 
 impl ChunkOffsetBox {
-    pub fn new(version: u8, flags: u32, offsets: Vec<u64>) -> Self {
+    pub fn new(version: u8, flags: u32, offsets: Vec<u64>, large: bool) -> Self {
         Self {
             version,
             flags,
             offsets,
+            large,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.offsets.len() as u32);
+        for &offset in &self.offsets {
+            if self.large {
+                writer.write_u64(offset);
+            } else {
+                writer.write_u32(offset as u32);
+            }
         }
     }
 
@@ -20,6 +35,27 @@ impl ChunkOffsetBox {
         "Chunk Offset Box"
     }
 
+    /// Payload size implied by the entries; `large` selects the co64 layout
+    pub fn content_size(&self, large: bool) -> u64 {
+        8 + self.offsets.len() as u64 * if large { 8 } else { 4 }
+    }
+
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    pub fn set_offsets(&mut self, offsets: Vec<u64>) {
+        self.offsets = offsets;
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.large
+    }
+
+    pub fn set_large(&mut self, large: bool) {
+        self.large = large;
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct SampleToChunkBox {
     version: u8,
@@ -22,10 +23,29 @@ impl SampleToChunkBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            writer.write_u32(entry.first_chunk);
+            writer.write_u32(entry.samples_per_chunk);
+            writer.write_u32(entry.sample_description_index);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Sample To Chunk Box"
     }
 
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        8 + self.entries.len() as u64 * 12
+    }
+
+    pub fn entries(&self) -> &[SampleToChunkEntry] {
+        &self.entries
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -71,4 +91,16 @@ impl SampleToChunkEntry {
             sample_description_index,
         }
     }
+
+    pub fn first_chunk(&self) -> u32 {
+        self.first_chunk
+    }
+
+    pub fn samples_per_chunk(&self) -> u32 {
+        self.samples_per_chunk
+    }
+
+    pub fn sample_description_index(&self) -> u32 {
+        self.sample_description_index
+    }
 }
@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct SampleDescriptionBox {
     version: u8,
@@ -24,10 +25,26 @@ impl SampleDescriptionBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            writer.write_u32(16 + entry.data.len() as u32);
+            writer.write_fixed_string(&entry.entry_type, 4);
+            writer.write_bytes(&[0; 6]); // reserved
+            writer.write_u16(entry.data_reference_index);
+            writer.write_bytes(&entry.data);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Sample Description Box"
     }
 
+    pub fn entries(&self) -> &[SampleEntry] {
+        &self.entries
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -73,4 +90,57 @@ impl SampleEntry {
             data,
         }
     }
+
+    /// Codec 4CC, e.g. `avc1` or `gpmd`
+    pub fn entry_type(&self) -> &str {
+        &self.entry_type
+    }
+
+    /// Sample rate of an audio sample entry (version 0/1 sound description)
+    pub fn audio_sample_rate(&self) -> Option<u32> {
+        let version = u16::from_be_bytes([*self.data.first()?, *self.data.get(1)?]);
+        if version > 1 || self.data.len() < 20 {
+            return None;
+        }
+        Some(u32::from_be_bytes([self.data[16], self.data[17], self.data[18], self.data[19]]) >> 16)
+    }
+
+    /// Payload of a child box of the entry (e.g. `avcC`, `esds`, `dOps`). The children follow
+    /// the fixed visual (70 bytes) or sound (20/36/56 bytes) fields; the layout whose boxes
+    /// tile the rest of the entry is used.
+    pub fn child_box(&self, box_type: &str) -> Option<&[u8]> {
+        [70, 20, 36, 56]
+            .into_iter()
+            .find_map(|start| self.children_at(start))?
+            .into_iter()
+            .find(|(name, _)| name.as_bytes() == box_type.as_bytes())
+            .map(|(_, payload)| payload)
+    }
+
+    /// Width and height of a visual sample entry
+    pub fn video_dimensions(&self) -> Option<(u16, u16)> {
+        let field = |at: usize| {
+            Some(u16::from_be_bytes([
+                *self.data.get(at)?,
+                *self.data.get(at + 1)?,
+            ]))
+        };
+        Some((field(16)?, field(18)?))
+    }
+
+    fn children_at(&self, start: usize) -> Option<Vec<(&str, &[u8])>> {
+        let mut children = Vec::new();
+        let mut at = start;
+        while at < self.data.len() {
+            let header = self.data.get(at..at + 8)?;
+            let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let name = std::str::from_utf8(&header[4..8]).ok()?;
+            if size < 8 || !name.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+                return None;
+            }
+            children.push((name, self.data.get(at + 8..at + size)?));
+            at += size;
+        }
+        (!children.is_empty()).then_some(children)
+    }
 }
@@ -0,0 +1,74 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct SyncSampleBox {
+    version: u8,
+    flags: u32,
+    sample_numbers: Vec<u32>, // 1-based numbers of the sync samples, ascending
+}
+
+impl SyncSampleBox {
+    pub fn new(version: u8, flags: u32, sample_numbers: Vec<u32>) -> Self {
+        Self {
+            version,
+            flags,
+            sample_numbers,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.sample_numbers.len() as u32);
+        for &number in &self.sample_numbers {
+            writer.write_u32(number);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Sync Sample Box"
+    }
+
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        8 + self.sample_numbers.len() as u64 * 4
+    }
+
+    pub fn sample_numbers(&self) -> &[u32] {
+        &self.sample_numbers
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new(
+            "version",
+            self.version.to_string(),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "entry_count",
+            self.sample_numbers.len().to_string(),
+            None::<String>,
+        ));
+
+        // Only show first few sync samples to avoid overwhelming output
+        for (i, number) in self.sample_numbers.iter().take(5).enumerate() {
+            properties.push(Property::new(
+                &format!("sample_number[{}]", i),
+                number.to_string(),
+                None::<String>,
+            ));
+        }
+        if self.sample_numbers.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more", self.sample_numbers.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct SampleSizeBox {
     version: u8,
@@ -25,10 +26,41 @@ impl SampleSizeBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.sample_size);
+        if self.sample_size == 0 {
+            writer.write_u32(self.entry_sizes.len() as u32);
+            for &size in &self.entry_sizes {
+                writer.write_u32(size);
+            }
+        } else {
+            writer.write_u32(self.sample_count);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Sample Size Box"
     }
 
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        12 + self.entry_sizes.len() as u64 * 4
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Size of the sample at the given 0-based index
+    pub fn sample_size(&self, index: usize) -> Option<u32> {
+        if self.sample_size != 0 {
+            (index < self.sample_count as usize).then_some(self.sample_size)
+        } else {
+            self.entry_sizes.get(index).copied()
+        }
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct TimeToSampleBox {
     version: u8,
@@ -21,10 +22,28 @@ impl TimeToSampleBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        for entry in &self.entries {
+            writer.write_u32(entry.sample_count);
+            writer.write_u32(entry.sample_delta);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Time To Sample Box"
     }
 
+    /// Payload size implied by the entries (version/flags included)
+    pub fn content_size(&self) -> u64 {
+        8 + self.entries.len() as u64 * 8
+    }
+
+    pub fn entries(&self) -> &[TimeToSampleEntry] {
+        &self.entries
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
@@ -64,4 +83,12 @@ impl TimeToSampleEntry {
             sample_delta,
         }
     }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn sample_delta(&self) -> u32 {
+        self.sample_delta
+    }
 }
@@ -0,0 +1,50 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct TrackFragmentDecodeTimeBox {
+    version: u8,
+    flags: u32,
+    base_media_decode_time: u64, // DTS of the first sample, in media timescale units
+}
+
+impl TrackFragmentDecodeTimeBox {
+    pub fn new(version: u8, flags: u32, base_media_decode_time: u64) -> Self {
+        Self {
+            version,
+            flags,
+            base_media_decode_time,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        if self.version == 1 {
+            writer.write_u64(self.base_media_decode_time);
+        } else {
+            writer.write_u32(self.base_media_decode_time as u32);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Track Fragment Decode Time Box"
+    }
+
+    pub fn base_media_decode_time(&self) -> u64 {
+        self.base_media_decode_time
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "base_media_decode_time",
+            self.base_media_decode_time,
+            None::<String>,
+        ));
+    }
+}
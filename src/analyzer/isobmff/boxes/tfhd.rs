@@ -0,0 +1,155 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+pub const TFHD_BASE_DATA_OFFSET: u32 = 0x000001;
+pub const TFHD_SAMPLE_DESCRIPTION_INDEX: u32 = 0x000002;
+pub const TFHD_DEFAULT_SAMPLE_DURATION: u32 = 0x000008;
+pub const TFHD_DEFAULT_SAMPLE_SIZE: u32 = 0x000010;
+pub const TFHD_DEFAULT_SAMPLE_FLAGS: u32 = 0x000020;
+pub const TFHD_DURATION_IS_EMPTY: u32 = 0x010000;
+pub const TFHD_DEFAULT_BASE_IS_MOOF: u32 = 0x020000;
+
+#[derive(Debug)]
+pub struct TrackFragmentHeaderBox {
+    version: u8,
+    flags: u32,
+    track_id: u32,
+    base_data_offset: Option<u64>,
+    sample_description_index: Option<u32>,
+    default_sample_duration: Option<u32>,
+    default_sample_size: Option<u32>,
+    default_sample_flags: Option<u32>,
+}
+
+impl TrackFragmentHeaderBox {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        version: u8,
+        flags: u32,
+        track_id: u32,
+        base_data_offset: Option<u64>,
+        sample_description_index: Option<u32>,
+        default_sample_duration: Option<u32>,
+        default_sample_size: Option<u32>,
+        default_sample_flags: Option<u32>,
+    ) -> Self {
+        Self {
+            version,
+            flags,
+            track_id,
+            base_data_offset,
+            sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.track_id);
+        if let Some(offset) = self.base_data_offset {
+            writer.write_u64(offset);
+        }
+        for value in [
+            self.sample_description_index,
+            self.default_sample_duration,
+            self.default_sample_size,
+            self.default_sample_flags,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            writer.write_u32(value);
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Track Fragment Header Box"
+    }
+
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn base_data_offset(&self) -> Option<u64> {
+        self.base_data_offset
+    }
+
+    /// Only replaces an offset that is present, the flags are left as they are
+    pub fn set_base_data_offset(&mut self, offset: u64) {
+        if self.base_data_offset.is_some() {
+            self.base_data_offset = Some(offset);
+        }
+    }
+
+    pub fn default_sample_size(&self) -> Option<u32> {
+        self.default_sample_size
+    }
+
+    pub fn default_sample_duration(&self) -> Option<u32> {
+        self.default_sample_duration
+    }
+
+    pub fn sample_description_index(&self) -> Option<u32> {
+        self.sample_description_index
+    }
+
+    pub fn default_sample_flags(&self) -> Option<u32> {
+        self.default_sample_flags
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+
+        let mut flag_names = Vec::new();
+        if self.flags & TFHD_DURATION_IS_EMPTY != 0 {
+            flag_names.push("duration-is-empty");
+        }
+        if self.flags & TFHD_DEFAULT_BASE_IS_MOOF != 0 {
+            flag_names.push("default-base-is-moof");
+        }
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            (!flag_names.is_empty()).then(|| flag_names.join(", ")),
+        ));
+        properties.push(Property::new("track_ID", self.track_id, None::<String>));
+
+        if let Some(offset) = self.base_data_offset {
+            properties.push(Property::new(
+                "base_data_offset",
+                offset,
+                Some(format!("{:#x}", offset)),
+            ));
+        }
+        if let Some(index) = self.sample_description_index {
+            properties.push(Property::new(
+                "sample_description_index",
+                index,
+                None::<String>,
+            ));
+        }
+        if let Some(duration) = self.default_sample_duration {
+            properties.push(Property::new(
+                "default_sample_duration",
+                duration,
+                None::<String>,
+            ));
+        }
+        if let Some(size) = self.default_sample_size {
+            properties.push(Property::new("default_sample_size", size, None::<String>));
+        }
+        if let Some(flags) = self.default_sample_flags {
+            properties.push(Property::new(
+                "default_sample_flags",
+                format!("0x{:08x}", flags),
+                None::<String>,
+            ));
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::analyzer::isobmff::types::{Fixed16_16, Matrix, Mp4DateTime};
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct TrackHeaderBox {
     version: u8,
@@ -47,10 +48,54 @@ impl TrackHeaderBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        if self.version == 1 {
+            writer.write_u64(self.creation_time.as_secs());
+            writer.write_u64(self.modification_time.as_secs());
+            writer.write_u32(self.track_id);
+            writer.write_u32(0); // reserved
+            writer.write_u64(self.duration);
+        } else {
+            writer.write_u32(self.creation_time.as_secs() as u32);
+            writer.write_u32(self.modification_time.as_secs() as u32);
+            writer.write_u32(self.track_id);
+            writer.write_u32(0); // reserved
+            writer.write_u32(self.duration as u32);
+        }
+        writer.write_bytes(&[0; 8]); // reserved
+        writer.write_i16(self.layer);
+        writer.write_i16(self.alternate_group);
+        writer.write_i16(self.volume.raw() as i16);
+        writer.write_u16(0); // reserved
+        for &value in &self.matrix.values {
+            writer.write_i32(value);
+        }
+        writer.write_i32(self.width.raw());
+        writer.write_i32(self.height.raw());
+    }
+
     pub fn description(&self) -> &str {
         "Track Header Box"
     }
 
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn set_duration(&mut self, duration: u64) {
+        self.duration = duration;
+    }
+
+    /// Track duration in movie timescale units
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
     pub fn fill_properties(&self, properties: &mut Vec<Property>) {
         properties.push(Property::new(
             "version",
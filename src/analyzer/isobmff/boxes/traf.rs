@@ -0,0 +1,18 @@
+use crate::analyzer::Property;
+
+#[derive(Debug, Default)]
+pub struct TrackFragmentBox;
+
+impl TrackFragmentBox {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn description(&self) -> &str {
+        "Track Fragment Box"
+    }
+
+    pub fn fill_properties(&self, _properties: &mut Vec<Property>) {
+        // Track fragment box itself doesn't have properties, its children contain the actual data
+    }
+}
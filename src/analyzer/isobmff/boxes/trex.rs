@@ -0,0 +1,98 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+#[derive(Debug)]
+pub struct TrackExtendsBox {
+    version: u8,
+    flags: u32,
+    track_id: u32,
+    default_sample_description_index: u32,
+    default_sample_duration: u32,
+    default_sample_size: u32,
+    default_sample_flags: u32,
+}
+
+impl TrackExtendsBox {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        track_id: u32,
+        default_sample_description_index: u32,
+        default_sample_duration: u32,
+        default_sample_size: u32,
+        default_sample_flags: u32,
+    ) -> Self {
+        Self {
+            version,
+            flags,
+            track_id,
+            default_sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.track_id);
+        writer.write_u32(self.default_sample_description_index);
+        writer.write_u32(self.default_sample_duration);
+        writer.write_u32(self.default_sample_size);
+        writer.write_u32(self.default_sample_flags);
+    }
+
+    pub fn description(&self) -> &str {
+        "Track Extends Box"
+    }
+
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+
+    pub fn default_sample_duration(&self) -> u32 {
+        self.default_sample_duration
+    }
+
+    pub fn default_sample_size(&self) -> u32 {
+        self.default_sample_size
+    }
+
+    pub fn default_sample_description_index(&self) -> u32 {
+        self.default_sample_description_index
+    }
+
+    pub fn default_sample_flags(&self) -> u32 {
+        self.default_sample_flags
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new("track_ID", self.track_id, None::<String>));
+        properties.push(Property::new(
+            "default_sample_description_index",
+            self.default_sample_description_index,
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "default_sample_duration",
+            self.default_sample_duration,
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "default_sample_size",
+            self.default_sample_size,
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "default_sample_flags",
+            format!("0x{:08x}", self.default_sample_flags),
+            None::<String>,
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::analyzer::Property;
+use crate::writer::Writer;
+
+pub const TRUN_DATA_OFFSET: u32 = 0x000001;
+pub const TRUN_FIRST_SAMPLE_FLAGS: u32 = 0x000004;
+pub const TRUN_SAMPLE_DURATION: u32 = 0x000100;
+pub const TRUN_SAMPLE_SIZE: u32 = 0x000200;
+pub const TRUN_SAMPLE_FLAGS: u32 = 0x000400;
+pub const TRUN_SAMPLE_COMPOSITION_TIME_OFFSET: u32 = 0x000800;
+
+#[derive(Debug)]
+pub struct TrackRunBox {
+    version: u8,
+    flags: u32,
+    data_offset: Option<i32>, // Relative to the base data offset of the traf
+    first_sample_flags: Option<u32>,
+    entries: Vec<TrackRunEntry>,
+}
+
+/// Per-sample fields of a trun; absent fields fall back to the tfhd/trex defaults
+#[derive(Debug)]
+pub struct TrackRunEntry {
+    pub duration: Option<u32>,
+    pub size: Option<u32>,
+    pub flags: Option<u32>,
+    pub composition_time_offset: Option<i64>, // Unsigned in version 0, signed in version 1
+}
+
+impl TrackRunBox {
+    pub fn new(
+        version: u8,
+        flags: u32,
+        data_offset: Option<i32>,
+        first_sample_flags: Option<u32>,
+        entries: Vec<TrackRunEntry>,
+    ) -> Self {
+        Self {
+            version,
+            flags,
+            data_offset,
+            first_sample_flags,
+            entries,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u32(self.entries.len() as u32);
+        if let Some(offset) = self.data_offset {
+            writer.write_i32(offset);
+        }
+        if let Some(flags) = self.first_sample_flags {
+            writer.write_u32(flags);
+        }
+        for entry in &self.entries {
+            for value in [entry.duration, entry.size, entry.flags]
+                .into_iter()
+                .flatten()
+            {
+                writer.write_u32(value);
+            }
+            if let Some(offset) = entry.composition_time_offset {
+                writer.write_u32(offset as u32);
+            }
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        "Track Run Box"
+    }
+
+    pub fn entries(&self) -> &[TrackRunEntry] {
+        &self.entries
+    }
+
+    pub fn data_offset(&self) -> Option<i32> {
+        self.data_offset
+    }
+
+    pub fn first_sample_flags(&self) -> Option<u32> {
+        self.first_sample_flags
+    }
+
+    pub fn fill_properties(&self, properties: &mut Vec<Property>) {
+        properties.push(Property::new("version", self.version, None::<String>));
+        properties.push(Property::new(
+            "flags",
+            format!("0x{:06x}", self.flags),
+            None::<String>,
+        ));
+        properties.push(Property::new(
+            "sample_count",
+            self.entries.len(),
+            None::<String>,
+        ));
+        if let Some(offset) = self.data_offset {
+            properties.push(Property::new("data_offset", offset, None::<String>));
+        }
+        if let Some(flags) = self.first_sample_flags {
+            properties.push(Property::new(
+                "first_sample_flags",
+                format!("0x{:08x}", flags),
+                None::<String>,
+            ));
+        }
+
+        // 只显示前几个样本
+        for (i, entry) in self.entries.iter().take(5).enumerate() {
+            let mut fields = Vec::new();
+            if let Some(duration) = entry.duration {
+                fields.push(format!("duration={}", duration));
+            }
+            if let Some(size) = entry.size {
+                fields.push(format!("size={}", size));
+            }
+            if let Some(flags) = entry.flags {
+                fields.push(format!("flags=0x{:08x}", flags));
+            }
+            if let Some(offset) = entry.composition_time_offset {
+                fields.push(format!("cto={}", offset));
+            }
+            properties.push(Property::new(
+                &format!("sample[{}]", i),
+                fields.join(", "),
+                None::<String>,
+            ));
+        }
+        if self.entries.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more entries", self.entries.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use crate::analyzer::Property;
+
+#[derive(Debug, Default)]
+pub struct UserDataBox;
+
+impl UserDataBox {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn description(&self) -> &str {
+        "User Data Box"
+    }
+
+    pub fn fill_properties(&self, _properties: &mut Vec<Property>) {
+        // User data box itself doesn't have properties, its children contain the actual data
+    }
+}
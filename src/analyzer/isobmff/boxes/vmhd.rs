@@ -1,4 +1,5 @@
 use crate::analyzer::Property;
+use crate::writer::Writer;
 #[derive(Debug)]
 pub struct VideoMediaHeaderBox {
     version: u8,
@@ -17,6 +18,14 @@ impl VideoMediaHeaderBox {
         }
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_version_flags(self.version, self.flags);
+        writer.write_u16(self.graphics_mode);
+        for &value in &self.op_color {
+            writer.write_u16(value);
+        }
+    }
+
     pub fn description(&self) -> &str {
         "Video Media Header Box"
     }
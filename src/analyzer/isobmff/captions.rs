@@ -0,0 +1,278 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::faststart::{read_bytes, same_file};
+use super::sample_index::TrackSampleIndex;
+use super::timeline::TrackTimeline;
+use super::track::{self, Track};
+use super::IsobmffAnalyzer;
+use crate::analyzer::captions::{
+    a53_cc_data, c608_cc_data, write_scc, write_srt, CaptionChannel, CaptionDecoder, CcData,
+};
+use crate::analyzer::extract::NalConfig;
+use crate::analyzer::sei::sei_payloads;
+use crate::error::MediaError;
+
+/// Where the cc_data of a track comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionSource {
+    /// ATSC A/53 user data in H.264/H.265 SEI
+    Sei,
+    /// QuickTime closed caption track
+    C608,
+}
+
+/// Closed captions carried by one track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackCaptions {
+    pub track_id: u32,
+    pub codec: String,
+    pub source: CaptionSource,
+    /// Samples that carry cc_data
+    pub samples_with_captions: u32,
+    pub channels: Vec<CaptionChannel>,
+    /// Video frame rate, or for `c608` tracks the rate at which the byte pairs of a sample
+    /// are spread
+    pub frame_rate: f64,
+    /// Raw CEA-608 field 1 pairs with their presentation time, for SCC
+    #[serde(skip)]
+    pub field1: Vec<(f64, [u8; 2])>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionReport {
+    pub tracks: Vec<TrackCaptions>,
+}
+
+/// Subtitle file written from a caption channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionFormat {
+    Srt,
+    /// Scenarist SCC; always the raw CEA-608 field 1 data (CC1 and CC2)
+    Scc,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptionExportReport {
+    pub track_id: u32,
+    pub channel: String,
+    pub format: CaptionFormat,
+    /// Cues written to SRT, or byte pairs written to SCC
+    pub entries: u64,
+    pub bytes_written: u64,
+}
+
+/// Decodes the CEA-608/708 captions of the H.264/H.265 tracks (A/53 SEI) and the `c608`
+/// tracks, with cue times on the presentation timeline
+pub fn analyze_captions(input: &Path) -> Result<CaptionReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let movie_timescale = track::movie_timescale(boxes);
+    let mut source = File::open(input)?;
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        let Some(entry) = track.sample_entry() else {
+            continue;
+        };
+        let codec = entry.entry_type().to_string();
+        let (caption_source, config) = match codec.as_str() {
+            "avc1" | "avc3" => (
+                CaptionSource::Sei,
+                entry.child_box("avcC").and_then(NalConfig::from_avcc),
+            ),
+            "hvc1" | "hev1" => (
+                CaptionSource::Sei,
+                entry.child_box("hvcC").and_then(NalConfig::from_hvcc),
+            ),
+            "c608" => (CaptionSource::C608, None),
+            _ => continue,
+        };
+        if caption_source == CaptionSource::Sei && config.is_none() {
+            continue;
+        }
+
+        // 以编辑列表把 CTS 映射到呈现时间轴
+        let timeline = TrackTimeline::build(track, Some(index), movie_timescale);
+        let timescale = index.timescale.max(1) as f64;
+        let pts =
+            |cts: i64| cts as f64 / timescale - timeline.media_start + timeline.presentation_start;
+        let frame_rate = frame_rate(index);
+
+        let mut timed: Vec<(f64, Vec<CcData>)> = Vec::new();
+        for sample in &index.samples {
+            let data = read_bytes(&mut source, sample.offset, sample.size as u64)?;
+            let start = pts(sample.cts);
+            match &config {
+                Some(config) => {
+                    let mut cc_data = Vec::new();
+                    let mut at = 0;
+                    while let Some(prefix) = data.get(at..at + config.length_size) {
+                        let len = prefix.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+                        let Some(nal) =
+                            data.get(at + config.length_size..at + config.length_size + len)
+                        else {
+                            break;
+                        };
+                        at += config.length_size + len;
+                        if is_sei(nal, config.hevc) {
+                            for (payload_type, payload) in sei_payloads(nal, config.hevc) {
+                                if payload_type == 4 {
+                                    cc_data.extend(a53_cc_data(&payload));
+                                }
+                            }
+                        }
+                    }
+                    if !cc_data.is_empty() {
+                        timed.push((start, cc_data));
+                    }
+                }
+                None => {
+                    // 每帧一个字节对, 按帧率展开样本内的多个字节对
+                    let cc_data = c608_cc_data(&data);
+                    let mut field_index = [0usize; 2];
+                    for cc in cc_data {
+                        let i = &mut field_index[cc.cc_type as usize & 1];
+                        timed.push((start + *i as f64 / frame_rate, vec![cc]));
+                        *i += 1;
+                    }
+                }
+            }
+        }
+        if timed.is_empty() && caption_source == CaptionSource::Sei {
+            continue;
+        }
+        let samples_with_captions = match caption_source {
+            CaptionSource::Sei => timed.len() as u32,
+            CaptionSource::C608 => index.samples.len() as u32,
+        };
+        // SEI 按解码顺序存放, 字幕需按呈现顺序解码
+        timed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let end = index
+            .samples
+            .iter()
+            .map(|s| pts(s.cts + s.duration as i64))
+            .fold(0.0, f64::max);
+        let mut decoder = CaptionDecoder::new();
+        for (time, cc_data) in timed {
+            for cc in cc_data {
+                decoder.push(time, cc);
+            }
+        }
+        let field1 = std::mem::take(&mut decoder.field1);
+        let mut warnings = index.warnings.clone();
+        let channels = decoder.finish(end);
+        if channels.is_empty() {
+            warnings.push("cc_data present but no caption text decoded".to_string());
+        }
+        tracks.push(TrackCaptions {
+            track_id: track.track_id(),
+            codec,
+            source: caption_source,
+            samples_with_captions,
+            channels,
+            frame_rate,
+            field1,
+            warnings,
+        });
+    }
+    if tracks.is_empty() {
+        return Err(MediaError::UnsupportedFormat(
+            "the file has no closed captions".to_string(),
+        ));
+    }
+    Ok(CaptionReport { tracks })
+}
+
+/// Writes one caption channel as SRT, or the CEA-608 field 1 data as SCC. Without `channel`
+/// the first channel with cues is used.
+pub fn export_captions(
+    input: &Path,
+    channel: Option<&str>,
+    output: &Path,
+    format: CaptionFormat,
+) -> Result<CaptionExportReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    let report = analyze_captions(input)?;
+    let found = report.tracks.iter().find_map(|track| {
+        track
+            .channels
+            .iter()
+            .find(|c| channel.is_none_or(|name| c.name.eq_ignore_ascii_case(name)))
+            .map(|c| (track, c))
+    });
+    let Some((track, selected)) = found else {
+        return Err(MediaError::InvalidFormat(match channel {
+            Some(name) => format!("no caption channel {}", name),
+            None => "no caption text to export".to_string(),
+        }));
+    };
+
+    let mut out = BufWriter::new(File::create(output)?);
+    let entries = match format {
+        CaptionFormat::Srt => {
+            write_srt(&selected.cues, &mut out)?;
+            selected.cues.len() as u64
+        }
+        CaptionFormat::Scc => {
+            if !selected.name.starts_with("CC") {
+                return Err(MediaError::InvalidFormat(
+                    "SCC holds CEA-608 data only; choose CC1 or CC2".to_string(),
+                ));
+            }
+            write_scc(&track.field1, &mut out)?;
+            track.field1.len() as u64
+        }
+    };
+    out.flush()?;
+    drop(out);
+    Ok(CaptionExportReport {
+        track_id: track.track_id,
+        channel: selected.name.clone(),
+        format,
+        entries,
+        bytes_written: std::fs::metadata(output)?.len(),
+    })
+}
+
+fn is_sei(nal: &[u8], hevc: bool) -> bool {
+    match nal.first() {
+        Some(&header) if hevc => matches!((header >> 1) & 0x3f, 39 | 40),
+        Some(&header) => header & 0x1f == 6,
+        None => false,
+    }
+}
+
+/// Frame rate from the most common sample duration, 29.97 when unknown
+fn frame_rate(index: &TrackSampleIndex) -> f64 {
+    let mut counts: Vec<(u32, u32)> = Vec::new();
+    for sample in &index.samples {
+        match counts
+            .iter_mut()
+            .find(|(duration, _)| *duration == sample.duration)
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((sample.duration, 1)),
+        }
+    }
+    match counts.iter().max_by_key(|(_, count)| *count) {
+        Some(&(duration, _)) if duration > 0 && index.timescale > 0 => {
+            index.timescale as f64 / duration as f64
+        }
+        _ => 30000.0 / 1001.0,
+    }
+}
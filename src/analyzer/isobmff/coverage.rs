@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo, TFHD_BASE_DATA_OFFSET, TFHD_DEFAULT_BASE_IS_MOOF};
+use super::sample_index::TrackSampleIndex;
+
+/// Upper bound of stray samples and overlaps listed individually
+const MAX_LISTED: usize = 64;
+/// Bytes of an unreferenced range inspected to tell padding from data
+const GAP_SCAN_LIMIT: u64 = 1 << 20;
+
+/// How many samples reference a byte range
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageKind {
+    Referenced,
+    Unreferenced,
+    /// Referenced by two or more samples
+    Overlapping,
+}
+
+/// What an unreferenced range contains
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapContent {
+    /// Only zero bytes (padding)
+    Zeros,
+    /// Non-zero bytes: leftover media or a hidden payload
+    Data,
+}
+
+/// Half-open byte range [start, end) of an mdat payload
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageRange {
+    pub start: u64,
+    pub end: u64,
+    pub kind: CoverageKind,
+    /// Set for unreferenced ranges once their bytes have been inspected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<GapContent>,
+}
+
+impl CoverageRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Coverage of the payload of one top-level mdat
+#[derive(Debug, Clone, Serialize)]
+pub struct MdatCoverage {
+    /// Offset of the mdat box header
+    pub offset: u64,
+    pub payload_start: u64,
+    pub payload_end: u64,
+    pub referenced_bytes: u64,
+    pub unreferenced_bytes: u64,
+    pub overlapping_bytes: u64,
+    pub ranges: Vec<CoverageRange>,
+}
+
+impl MdatCoverage {
+    pub fn payload_size(&self) -> u64 {
+        self.payload_end - self.payload_start
+    }
+}
+
+/// One sample, identified by track and 1-based sample number
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SampleRef {
+    pub track_id: u32,
+    pub number: u32,
+}
+
+/// Why a sample is not fully inside an mdat payload
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrayReason {
+    /// Ends beyond the end of the file
+    OutsideFile,
+    /// Lies in the file but in no mdat (e.g. inside moov)
+    OutsideMdat,
+    /// Starts in an mdat but runs past its end
+    CrossesMdatEnd,
+}
+
+/// A sample whose bytes are not (entirely) stored in an mdat
+#[derive(Debug, Clone, Serialize)]
+pub struct StraySample {
+    pub sample: SampleRef,
+    pub offset: u64,
+    pub size: u64,
+    pub reason: StrayReason,
+}
+
+/// Bytes shared by several samples
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleOverlap {
+    pub start: u64,
+    pub end: u64,
+    pub samples: Vec<SampleRef>,
+}
+
+/// Which bytes of the mdat boxes the sample tables actually reference
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    pub file_size: u64,
+    pub mdats: Vec<MdatCoverage>,
+    /// First stray samples, `stray_sample_count` has the total
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stray_samples: Vec<StraySample>,
+    pub stray_sample_count: u32,
+    /// First overlapping ranges, `overlap_count` has the total
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<SampleOverlap>,
+    pub overlap_count: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Byte span [start, end) of one sample
+struct SampleSpan {
+    sample: SampleRef,
+    start: u64,
+    end: u64,
+}
+
+impl CoverageReport {
+    /// Maps the samples of the moov sample tables and of every movie fragment onto the mdat boxes
+    pub fn build(boxes: &[Box], indexes: &[TrackSampleIndex], file_size: u64) -> CoverageReport {
+        let mut spans: Vec<SampleSpan> = indexes
+            .iter()
+            .flat_map(|index| {
+                index.samples.iter().map(move |s| SampleSpan {
+                    sample: SampleRef {
+                        track_id: index.track_id,
+                        number: s.number,
+                    },
+                    start: s.offset,
+                    end: s.offset.saturating_add(s.size as u64),
+                })
+            })
+            .collect();
+        let mut numbers: HashMap<u32, u32> = indexes
+            .iter()
+            .map(|index| (index.track_id, index.samples.len() as u32))
+            .collect();
+        spans.extend(fragment_spans(boxes, &mut numbers));
+        spans.retain(|span| span.end > span.start);
+
+        let payloads: Vec<(u64, u64, u64)> = boxes
+            .iter()
+            .filter(|b| b.box_type().as_str() == "mdat")
+            .map(|b| {
+                let start = (b.offset() + b.header_size()).min(file_size);
+                let end = (b.offset() + b.size()).min(file_size);
+                (b.offset(), start, end.max(start))
+            })
+            .collect();
+
+        // 检查每个样本是否完整地位于某个 mdat 内
+        let mut stray_samples = Vec::new();
+        let mut stray_sample_count = 0u32;
+        for span in &spans {
+            let reason = if span.end > file_size {
+                StrayReason::OutsideFile
+            } else {
+                match payloads
+                    .iter()
+                    .find(|&&(_, start, end)| span.start >= start && span.start < end)
+                {
+                    Some(&(_, _, end)) if span.end > end => StrayReason::CrossesMdatEnd,
+                    Some(_) => continue,
+                    None => StrayReason::OutsideMdat,
+                }
+            };
+            stray_sample_count += 1;
+            if stray_samples.len() < MAX_LISTED {
+                stray_samples.push(StraySample {
+                    sample: span.sample,
+                    offset: span.start,
+                    size: span.end - span.start,
+                    reason,
+                });
+            }
+        }
+
+        let (segments, overlaps, overlap_count) = sweep(&spans);
+
+        let mdats = payloads
+            .iter()
+            .map(|&(offset, start, end)| mdat_coverage(offset, start, end, &segments))
+            .collect::<Vec<_>>();
+
+        let mut warnings = Vec::new();
+        if stray_sample_count > 0 {
+            warnings.push(format!(
+                "{} samples are not fully stored inside an mdat",
+                stray_sample_count
+            ));
+        }
+        if overlap_count > 0 {
+            warnings.push(format!(
+                "{} byte ranges are referenced by more than one sample",
+                overlap_count
+            ));
+        }
+
+        CoverageReport {
+            file_size,
+            mdats,
+            stray_samples,
+            stray_sample_count,
+            overlaps,
+            overlap_count,
+            warnings,
+        }
+    }
+
+    /// Reads the start of every unreferenced range to tell padding from leftover data
+    pub fn inspect_gaps(&mut self, mut read: impl FnMut(u64, usize) -> Option<Vec<u8>>) {
+        let mut data_ranges = 0u32;
+        let mut data_bytes = 0u64;
+        for mdat in &mut self.mdats {
+            for range in &mut mdat.ranges {
+                if range.kind != CoverageKind::Unreferenced {
+                    continue;
+                }
+                let len = range.len().min(GAP_SCAN_LIMIT) as usize;
+                range.content = read(range.start, len).map(|bytes| {
+                    if bytes.iter().all(|&b| b == 0) {
+                        GapContent::Zeros
+                    } else {
+                        GapContent::Data
+                    }
+                });
+                if range.content == Some(GapContent::Data) {
+                    data_ranges += 1;
+                    data_bytes += range.len();
+                }
+            }
+        }
+        if data_ranges > 0 {
+            self.warnings.push(format!(
+                "{} unreferenced ranges ({} bytes) hold non-zero data: leftover media or a hidden payload",
+                data_ranges, data_bytes
+            ));
+        }
+    }
+}
+
+/// Byte spans of the samples described by the trun boxes of every moof
+fn fragment_spans(boxes: &[Box], numbers: &mut HashMap<u32, u32>) -> Vec<SampleSpan> {
+    let trex_sizes: HashMap<u32, u32> = boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "moov")
+        .filter_map(|moov| moov.child("mvex"))
+        .flat_map(|mvex| mvex.children())
+        .filter_map(|child| match child.data() {
+            BoxData::TrackExtends(trex) => Some((trex.track_id(), trex.default_sample_size())),
+            _ => None,
+        })
+        .collect();
+
+    let mut spans = Vec::new();
+    for moof in boxes.iter().filter(|b| b.box_type().as_str() == "moof") {
+        // 未指定 base_data_offset 时, 第一个 traf 以 moof 起点为基准, 之后的 traf 接着上一个的数据末尾
+        let mut previous_end = moof.offset();
+        for traf in moof
+            .children()
+            .iter()
+            .filter(|child| child.box_type().as_str() == "traf")
+        {
+            let tfhd = match traf.child("tfhd").map(|b| b.data()) {
+                Some(BoxData::TrackFragmentHeader(tfhd)) => tfhd,
+                _ => continue,
+            };
+            let track_id = tfhd.track_id();
+            let base = match tfhd.base_data_offset() {
+                Some(offset) if tfhd.flags() & TFHD_BASE_DATA_OFFSET != 0 => offset,
+                _ if tfhd.flags() & TFHD_DEFAULT_BASE_IS_MOOF != 0 => moof.offset(),
+                _ => previous_end,
+            };
+            let default_size = tfhd
+                .default_sample_size()
+                .or_else(|| trex_sizes.get(&track_id).copied())
+                .unwrap_or(0);
+
+            let mut position = base;
+            for child in traf.children() {
+                let BoxData::TrackRun(trun) = child.data() else {
+                    continue;
+                };
+                if let Some(data_offset) = trun.data_offset() {
+                    position = base.saturating_add_signed(data_offset as i64);
+                }
+                for entry in trun.entries() {
+                    let number = numbers.entry(track_id).or_insert(0);
+                    *number += 1;
+                    let size = entry.size.unwrap_or(default_size) as u64;
+                    spans.push(SampleSpan {
+                        sample: SampleRef {
+                            track_id,
+                            number: *number,
+                        },
+                        start: position,
+                        end: position.saturating_add(size),
+                    });
+                    position = position.saturating_add(size);
+                }
+            }
+            previous_end = position;
+        }
+    }
+    spans
+}
+
+/// Splits the referenced bytes into [start, end, reference count) segments, sorted by offset
+#[allow(clippy::type_complexity)]
+fn sweep(spans: &[SampleSpan]) -> (Vec<(u64, u64, usize)>, Vec<SampleOverlap>, u32) {
+    // (位置, 是否为起点, 样本下标); 同一位置先处理终点
+    let mut events: Vec<(u64, bool, usize)> = Vec::with_capacity(spans.len() * 2);
+    for (i, span) in spans.iter().enumerate() {
+        events.push((span.start, true, i));
+        events.push((span.end, false, i));
+    }
+    events.sort_unstable();
+
+    let mut segments: Vec<(u64, u64, usize)> = Vec::new();
+    let mut overlaps: Vec<SampleOverlap> = Vec::new();
+    let mut overlap_count = 0u32;
+    let mut active: Vec<usize> = Vec::new();
+    let mut previous = 0u64;
+    let mut last_overlap: Option<(u64, Vec<usize>)> = None;
+
+    for (position, is_start, i) in events {
+        if position > previous && !active.is_empty() {
+            segments.push((previous, position, active.len()));
+            if active.len() > 1 {
+                let mut members = active.clone();
+                members.sort_unstable();
+                // 相邻且样本集合相同的重叠区间合并为一条
+                let contiguous = matches!(&last_overlap, Some((end, last)) if *end == previous && *last == members);
+                if contiguous {
+                    if let Some(overlap) = overlaps.last_mut().filter(|o| o.end == previous) {
+                        overlap.end = position;
+                    }
+                } else {
+                    overlap_count += 1;
+                    if overlaps.len() < MAX_LISTED {
+                        overlaps.push(SampleOverlap {
+                            start: previous,
+                            end: position,
+                            samples: members.iter().map(|&m| spans[m].sample).collect(),
+                        });
+                    }
+                }
+                last_overlap = Some((position, members));
+            }
+        }
+        previous = position;
+        if is_start {
+            active.push(i);
+        } else if let Some(pos) = active.iter().position(|&a| a == i) {
+            active.swap_remove(pos);
+        }
+    }
+    (segments, overlaps, overlap_count)
+}
+
+fn mdat_coverage(
+    offset: u64,
+    payload_start: u64,
+    payload_end: u64,
+    segments: &[(u64, u64, usize)],
+) -> MdatCoverage {
+    let mut ranges: Vec<CoverageRange> = Vec::new();
+    let mut push = |start: u64, end: u64, kind: CoverageKind| {
+        if end <= start {
+            return;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.kind == kind && last.end == start => last.end = end,
+            _ => ranges.push(CoverageRange {
+                start,
+                end,
+                kind,
+                content: None,
+            }),
+        }
+    };
+
+    let mut position = payload_start;
+    let first = segments.partition_point(|&(_, end, _)| end <= payload_start);
+    for &(start, end, count) in &segments[first..] {
+        if start >= payload_end {
+            break;
+        }
+        let start = start.max(payload_start);
+        let end = end.min(payload_end);
+        push(position, start, CoverageKind::Unreferenced);
+        let kind = if count > 1 {
+            CoverageKind::Overlapping
+        } else {
+            CoverageKind::Referenced
+        };
+        push(start, end, kind);
+        position = end;
+    }
+    push(position, payload_end, CoverageKind::Unreferenced);
+
+    let total = |kind: CoverageKind| -> u64 {
+        ranges
+            .iter()
+            .filter(|r| r.kind == kind)
+            .map(|r| r.len())
+            .sum()
+    };
+    MdatCoverage {
+        offset,
+        payload_start,
+        payload_end,
+        referenced_bytes: total(CoverageKind::Referenced),
+        unreferenced_bytes: total(CoverageKind::Unreferenced),
+        overlapping_bytes: total(CoverageKind::Overlapping),
+        ranges,
+    }
+}
@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::faststart::{read_bytes, same_file};
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::extract::{
+    opus_head_from_dops, opus_tags, AnnexBWriter, AudioSpecificConfig, ExtractReport, IvfWriter,
+    NalConfig, OggWriter, StreamFormat, AV1_TEMPORAL_DELIMITER,
+};
+use crate::error::MediaError;
+
+/// Writes the samples of track `track_id` to `output` as a raw elementary stream: Annex B for
+/// H.264/H.265 (parameter sets repeated at keyframes), ADTS for AAC, IVF or OBU for AV1
+/// (`av1_format`) and Ogg for Opus.
+pub fn extract_track(
+    input: &Path,
+    track_id: u32,
+    output: &Path,
+    av1_format: StreamFormat,
+) -> Result<ExtractReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let track = Track::all(boxes)
+        .into_iter()
+        .find(|track| track.track_id() == track_id)
+        .ok_or_else(|| MediaError::InvalidFormat(format!("no track with id {}", track_id)))?;
+    let entry = track.sample_entry().ok_or_else(|| {
+        MediaError::InvalidFormat(format!("track {} has no sample description", track_id))
+    })?;
+    let codec = entry.entry_type().to_string();
+    let index = TrackSampleIndex::build_all_with_fragments(boxes)?
+        .into_iter()
+        .find(|index| index.track_id == track_id)
+        .ok_or_else(|| MediaError::InvalidFormat(format!("no track with id {}", track_id)))?;
+
+    let mut report = ExtractReport {
+        track_id,
+        codec: codec.clone(),
+        format: StreamFormat::AnnexB,
+        samples: index.samples.len() as u64,
+        parameter_sets_inserted: 0,
+        bytes_written: 0,
+        warnings: Vec::new(),
+    };
+    if index
+        .samples
+        .iter()
+        .any(|s| s.sample_description_index != 1)
+    {
+        report.warnings.push(
+            "the track has several sample descriptions; only the first one's configuration was used"
+                .to_string(),
+        );
+    }
+    let missing = |name: &str| {
+        MediaError::InvalidFormat(format!("{} sample entry has no usable {} box", codec, name))
+    };
+
+    let mut source = File::open(input)?;
+    let out = BufWriter::new(File::create(output)?);
+    let result = (|| match codec.as_str() {
+        "avc1" | "avc3" | "hvc1" | "hev1" => {
+            let config = if codec.starts_with("avc") {
+                entry
+                    .child_box("avcC")
+                    .and_then(NalConfig::from_avcc)
+                    .ok_or_else(|| missing("avcC"))?
+            } else {
+                entry
+                    .child_box("hvcC")
+                    .and_then(NalConfig::from_hvcc)
+                    .ok_or_else(|| missing("hvcC"))?
+            };
+            let mut writer = AnnexBWriter::new(out, config);
+            let written = for_each_sample(&mut source, &index.samples, |sample, data| {
+                writer.write_sample(data, sample.is_sync)
+            });
+            report.parameter_sets_inserted = writer.parameter_sets_inserted;
+            report.bytes_written = writer.bytes_written;
+            written.and_then(|_| writer.finish())
+        }
+        "mp4a" => {
+            report.format = StreamFormat::Adts;
+            let asc = entry
+                .child_box("esds")
+                .and_then(AudioSpecificConfig::from_esds)
+                .ok_or_else(|| missing("esds"))?;
+            let config = AudioSpecificConfig::parse(&asc).ok_or_else(|| missing("esds"))?;
+            if !config.fits_adts() {
+                return Err(MediaError::UnsupportedFormat(format!(
+                    "audio object type {} cannot be carried in ADTS",
+                    config.object_type
+                )));
+            }
+            let mut out = out;
+            let written = for_each_sample(&mut source, &index.samples, |_, data| {
+                if data.len() + 7 > 0x1fff {
+                    return Err(MediaError::InvalidFormat(format!(
+                        "AAC frame of {} bytes exceeds the ADTS frame length",
+                        data.len()
+                    )));
+                }
+                out.write_all(&config.adts_header(data.len()))?;
+                out.write_all(data)?;
+                report.bytes_written += 7 + data.len() as u64;
+                Ok(())
+            });
+            written.and_then(|_| Ok(out.flush()?))
+        }
+        "av01" => {
+            report.format = av1_format;
+            if av1_format == StreamFormat::Ivf {
+                let dimensions = entry.video_dimensions().unwrap_or((0, 0));
+                let mut writer = IvfWriter::new(
+                    out,
+                    b"AV01",
+                    dimensions,
+                    index.timescale,
+                    index.samples.len() as u32,
+                )?;
+                let written = for_each_sample(&mut source, &index.samples, |sample, data| {
+                    writer.write_frame(data, sample.cts.max(0) as u64)
+                });
+                report.bytes_written = writer.bytes_written;
+                written.and_then(|_| writer.finish())
+            } else {
+                let mut out = out;
+                let written = for_each_sample(&mut source, &index.samples, |_, data| {
+                    // MP4 中的 AV1 样本不含 temporal delimiter
+                    out.write_all(&AV1_TEMPORAL_DELIMITER)?;
+                    out.write_all(data)?;
+                    report.bytes_written += (AV1_TEMPORAL_DELIMITER.len() + data.len()) as u64;
+                    Ok(())
+                });
+                written.and_then(|_| Ok(out.flush()?))
+            }
+        }
+        "Opus" => {
+            report.format = StreamFormat::Ogg;
+            let head = entry
+                .child_box("dOps")
+                .and_then(opus_head_from_dops)
+                .ok_or_else(|| missing("dOps"))?;
+            let mut writer = OggWriter::new(out, track_id);
+            writer.write_page(&head, 0x02, 0)?;
+            writer.write_page(&opus_tags(), 0x00, 0)?;
+            // Ogg 的 granule 以 48 kHz 计, 包含 pre-skip
+            let timescale = index.timescale.max(1) as u64;
+            let mut remaining = index.samples.len();
+            let mut end = 0u64;
+            let written = for_each_sample(&mut source, &index.samples, |sample, data| {
+                end += sample.duration as u64;
+                remaining -= 1;
+                let header_type = if remaining == 0 { 0x04 } else { 0x00 };
+                writer.write_page(data, header_type, end * 48000 / timescale)
+            });
+            report.bytes_written = writer.bytes_written;
+            written.and_then(|_| writer.finish())
+        }
+        "encv" | "enca" => Err(MediaError::UnsupportedFormat(
+            "the track is encrypted".to_string(),
+        )),
+        other => Err(MediaError::UnsupportedFormat(format!(
+            "extracting {} samples is not supported",
+            other
+        ))),
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(output);
+        return Err(e);
+    }
+    Ok(report)
+}
+
+fn for_each_sample(
+    source: &mut File,
+    samples: &[Sample],
+    mut write: impl FnMut(&Sample, &[u8]) -> Result<(), MediaError>,
+) -> Result<(), MediaError> {
+    for sample in samples {
+        let data = read_bytes(source, sample.offset, sample.size as u64)?;
+        write(sample, &data)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::boxes::{Box, BoxInfo};
+use super::sample_index::TrackSampleIndex;
+use super::serializer;
+use super::IsobmffAnalyzer;
+use crate::error::MediaError;
+
+/// Outcome of relocating the moov box
+#[derive(Debug, Clone, Serialize)]
+pub struct FaststartReport {
+    /// moov was stored after the first mdat and has been moved in front of it
+    pub moov_moved: bool,
+    pub moov_size: u64,
+    pub chunk_offsets_rewritten: u64,
+    /// stco boxes converted to co64 because an offset no longer fits in 32 bits
+    pub upgraded_to_co64: u32,
+    /// Samples whose bytes were compared between input and output
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Writes `output` with moov placed before the first mdat, rewrites every chunk offset and
+/// verifies that the samples of the new file resolve to the same bytes
+pub fn relocate_moov(input: &Path, output: &Path) -> Result<FaststartReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+
+    let moov_index = boxes
+        .iter()
+        .position(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+    let first_mdat = boxes.iter().position(|b| b.box_type().as_str() == "mdat");
+
+    // 新的顶层顺序: moov 挪到第一个 mdat 之前, 其余保持原样
+    let mut order: Vec<usize> = (0..boxes.len()).filter(|&i| i != moov_index).collect();
+    let moov_moved = matches!(first_mdat, Some(mdat) if mdat < moov_index);
+    let insert_at = match first_mdat {
+        Some(mdat) if moov_moved => order.iter().position(|&i| i == mdat).unwrap_or(0),
+        _ => order
+            .iter()
+            .position(|&i| i > moov_index)
+            .unwrap_or(order.len()),
+    };
+    order.insert(insert_at, moov_index);
+
+    if moov_moved && boxes.iter().any(|b| b.box_type().as_str() == "moof") {
+        return Err(MediaError::UnsupportedFormat(
+            "moving moov of a fragmented file is not supported".to_string(),
+        ));
+    }
+
+    // 按新顺序写出, 偏移映射与 co64 升级由序列化器完成
+    let mut boxes: Vec<Option<Box>> = analyzer
+        .boxes
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .map(Some)
+        .collect();
+    let mut reordered: Vec<Box> = order.iter().filter_map(|&i| boxes[i].take()).collect();
+    let write = serializer::write_file(input, output, &mut reordered)?;
+
+    Ok(FaststartReport {
+        moov_moved,
+        moov_size: write.moov_size,
+        chunk_offsets_rewritten: write.chunk_offsets_rewritten,
+        upgraded_to_co64: write.upgraded_to_co64,
+        verified_samples: write.verified_samples,
+        warnings: write.warnings,
+    })
+}
+
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+pub(super) fn read_bytes(source: &mut File, offset: u64, len: u64) -> Result<Vec<u8>, MediaError> {
+    source.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; len as usize];
+    source.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+pub(super) fn copy_range(
+    source: &mut File,
+    out: &mut impl Write,
+    offset: u64,
+    len: u64,
+) -> Result<(), MediaError> {
+    source.seek(SeekFrom::Start(offset))?;
+    let copied = io::copy(&mut source.take(len), out)?;
+    if copied != len {
+        return Err(MediaError::UnexpectedEof);
+    }
+    Ok(())
+}
+
+/// Re-parses the output and compares the bytes of every sample with the input
+pub(super) fn verify(
+    original: &mut IsobmffAnalyzer,
+    input: &Path,
+    output: &Path,
+) -> Result<u64, MediaError> {
+    let before = original.sample_index()?;
+    let mut rewritten = IsobmffAnalyzer::new(output)?;
+    rewritten.set_debug(false);
+    let after = rewritten.sample_index()?;
+
+    if before.len() != after.len() {
+        return Err(MediaError::InvalidFormat(format!(
+            "verification failed: {} tracks in the input, {} in the output",
+            before.len(),
+            after.len()
+        )));
+    }
+
+    let mut source = File::open(input)?;
+    let mut target = File::open(output)?;
+    let mut verified = 0u64;
+    for (old, new) in before.iter().zip(after.iter()) {
+        check_track(old, new)?;
+        for (a, b) in old.samples.iter().zip(new.samples.iter()) {
+            let expected = read_bytes(&mut source, a.offset, a.size as u64)?;
+            let actual = read_bytes(&mut target, b.offset, b.size as u64)?;
+            if expected != actual {
+                return Err(MediaError::InvalidFormat(format!(
+                    "verification failed: track {} sample {} differs (offset {} -> {})",
+                    old.track_id, a.number, a.offset, b.offset
+                )));
+            }
+            verified += 1;
+        }
+    }
+    Ok(verified)
+}
+
+fn check_track(old: &TrackSampleIndex, new: &TrackSampleIndex) -> Result<(), MediaError> {
+    if old.track_id != new.track_id || old.samples.len() != new.samples.len() {
+        return Err(MediaError::InvalidFormat(format!(
+            "verification failed: track {} has {} samples in the input, track {} has {} in the output",
+            old.track_id,
+            old.samples.len(),
+            new.track_id,
+            new.samples.len()
+        )));
+    }
+    Ok(())
+}
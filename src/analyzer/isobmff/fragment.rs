@@ -0,0 +1,721 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::boxes::{
+    Box, BoxData, BoxInfo, EditListBox, EditListEntry, FileTypeBox, MovieExtendsBox,
+    MovieExtendsHeaderBox, MovieFragmentBox, MovieFragmentHeaderBox, SegmentIndexBox,
+    SegmentReference, TrackExtendsBox, TrackFragmentBox, TrackFragmentDecodeTimeBox,
+    TrackFragmentHeaderBox, TrackRunBox, TrackRunEntry, TFHD_DEFAULT_BASE_IS_MOOF,
+    TFHD_SAMPLE_DESCRIPTION_INDEX, TRUN_DATA_OFFSET, TRUN_SAMPLE_COMPOSITION_TIME_OFFSET,
+    TRUN_SAMPLE_DURATION, TRUN_SAMPLE_FLAGS, TRUN_SAMPLE_SIZE,
+};
+use super::faststart::{copy_range, same_file};
+use super::sample_index::{Sample, TrackSampleIndex, SAMPLE_FLAG_NON_SYNC};
+use super::serializer::BoxSerializer;
+use super::track::{movie_header, movie_timescale};
+use super::trim::{
+    chunk_runs, compare_samples, rebuild_sample_table, set_durations, verify, write_progressive,
+};
+use super::types::BoxType;
+use super::IsobmffAnalyzer;
+use crate::error::MediaError;
+
+/// sample_depends_on = 2: the sample does not depend on others
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// sample_depends_on = 1 and sample_is_non_sync_sample
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0100_0000 | SAMPLE_FLAG_NON_SYNC;
+
+/// Top-level boxes that only make sense in a fragmented layout
+const FRAGMENT_BOXES: &[&str] = &["moof", "sidx", "ssix", "mfra", "styp", "emsg", "prft"];
+
+/// One moof/mdat pair of a fragmented output
+#[derive(Debug, Clone, Serialize)]
+pub struct FragmentInfo {
+    pub sequence_number: u32,
+    /// Decode time of the first sample of the reference track, in seconds
+    pub start: f64,
+    pub duration: f64,
+    /// moof + mdat bytes
+    pub size: u64,
+    /// Segment file, when each fragment was written to its own file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Outcome of converting a progressive file into fragments
+#[derive(Debug, Clone, Serialize)]
+pub struct FragmentReport {
+    /// Track whose sync samples decide where fragments start
+    pub reference_track: u32,
+    pub fragments: Vec<FragmentInfo>,
+    /// Init segment file, when fragments were written to separate files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_segment: Option<String>,
+    pub bytes_written: u64,
+    /// Samples whose bytes were compared between input and output
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlattenedTrack {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub sample_count: u32,
+    /// Media duration, in seconds
+    pub duration: f64,
+}
+
+/// Outcome of flattening a fragmented file into a single moov
+#[derive(Debug, Clone, Serialize)]
+pub struct DefragmentReport {
+    /// moof boxes of the input
+    pub fragments: u32,
+    pub tracks: Vec<FlattenedTrack>,
+    pub bytes_written: u64,
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Samples of one fragment, per track in moov order
+struct Fragment {
+    samples: Vec<Vec<Sample>>,
+    /// Decode time and duration in the reference track's timescale
+    start: u64,
+    duration: u64,
+    starts_with_sap: bool,
+    earliest_presentation_time: i64,
+}
+
+/// Rewrites a progressive file as an init segment (ftyp + moov with mvex) followed by moof/mdat
+/// fragments that start at sync samples of the first video track, each at least
+/// `target_duration` seconds long. A single output file gets one sidx in front of the fragments;
+/// with `segments`, `output` is a directory that receives init.mp4 and one segment-N.m4s
+/// (styp + sidx + moof + mdat) per fragment.
+pub fn fragment(
+    input: &Path,
+    output: &Path,
+    target_duration: f64,
+    segments: bool,
+) -> Result<FragmentReport, MediaError> {
+    if !segments && same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    if target_duration.is_nan() || target_duration <= 0.0 {
+        return Err(MediaError::InvalidFormat(format!(
+            "invalid fragment duration {}",
+            target_duration
+        )));
+    }
+
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let moov = boxes
+        .iter()
+        .find(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+    if moov.child("mvex").is_some() || boxes.iter().any(|b| b.box_type().as_str() == "moof") {
+        return Err(MediaError::UnsupportedFormat(
+            "the file is already fragmented".to_string(),
+        ));
+    }
+    let movie_duration = movie_header(boxes).map_or(0, |mvhd| mvhd.duration());
+    let indexes = TrackSampleIndex::build_all(boxes)?;
+    let reference = indexes
+        .iter()
+        .position(|index| index.handler_type == "vide" && !index.samples.is_empty())
+        .or_else(|| indexes.iter().position(|index| !index.samples.is_empty()))
+        .ok_or_else(|| MediaError::InvalidFormat("file has no samples".to_string()))?;
+    let fragments = split(&indexes, reference, target_duration);
+
+    let mut warnings = Vec::new();
+    let mut boxes = analyzer.boxes.take().unwrap_or_default();
+    boxes.retain(|b| !matches!(b.box_type().as_str(), "mdat" | "free" | "skip" | "wide"));
+    prepare_init(&mut boxes, &indexes, movie_duration, &mut warnings)?;
+
+    let mut source = File::open(input)?;
+    let reference_index = &indexes[reference];
+    let mut report = FragmentReport {
+        reference_track: reference_index.track_id,
+        fragments: Vec::new(),
+        init_segment: None,
+        bytes_written: 0,
+        verified_samples: 0,
+        warnings,
+    };
+
+    // moof 的大小与 data_offset 的取值无关, 先全部构建好以便写 sidx
+    let mut encoded = Vec::with_capacity(fragments.len());
+    for (i, fragment) in fragments.iter().enumerate() {
+        let (moof, payload) = build_moof(i as u32 + 1, &indexes, fragment)?;
+        let size = moof.len() as u64 + mdat_header_len(payload) + payload;
+        report.fragments.push(FragmentInfo {
+            sequence_number: i as u32 + 1,
+            start: fragment.start as f64 / reference_index.timescale as f64,
+            duration: fragment.duration as f64 / reference_index.timescale as f64,
+            size,
+            file: None,
+        });
+        encoded.push((moof, payload));
+    }
+    let reference_for = |fragment: &Fragment, size: u64| SegmentReference {
+        reference_type: false,
+        referenced_size: size as u32,
+        subsegment_duration: fragment.duration as u32,
+        starts_with_sap: fragment.starts_with_sap,
+        sap_type: if fragment.starts_with_sap { 1 } else { 0 },
+        sap_delta_time: 0,
+    };
+
+    let expected = |fragment: &Fragment| -> Vec<(u32, Vec<Sample>)> {
+        indexes
+            .iter()
+            .zip(&fragment.samples)
+            .map(|(index, samples)| (index.track_id, samples.clone()))
+            .collect()
+    };
+    if !segments {
+        let sidx = segment_index(
+            reference_index,
+            fragments
+                .first()
+                .map_or(0, |f| f.earliest_presentation_time),
+            fragments
+                .iter()
+                .zip(&report.fragments)
+                .map(|(fragment, info)| reference_for(fragment, info.size))
+                .collect(),
+        );
+        let result = (|| {
+            let mut out = BufWriter::new(File::create(output)?);
+            let mut written =
+                BoxSerializer::new(Some(&mut source)).write_boxes(&boxes, &mut out)?;
+            written += write_box(&mut out, &sidx)?;
+            for (fragment, (moof, payload)) in fragments.iter().zip(&encoded) {
+                written += write_fragment(&mut source, &mut out, moof, *payload, fragment)?;
+            }
+            out.flush()?;
+            drop(out);
+            let mut all: Vec<(u32, Vec<Sample>)> = indexes
+                .iter()
+                .map(|index| (index.track_id, Vec::new()))
+                .collect();
+            for fragment in &fragments {
+                for (track, samples) in all.iter_mut().zip(&fragment.samples) {
+                    track.1.extend(samples.iter().cloned());
+                }
+            }
+            let verified = verify(&mut source, output, &all)?;
+            Ok::<_, MediaError>((written, verified))
+        })();
+        match result {
+            Ok((written, verified)) => {
+                report.bytes_written = written;
+                report.verified_samples = verified;
+            }
+            Err(e) => {
+                fs::remove_file(output).ok();
+                return Err(e);
+            }
+        }
+        return Ok(report);
+    }
+
+    fs::create_dir_all(output)?;
+    let init_path = output.join("init.mp4");
+    let mut out = BufWriter::new(File::create(&init_path)?);
+    report.bytes_written += BoxSerializer::new(Some(&mut source)).write_boxes(&boxes, &mut out)?;
+    out.flush()?;
+    drop(out);
+    report.init_segment = Some(init_path.display().to_string());
+
+    // 每个分段单独解析, 用 init 中的 trex 默认值还原样本
+    let mut init = IsobmffAnalyzer::new(&init_path)?;
+    init.set_debug(false);
+    let init_boxes = init.boxes()?;
+    let empty = TrackSampleIndex::build_all(init_boxes)?;
+    let mvex = init_boxes
+        .iter()
+        .find(|b| b.box_type().as_str() == "moov")
+        .and_then(|moov| moov.child("mvex"));
+    for (i, (fragment, (moof, payload))) in fragments.iter().zip(&encoded).enumerate() {
+        let path: PathBuf = output.join(format!("segment-{}.m4s", i + 1));
+        let styp = new_box(
+            *b"styp",
+            BoxData::FileType(FileTypeBox::new(
+                "msdh".to_string(),
+                0,
+                vec!["msdh".to_string(), "msix".to_string()],
+            )),
+        );
+        let sidx = segment_index(
+            reference_index,
+            fragment.earliest_presentation_time,
+            vec![reference_for(fragment, report.fragments[i].size)],
+        );
+        let mut out = BufWriter::new(File::create(&path)?);
+        report.bytes_written += write_box(&mut out, &styp)? + write_box(&mut out, &sidx)?;
+        report.bytes_written += write_fragment(&mut source, &mut out, moof, *payload, fragment)?;
+        out.flush()?;
+        drop(out);
+
+        let mut segment = IsobmffAnalyzer::new(&path)?;
+        segment.set_debug(false);
+        let mut after = empty.clone();
+        TrackSampleIndex::append_fragments(&mut after, mvex, segment.boxes()?);
+        report.verified_samples += compare_samples(
+            &mut source,
+            &mut File::open(&path)?,
+            &expected(fragment),
+            &after,
+        )?;
+        report.fragments[i].file = Some(path.display().to_string());
+    }
+    Ok(report)
+}
+
+/// Chooses fragment boundaries on sync samples of the reference track and assigns the samples
+/// of every other track by decode time
+fn split(indexes: &[TrackSampleIndex], reference: usize, target_duration: f64) -> Vec<Fragment> {
+    let reference_index = &indexes[reference];
+    let timescale = reference_index.timescale as u64;
+    let target = (target_duration * timescale as f64).round().max(1.0) as u64;
+    let samples = &reference_index.samples;
+    let mut starts = vec![samples[0].dts];
+    for sample in samples.iter().skip(1) {
+        if sample.is_sync && sample.dts - starts[starts.len() - 1] >= target {
+            starts.push(sample.dts);
+        }
+    }
+    let end = samples
+        .last()
+        .map_or(0, |last| last.dts + last.duration as u64);
+
+    let mut fragments: Vec<Fragment> = starts
+        .iter()
+        .enumerate()
+        .map(|(k, &start)| Fragment {
+            samples: Vec::new(),
+            start,
+            duration: starts.get(k + 1).copied().unwrap_or(end) - start,
+            starts_with_sap: false,
+            earliest_presentation_time: 0,
+        })
+        .collect();
+    for index in indexes {
+        // 以参考轨的时间刻度比较解码时间: dts / ts >= start / ts_ref
+        let ts = index.timescale as u128;
+        let bounds: Vec<usize> = starts
+            .iter()
+            .enumerate()
+            .map(|(k, &start)| match k {
+                0 => 0,
+                _ => index.samples.partition_point(|s| {
+                    (s.dts as u128) * (timescale as u128) < (start as u128) * ts
+                }),
+            })
+            .chain(std::iter::once(index.samples.len()))
+            .collect();
+        for (k, fragment) in fragments.iter_mut().enumerate() {
+            fragment
+                .samples
+                .push(index.samples[bounds[k]..bounds[k + 1].max(bounds[k])].to_vec());
+        }
+    }
+    for fragment in &mut fragments {
+        let reference_samples = &fragment.samples[reference];
+        fragment.starts_with_sap = reference_samples.first().is_some_and(|s| s.is_sync);
+        fragment.earliest_presentation_time =
+            reference_samples.iter().map(|s| s.cts).min().unwrap_or(0);
+    }
+    fragments
+}
+
+/// Empties the sample tables, zeroes the durations and adds mvex (mehd + one trex per track)
+fn prepare_init(
+    boxes: &mut [Box],
+    indexes: &[TrackSampleIndex],
+    movie_duration: u64,
+    warnings: &mut Vec<String>,
+) -> Result<(), MediaError> {
+    if let Some(BoxData::FileType(ftyp)) = boxes
+        .iter_mut()
+        .find(|b| b.box_type().as_str() == "ftyp")
+        .map(|b| b.data_mut())
+    {
+        ftyp.add_compatible_brand("iso6");
+    }
+    let moov = boxes
+        .iter_mut()
+        .find(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+    for trak in moov
+        .children_mut()
+        .iter_mut()
+        .filter(|b| b.box_type().as_str() == "trak")
+    {
+        if let Some(stbl) = trak
+            .child_mut("mdia")
+            .and_then(|mdia| mdia.child_mut("minf"))
+            .and_then(|minf| minf.child_mut("stbl"))
+        {
+            stbl.children_mut()
+                .retain(|b| !matches!(b.box_type().as_str(), "stss" | "ctts" | "sbgp"));
+        }
+        rebuild_sample_table(trak, &[], 0, &[], warnings);
+        set_durations(trak, 0, 0);
+    }
+    if let Some(BoxData::MovieHeader(mvhd)) = moov.child_mut("mvhd").map(|b| b.data_mut()) {
+        mvhd.set_duration(0);
+    }
+
+    let mut mvex = new_box(*b"mvex", BoxData::MovieExtends(MovieExtendsBox::new()));
+    mvex.add_child(new_box(
+        *b"mehd",
+        BoxData::MovieExtendsHeader(MovieExtendsHeaderBox::new(
+            u8::from(movie_duration > u32::MAX as u64),
+            0,
+            movie_duration,
+        )),
+    ));
+    for index in indexes {
+        mvex.add_child(new_box(
+            *b"trex",
+            BoxData::TrackExtends(TrackExtendsBox::new(0, 0, index.track_id, 1, 0, 0, 0)),
+        ));
+    }
+    // mvex 放在最后一个 trak 之后
+    let at = moov
+        .children()
+        .iter()
+        .rposition(|b| b.box_type().as_str() == "trak")
+        .map_or(moov.children().len(), |i| i + 1);
+    moov.children_mut().insert(at, mvex);
+    Ok(())
+}
+
+/// Encodes the moof of one fragment; returns its bytes and the mdat payload size
+fn build_moof(
+    sequence_number: u32,
+    indexes: &[TrackSampleIndex],
+    fragment: &Fragment,
+) -> Result<(Vec<u8>, u64), MediaError> {
+    let mut serializer = BoxSerializer::new(None);
+    let placeholder = moof_box(sequence_number, indexes, fragment, 0);
+    let moof_len = serializer.encoded_size(&placeholder)?;
+    let payload: u64 = fragment
+        .samples
+        .iter()
+        .flatten()
+        .map(|s| s.size as u64)
+        .sum();
+    // data_offset 相对于 moof 起点 (default-base-is-moof)
+    let moof = moof_box(
+        sequence_number,
+        indexes,
+        fragment,
+        moof_len + mdat_header_len(payload),
+    );
+    Ok((serializer.encode(&moof)?, payload))
+}
+
+fn moof_box(
+    sequence_number: u32,
+    indexes: &[TrackSampleIndex],
+    fragment: &Fragment,
+    data_start: u64,
+) -> Box {
+    let mut moof = new_box(*b"moof", BoxData::MovieFragment(MovieFragmentBox::new()));
+    moof.add_child(new_box(
+        *b"mfhd",
+        BoxData::MovieFragmentHeader(MovieFragmentHeaderBox::new(0, 0, sequence_number)),
+    ));
+    let mut data_offset = data_start;
+    for (index, samples) in indexes.iter().zip(&fragment.samples) {
+        // 样本描述变化时另起一个 traf
+        for run in samples.chunk_by(|a, b| a.sample_description_index == b.sample_description_index)
+        {
+            let description_index = run[0].sample_description_index;
+            let mut traf = new_box(*b"traf", BoxData::TrackFragment(TrackFragmentBox::new()));
+            let mut tfhd_flags = TFHD_DEFAULT_BASE_IS_MOOF;
+            if description_index != 1 {
+                tfhd_flags |= TFHD_SAMPLE_DESCRIPTION_INDEX;
+            }
+            traf.add_child(new_box(
+                *b"tfhd",
+                BoxData::TrackFragmentHeader(TrackFragmentHeaderBox::new(
+                    0,
+                    tfhd_flags,
+                    index.track_id,
+                    None,
+                    (description_index != 1).then_some(description_index),
+                    None,
+                    None,
+                    None,
+                )),
+            ));
+            traf.add_child(new_box(
+                *b"tfdt",
+                BoxData::TrackFragmentDecodeTime(TrackFragmentDecodeTimeBox::new(1, 0, run[0].dts)),
+            ));
+            let offsets: Vec<i64> = run.iter().map(|s| s.cts - s.dts as i64).collect();
+            let has_offsets = offsets.iter().any(|&offset| offset != 0);
+            let mut trun_flags =
+                TRUN_DATA_OFFSET | TRUN_SAMPLE_DURATION | TRUN_SAMPLE_SIZE | TRUN_SAMPLE_FLAGS;
+            if has_offsets {
+                trun_flags |= TRUN_SAMPLE_COMPOSITION_TIME_OFFSET;
+            }
+            let entries = run
+                .iter()
+                .zip(&offsets)
+                .map(|(s, &offset)| TrackRunEntry {
+                    duration: Some(s.duration),
+                    size: Some(s.size),
+                    flags: Some(if s.is_sync {
+                        SYNC_SAMPLE_FLAGS
+                    } else {
+                        NON_SYNC_SAMPLE_FLAGS
+                    }),
+                    composition_time_offset: has_offsets.then_some(offset),
+                })
+                .collect();
+            traf.add_child(new_box(
+                *b"trun",
+                BoxData::TrackRun(TrackRunBox::new(
+                    u8::from(offsets.iter().any(|&offset| offset < 0)),
+                    trun_flags,
+                    Some(data_offset as i32),
+                    None,
+                    entries,
+                )),
+            ));
+            data_offset += run.iter().map(|s| s.size as u64).sum::<u64>();
+            moof.add_child(traf);
+        }
+    }
+    moof
+}
+
+fn segment_index(
+    index: &TrackSampleIndex,
+    earliest_presentation_time: i64,
+    references: Vec<SegmentReference>,
+) -> Box {
+    let earliest = earliest_presentation_time.max(0) as u64;
+    new_box(
+        *b"sidx",
+        BoxData::SegmentIndex(SegmentIndexBox::new(
+            u8::from(earliest > u32::MAX as u64),
+            0,
+            index.track_id,
+            index.timescale,
+            earliest,
+            0,
+            references,
+        )),
+    )
+}
+
+fn mdat_header_len(payload: u64) -> u64 {
+    if payload + 8 > u32::MAX as u64 {
+        16
+    } else {
+        8
+    }
+}
+
+fn write_box(out: &mut impl Write, b: &Box) -> Result<u64, MediaError> {
+    let bytes = BoxSerializer::new(None).encode(b)?;
+    out.write_all(&bytes)?;
+    Ok(bytes.len() as u64)
+}
+
+/// Writes moof + mdat, copying the samples from the input in traf order
+fn write_fragment(
+    source: &mut File,
+    out: &mut impl Write,
+    moof: &[u8],
+    payload: u64,
+    fragment: &Fragment,
+) -> Result<u64, MediaError> {
+    out.write_all(moof)?;
+    let header = mdat_header_len(payload);
+    if header == 16 {
+        out.write_all(&1u32.to_be_bytes())?;
+        out.write_all(b"mdat")?;
+        out.write_all(&(payload + 16).to_be_bytes())?;
+    } else {
+        out.write_all(&((payload + 8) as u32).to_be_bytes())?;
+        out.write_all(b"mdat")?;
+    }
+    // 连续存放的样本合并为一次拷贝
+    let mut pending: Option<(u64, u64)> = None;
+    for sample in fragment.samples.iter().flatten() {
+        match &mut pending {
+            Some((offset, len)) if *offset + *len == sample.offset => *len += sample.size as u64,
+            _ => {
+                if let Some((offset, len)) = pending {
+                    copy_range(source, out, offset, len)?;
+                }
+                pending = Some((sample.offset, sample.size as u64));
+            }
+        }
+    }
+    if let Some((offset, len)) = pending {
+        copy_range(source, out, offset, len)?;
+    }
+    Ok(moof.len() as u64 + header + payload)
+}
+
+/// Rewrites a fragmented file as a regular one: the samples of every moof are indexed in the
+/// track sample tables, stored in a single mdat, and mvex is removed
+pub fn defragment(input: &Path, output: &Path) -> Result<DefragmentReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let fragments = boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "moof")
+        .count() as u32;
+    if fragments == 0 {
+        return Err(MediaError::UnsupportedFormat(
+            "the file is not fragmented".to_string(),
+        ));
+    }
+    let movie_timescale = movie_timescale(boxes);
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+
+    let mut warnings = Vec::new();
+    for traf in boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "moof")
+        .flat_map(|moof| moof.children())
+        .filter(|b| b.box_type().as_str() == "traf")
+    {
+        for child in traf.children() {
+            let name = child.box_type().to_string();
+            if !matches!(name.as_str(), "tfhd" | "tfdt" | "trun") {
+                let warning = format!("traf {} boxes were dropped", name);
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+        }
+    }
+
+    let mut boxes = analyzer.boxes.take().unwrap_or_default();
+    boxes.retain(|b| {
+        let name = b.box_type().to_string();
+        !FRAGMENT_BOXES.contains(&name.as_str())
+            && !matches!(name.as_str(), "mdat" | "free" | "skip" | "wide")
+    });
+    let moov = boxes
+        .iter_mut()
+        .find(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+    moov.children_mut()
+        .retain(|b| b.box_type().as_str() != "mvex");
+
+    let mut tracks = Vec::new();
+    let mut chunks = Vec::new();
+    let mut expected = Vec::new();
+    let mut movie_duration = 0;
+    let traks = moov
+        .children_mut()
+        .iter_mut()
+        .filter(|b| b.box_type().as_str() == "trak");
+    for (track, (trak, index)) in traks.zip(&indexes).enumerate() {
+        let track_chunks = chunk_runs(track, &index.samples);
+        rebuild_sample_table(trak, &index.samples, 0, &track_chunks, &mut warnings);
+        let media_duration: u64 = index.samples.iter().map(|s| s.duration as u64).sum();
+        let track_duration =
+            complete_edit_list(trak, media_duration, index.timescale, movie_timescale);
+        set_durations(trak, track_duration, media_duration);
+        movie_duration = movie_duration.max(track_duration);
+
+        tracks.push(FlattenedTrack {
+            track_id: index.track_id,
+            handler_type: index.handler_type.clone(),
+            sample_count: index.samples.len() as u32,
+            duration: media_duration as f64 / index.timescale.max(1) as f64,
+        });
+        chunks.extend(track_chunks);
+        expected.push((index.track_id, index.samples.clone()));
+    }
+    if let Some(BoxData::MovieHeader(mvhd)) = moov.child_mut("mvhd").map(|b| b.data_mut()) {
+        mvhd.set_duration(movie_duration);
+    }
+
+    let (bytes_written, verified_samples) =
+        write_progressive(input, output, &mut boxes, chunks, &expected)?;
+    Ok(DefragmentReport {
+        fragments,
+        tracks,
+        bytes_written,
+        verified_samples,
+        warnings,
+    })
+}
+
+/// Fills in normal edits whose duration is 0 (meaning "until the end of the fragments") and
+/// returns the track duration in movie units
+fn complete_edit_list(
+    trak: &mut Box,
+    media_duration: u64,
+    timescale: u32,
+    movie_timescale: u32,
+) -> u64 {
+    let to_movie =
+        |media: u64| (media as u128 * movie_timescale as u128 / timescale.max(1) as u128) as u64;
+    let elst = trak
+        .child_mut("edts")
+        .and_then(|edts| edts.child_mut("elst"));
+    let Some(elst) = elst else {
+        return to_movie(media_duration);
+    };
+    let BoxData::EditList(list) = elst.data() else {
+        return to_movie(media_duration);
+    };
+    let entries: Vec<EditListEntry> = list
+        .entries()
+        .iter()
+        .map(|entry| {
+            let segment_duration = match entry.segment_duration {
+                0 if entry.media_time >= 0 => {
+                    to_movie(media_duration.saturating_sub(entry.media_time as u64))
+                }
+                duration => duration,
+            };
+            EditListEntry::new(
+                segment_duration,
+                entry.media_time,
+                entry.media_rate,
+                entry.media_rate_fraction,
+            )
+        })
+        .collect();
+    let duration = entries.iter().map(|entry| entry.segment_duration).sum();
+    let version = u8::from(entries.iter().any(|entry| {
+        entry.segment_duration > u32::MAX as u64 || entry.media_time > i32::MAX as i64
+    }));
+    *elst.data_mut() = BoxData::EditList(EditListBox::new(version, 0, entries));
+    elst.set_trailing(Default::default());
+    duration
+}
+
+fn new_box(box_type: [u8; 4], data: BoxData) -> Box {
+    Box::new(BoxType::new(box_type), 0, 0, 8, data)
+}
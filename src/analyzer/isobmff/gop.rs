@@ -0,0 +1,282 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::nal::{analyze_nal_units, FrameNals, TrackNals};
+use crate::analyzer::bitstream::SliceType;
+use crate::error::MediaError;
+
+/// Frames from one sync sample up to the next
+#[derive(Debug, Clone, Serialize)]
+pub struct Gop {
+    pub start_sample: u32,
+    pub frames: u32,
+    /// Seconds until the next keyframe, or until the end of the track for the last GOP
+    pub duration: f64,
+    /// No frame of the GOP depends on the GOP before it
+    pub closed: bool,
+    /// Frame types in presentation order, e.g. "IBBPBBP"
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Interval {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GopLengthCount {
+    pub frames: u32,
+    pub count: u32,
+}
+
+/// GOP structure of one H.264/H.265 track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackGops {
+    pub track_id: u32,
+    pub codec: String,
+    pub gops: Vec<Gop>,
+    pub length_distribution: Vec<GopLengthCount>,
+    /// Distance between consecutive keyframes; None with fewer than two keyframes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyframe_interval_frames: Option<Interval>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyframe_interval_seconds: Option<Interval>,
+    pub open_gops: u32,
+    pub closed_gops: u32,
+    /// Every GOP except the last has the same length
+    pub regular: bool,
+    pub i_frames: u32,
+    pub p_frames: u32,
+    pub b_frames: u32,
+    /// Levels of reordered frames: 0 without B-frames, 1 for plain B-frames, more for a
+    /// hierarchy of referenced B-frames
+    pub pyramid_depth: u32,
+    /// Largest number of frames that precede a frame in decoding order but follow it in
+    /// presentation order
+    pub max_reorder: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GopReport {
+    pub tracks: Vec<TrackGops>,
+}
+
+/// GOP lengths, open/closed GOPs, keyframe interval and B-frame structure of the H.264/H.265
+/// tracks, from the sync sample table and the slice types of each sample
+pub fn analyze_gops(input: &Path) -> Result<GopReport, MediaError> {
+    let report = analyze_nal_units(input)?;
+    Ok(GopReport {
+        tracks: report.tracks.iter().map(track_gops).collect(),
+    })
+}
+
+fn track_gops(track: &TrackNals) -> TrackGops {
+    let frames = &track.frames;
+    let hevc = matches!(track.codec.as_str(), "hvc1" | "hev1");
+    let timescale = track.timescale.max(1) as f64;
+    let ranks = presentation_ranks(frames);
+    let mut warnings = track.warnings.clone();
+
+    // 以同步样本划分 GOP; 首个样本不是同步样本时也从它开始
+    let mut starts: Vec<usize> = frames
+        .iter()
+        .enumerate()
+        .filter(|(i, f)| f.is_sync || *i == 0)
+        .map(|(i, _)| i)
+        .collect();
+    if frames.first().is_some_and(|f| !f.is_sync) {
+        warnings.push("the track does not start with a sync sample".to_string());
+    }
+    if !frames.is_empty() && starts.len() == frames.len() && frames.len() > 1 {
+        warnings.push("every sample is a sync sample (intra-only or no stss)".to_string());
+    }
+    starts.push(frames.len());
+
+    let mut gops = Vec::new();
+    for bounds in starts.windows(2) {
+        let (start, end) = (bounds[0], bounds[1]);
+        let members = &frames[start..end];
+        let key = &members[0];
+        let end_time = frames
+            .get(end)
+            .map_or_else(|| last_end(frames), |next| next.dts);
+        let mut order: Vec<usize> = (start..end).collect();
+        order.sort_by_key(|&i| ranks[i]);
+        let pattern: String = order
+            .iter()
+            .map(|&i| match frames[i].frame_type {
+                Some(SliceType::I | SliceType::SI) => 'I',
+                Some(SliceType::P | SliceType::SP) => 'P',
+                Some(SliceType::B) => 'B',
+                None => '?',
+            })
+            .collect();
+        gops.push(Gop {
+            start_sample: key.sample,
+            frames: members.len() as u32,
+            duration: end_time.saturating_sub(key.dts) as f64 / timescale,
+            closed: is_closed(members, hevc),
+            pattern,
+        });
+    }
+
+    let mut length_distribution: Vec<GopLengthCount> = Vec::new();
+    for gop in &gops {
+        match length_distribution
+            .iter_mut()
+            .find(|l| l.frames == gop.frames)
+        {
+            Some(length) => length.count += 1,
+            None => length_distribution.push(GopLengthCount {
+                frames: gop.frames,
+                count: 1,
+            }),
+        }
+    }
+    length_distribution.sort_by_key(|l| l.frames);
+
+    // 最后一个 GOP 被文件结尾截断, 不计入关键帧间隔
+    let complete = &gops[..gops.len().saturating_sub(1)];
+    let keyframe_interval_frames =
+        interval(complete.iter().map(|g| g.frames as f64).collect::<Vec<_>>());
+    let keyframe_interval_seconds = interval(complete.iter().map(|g| g.duration).collect());
+    let regular = complete.windows(2).all(|w| w[0].frames == w[1].frames);
+
+    let count = |wanted: &[SliceType]| {
+        frames
+            .iter()
+            .filter(|f| f.frame_type.is_some_and(|t| wanted.contains(&t)))
+            .count() as u32
+    };
+    TrackGops {
+        track_id: track.track_id,
+        codec: track.codec.clone(),
+        open_gops: gops.iter().filter(|g| !g.closed).count() as u32,
+        closed_gops: gops.iter().filter(|g| g.closed).count() as u32,
+        gops,
+        length_distribution,
+        keyframe_interval_frames,
+        keyframe_interval_seconds,
+        regular,
+        i_frames: count(&[SliceType::I, SliceType::SI]),
+        p_frames: count(&[SliceType::P, SliceType::SP]),
+        b_frames: count(&[SliceType::B]),
+        pyramid_depth: pyramid_depth(frames, &ranks, hevc),
+        max_reorder: max_reorder(&ranks),
+        warnings,
+    }
+}
+
+/// Position of each frame in presentation (CTS) order
+fn presentation_ranks(frames: &[FrameNals]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..frames.len()).collect();
+    order.sort_by_key(|&i| (frames[i].cts, i));
+    let mut ranks = vec![0; frames.len()];
+    for (rank, &i) in order.iter().enumerate() {
+        ranks[i] = rank;
+    }
+    ranks
+}
+
+fn last_end(frames: &[FrameNals]) -> u64 {
+    frames.last().map_or(0, |f| f.dts + f.duration as u64)
+}
+
+/// A GOP is open when it has leading pictures that reference the previous GOP: RASL pictures
+/// in H.265, and frames shown before a non-IDR keyframe in H.264
+fn is_closed(members: &[FrameNals], hevc: bool) -> bool {
+    let key = &members[0];
+    if hevc {
+        !members
+            .iter()
+            .flat_map(|f| &f.nal_units)
+            .any(|u| matches!(u.nal_type, 8 | 9))
+    } else {
+        let idr = key.nal_units.iter().any(|u| u.nal_type == 5);
+        idr || !members[1..].iter().any(|f| f.cts < key.cts)
+    }
+}
+
+/// Whether other pictures may reference the frame: nal_ref_idc != 0 in H.264, anything but a
+/// sub-layer non-reference picture in H.265
+fn is_reference(frame: &FrameNals, hevc: bool) -> bool {
+    frame.nal_units.iter().any(|u| match (hevc, u.ref_idc) {
+        (true, _) => u.nal_type <= 23 && (u.nal_type >= 16 || u.nal_type % 2 == 1),
+        (false, Some(ref_idc)) => matches!(u.nal_type, 1 | 5) && ref_idc != 0,
+        (false, None) => false,
+    })
+}
+
+/// Levels of the B-frame hierarchy. Frames shown before an earlier decoded frame are
+/// reordered; each gets one level more than the nearest already decoded reference frames on
+/// either side of it in presentation order, with the anchors at level 0.
+fn pyramid_depth(frames: &[FrameNals], ranks: &[usize], hevc: bool) -> u32 {
+    let mut depth = 0;
+    // 当前 mini-GOP 中已解码的参考帧: (显示位置, 层级)
+    let mut references: Vec<(usize, u32)> = Vec::new();
+    let mut latest = None;
+    for (i, frame) in frames.iter().enumerate() {
+        let rank = ranks[i];
+        if latest.is_none_or(|latest| rank > latest) {
+            // 锚帧开始新的 mini-GOP, 保留前一个锚帧作为左侧参考
+            references.retain(|&(r, level)| level == 0 && Some(r) == latest);
+            references.push((rank, 0));
+            latest = Some(rank);
+            continue;
+        }
+        let left = references
+            .iter()
+            .filter(|&&(r, _)| r < rank)
+            .max_by_key(|&&(r, _)| r)
+            .map_or(0, |&(_, level)| level);
+        let right = references
+            .iter()
+            .filter(|&&(r, _)| r > rank)
+            .min_by_key(|&&(r, _)| r)
+            .map_or(0, |&(_, level)| level);
+        let level = left.max(right) + 1;
+        depth = depth.max(level);
+        if is_reference(frame, hevc) {
+            references.push((rank, level));
+        }
+    }
+    depth
+}
+
+/// For each frame, the number of earlier decoded frames shown after it; counted with a
+/// Fenwick tree over presentation positions
+fn max_reorder(ranks: &[usize]) -> u32 {
+    let mut tree = vec![0u32; ranks.len() + 1];
+    let mut reorder = 0;
+    for (decoded, &rank) in ranks.iter().enumerate() {
+        let mut shown_before = 0;
+        let mut k = rank;
+        while k > 0 {
+            shown_before += tree[k];
+            k &= k - 1;
+        }
+        reorder = reorder.max(decoded as u32 - shown_before);
+        let mut k = rank + 1;
+        while k < tree.len() {
+            tree[k] += 1;
+            k += k & k.wrapping_neg();
+        }
+    }
+    reorder
+}
+
+fn interval(values: Vec<f64>) -> Option<Interval> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(Interval {
+        min: values.iter().copied().fold(f64::INFINITY, f64::min),
+        max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+    })
+}
@@ -0,0 +1,237 @@
+use serde::Serialize;
+
+use super::boxes::{Box, BoxInfo};
+use super::sample_index::TrackSampleIndex;
+
+/// Tracks drifting further apart than this in file order get a warning, in seconds
+const MAX_RECOMMENDED_INTERLEAVE: f64 = 1.0;
+
+/// Chunk layout of one track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackInterleave {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub chunk_count: u32,
+    /// Longest chunk, in seconds
+    pub max_chunk_duration: f64,
+    pub average_chunk_duration: f64,
+    pub max_chunk_bytes: u64,
+}
+
+/// How well the file can be played while it is being downloaded
+#[derive(Debug, Clone, Serialize)]
+pub struct InterleaveReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moov_offset: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_mdat_offset: Option<u64>,
+    /// moov is stored before the first mdat
+    pub faststart: bool,
+    /// Samples are described by movie fragments; each trun run counts as a chunk
+    pub fragmented: bool,
+    /// Bytes to download before the first sample of every track can be decoded
+    pub startup_bytes: u64,
+    pub tracks: Vec<TrackInterleave>,
+    /// Largest distance between the tracks' download progress, in seconds
+    pub max_interleave: f64,
+    /// Largest amount of media read ahead of the playback position
+    pub max_buffer_bytes: u64,
+    pub max_buffer_duration: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// One chunk: consecutive samples of a track stored back to back
+struct Chunk {
+    track: usize,
+    offset: u64,
+    end: u64,
+    start_time: f64,
+    end_time: f64,
+}
+
+impl InterleaveReport {
+    /// Derives the chunk layout from the sample indexes (stco/stsc/stsz/stts, and the trun
+    /// boxes of the fragments)
+    pub fn build(boxes: &[Box], indexes: &[TrackSampleIndex]) -> InterleaveReport {
+        let moov_offset = boxes
+            .iter()
+            .find(|b| b.box_type().as_str() == "moov")
+            .map(|b| b.offset());
+        let moov_end = boxes
+            .iter()
+            .find(|b| b.box_type().as_str() == "moov")
+            .map(|b| b.offset() + b.size())
+            .unwrap_or(0);
+        let first_mdat_offset = boxes
+            .iter()
+            .find(|b| b.box_type().as_str() == "mdat")
+            .map(|b| b.offset());
+        let faststart = match (moov_offset, first_mdat_offset) {
+            (Some(moov), Some(mdat)) => moov < mdat,
+            (Some(_), None) => true,
+            _ => false,
+        };
+        let fragmented = boxes.iter().any(|b| b.box_type().as_str() == "moof");
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut tracks = Vec::new();
+        for (track, index) in indexes.iter().enumerate() {
+            let seconds = |value: u64| -> f64 {
+                if index.timescale == 0 {
+                    0.0
+                } else {
+                    value as f64 / index.timescale as f64
+                }
+            };
+            let first_chunk = chunks.len();
+            for sample in &index.samples {
+                let start_time = seconds(sample.dts);
+                let end_time = seconds(sample.dts + sample.duration as u64);
+                let end = sample.offset + sample.size as u64;
+                let own_chunks = chunks.len() > first_chunk;
+                match chunks.last_mut() {
+                    Some(chunk) if own_chunks && chunk.end == sample.offset => {
+                        chunk.end = end;
+                        chunk.end_time = end_time;
+                    }
+                    _ => chunks.push(Chunk {
+                        track,
+                        offset: sample.offset,
+                        end,
+                        start_time,
+                        end_time,
+                    }),
+                }
+            }
+            let own = &chunks[first_chunk..];
+            if own.is_empty() {
+                continue;
+            }
+            let durations: Vec<f64> = own.iter().map(|c| c.end_time - c.start_time).collect();
+            tracks.push(TrackInterleave {
+                track_id: index.track_id,
+                handler_type: index.handler_type.clone(),
+                chunk_count: own.len() as u32,
+                max_chunk_duration: durations.iter().cloned().fold(0.0, f64::max),
+                average_chunk_duration: durations.iter().fold(0.0, |a, b| a + b)
+                    / durations.len() as f64,
+                max_chunk_bytes: own.iter().map(|c| c.end - c.offset).max().unwrap_or(0),
+            });
+        }
+
+        let mut warnings = Vec::new();
+        if !faststart && moov_offset.is_some() {
+            warnings.push(
+                "moov is stored after mdat: the whole media data must be downloaded before playback can start"
+                    .to_string(),
+            );
+        }
+
+        let (max_interleave, startup_end) = drift(&chunks, indexes.len());
+        let (max_buffer_bytes, max_buffer_duration) = buffering(&chunks);
+        if max_interleave > MAX_RECOMMENDED_INTERLEAVE {
+            warnings.push(format!(
+                "tracks drift up to {:.3}s apart in file order (recommended at most {:.1}s)",
+                max_interleave, MAX_RECOMMENDED_INTERLEAVE
+            ));
+        }
+
+        InterleaveReport {
+            moov_offset,
+            first_mdat_offset,
+            faststart,
+            fragmented,
+            startup_bytes: startup_end.max(moov_end),
+            tracks,
+            max_interleave,
+            max_buffer_bytes,
+            max_buffer_duration,
+            warnings,
+        }
+    }
+}
+
+/// Reads the chunks in file order and returns (largest gap between the tracks' contiguous
+/// download progress in seconds, file position at which every track has its first chunk)
+fn drift(chunks: &[Chunk], track_count: usize) -> (f64, u64) {
+    // 每条轨道的 chunk 按时间排序, 用于判断已连续下载到的时间点
+    let mut per_track: Vec<Vec<usize>> = vec![Vec::new(); track_count];
+    for (i, chunk) in chunks.iter().enumerate() {
+        per_track[chunk.track].push(i);
+    }
+    for list in &mut per_track {
+        list.sort_by(|&a, &b| chunks[a].start_time.total_cmp(&chunks[b].start_time));
+    }
+
+    let mut file_order: Vec<usize> = (0..chunks.len()).collect();
+    file_order.sort_by_key(|&i| chunks[i].offset);
+
+    let mut read = vec![false; chunks.len()];
+    let mut next = vec![0usize; track_count];
+    let mut reached = vec![0.0f64; track_count];
+    let mut max_drift = 0.0f64;
+    let mut startup_end = 0u64;
+    let mut started = per_track.iter().filter(|list| list.is_empty()).count();
+
+    for i in file_order {
+        read[i] = true;
+        let track = chunks[i].track;
+        let before = next[track];
+        while next[track] < per_track[track].len() && read[per_track[track][next[track]]] {
+            reached[track] = chunks[per_track[track][next[track]]].end_time;
+            next[track] += 1;
+        }
+        if before == 0 && next[track] > 0 {
+            started += 1;
+            if started == track_count {
+                startup_end = chunks[i].end;
+            }
+        }
+
+        let unfinished = (0..track_count).filter(|&t| next[t] < per_track[t].len());
+        let slowest = unfinished.map(|t| reached[t]).fold(f64::INFINITY, f64::min);
+        if slowest.is_finite() {
+            let fastest = reached.iter().cloned().fold(0.0, f64::max);
+            max_drift = max_drift.max(fastest - slowest);
+        }
+    }
+    (max_drift, startup_end)
+}
+
+/// Plays the chunks in time order while downloading sequentially and returns the largest
+/// (bytes, seconds) of media that had to be read before it was needed
+fn buffering(chunks: &[Chunk]) -> (u64, f64) {
+    let mut file_order: Vec<usize> = (0..chunks.len()).collect();
+    file_order.sort_by_key(|&i| chunks[i].offset);
+    // 按文件顺序的累计媒体字节数和已读到的最大时间
+    let mut media_prefix = Vec::with_capacity(chunks.len());
+    let mut time_prefix = Vec::with_capacity(chunks.len());
+    let mut bytes = 0u64;
+    let mut time = 0.0f64;
+    for &i in &file_order {
+        bytes += chunks[i].end - chunks[i].offset;
+        time = time.max(chunks[i].end_time);
+        media_prefix.push(bytes);
+        time_prefix.push(time);
+    }
+
+    let mut time_order: Vec<usize> = (0..chunks.len()).collect();
+    time_order.sort_by(|&a, &b| chunks[a].start_time.total_cmp(&chunks[b].start_time));
+
+    let mut needed = 0u64;
+    let mut consumed = 0u64;
+    let mut max_bytes = 0u64;
+    let mut max_duration = 0.0f64;
+    for i in time_order {
+        needed = needed.max(chunks[i].end);
+        // 读到 needed 为止已下载的 chunk 数
+        let count = file_order.partition_point(|&j| chunks[j].end <= needed);
+        if count > 0 {
+            max_bytes = max_bytes.max(media_prefix[count - 1].saturating_sub(consumed));
+            max_duration = max_duration.max(time_prefix[count - 1] - chunks[i].start_time);
+        }
+        consumed += chunks[i].end - chunks[i].offset;
+    }
+    (max_bytes, max_duration)
+}
@@ -1,18 +1,56 @@
+pub mod aac;
+pub mod av_sync;
+pub mod bitrate;
 pub mod box_parser;
 pub mod boxes;
+pub mod captions;
+pub mod coverage;
+pub mod extract;
+pub mod faststart;
+pub mod fragment;
+pub mod gop;
+pub mod interleave;
+pub mod mux;
+pub mod nal;
+pub mod privacy;
+pub mod recover;
+pub mod repair;
+pub mod sample_groups;
+pub mod sample_index;
+pub mod sei;
+pub mod serializer;
+pub mod stats;
+pub mod tags;
+pub mod timeline;
+pub mod timing;
+pub mod track;
+pub mod trim;
 pub mod types;
+pub mod validator;
 
 use crate::error::MediaError;
 use crate::reader::Reader;
+use av_sync::AvSyncReport;
 pub use box_parser::BoxParser;
-use boxes::BoxInfo;
+use boxes::{BoxData, BoxInfo};
+use coverage::CoverageReport;
+use interleave::InterleaveReport;
+use sample_groups::SampleGroups;
+use sample_index::TrackSampleIndex;
+use timeline::TrackTimeline;
+use timing::TimingReport;
+use track::Track;
+use validator::ValidationReport;
 
-use crate::analyzer::{ElementInfo, MediaInfo};
+use crate::analyzer::privacy::PrivacyFinding;
+use crate::analyzer::{AnalysisOptions, ElementInfo, MediaInfo};
 use std::fs::File;
 use std::path::Path;
 
 pub struct IsobmffAnalyzer {
     parser: BoxParser,
+    boxes: Option<Vec<boxes::Box>>,
+    options: AnalysisOptions,
 }
 
 impl IsobmffAnalyzer {
@@ -21,15 +59,88 @@ impl IsobmffAnalyzer {
         let reader = Reader::new(file);
         let parser = BoxParser::new(reader);
 
-        Ok(Self { parser })
+        Ok(Self {
+            parser,
+            boxes: None,
+            options: AnalysisOptions::default(),
+        })
     }
 
     pub fn set_debug(&mut self, debug: bool) {
         self.parser.set_debug(debug);
     }
 
+    pub fn set_options(&mut self, options: AnalysisOptions) {
+        self.options = options;
+    }
+
+    /// Parsed top-level boxes, parsing the file on first use
+    pub fn boxes(&mut self) -> Result<&[boxes::Box], MediaError> {
+        if self.boxes.is_none() {
+            self.boxes = Some(self.parser.parse_boxes()?);
+        }
+        Ok(self.boxes.as_deref().unwrap_or_default())
+    }
+
+    /// Per-track index of every sample: file offset, size, timestamps, sync flag and chunk
+    pub fn sample_index(&mut self) -> Result<Vec<TrackSampleIndex>, MediaError> {
+        TrackSampleIndex::build_all(self.boxes()?)
+    }
+
+    /// Runs the structural lint rules over the box tree
+    pub fn validate(&mut self) -> Result<ValidationReport, MediaError> {
+        let file_size = self.parser.file_size()?;
+        Ok(validator::validate(self.boxes()?, file_size))
+    }
+
+    /// Looks for identifying metadata: locations, serial numbers, names, XMP, Exif, comments
+    pub fn privacy_audit(&mut self) -> Result<Vec<PrivacyFinding>, MediaError> {
+        self.boxes()?;
+        let boxes = self.boxes.as_deref().unwrap_or_default();
+        let parser = &mut self.parser;
+        Ok(privacy::audit(boxes, &mut |offset, len| {
+            parser.read_at(offset, len).ok()
+        }))
+    }
+
     pub fn analyze(&mut self) -> Result<MediaInfo, MediaError> {
-        let boxes = self.parser.parse_boxes()?;
+        let file_size = self.parser.file_size()?;
+        self.boxes()?;
+        let boxes = self.boxes.as_deref().unwrap_or_default();
+        let tracks = Track::all(boxes);
+        let built: Vec<_> = tracks.iter().map(TrackSampleIndex::build).collect();
+        // 某个轨道的样本表不完整时只在该轨道上报告, 其余轨道照常列出
+        let samples = if self.options.list_samples {
+            tracks
+                .iter()
+                .zip(&built)
+                .map(|(track, index)| match index {
+                    Ok(index) => index.clone(),
+                    Err(e) => TrackSampleIndex::unavailable(track, e),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let indexes: Vec<TrackSampleIndex> = built.into_iter().filter_map(Result::ok).collect();
+        // 时间轴与交织分析需要分片中的样本; A/V 同步与覆盖率报告自行读取分片, 只用 moov 的样本表
+        let fragment_indexes =
+            TrackSampleIndex::build_all_with_fragments(boxes).unwrap_or_else(|_| indexes.clone());
+        let timelines = Self::timelines(boxes, &fragment_indexes);
+        let av_sync =
+            (!timelines.is_empty()).then(|| AvSyncReport::build(boxes, &indexes, &timelines));
+        let timing = (!timelines.is_empty()).then(|| TimingReport::build(boxes, &indexes));
+        let interleave = (!fragment_indexes.is_empty())
+            .then(|| InterleaveReport::build(boxes, &fragment_indexes));
+        let coverage = boxes
+            .iter()
+            .any(|b| b.box_type().as_str() == "mdat")
+            .then(|| {
+                let mut coverage = CoverageReport::build(boxes, &indexes, file_size);
+                let parser = &mut self.parser;
+                coverage.inspect_gaps(|offset, len| parser.read_at(offset, len).ok());
+                coverage
+            });
 
         Ok(MediaInfo {
             format: "ISOBMFF".to_string(),
@@ -37,10 +148,30 @@ impl IsobmffAnalyzer {
             video_streams: Vec::new(),
             audio_streams: Vec::new(),
             metadata: Default::default(),
-            structure: Self::convert_to_elements(&boxes),
+            structure: Self::convert_to_elements(boxes),
+            samples,
+            timelines,
+            av_sync,
+            timing,
+            coverage,
+            interleave,
         })
     }
 
+    /// Applies the edit list of every track to its media timeline
+    fn timelines(boxes: &[boxes::Box], indexes: &[TrackSampleIndex]) -> Vec<TrackTimeline> {
+        let movie_timescale = track::movie_timescale(boxes);
+        Track::all(boxes)
+            .iter()
+            .map(|track| {
+                let index = indexes
+                    .iter()
+                    .find(|index| index.track_id == track.track_id());
+                TrackTimeline::build(track, index, movie_timescale)
+            })
+            .collect()
+    }
+
     fn convert_to_elements(boxes: &[boxes::Box]) -> Vec<ElementInfo> {
         boxes
             .iter()
@@ -54,6 +185,21 @@ impl IsobmffAnalyzer {
 
                 // 让box自己填充属性
                 box_info.fill_properties(&mut element.properties);
+
+                // 样本表额外列出每个样本所属的样本分组
+                if box_info.box_type().as_str() == "stbl" {
+                    let groups = SampleGroups::from_container(box_info);
+                    if !groups.is_empty() {
+                        let sample_count = box_info
+                            .child("stsz")
+                            .and_then(|stsz| match stsz.data() {
+                                BoxData::SampleSize(b) => Some(b.sample_count()),
+                                _ => None,
+                            })
+                            .unwrap_or(0);
+                        groups.fill_properties(sample_count, &mut element.properties);
+                    }
+                }
                 element.children = Self::convert_to_elements(box_info.children());
                 element
             })
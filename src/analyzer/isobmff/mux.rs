@@ -0,0 +1,486 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::boxes::{
+    Box, BoxData, DataEntryBox, DataInformationBox, DataReferenceBox, EditBox, EditListBox,
+    EditListEntry, FileTypeBox, HandlerBox, MediaBox, MediaHeaderBox, MediaInfoBox, MovieBox,
+    MovieHeaderBox, SampleDescriptionBox, SampleEntry, SampleTableBox, SoundMediaHeaderBox,
+    TrackBox, TrackHeaderBox, VideoMediaHeaderBox,
+};
+use super::faststart::same_file;
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::serializer::BoxSerializer;
+use super::trim::{rebuild_sample_table, set_chunk_offsets, Chunk};
+use super::types::{BoxType, Fixed16_16, Matrix, Mp4DateTime};
+use super::IsobmffAnalyzer;
+use crate::analyzer::elementary::{ElementaryStream, StreamFormat, StreamKind};
+use crate::error::MediaError;
+
+const MOVIE_TIMESCALE: u32 = 1000;
+/// Longest stretch of one track stored in a single chunk, in seconds
+const CHUNK_DURATION: f64 = 0.5;
+const IDENTITY_MATRIX: [i32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x4000_0000];
+
+/// One input stream as stored in the new file
+#[derive(Debug, Clone, Serialize)]
+pub struct MuxedTrack {
+    pub track_id: u32,
+    pub input: String,
+    pub kind: StreamKind,
+    pub sample_entry: String,
+    pub samples: u32,
+    /// Presentation duration, in seconds
+    pub duration: f64,
+    /// Media hidden by the edit list (reordering delay, Opus pre-skip), in seconds
+    #[serde(skip_serializing_if = "is_zero")]
+    pub hidden_leading: f64,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Outcome of wrapping elementary streams into an MP4
+#[derive(Debug, Clone, Serialize)]
+pub struct MuxReport {
+    pub tracks: Vec<MuxedTrack>,
+    pub bytes_written: u64,
+    /// Samples read back from the output and compared with the streams
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Builds an MP4 (ftyp, moov, one interleaved mdat) with one track per elementary stream:
+/// Annex B H.264/H.265, ADTS AAC, AC-3 or Ogg Opus. Video without timing information runs at
+/// `frame_rate` (default 25 fps).
+pub fn mux(
+    inputs: &[PathBuf],
+    output: &Path,
+    frame_rate: Option<f64>,
+) -> Result<MuxReport, MediaError> {
+    if inputs.is_empty() {
+        return Err(MediaError::InvalidFormat("no input streams".to_string()));
+    }
+    if inputs.iter().any(|input| same_file(input, output)) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be one of the inputs".to_string(),
+        ));
+    }
+    let mut warnings = Vec::new();
+    let mut streams = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let stream = ElementaryStream::read(input, frame_rate)?;
+        if stream.samples.is_empty() {
+            return Err(MediaError::InvalidFormat(format!(
+                "{} contains no samples",
+                input.display()
+            )));
+        }
+        for warning in &stream.warnings {
+            warnings.push(format!("{}: {}", input.display(), warning));
+        }
+        streams.push(stream);
+    }
+
+    let samples: Vec<Vec<Sample>> = streams.iter().map(sample_table).collect();
+    let chunks = interleave(&streams, &samples);
+    let mut tracks = Vec::with_capacity(streams.len());
+    let mut moov = new_box(*b"moov", BoxData::Movie(MovieBox::new()));
+    moov.add_child(Box::new(BoxType::new(*b"mvhd"), 0, 0, 8, BoxData::Unknown));
+    let mut movie_duration = 0;
+    for (i, (stream, samples)) in streams.iter().zip(&samples).enumerate() {
+        let track_id = i as u32 + 1;
+        let track_chunks: Vec<Chunk> = chunks
+            .iter()
+            .filter(|chunk| chunk.track == i)
+            .map(|chunk| Chunk { ..*chunk })
+            .collect();
+        let (trak, presented) = build_trak(track_id, stream, samples, &track_chunks, &mut warnings);
+        moov.add_child(trak);
+        movie_duration = movie_duration.max(presented);
+        tracks.push(MuxedTrack {
+            track_id,
+            input: inputs[i].display().to_string(),
+            kind: stream.kind,
+            sample_entry: stream.sample_entry.to_string(),
+            samples: samples.len() as u32,
+            duration: presented as f64 / MOVIE_TIMESCALE as f64,
+            hidden_leading: stream.presentation_start as f64 / stream.timescale as f64,
+        });
+    }
+    if let Some(mvhd) = moov.child_mut("mvhd") {
+        *mvhd.data_mut() = BoxData::MovieHeader(MovieHeaderBox::new(
+            u8::from(movie_duration > u32::MAX as u64),
+            0,
+            Mp4DateTime::new(0),
+            Mp4DateTime::new(0),
+            MOVIE_TIMESCALE,
+            movie_duration,
+            Fixed16_16::new(0x10000),
+            Fixed16_16::new(0x0100 << 16),
+            Matrix::new(IDENTITY_MATRIX),
+            streams.len() as u32 + 1,
+        ));
+    }
+
+    let mut brands = vec!["isom".to_string(), "iso2".to_string()];
+    if streams.iter().any(|s| s.kind == StreamKind::H264) {
+        brands.push("avc1".to_string());
+    }
+    brands.push("mp41".to_string());
+    let ftyp = new_box(
+        *b"ftyp",
+        BoxData::FileType(FileTypeBox::new("isom".to_string(), 512, brands)),
+    );
+    let mut boxes = vec![ftyp, moov];
+
+    let result = write(output, &mut boxes, &streams, &samples, &chunks)
+        .and_then(|written| verify(output, &streams, &samples).map(|verified| (written, verified)));
+    match result {
+        Ok((bytes_written, verified_samples)) => Ok(MuxReport {
+            tracks,
+            bytes_written,
+            verified_samples,
+            warnings,
+        }),
+        Err(e) => {
+            std::fs::remove_file(output).ok();
+            Err(e)
+        }
+    }
+}
+
+/// Sample table entries of a stream; `offset` is the position in the stream's concatenated
+/// sample data
+fn sample_table(stream: &ElementaryStream) -> Vec<Sample> {
+    let mut dts = 0u64;
+    let mut offset = 0u64;
+    (1..)
+        .zip(&stream.samples)
+        .map(|(number, es)| {
+            let sample = Sample {
+                number,
+                offset,
+                size: es.data.len() as u32,
+                dts,
+                cts: dts as i64 + es.composition_offset,
+                duration: es.duration,
+                is_sync: es.is_sync,
+                chunk_index: 0,
+                sample_description_index: 1,
+                groups: Vec::new(),
+            };
+            dts += es.duration as u64;
+            offset += es.data.len() as u64;
+            sample
+        })
+        .collect()
+}
+
+/// Cuts every track into chunks of at most CHUNK_DURATION and orders them by start time
+fn interleave(streams: &[ElementaryStream], samples: &[Vec<Sample>]) -> Vec<Chunk> {
+    let mut chunks: Vec<(f64, Chunk)> = Vec::new();
+    for (track, (stream, samples)) in streams.iter().zip(samples).enumerate() {
+        let limit = (CHUNK_DURATION * stream.timescale as f64) as u64;
+        let mut start = 0;
+        while start < samples.len() {
+            let first = &samples[start];
+            let mut end = start + 1;
+            while end < samples.len() && samples[end].dts - first.dts < limit.max(1) {
+                end += 1;
+            }
+            let len = samples[start..end].iter().map(|s| s.size as u64).sum();
+            chunks.push((
+                first.dts as f64 / stream.timescale as f64,
+                Chunk {
+                    track,
+                    source_offset: first.offset,
+                    len,
+                    samples: (end - start) as u32,
+                    sample_description_index: 1,
+                },
+            ));
+            start = end;
+        }
+    }
+    chunks.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.track.cmp(&b.1.track)));
+    chunks.into_iter().map(|(_, chunk)| chunk).collect()
+}
+
+/// trak with its sample entry, sample tables and edit list. Returns the box and its
+/// presentation duration in movie units.
+fn build_trak(
+    track_id: u32,
+    stream: &ElementaryStream,
+    samples: &[Sample],
+    chunks: &[Chunk],
+    warnings: &mut Vec<String>,
+) -> (Box, u64) {
+    let media_duration: u64 = samples.iter().map(|s| s.duration as u64).sum();
+    let video = matches!(stream.format, StreamFormat::Video { .. });
+    // 视频的 presentation_start 是重排序延迟, 所有帧都要呈现; 音频的是需跳过的预滚样本
+    let presented_media = stream.presentation_duration.unwrap_or(if video {
+        media_duration
+    } else {
+        media_duration.saturating_sub(stream.presentation_start)
+    });
+    let presented = presented_media * MOVIE_TIMESCALE as u64 / stream.timescale as u64;
+    let (width, height) = match stream.format {
+        StreamFormat::Video { width, height } => (width, height),
+        StreamFormat::Audio { .. } => (0, 0),
+    };
+
+    let mut trak = new_box(*b"trak", BoxData::Track(TrackBox::new()));
+    trak.add_child(new_box(
+        *b"tkhd",
+        BoxData::TrackHeader(TrackHeaderBox::new(
+            u8::from(presented > u32::MAX as u64),
+            0x000003, // enabled, in movie
+            Mp4DateTime::new(0),
+            Mp4DateTime::new(0),
+            track_id,
+            presented,
+            0,
+            if video { 0 } else { 1 },
+            Fixed16_16::new(if video { 0 } else { 0x0100 }),
+            Matrix::new(IDENTITY_MATRIX),
+            Fixed16_16::new((width << 16) as i32),
+            Fixed16_16::new((height << 16) as i32),
+        )),
+    ));
+    let mut edts = new_box(*b"edts", BoxData::Edit(EditBox::new()));
+    edts.add_child(new_box(
+        *b"elst",
+        BoxData::EditList(EditListBox::new(
+            u8::from(presented > u32::MAX as u64),
+            0,
+            vec![EditListEntry::new(
+                presented,
+                stream.presentation_start as i64,
+                1,
+                0,
+            )],
+        )),
+    ));
+    trak.add_child(edts);
+
+    let mut mdia = new_box(*b"mdia", BoxData::Media(MediaBox::new()));
+    mdia.add_child(new_box(
+        *b"mdhd",
+        BoxData::MediaHeader(MediaHeaderBox::new(
+            u8::from(media_duration > u32::MAX as u64),
+            0,
+            Mp4DateTime::new(0),
+            Mp4DateTime::new(0),
+            stream.timescale,
+            media_duration,
+            0x55c4, // und
+        )),
+    ));
+    let (handler, name) = if video {
+        ("vide", &b"VideoHandler\0"[..])
+    } else {
+        ("soun", &b"SoundHandler\0"[..])
+    };
+    mdia.add_child(new_box(
+        *b"hdlr",
+        BoxData::Handler(HandlerBox::new(
+            0,
+            0,
+            0,
+            handler.to_string(),
+            [0; 3],
+            name.to_vec(),
+        )),
+    ));
+
+    let mut minf = new_box(*b"minf", BoxData::MediaInfo(MediaInfoBox::new()));
+    minf.add_child(if video {
+        new_box(
+            *b"vmhd",
+            BoxData::VideoMediaHeader(VideoMediaHeaderBox::new(0, 1, 0, [0; 3])),
+        )
+    } else {
+        new_box(
+            *b"smhd",
+            BoxData::SoundMediaHeader(SoundMediaHeaderBox::new(0, 0, Fixed16_16::new(0))),
+        )
+    });
+    let mut dinf = new_box(
+        *b"dinf",
+        BoxData::DataInformation(DataInformationBox::new()),
+    );
+    dinf.add_child(new_box(
+        *b"dref",
+        BoxData::DataReference(DataReferenceBox::new(
+            0,
+            0,
+            vec![DataEntryBox::Url {
+                version: 0,
+                flags: 1, // media data is in this file
+                location: String::new(),
+            }],
+        )),
+    ));
+    minf.add_child(dinf);
+
+    let mut stbl = new_box(*b"stbl", BoxData::SampleTable(SampleTableBox::new()));
+    stbl.add_child(new_box(
+        *b"stsd",
+        BoxData::SampleDescription(SampleDescriptionBox::new(
+            0,
+            0,
+            1,
+            vec![SampleEntry::new(
+                stream.sample_entry.to_string(),
+                1,
+                sample_entry_fields(stream),
+            )],
+        )),
+    ));
+    // 样本表由 trim 的重建逻辑填写
+    for table in [*b"stts", *b"stsc", *b"stsz", *b"stco"] {
+        stbl.add_child(new_box(table, BoxData::Unknown));
+    }
+    minf.add_child(stbl);
+    mdia.add_child(minf);
+    trak.add_child(mdia);
+    rebuild_sample_table(&mut trak, samples, 0, chunks, warnings);
+    (trak, presented)
+}
+
+/// Sample entry fields after data_reference_index: the visual or sound description followed
+/// by the configuration box
+fn sample_entry_fields(stream: &ElementaryStream) -> Vec<u8> {
+    let mut data = Vec::new();
+    match stream.format {
+        StreamFormat::Video { width, height } => {
+            data.extend_from_slice(&[0; 16]); // pre_defined, reserved
+            data.extend_from_slice(&(width as u16).to_be_bytes());
+            data.extend_from_slice(&(height as u16).to_be_bytes());
+            data.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+            data.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&1u16.to_be_bytes()); // frame_count
+            data.extend_from_slice(&[0; 32]); // compressorname
+            data.extend_from_slice(&0x0018u16.to_be_bytes());
+            data.extend_from_slice(&(-1i16).to_be_bytes());
+        }
+        StreamFormat::Audio {
+            channels,
+            sample_rate,
+        } => {
+            data.extend_from_slice(&[0; 8]);
+            // AC-3 与 Opus 的 channelcount 固定写 2
+            let channels = match stream.kind {
+                StreamKind::Aac => channels,
+                _ => 2,
+            };
+            data.extend_from_slice(&channels.to_be_bytes());
+            data.extend_from_slice(&16u16.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&(sample_rate.min(0xffff) << 16).to_be_bytes());
+        }
+    }
+    let (box_type, payload) = &stream.config;
+    data.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(box_type);
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Lays out ftyp + moov + mdat, fills the chunk offsets and writes the file
+fn write(
+    output: &Path,
+    boxes: &mut [Box],
+    streams: &[ElementaryStream],
+    samples: &[Vec<Sample>],
+    chunks: &[Chunk],
+) -> Result<u64, MediaError> {
+    let payload: u64 = chunks.iter().map(|chunk| chunk.len).sum();
+    let mdat_header = if payload + 8 > u32::MAX as u64 { 16 } else { 8 };
+    let mut serializer = BoxSerializer::new(None);
+    let mut large = false;
+    let header_len = loop {
+        set_chunk_offsets(boxes, chunks, 0, large);
+        let header_len = boxes
+            .iter()
+            .map(|b| serializer.encoded_size(b))
+            .sum::<Result<u64, _>>()?;
+        if large || header_len + mdat_header + payload <= u32::MAX as u64 {
+            break header_len;
+        }
+        large = true;
+    };
+    set_chunk_offsets(boxes, chunks, header_len + mdat_header, large);
+
+    let mut out = BufWriter::new(File::create(output)?);
+    let mut written = serializer.write_boxes(boxes, &mut out)?;
+    if mdat_header == 16 {
+        out.write_all(&1u32.to_be_bytes())?;
+        out.write_all(b"mdat")?;
+        out.write_all(&(payload + 16).to_be_bytes())?;
+    } else {
+        out.write_all(&((payload + 8) as u32).to_be_bytes())?;
+        out.write_all(b"mdat")?;
+    }
+    let mut next = vec![0usize; streams.len()];
+    for chunk in chunks {
+        let first = next[chunk.track];
+        for i in first..first + chunk.samples as usize {
+            out.write_all(&streams[chunk.track].samples[i].data)?;
+        }
+        next[chunk.track] += chunk.samples as usize;
+    }
+    debug_assert!(next.iter().zip(samples).all(|(n, s)| *n == s.len()));
+    written += mdat_header + payload;
+    out.flush()?;
+    Ok(written)
+}
+
+/// Re-parses the output and compares every sample with the stream it came from
+fn verify(
+    output: &Path,
+    streams: &[ElementaryStream],
+    expected: &[Vec<Sample>],
+) -> Result<u64, MediaError> {
+    let mut written = IsobmffAnalyzer::new(output)?;
+    written.set_debug(false);
+    let indexes = TrackSampleIndex::build_all(written.boxes()?)?;
+    let mut file = File::open(output)?;
+    let mut verified = 0;
+    for ((stream, expected), index) in streams.iter().zip(expected).zip(&indexes) {
+        if index.samples.len() != expected.len() {
+            return Err(MediaError::InvalidFormat(format!(
+                "verification failed: track {} has {} samples instead of {}",
+                index.track_id,
+                index.samples.len(),
+                expected.len()
+            )));
+        }
+        for ((es, want), got) in stream.samples.iter().zip(expected).zip(&index.samples) {
+            let bytes = super::faststart::read_bytes(&mut file, got.offset, got.size as u64)?;
+            if bytes != es.data
+                || got.dts != want.dts
+                || got.cts != want.cts
+                || got.duration != want.duration
+                || got.is_sync != want.is_sync
+            {
+                return Err(MediaError::InvalidFormat(format!(
+                    "verification failed: sample {} of track {} differs",
+                    got.number, index.track_id
+                )));
+            }
+            verified += 1;
+        }
+    }
+    Ok(verified)
+}
+
+fn new_box(box_type: [u8; 4], data: BoxData) -> Box {
+    Box::new(BoxType::new(box_type), 0, 0, 8, data)
+}
@@ -0,0 +1,326 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::faststart::read_bytes;
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::bitstream::SliceType;
+use crate::analyzer::elementary::{nal_role, PocTracker};
+use crate::analyzer::extract::NalConfig;
+use crate::error::MediaError;
+
+/// One NAL unit of a sample
+#[derive(Debug, Clone, Serialize)]
+pub struct NalUnit {
+    /// Offset of the length prefix within the sample
+    pub offset: u32,
+    pub nal_type: u8,
+    pub name: &'static str,
+    /// H.264 nal_ref_idc
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_idc: Option<u8>,
+    /// H.265 TemporalId
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporal_id: Option<u8>,
+    /// NAL unit bytes, header included and length prefix excluded
+    pub size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slice_type: Option<SliceType>,
+}
+
+/// NAL units of one video sample
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameNals {
+    pub sample: u32,
+    pub dts: u64,
+    pub cts: i64,
+    pub duration: u32,
+    pub size: u32,
+    /// Sync sample according to stss or the fragment sample flags
+    pub is_sync: bool,
+    /// Contains an IDR picture, or an IRAP picture for H.265
+    pub random_access: bool,
+    /// Most predicted slice type of the picture, e.g. B if any slice is B
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_type: Option<SliceType>,
+    /// H.264 frame_num
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_num: Option<u32>,
+    /// Picture order count
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poc: Option<i64>,
+    pub nal_units: Vec<NalUnit>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<String>,
+}
+
+/// Occurrences of one NAL unit type in a track
+#[derive(Debug, Clone, Serialize)]
+pub struct NalTypeCount {
+    pub nal_type: u8,
+    pub name: &'static str,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// NAL unit structure of an H.264/H.265 track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackNals {
+    pub track_id: u32,
+    pub codec: String,
+    pub timescale: u32,
+    /// Size of the NAL length prefix from avcC/hvcC
+    pub length_size: usize,
+    pub nal_types: Vec<NalTypeCount>,
+    pub frames: Vec<FrameNals>,
+    /// Samples marked sync that contain no IDR/IRAP picture
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sync_without_random_access: Vec<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NalReport {
+    pub tracks: Vec<TrackNals>,
+}
+
+/// Lists the NAL units of every sample of the H.264/H.265 tracks, with the slice type,
+/// frame_num and POC of each picture
+pub fn analyze_nal_units(input: &Path) -> Result<NalReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let mut source = File::open(input)?;
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        let Some(entry) = track.sample_entry() else {
+            continue;
+        };
+        let codec = entry.entry_type().to_string();
+        let config = match codec.as_str() {
+            "avc1" | "avc3" => entry.child_box("avcC").and_then(NalConfig::from_avcc),
+            "hvc1" | "hev1" => entry.child_box("hvcC").and_then(NalConfig::from_hvcc),
+            _ => continue,
+        };
+        let Some(config) = config else {
+            tracks.push(TrackNals {
+                track_id: track.track_id(),
+                warnings: vec![format!(
+                    "{} sample entry has no usable configuration",
+                    codec
+                )],
+                codec,
+                timescale: index.timescale,
+                length_size: 0,
+                nal_types: Vec::new(),
+                frames: Vec::new(),
+                sync_without_random_access: Vec::new(),
+            });
+            continue;
+        };
+        let mut nals = analyze_track(&mut source, &config, &index.samples)?;
+        nals.track_id = track.track_id();
+        nals.codec = codec;
+        nals.timescale = index.timescale;
+        nals.warnings.extend(index.warnings.iter().cloned());
+        tracks.push(nals);
+    }
+    if tracks.is_empty() {
+        return Err(MediaError::UnsupportedFormat(
+            "the file has no H.264/H.265 track".to_string(),
+        ));
+    }
+    Ok(NalReport { tracks })
+}
+
+fn analyze_track(
+    source: &mut File,
+    config: &NalConfig,
+    samples: &[Sample],
+) -> Result<TrackNals, MediaError> {
+    let mut tracker = PocTracker::new(config.hevc);
+    for parameter_set in &config.parameter_sets {
+        tracker.parameter_set(parameter_set);
+    }
+    let mut result = TrackNals {
+        track_id: 0,
+        codec: String::new(),
+        timescale: 0,
+        length_size: config.length_size,
+        nal_types: Vec::new(),
+        frames: Vec::with_capacity(samples.len()),
+        sync_without_random_access: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    for sample in samples {
+        let data = read_bytes(source, sample.offset, sample.size as u64)?;
+        let mut frame = FrameNals {
+            sample: sample.number,
+            dts: sample.dts,
+            cts: sample.cts,
+            duration: sample.duration,
+            size: sample.size,
+            is_sync: sample.is_sync,
+            random_access: false,
+            frame_type: None,
+            frame_num: None,
+            poc: None,
+            nal_units: Vec::new(),
+            problems: Vec::new(),
+        };
+        let mut has_vcl = false;
+        let mut at = 0;
+        while at < data.len() {
+            let Some(prefix) = data.get(at..at + config.length_size) else {
+                frame
+                    .problems
+                    .push(format!("truncated NAL length prefix at byte {}", at));
+                break;
+            };
+            let len = prefix.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+            let Some(nal) = data.get(at + config.length_size..at + config.length_size + len) else {
+                frame.problems.push(format!(
+                    "NAL unit of {} bytes at byte {} exceeds the sample",
+                    len, at
+                ));
+                break;
+            };
+            let offset = at as u32;
+            at += config.length_size + len;
+            let Some(&header) = nal.first() else {
+                frame
+                    .problems
+                    .push(format!("empty NAL unit at byte {}", offset));
+                continue;
+            };
+
+            tracker.parameter_set(nal);
+            let role = nal_role(nal, config.hevc);
+            let slice = if role.vcl { tracker.slice(nal) } else { None };
+            if role.vcl && !has_vcl {
+                has_vcl = true;
+                frame.poc = tracker.picture(nal).map(|(poc, _)| poc);
+                frame.frame_num = slice.and_then(|(_, frame_num)| frame_num);
+            }
+            frame.random_access |= role.random_access;
+            if let Some((slice_type, _)) = slice {
+                frame.frame_type = frame.frame_type.max(Some(slice_type));
+            }
+
+            let nal_type = if config.hevc {
+                (header >> 1) & 0x3f
+            } else {
+                header & 0x1f
+            };
+            let name = nal_type_name(nal_type, config.hevc);
+            match result
+                .nal_types
+                .iter_mut()
+                .find(|count| count.nal_type == nal_type)
+            {
+                Some(count) => {
+                    count.count += 1;
+                    count.bytes += len as u64;
+                }
+                None => result.nal_types.push(NalTypeCount {
+                    nal_type,
+                    name,
+                    count: 1,
+                    bytes: len as u64,
+                }),
+            }
+            frame.nal_units.push(NalUnit {
+                offset,
+                nal_type,
+                name,
+                ref_idc: (!config.hevc).then_some((header >> 5) & 0x03),
+                temporal_id: config
+                    .hevc
+                    .then(|| nal.get(1).map_or(0, |b| (b & 0x07).saturating_sub(1))),
+                size: len as u32,
+                slice_type: slice.map(|(slice_type, _)| slice_type),
+            });
+        }
+
+        if !has_vcl {
+            frame.problems.push("no coded slice".to_string());
+        }
+        if frame.is_sync && !frame.random_access {
+            result.sync_without_random_access.push(frame.sample);
+            frame.problems.push(format!(
+                "marked sync but contains no {} picture",
+                if config.hevc { "IRAP" } else { "IDR" }
+            ));
+        }
+        result.frames.push(frame);
+    }
+    result.nal_types.sort_by_key(|count| count.nal_type);
+    Ok(result)
+}
+
+/// Short name of a NAL unit type (H.264 table 7-1, H.265 table 7-1)
+fn nal_type_name(nal_type: u8, hevc: bool) -> &'static str {
+    if hevc {
+        match nal_type {
+            0 => "TRAIL_N",
+            1 => "TRAIL_R",
+            2 => "TSA_N",
+            3 => "TSA_R",
+            4 => "STSA_N",
+            5 => "STSA_R",
+            6 => "RADL_N",
+            7 => "RADL_R",
+            8 => "RASL_N",
+            9 => "RASL_R",
+            16 => "BLA_W_LP",
+            17 => "BLA_W_RADL",
+            18 => "BLA_N_LP",
+            19 => "IDR_W_RADL",
+            20 => "IDR_N_LP",
+            21 => "CRA",
+            22 | 23 => "RSV_IRAP",
+            32 => "VPS",
+            33 => "SPS",
+            34 => "PPS",
+            35 => "AUD",
+            36 => "EOS",
+            37 => "EOB",
+            38 => "FD",
+            39 => "PREFIX_SEI",
+            40 => "SUFFIX_SEI",
+            48..=63 => "UNSPEC",
+            _ => "RSV",
+        }
+    } else {
+        match nal_type {
+            1 => "SLICE",
+            2 => "DPA",
+            3 => "DPB",
+            4 => "DPC",
+            5 => "IDR",
+            6 => "SEI",
+            7 => "SPS",
+            8 => "PPS",
+            9 => "AUD",
+            10 => "END_SEQ",
+            11 => "END_STREAM",
+            12 => "FILLER",
+            13 => "SPS_EXT",
+            14 => "PREFIX",
+            15 => "SUBSET_SPS",
+            16 => "DPS",
+            19 => "AUX_SLICE",
+            20 => "SLICE_EXT",
+            21 => "SLICE_3D",
+            0 | 24..=31 => "UNSPEC",
+            _ => "RSV",
+        }
+    }
+}
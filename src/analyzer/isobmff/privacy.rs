@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+
+use super::boxes::{Box, BoxData, BoxInfo, RawBytes};
+use super::sample_index::TrackSampleIndex;
+use super::track::Track;
+use super::validator::indexed;
+use crate::analyzer::privacy::{classify_key, scan_exif, scan_xmp, Category, PrivacyFinding};
+
+/// XMP packets stored in a `uuid` box
+const XMP_UUID: [u8; 16] = [
+    0xbe, 0x7a, 0xcf, 0xcb, 0x97, 0xa9, 0x42, 0xe8, 0x9c, 0x71, 0x99, 0x94, 0x91, 0xe3, 0xaf, 0xac,
+];
+
+/// Payloads above this size are not read back from the file
+const MAX_SCANNED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Walks the box tree looking for identifying metadata. `read` fetches payloads that the parser
+/// left in the file, and GPMF telemetry samples.
+pub fn audit(
+    boxes: &[Box],
+    read: &mut dyn FnMut(u64, usize) -> Option<Vec<u8>>,
+) -> Vec<PrivacyFinding> {
+    let mut auditor = Auditor {
+        read,
+        findings: Vec::new(),
+    };
+    for (b, path) in indexed(boxes, "") {
+        auditor.visit(b, &path);
+    }
+    auditor.findings
+}
+
+struct Auditor<'a> {
+    read: &'a mut dyn FnMut(u64, usize) -> Option<Vec<u8>>,
+    findings: Vec<PrivacyFinding>,
+}
+
+impl Auditor<'_> {
+    fn visit(&mut self, b: &Box, path: &str) {
+        match b.box_type().as_str() {
+            "meta" => self.check_meta(b, path),
+            "udta" => self.check_user_data(b, path),
+            "uuid" => {
+                if let Some(payload) = self.payload(b) {
+                    if let Some(packet) = payload.strip_prefix(&XMP_UUID) {
+                        self.report_xmp(b, path, packet);
+                    }
+                }
+            }
+            "trak" => self.check_telemetry(b, path),
+            _ => {}
+        }
+        for (child, path) in indexed(b.children(), path) {
+            self.visit(child, &path);
+        }
+    }
+
+    fn report(&mut self, category: Category, b: &Box, path: String, key: &str, value: &str) {
+        self.findings
+            .push(PrivacyFinding::new(category, b.offset(), path, key, value));
+    }
+
+    /// Bytes of a box the parser kept opaque, read from the file if they were left there
+    fn payload(&mut self, b: &Box) -> Option<Vec<u8>> {
+        match b.data().raw_payload()? {
+            RawBytes::Loaded(bytes) => Some(bytes.clone()),
+            RawBytes::InFile { offset, len } if *len <= MAX_SCANNED_BYTES => {
+                (self.read)(*offset, *len as usize)
+            }
+            RawBytes::InFile { .. } => None,
+        }
+    }
+
+    fn report_xmp(&mut self, b: &Box, path: &str, packet: &[u8]) {
+        let text = String::from_utf8_lossy(packet).to_string();
+        self.report(Category::Xmp, b, path.to_string(), "XMP", &text);
+        for (category, property, value) in scan_xmp(packet) {
+            self.report(category, b, format!("{}/XMP", path), &property, &value);
+        }
+    }
+
+    /// iTunes (`mdir`) and QuickTime (`mdta`) item lists, and HEIF Exif/XMP items
+    fn check_meta(&mut self, meta: &Box, path: &str) {
+        if let Some(ilst) = meta.child("ilst") {
+            let keys = meta
+                .child("keys")
+                .and_then(|keys| self.payload(keys))
+                .map(|payload| mdta_keys(&payload))
+                .unwrap_or_default();
+            if let BoxData::ItemList(list) = ilst.data() {
+                for item in list.items() {
+                    let key = item.key();
+                    // mdta 的条目类型是 keys 中的 1 基序号
+                    let key = key
+                        .as_bytes()
+                        .try_into()
+                        .ok()
+                        .and_then(|index| keys.get(&u32::from_be_bytes(index)).cloned())
+                        .unwrap_or(key);
+                    let Some(category) = classify_key(&key) else {
+                        continue;
+                    };
+                    let value = item
+                        .values()
+                        .first()
+                        .map(|value| value.to_string())
+                        .unwrap_or_default();
+                    let item_path = format!("{}/ilst/{}", path, key);
+                    self.report(category, ilst, item_path, &key, &value);
+                }
+            }
+        }
+
+        let (Some(iinf), Some(iloc)) = (meta.child("iinf"), meta.child("iloc")) else {
+            return;
+        };
+        let (Some(iinf_payload), Some(iloc_payload)) = (self.payload(iinf), self.payload(iloc))
+        else {
+            return;
+        };
+        let locations = item_locations(&iloc_payload);
+        for (item_id, item_type, content_type) in item_infos(&iinf_payload) {
+            let xmp = item_type == "mime" && content_type.contains("rdf+xml");
+            if item_type != "Exif" && !xmp {
+                continue;
+            }
+            let Some(extents) = locations.get(&item_id) else {
+                continue;
+            };
+            let mut data = Vec::new();
+            for &(offset, len) in extents {
+                if len > MAX_SCANNED_BYTES {
+                    continue;
+                }
+                data.extend((self.read)(offset, len as usize).unwrap_or_default());
+            }
+            let item_path = format!("{}/item[{}]", path, item_id);
+            if xmp {
+                self.report_xmp(iloc, &item_path, &data);
+                continue;
+            }
+            self.report(
+                Category::Exif,
+                iloc,
+                item_path.clone(),
+                "Exif",
+                &format!("{} bytes", data.len()),
+            );
+            for (category, tag, value) in scan_exif(&data) {
+                self.report(category, iloc, format!("{}/Exif", item_path), &tag, &value);
+            }
+        }
+    }
+
+    /// QuickTime `©` text atoms, 3GPP asset boxes, `XMP_` and GoPro `GPMF` user data
+    fn check_user_data(&mut self, udta: &Box, path: &str) {
+        for (child, child_path) in indexed(udta.children(), path) {
+            let four_cc = child.box_type().to_string();
+            let Some(payload) = self.payload(child) else {
+                continue;
+            };
+            match four_cc.as_str() {
+                "XMP_" => self.report_xmp(child, &child_path, &payload),
+                "GPMF" => {
+                    let mut found = Vec::new();
+                    scan_gpmf(&payload, &mut Vec::new(), &mut found);
+                    for (category, key, value) in found {
+                        self.report(category, child, child_path.clone(), &key, &value);
+                    }
+                }
+                "loci" => {
+                    let value = location_info(&payload).unwrap_or_default();
+                    self.report(Category::Location, child, child_path, "loci", &value);
+                }
+                "auth" | "perf" | "cprt" | "dscp" => {
+                    let value = asset_string(&payload);
+                    if let Some(category) = classify_key(&four_cc) {
+                        self.report(category, child, child_path, &four_cc, &value);
+                    }
+                }
+                _ if four_cc.starts_with('©') => {
+                    if let Some(category) = classify_key(&four_cc) {
+                        let value = quicktime_text(&payload);
+                        self.report(category, child, child_path, &four_cc, &value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// GPS fixes in a GoPro telemetry (`gpmd`) track; the first fix is reported
+    fn check_telemetry(&mut self, trak: &Box, path: &str) {
+        let track = Track::new(trak);
+        if track.sample_entry().map(|entry| entry.entry_type()) != Some("gpmd") {
+            return;
+        }
+        let Ok(index) = TrackSampleIndex::build(&track) else {
+            return;
+        };
+        for sample in &index.samples {
+            let Some(data) = (self.read)(sample.offset, sample.size as usize) else {
+                break;
+            };
+            let mut found = Vec::new();
+            scan_gpmf(&data, &mut Vec::new(), &mut found);
+            if let Some((category, key, value)) = found
+                .into_iter()
+                .find(|(category, _, _)| *category == Category::Location)
+            {
+                self.findings.push(PrivacyFinding::new(
+                    category,
+                    sample.offset,
+                    format!("{} sample {}", path, sample.number),
+                    &key,
+                    &value,
+                ));
+                break;
+            }
+        }
+    }
+}
+
+/// Big-endian cursor over a box payload
+struct Bytes<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Bytes<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    /// Unsigned big-endian integer of 0..=8 bytes
+    fn uint(&mut self, len: usize) -> Option<u64> {
+        Some(
+            self.take(len)?
+                .iter()
+                .fold(0u64, |value, &byte| (value << 8) | byte as u64),
+        )
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(self.uint(2)? as u16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(self.uint(4)? as u32)
+    }
+
+    fn c_string(&mut self) -> Option<String> {
+        let rest = self.data.get(self.position..)?;
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        let text = String::from_utf8_lossy(&rest[..len]).to_string();
+        self.position += (len + 1).min(rest.len());
+        Some(text)
+    }
+}
+
+/// Key names of a QuickTime `keys` box by 1-based index
+fn mdta_keys(payload: &[u8]) -> HashMap<u32, String> {
+    let mut bytes = Bytes::new(payload);
+    let mut keys = HashMap::new();
+    let (Some(_), Some(count)) = (bytes.u32(), bytes.u32()) else {
+        return keys;
+    };
+    for index in 1..=count {
+        let Some(size) = bytes.u32().filter(|&size| size >= 8) else {
+            break;
+        };
+        let (Some(_namespace), Some(name)) = (bytes.take(4), bytes.take(size as usize - 8)) else {
+            break;
+        };
+        keys.insert(index, String::from_utf8_lossy(name).to_string());
+    }
+    keys
+}
+
+/// (item_ID, item_type, content_type) of every `infe` entry of an `iinf` payload
+fn item_infos(payload: &[u8]) -> Vec<(u32, String, String)> {
+    let mut bytes = Bytes::new(payload);
+    let mut items = Vec::new();
+    let Some(version) = bytes.u8() else {
+        return items;
+    };
+    bytes.take(3);
+    let count = if version == 0 {
+        bytes.u16().map(u32::from)
+    } else {
+        bytes.u32()
+    }
+    .unwrap_or(0);
+    for _ in 0..count {
+        let start = bytes.position;
+        let (Some(size), Some(b"infe")) = (bytes.u32(), bytes.take(4)) else {
+            break;
+        };
+        let Some(infe_version) = bytes.u8() else {
+            break;
+        };
+        bytes.take(3);
+        // 只有 v2/v3 带 item_type
+        if infe_version >= 2 {
+            let id_size = if infe_version == 2 { 2 } else { 4 };
+            let item = (|| {
+                let item_id = bytes.uint(id_size)? as u32;
+                bytes.u16()?; // item_protection_index
+                let item_type = String::from_utf8_lossy(bytes.take(4)?).to_string();
+                bytes.c_string()?; // item_name
+                let content_type = if item_type == "mime" {
+                    bytes.c_string().unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                Some((item_id, item_type, content_type))
+            })();
+            items.extend(item);
+        }
+        bytes.position = start + size as usize;
+    }
+    items
+}
+
+/// File extents (offset, length) of every item stored in the file (construction method 0)
+fn item_locations(payload: &[u8]) -> HashMap<u32, Vec<(u64, u64)>> {
+    let mut locations = HashMap::new();
+    let mut bytes = Bytes::new(payload);
+    let parse = |bytes: &mut Bytes, locations: &mut HashMap<u32, Vec<(u64, u64)>>| {
+        let version = bytes.u8()?;
+        bytes.take(3)?;
+        let sizes = bytes.u8()?;
+        let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0x0f) as usize);
+        let sizes = bytes.u8()?;
+        let base_offset_size = (sizes >> 4) as usize;
+        let index_size = if version >= 1 {
+            (sizes & 0x0f) as usize
+        } else {
+            0
+        };
+        let count = if version < 2 {
+            bytes.u16()? as u32
+        } else {
+            bytes.u32()?
+        };
+        for _ in 0..count {
+            let item_id = if version < 2 {
+                bytes.u16()? as u32
+            } else {
+                bytes.u32()?
+            };
+            let construction_method = if version >= 1 {
+                bytes.u16()? & 0x000f
+            } else {
+                0
+            };
+            bytes.u16()?; // data_reference_index
+            let base_offset = bytes.uint(base_offset_size)?;
+            let extent_count = bytes.u16()?;
+            let mut extents = Vec::new();
+            for _ in 0..extent_count {
+                bytes.uint(index_size)?;
+                let offset = bytes.uint(offset_size)?;
+                let length = bytes.uint(length_size)?;
+                extents.push((base_offset + offset, length));
+            }
+            if construction_method == 0 {
+                locations.insert(item_id, extents);
+            }
+        }
+        Some(())
+    };
+    parse(&mut bytes, &mut locations);
+    locations
+}
+
+/// First string of a QuickTime `©xxx` atom: 16-bit length, 16-bit language, text
+fn quicktime_text(payload: &[u8]) -> String {
+    match payload.get(..2) {
+        Some(len) if payload.len() >= 4 => {
+            // 长度字段不可信时取到 box 末尾为止
+            let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+            String::from_utf8_lossy(&payload[4..(4 + len).min(payload.len())]).to_string()
+        }
+        _ => String::from_utf8_lossy(payload).to_string(),
+    }
+}
+
+/// String of a 3GPP asset box: version/flags, packed language, UTF-8 or UTF-16 text
+fn asset_string(payload: &[u8]) -> String {
+    let text = payload.get(6..).unwrap_or_default();
+    if let Some(utf16) = text.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    Bytes::new(text).c_string().unwrap_or_default()
+}
+
+/// Place name and "lat, lon" of a 3GPP `loci` box
+fn location_info(payload: &[u8]) -> Option<String> {
+    let mut bytes = Bytes::new(payload);
+    bytes.take(6)?; // version/flags, language
+    let name = bytes.c_string()?;
+    bytes.u8()?; // role
+    let fixed = |value: u32| value as i32 as f64 / 65536.0;
+    let longitude = fixed(bytes.u32()?);
+    let latitude = fixed(bytes.u32()?);
+    Some(
+        format!("{} {:.6}, {:.6}", name, latitude, longitude)
+            .trim()
+            .to_string(),
+    )
+}
+
+/// Walks GPMF KLV entries: 4CC key, type, structure size, repeat count, padded data. Nested
+/// entries (type 0) are descended into; `scale` holds the SCAL values of the enclosing stream.
+fn scan_gpmf(data: &[u8], scale: &mut Vec<f64>, found: &mut Vec<(Category, String, String)>) {
+    let mut bytes = Bytes::new(data);
+    while let (Some(key), Some(value_type), Some(size), Some(repeat)) =
+        (bytes.take(4), bytes.u8(), bytes.u8(), bytes.u16())
+    {
+        let len = size as usize * repeat as usize;
+        let Some(value) = bytes.take(len) else {
+            break;
+        };
+        bytes.take((4 - len % 4) % 4);
+        let key = String::from_utf8_lossy(key).to_string();
+        match (key.as_str(), value_type) {
+            (_, 0) => scan_gpmf(value, &mut Vec::new(), found),
+            ("SCAL", _) => *scale = gpmf_numbers(value_type, value),
+            ("GPS5" | "GPS9", b'l') | ("GPS9", b'?') => {
+                let numbers: Vec<f64> = value
+                    .chunks_exact(4)
+                    .take(2)
+                    .map(|n| i32::from_be_bytes([n[0], n[1], n[2], n[3]]) as f64)
+                    .collect();
+                let divisor = |i: usize| {
+                    scale
+                        .get(i)
+                        .or(scale.first())
+                        .copied()
+                        .filter(|&d| d != 0.0)
+                        .unwrap_or(1.0)
+                };
+                let position = match numbers.as_slice() {
+                    [latitude, longitude] => format!(
+                        "{:.6}, {:.6}",
+                        latitude / divisor(0),
+                        longitude / divisor(1)
+                    ),
+                    _ => String::new(),
+                };
+                found.push((Category::Location, key, position));
+            }
+            ("CASN", b'c') => found.push((
+                Category::SerialNumber,
+                key,
+                String::from_utf8_lossy(value).to_string(),
+            )),
+            ("MINF", b'c') => found.push((
+                Category::Device,
+                key,
+                String::from_utf8_lossy(value).to_string(),
+            )),
+            _ => {}
+        }
+    }
+}
+
+fn gpmf_numbers(value_type: u8, value: &[u8]) -> Vec<f64> {
+    match value_type {
+        b'l' => value
+            .chunks_exact(4)
+            .map(|n| i32::from_be_bytes([n[0], n[1], n[2], n[3]]) as f64)
+            .collect(),
+        b'L' => value
+            .chunks_exact(4)
+            .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]) as f64)
+            .collect(),
+        b's' => value
+            .chunks_exact(2)
+            .map(|n| i16::from_be_bytes([n[0], n[1]]) as f64)
+            .collect(),
+        b'S' => value
+            .chunks_exact(2)
+            .map(|n| u16::from_be_bytes([n[0], n[1]]) as f64)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
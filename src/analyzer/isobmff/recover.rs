@@ -0,0 +1,875 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo, EditBox, EditListBox, EditListEntry};
+use super::faststart::{read_bytes, same_file};
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::track::{movie_timescale, Track};
+use super::trim::{chunk_runs, rebuild_sample_table, set_durations, write_progressive};
+use super::types::BoxType;
+use super::IsobmffAnalyzer;
+use crate::analyzer::elementary::{nal_role, presentation_ranks, PocTracker};
+use crate::analyzer::extract::NalConfig;
+use crate::error::MediaError;
+
+const WINDOW_SIZE: usize = 8 << 20;
+/// Reference audio frames whose first byte is read to learn how frames start
+const LEARNED_FRAMES: usize = 1000;
+/// Slice header bytes needed for the picture order count
+const SLICE_HEADER_BYTES: u64 = 64;
+
+/// A track of the rebuilt file
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveredTrack {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub codec: String,
+    pub sample_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_samples: Option<u32>,
+    /// Presentation duration, in seconds
+    pub duration: f64,
+}
+
+/// Outcome of rebuilding the moov of a recording from a reference file
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoverReport {
+    pub reference: String,
+    /// mdat payload bytes scanned
+    pub media_data: u64,
+    /// Bytes that became samples
+    pub recovered_bytes: u64,
+    /// Bytes that matched neither the video nor the audio track
+    pub skipped_bytes: u64,
+    pub tracks: Vec<RecoveredTrack>,
+    pub bytes_written: u64,
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// What the reference tells about the video track
+struct VideoModel {
+    track_id: u32,
+    config: NalConfig,
+    /// Longest NAL unit accepted while scanning
+    max_nal: u64,
+    duration: u32,
+}
+
+enum FrameModel {
+    /// Every frame has the same size (PCM and other constant-size codecs)
+    Fixed(u64),
+    /// Frames recognised by their first byte, with sizes around those of the reference
+    Framed {
+        /// Indexed by byte value
+        starts: Vec<bool>,
+        min: u64,
+        max: u64,
+        mean: f64,
+    },
+}
+
+/// What the reference tells about the audio track
+struct AudioModel {
+    track_id: u32,
+    frames: FrameModel,
+    duration: u32,
+    /// Media time of the reference edit (encoder priming)
+    media_time: i64,
+}
+
+impl AudioModel {
+    fn could_start(&self, byte: u8) -> bool {
+        match &self.frames {
+            FrameModel::Fixed(_) => true,
+            FrameModel::Framed { starts, .. } => starts[byte as usize],
+        }
+    }
+}
+
+/// Buffered access to a damaged file; scans move forward, so one window is enough
+pub(super) struct Window {
+    file: File,
+    start: u64,
+    data: Vec<u8>,
+}
+
+impl Window {
+    pub(super) fn new(file: File) -> Self {
+        Self {
+            file,
+            start: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub(super) fn file_size(&self) -> Result<u64, MediaError> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// `len` bytes at `offset`, fewer at the end of the file
+    pub(super) fn get(&mut self, offset: u64, len: usize) -> Result<&[u8], MediaError> {
+        let end = self.start + self.data.len() as u64;
+        if offset < self.start || offset + len as u64 > end {
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.data.clear();
+            (&mut self.file)
+                .take(len.max(WINDOW_SIZE) as u64)
+                .read_to_end(&mut self.data)?;
+            self.start = offset;
+        }
+        let at = (offset - self.start) as usize;
+        Ok(&self.data[at..(at + len).min(self.data.len())])
+    }
+
+    pub(super) fn byte(&mut self, offset: u64) -> Result<u8, MediaError> {
+        self.get(offset, 1)?
+            .first()
+            .copied()
+            .ok_or(MediaError::UnexpectedEof)
+    }
+}
+
+/// A length-prefixed NAL unit found in the mdat
+struct Nal {
+    offset: u64,
+    /// Length prefix included
+    len: u64,
+    header: [u8; 3],
+}
+
+/// Samples found while scanning, with offsets in the damaged file
+#[derive(Default)]
+struct Scan {
+    video: Vec<Sample>,
+    orders: Vec<Option<(i64, bool)>>,
+    audio: Vec<Sample>,
+    skipped: u64,
+    /// The data ends inside a video sample
+    truncated: bool,
+    /// Counts contiguous runs; samples of one run share a chunk
+    run: u32,
+}
+
+/// Rebuilds a recording whose moov is missing (camera or recorder crash) from the codec
+/// configuration of `reference`, a healthy file from the same device. The mdat is scanned for
+/// length-prefixed H.264/H.265 NAL units; the bytes in between are cut into audio frames that
+/// start and are sized like the reference's.
+pub fn recover(input: &Path, reference: &Path, output: &Path) -> Result<RecoverReport, MediaError> {
+    if same_file(input, output) || same_file(reference, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input or the reference file".to_string(),
+        ));
+    }
+    let mut analyzer = IsobmffAnalyzer::new(reference)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let tracks = Track::all(boxes);
+    if tracks.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "the reference file has no tracks".to_string(),
+        ));
+    }
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let mut warnings = Vec::new();
+    let mut reference_file = File::open(reference)?;
+    let video = learn_video(&tracks, &indexes)?;
+    let audio = learn_audio(&tracks, &indexes, &mut reference_file, &mut warnings)?;
+    if video.is_none() && audio.is_none() {
+        return Err(MediaError::UnsupportedFormat(
+            "the reference file has no H.264/H.265 video or audio track to learn from".to_string(),
+        ));
+    }
+    for track in &tracks {
+        let id = track.track_id();
+        if video.as_ref().is_none_or(|v| v.track_id != id)
+            && audio.as_ref().is_none_or(|a| a.track_id != id)
+        {
+            warnings.push(format!(
+                "reference track {} ({}) is not recovered",
+                id,
+                track.handler_type()
+            ));
+        }
+    }
+
+    let mut window = Window::new(File::open(input)?);
+    let file_size = window.file_size()?;
+    let ranges = media_data_ranges(&mut window, file_size, &mut warnings)?;
+    let media_data: u64 = ranges.iter().map(|(start, end)| end - start).sum();
+    let mut scan = Scan::default();
+    let mut tracker = video.as_ref().map(|v| {
+        let mut tracker = PocTracker::new(v.config.hevc);
+        for nal in &v.config.parameter_sets {
+            tracker.parameter_set(nal);
+        }
+        tracker
+    });
+    for &(start, end) in &ranges {
+        scan_range(
+            &mut window,
+            start,
+            end,
+            video.as_ref().zip(tracker.as_mut()),
+            audio.as_ref(),
+            &mut scan,
+        )?;
+    }
+    if video.is_some() && scan.video.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "no video matching the reference was found in the mdat; is the reference from the same device?"
+                .to_string(),
+        ));
+    }
+    if scan.audio.is_empty() && scan.video.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "no audio matching the reference was found in the mdat".to_string(),
+        ));
+    }
+    if scan.truncated {
+        warnings.push("the recording ends inside a video sample, which was dropped".to_string());
+    }
+    if scan.skipped > 0 {
+        warnings.push(format!(
+            "{} bytes matched neither track and were left out",
+            scan.skipped
+        ));
+    }
+    if scan.video.first().is_some_and(|s| !s.is_sync) {
+        warnings.push("the recovered video does not start with a keyframe".to_string());
+    }
+
+    // 视频: 按参考文件的帧间隔排列, 由 POC 得到合成时间偏移
+    let (presentation, delay) = presentation_ranks(&scan.orders);
+    let video_start = match &video {
+        Some(model) => {
+            let duration = model.duration as u64;
+            for (i, sample) in scan.video.iter_mut().enumerate() {
+                sample.dts = i as u64 * duration;
+                sample.cts = (presentation[i] + delay) * duration as i64;
+                sample.duration = model.duration;
+            }
+            delay * duration as i64
+        }
+        None => 0,
+    };
+    if let Some(model) = &audio {
+        for (i, sample) in scan.audio.iter_mut().enumerate() {
+            sample.dts = i as u64 * model.duration as u64;
+            sample.cts = sample.dts as i64;
+            sample.duration = model.duration;
+        }
+    }
+
+    let movie_timescale = movie_timescale(boxes).max(1);
+    let reference_tracks: Vec<(u32, u32)> = tracks
+        .iter()
+        .map(|t| (t.track_id(), t.timescale()))
+        .collect();
+    let mut boxes = analyzer.boxes.take().unwrap_or_default();
+    boxes.retain(|b| matches!(b.box_type().as_str(), "ftyp" | "moov"));
+    let moov = boxes
+        .iter_mut()
+        .find(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("the reference has no moov box".to_string()))?;
+    // 参考文件若是分片的, 样本表全部写进 moov
+    moov.children_mut()
+        .retain(|b| b.box_type().as_str() != "mvex");
+
+    let mut recovered = Vec::new();
+    let mut chunks = Vec::new();
+    let mut expected = Vec::new();
+    let mut movie_duration = 0;
+    let mut reference_tracks = reference_tracks.into_iter();
+    moov.children_mut().retain_mut(|trak| {
+        if trak.box_type().as_str() != "trak" {
+            return true;
+        }
+        let Some((track_id, timescale)) = reference_tracks.next() else {
+            return false;
+        };
+        let (samples, media_time, handler_type) =
+            if video.as_ref().is_some_and(|v| v.track_id == track_id) {
+                (&scan.video, video_start, "vide")
+            } else if let Some(model) = audio.as_ref().filter(|a| a.track_id == track_id) {
+                (&scan.audio, model.media_time, "soun")
+            } else {
+                return false;
+            };
+        if samples.is_empty() {
+            warnings.push(format!("no samples of track {} were found", track_id));
+            return false;
+        }
+        let track_chunks = chunk_runs(recovered.len(), samples);
+        rebuild_sample_table(trak, samples, 0, &track_chunks, &mut warnings);
+        remove_sample_groups(trak);
+        let media_duration: u64 = samples.iter().map(|s| s.duration as u64).sum();
+        let media_time = media_time.min(media_duration as i64);
+        // 视频的 media_time 是组合时间偏移, 所有帧都要呈现; 只有音频的编码器延迟被跳过
+        let presented = if handler_type == "soun" {
+            media_duration - media_time as u64
+        } else {
+            media_duration
+        };
+        let segment = presented * movie_timescale as u64 / timescale.max(1) as u64;
+        set_edit(trak, segment, media_time);
+        set_durations(trak, segment, media_duration);
+        movie_duration = movie_duration.max(segment);
+
+        recovered.push(RecoveredTrack {
+            track_id,
+            handler_type: handler_type.to_string(),
+            codec: Track::new(trak)
+                .sample_entry()
+                .map(|entry| entry.entry_type().to_string())
+                .unwrap_or_default(),
+            sample_count: samples.len() as u32,
+            sync_samples: (handler_type == "vide")
+                .then(|| samples.iter().filter(|s| s.is_sync).count() as u32),
+            duration: segment as f64 / movie_timescale as f64,
+        });
+        chunks.extend(track_chunks);
+        expected.push((track_id, samples.clone()));
+        true
+    });
+    if let Some(BoxData::MovieHeader(mvhd)) = moov.child_mut("mvhd").map(|b| b.data_mut()) {
+        mvhd.set_duration(movie_duration);
+    }
+
+    let recovered_bytes = chunks.iter().map(|chunk| chunk.len).sum();
+    let (bytes_written, verified_samples) =
+        write_progressive(input, output, &mut boxes, chunks, &expected)?;
+    Ok(RecoverReport {
+        reference: reference.display().to_string(),
+        media_data,
+        recovered_bytes,
+        skipped_bytes: scan.skipped,
+        tracks: recovered,
+        bytes_written,
+        verified_samples,
+        warnings,
+    })
+}
+
+fn learn_video(
+    tracks: &[Track],
+    indexes: &[TrackSampleIndex],
+) -> Result<Option<VideoModel>, MediaError> {
+    let Some((track, index)) = tracks
+        .iter()
+        .zip(indexes)
+        .find(|(track, _)| track.handler_type() == "vide")
+    else {
+        return Ok(None);
+    };
+    let entry = track.sample_entry().ok_or_else(|| {
+        MediaError::InvalidFormat("the reference video track has no sample description".into())
+    })?;
+    let config = match entry.entry_type() {
+        "avc1" | "avc3" => entry.child_box("avcC").and_then(NalConfig::from_avcc),
+        "hvc1" | "hev1" => entry.child_box("hvcC").and_then(NalConfig::from_hvcc),
+        other => {
+            return Err(MediaError::UnsupportedFormat(format!(
+                "recovering {} video is not supported",
+                other
+            )))
+        }
+    }
+    .ok_or_else(|| {
+        MediaError::InvalidFormat(format!(
+            "the reference {} sample entry has no usable configuration",
+            entry.entry_type()
+        ))
+    })?;
+    let max_sample = index
+        .samples
+        .iter()
+        .map(|s| s.size as u64)
+        .max()
+        .unwrap_or(0);
+    Ok(Some(VideoModel {
+        track_id: track.track_id(),
+        config,
+        max_nal: (max_sample * 8).max(1 << 20),
+        duration: typical_duration(&index.samples).unwrap_or(index.timescale / 25),
+    }))
+}
+
+fn learn_audio(
+    tracks: &[Track],
+    indexes: &[TrackSampleIndex],
+    reference: &mut File,
+    warnings: &mut Vec<String>,
+) -> Result<Option<AudioModel>, MediaError> {
+    let Some((track, index)) = tracks
+        .iter()
+        .zip(indexes)
+        .find(|(track, _)| track.handler_type() == "soun")
+    else {
+        return Ok(None);
+    };
+    let samples = &index.samples;
+    let (Some(duration), Some(first)) = (typical_duration(samples), samples.first()) else {
+        warnings.push(format!(
+            "reference audio track {} has no samples to learn from",
+            track.track_id()
+        ));
+        return Ok(None);
+    };
+    let frames = if samples.iter().all(|s| s.size == first.size) {
+        FrameModel::Fixed(first.size as u64)
+    } else {
+        let learned = &samples[..samples.len().min(LEARNED_FRAMES)];
+        let mut counts = [0usize; 256];
+        for sample in learned {
+            let byte = read_bytes(reference, sample.offset, 1)?;
+            counts[byte[0] as usize] += 1;
+        }
+        // 偶尔出现的首字节视为噪声
+        let mut starts = vec![false; 256];
+        for (start, count) in starts.iter_mut().zip(counts) {
+            *start = count * 200 >= learned.len();
+        }
+        let sizes = samples.iter().map(|s| s.size as u64);
+        FrameModel::Framed {
+            starts,
+            min: (sizes.clone().min().unwrap_or(1) / 2).max(1),
+            max: sizes.clone().max().unwrap_or(1) * 2,
+            mean: sizes.sum::<u64>() as f64 / samples.len() as f64,
+        }
+    };
+    let media_time = track
+        .edit_list()
+        .and_then(|elst| elst.entries().iter().find(|e| e.media_time >= 0))
+        .map_or(0, |entry| entry.media_time);
+    Ok(Some(AudioModel {
+        track_id: track.track_id(),
+        frames,
+        duration,
+        media_time,
+    }))
+}
+
+/// The most frequent sample duration
+fn typical_duration(samples: &[Sample]) -> Option<u32> {
+    let mut counts: Vec<(u32, usize)> = Vec::new();
+    for sample in samples {
+        match counts.iter_mut().find(|(d, _)| *d == sample.duration) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((sample.duration, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(duration, _)| *duration > 0)
+        .max_by_key(|(_, count)| *count)
+        .map(|(duration, _)| duration)
+}
+
+/// Payload ranges of the top-level mdat boxes. The size of an mdat that was never finalised
+/// (0, an empty placeholder or beyond the end of the file) is taken to run to the end.
+fn media_data_ranges(
+    window: &mut Window,
+    file_size: u64,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<(u64, u64)>, MediaError> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= file_size {
+        let header = window.get(offset, 16)?.to_vec();
+        let declared = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let box_type = [header[4], header[5], header[6], header[7]];
+        let (header_len, size) = match declared {
+            0 => (8, file_size - offset),
+            1 if header.len() == 16 => (
+                16,
+                u64::from_be_bytes(header[8..16].try_into().unwrap_or_default()),
+            ),
+            size => (8, size),
+        };
+        if !box_type.iter().all(|b| b.is_ascii_graphic() || *b == b' ')
+            || (size < header_len && &box_type != b"mdat")
+        {
+            warnings.push(format!(
+                "unreadable data at offset {} ends the box scan",
+                offset
+            ));
+            break;
+        }
+        match &box_type {
+            b"moov" => {
+                return Err(MediaError::InvalidFormat(
+                    "the file has a moov box; there is nothing to recover".to_string(),
+                ))
+            }
+            b"mdat" => {
+                let mut end = offset.saturating_add(size);
+                if size <= header_len || end > file_size {
+                    // 崩溃时 mdat 的大小往往没有回填
+                    if declared != 0 {
+                        warnings.push(format!(
+                            "mdat at offset {} declares {} bytes; it is taken to run to the end of the file",
+                            offset, size
+                        ));
+                    }
+                    end = file_size;
+                }
+                ranges.push((offset + header_len, end));
+                offset = end;
+            }
+            _ => {
+                if offset.saturating_add(size) > file_size {
+                    warnings.push(format!(
+                        "{} box at offset {} is truncated",
+                        String::from_utf8_lossy(&box_type),
+                        offset
+                    ));
+                    break;
+                }
+                offset += size;
+            }
+        }
+    }
+    if ranges.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "the file has no mdat box".to_string(),
+        ));
+    }
+    Ok(ranges)
+}
+
+/// Splits one mdat payload into video access units and audio frames
+fn scan_range(
+    window: &mut Window,
+    start: u64,
+    mut end: u64,
+    mut video: Option<(&VideoModel, &mut PocTracker)>,
+    audio: Option<&AudioModel>,
+    scan: &mut Scan,
+) -> Result<(), MediaError> {
+    // 预分配但未写入的尾部 (全零) 不参与识别
+    while end > start && window.byte(end - 1)? == 0 {
+        end -= 1;
+    }
+    let mut position = start;
+    let mut run_start = start;
+    while position < end {
+        let nals = match video.as_mut() {
+            Some((model, _)) => video_chain(window, model, position, end)?,
+            None => None,
+        };
+        let Some((nals, truncated)) = nals else {
+            position += 1;
+            continue;
+        };
+        if run_start < position {
+            split_audio(window, run_start, position, audio, scan)?;
+        }
+        if let Some((model, tracker)) = video.as_mut() {
+            add_access_units(window, model, tracker, &nals, scan)?;
+        }
+        position = nals.last().map_or(position, |nal| nal.offset + nal.len);
+        run_start = position;
+        if truncated {
+            // 录制中断在视频样本中间, 残余部分无法使用
+            scan.skipped += end - position;
+            scan.truncated = true;
+            return Ok(());
+        }
+    }
+    if run_start < end {
+        split_audio(window, run_start, end, audio, scan)?;
+    }
+    Ok(())
+}
+
+/// The NAL units starting at `position` when they look like the start of video: valid
+/// headers, an access unit boundary first and at least one coded slice. The flag tells that
+/// the NAL unit after them is cut off by the end of the data.
+fn video_chain(
+    window: &mut Window,
+    model: &VideoModel,
+    position: u64,
+    end: u64,
+) -> Result<Option<(Vec<Nal>, bool)>, MediaError> {
+    let mut nals = Vec::new();
+    let mut at = position;
+    let mut truncated = false;
+    while let Some(nal) = nal_at(window, model, at, end)? {
+        if nals.is_empty() && !nal_role(&nal.header, model.config.hevc).boundary {
+            return Ok(None);
+        }
+        if nal.offset + nal.len > end {
+            truncated = true;
+            break;
+        }
+        at += nal.len;
+        nals.push(nal);
+    }
+    let has_slice = nals
+        .iter()
+        .any(|nal| nal_role(&nal.header, model.config.hevc).vcl);
+    Ok(has_slice.then_some((nals, truncated)))
+}
+
+/// A NAL unit with a plausible length prefix and header at `offset`; it may run past `end`
+fn nal_at(
+    window: &mut Window,
+    model: &VideoModel,
+    offset: u64,
+    end: u64,
+) -> Result<Option<Nal>, MediaError> {
+    let length_size = model.config.length_size;
+    if offset + length_size as u64 + 3 > end {
+        return Ok(None);
+    }
+    let bytes = window.get(offset, length_size + 3)?;
+    let len = bytes[..length_size]
+        .iter()
+        .fold(0u64, |value, &b| value << 8 | b as u64);
+    let header = [
+        bytes[length_size],
+        bytes[length_size + 1],
+        bytes[length_size + 2],
+    ];
+    if len < 2 || len > model.max_nal {
+        return Ok(None);
+    }
+    let valid = if model.config.hevc {
+        let nal_type = (header[0] >> 1) & 0x3f;
+        // forbidden_zero_bit 为 0, nuh_layer_id 为 0, TemporalId + 1 不为 0
+        header[0] & 0x81 == 0
+            && header[1] >> 3 == 0
+            && header[1] & 0x07 != 0
+            && matches!(nal_type, 0..=9 | 16..=21 | 32..=40)
+    } else {
+        let (nal_ref_idc, nal_type) = (header[0] >> 5 & 0x03, header[0] & 0x1f);
+        header[0] & 0x80 == 0
+            && match nal_type {
+                1 => true,
+                5 | 7 | 8 => nal_ref_idc != 0,
+                6 | 9..=12 => nal_ref_idc == 0,
+                _ => false,
+            }
+    };
+    Ok(valid.then_some(Nal {
+        offset,
+        len: length_size as u64 + len,
+        header,
+    }))
+}
+
+/// Groups the NAL units of one contiguous run into access units (H.264 7.4.1.2.3, H.265
+/// 7.4.2.4.4); each becomes a sample
+fn add_access_units(
+    window: &mut Window,
+    model: &VideoModel,
+    tracker: &mut PocTracker,
+    nals: &[Nal],
+    scan: &mut Scan,
+) -> Result<(), MediaError> {
+    scan.run += 1;
+    let hevc = model.config.hevc;
+    let length_size = model.config.length_size as u64;
+    let mut current: Option<(Sample, Option<(i64, bool)>)> = None;
+    let mut pending = 0u64;
+    for nal in nals {
+        let role = nal_role(&nal.header, hevc);
+        if role.boundary && current.is_some() {
+            let (sample, order) = current.take().unwrap_or_else(|| unreachable!());
+            scan.video.push(sample);
+            scan.orders.push(order);
+        }
+        let payload = nal.offset + length_size;
+        let is_parameter_set = if hevc {
+            matches!((nal.header[0] >> 1) & 0x3f, 32..=34)
+        } else {
+            matches!(nal.header[0] & 0x1f, 7 | 8)
+        };
+        if is_parameter_set {
+            let bytes = window
+                .get(payload, (nal.len - length_size) as usize)?
+                .to_vec();
+            tracker.parameter_set(&bytes);
+        }
+        match current.as_mut() {
+            Some((sample, _)) => sample.size += nal.len as u32,
+            None if role.vcl => {
+                let len = (nal.len - length_size).min(SLICE_HEADER_BYTES) as usize;
+                let order = tracker.picture(window.get(payload, len)?);
+                let sample = Sample {
+                    number: scan.video.len() as u32 + 1,
+                    offset: nal.offset - pending,
+                    size: (pending + nal.len) as u32,
+                    dts: 0,
+                    cts: 0,
+                    duration: 0,
+                    is_sync: role.random_access,
+                    chunk_index: scan.run,
+                    sample_description_index: 1,
+                    groups: Vec::new(),
+                };
+                current = Some((sample, order));
+                pending = 0;
+            }
+            // 访问单元中位于第一个 slice 之前的 NAL (AUD, 参数集, SEI)
+            None => pending += nal.len,
+        }
+    }
+    if let Some((sample, order)) = current {
+        scan.video.push(sample);
+        scan.orders.push(order);
+    }
+    scan.skipped += pending;
+    Ok(())
+}
+
+/// Cuts the bytes between two video runs into audio frames
+fn split_audio(
+    window: &mut Window,
+    start: u64,
+    end: u64,
+    audio: Option<&AudioModel>,
+    scan: &mut Scan,
+) -> Result<(), MediaError> {
+    let Some(model) = audio else {
+        scan.skipped += end - start;
+        return Ok(());
+    };
+    scan.run += 1;
+    let frames = match model.frames {
+        FrameModel::Fixed(size) => {
+            let count = (end - start) / size;
+            scan.skipped += (end - start) % size;
+            (0..count).map(|i| (start + i * size, size)).collect()
+        }
+        FrameModel::Framed { min, max, mean, .. } => {
+            match frame_boundaries(window, model, start, end, min, max, mean)? {
+                Some(frames) => frames,
+                None => {
+                    scan.skipped += end - start;
+                    return Ok(());
+                }
+            }
+        }
+    };
+    for (offset, size) in frames {
+        scan.audio.push(Sample {
+            number: scan.audio.len() as u32 + 1,
+            offset,
+            size: size as u32,
+            dts: 0,
+            cts: 0,
+            duration: 0,
+            is_sync: true,
+            chunk_index: scan.run,
+            sample_description_index: 1,
+            groups: Vec::new(),
+        });
+    }
+    Ok(())
+}
+
+/// Chooses frame starts among the positions whose byte can start a frame so that every frame
+/// size lies in `min..=max` and the sizes stay closest to the reference mean
+fn frame_boundaries(
+    window: &mut Window,
+    model: &AudioModel,
+    start: u64,
+    end: u64,
+    min: u64,
+    max: u64,
+    mean: f64,
+) -> Result<Option<Vec<(u64, u64)>>, MediaError> {
+    let mut candidates = vec![start];
+    for position in start + 1..end {
+        if model.could_start(window.byte(position)?) {
+            candidates.push(position);
+        }
+    }
+    candidates.push(end);
+    // 动态规划: cost[i] 为从 start 切到 candidates[i] 的最小代价
+    let mut cost = vec![f64::INFINITY; candidates.len()];
+    let mut previous = vec![0usize; candidates.len()];
+    cost[0] = 0.0;
+    for i in 0..candidates.len() - 1 {
+        if cost[i].is_infinite() {
+            continue;
+        }
+        let from = candidates.partition_point(|&p| p < candidates[i] + min);
+        for j in from..candidates.len() {
+            let size = candidates[j] - candidates[i];
+            if size > max {
+                break;
+            }
+            let deviation = (size as f64 - mean) / mean;
+            let total = cost[i] + deviation * deviation;
+            if total < cost[j] {
+                cost[j] = total;
+                previous[j] = i;
+            }
+        }
+    }
+    let last = candidates.len() - 1;
+    if cost[last].is_infinite() {
+        return Ok(None);
+    }
+    let mut frames = Vec::new();
+    let mut j = last;
+    while j > 0 {
+        let i = previous[j];
+        frames.push((candidates[i], candidates[j] - candidates[i]));
+        j = i;
+    }
+    frames.reverse();
+    Ok(Some(frames))
+}
+
+/// sbgp/sgpd of the reference describe its own samples
+fn remove_sample_groups(trak: &mut Box) {
+    let stbl = trak
+        .child_mut("mdia")
+        .and_then(|mdia| mdia.child_mut("minf"))
+        .and_then(|minf| minf.child_mut("stbl"));
+    if let Some(stbl) = stbl {
+        stbl.children_mut()
+            .retain(|b| !matches!(b.box_type().as_str(), "sbgp" | "sgpd"));
+    }
+}
+
+/// Replaces the edit list with one edit showing the media from `media_time`
+fn set_edit(trak: &mut Box, segment: u64, media_time: i64) {
+    let large = segment > u32::MAX as u64 || media_time > i32::MAX as i64;
+    let elst = new_box(
+        *b"elst",
+        BoxData::EditList(EditListBox::new(
+            u8::from(large),
+            0,
+            vec![EditListEntry::new(segment, media_time, 1, 0)],
+        )),
+    );
+    let mut edts = new_box(*b"edts", BoxData::Edit(EditBox::new()));
+    edts.add_child(elst);
+    let children = trak.children_mut();
+    match children
+        .iter()
+        .position(|b| b.box_type().as_str() == "edts")
+    {
+        Some(i) => children[i] = edts,
+        None => {
+            let after_tkhd = children
+                .iter()
+                .position(|b| b.box_type().as_str() == "tkhd")
+                .map_or(0, |i| i + 1);
+            children.insert(after_tkhd, edts);
+        }
+    }
+}
+
+fn new_box(box_type: [u8; 4], data: BoxData) -> Box {
+    Box::new(BoxType::new(box_type), 0, 0, 8, data)
+}
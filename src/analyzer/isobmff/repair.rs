@@ -0,0 +1,632 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo};
+use super::faststart::{copy_range, read_bytes, same_file};
+use super::recover::Window;
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::track::Track;
+use super::validator::{validate, Finding, Severity, ValidationReport};
+use super::IsobmffAnalyzer;
+use crate::analyzer::extract::NalConfig;
+use crate::error::MediaError;
+
+/// Box types expected at the top level; a wrongly sized box is only resized onto one of these
+const TOP_LEVEL: [&[u8; 4]; 17] = [
+    b"ftyp", b"styp", b"moov", b"moof", b"mfra", b"mdat", b"free", b"skip", b"wide", b"udta",
+    b"meta", b"uuid", b"sidx", b"ssix", b"pdin", b"emsg", b"prft",
+];
+/// Top-level types that never occur inside a container; a child walk stops at them
+const TOP_LEVEL_ONLY: [&[u8; 4]; 11] = [
+    b"ftyp", b"styp", b"moov", b"moof", b"mfra", b"mdat", b"sidx", b"ssix", b"pdin", b"emsg",
+    b"prft",
+];
+const SEARCH_BLOCK: usize = 8 << 20;
+/// Samples per track whose NAL length prefixes are checked
+const FRAMING_CHECKS: usize = 8;
+
+/// One modification made to the copy
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairChange {
+    /// Validator rule the change addresses
+    pub rule: &'static str,
+    /// Offset of the changed box (the same in input and output)
+    pub offset: u64,
+    pub description: String,
+}
+
+/// Outcome of repairing a structurally damaged file
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    pub errors_before: usize,
+    pub warnings_before: usize,
+    pub changes: Vec<RepairChange>,
+    /// Errors and warnings the validator still reports for the output
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub remaining: Vec<Finding>,
+    /// 0 when nothing needed repairing and no file was written
+    pub bytes_written: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl RepairReport {
+    /// 0 when the output validates without errors, 2 otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.remaining.iter().any(|f| f.severity == Severity::Error) {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// A top-level box header as found in the file
+#[derive(Debug, Clone, Copy)]
+struct TopBox {
+    offset: u64,
+    header_len: u64,
+    box_type: [u8; 4],
+    /// Size from the header; a size of 0 is resolved to the end of the file
+    declared: u64,
+    size: u64,
+}
+
+/// Writes a repaired copy of `input`. Fixes are limited to wrong top-level box sizes, data
+/// after the last box and chunk offsets that are all off by the same delta; box payloads are
+/// never moved, so every other byte keeps its offset.
+pub fn repair(input: &Path, output: &Path) -> Result<RepairReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    let mut warnings = Vec::new();
+    let before = match validate_file(input) {
+        Ok(report) => Some(report),
+        Err(e) => {
+            warnings.push(format!("the input could not be validated: {}", e));
+            None
+        }
+    };
+
+    let media_end = media_end(input).ok().flatten();
+
+    let mut window = Window::new(File::open(input)?);
+    let file_size = window.file_size()?;
+    let mut changes = Vec::new();
+    let (layout, end) = top_level_layout(&mut window, file_size, media_end, &mut changes)?;
+    if layout.is_empty() {
+        return Err(MediaError::InvalidFormat(
+            "no box header found at the start of the file".to_string(),
+        ));
+    }
+    if end < file_size {
+        changes.push(RepairChange {
+            rule: "trailing-data",
+            offset: end,
+            description: format!("dropped {} bytes after the last box", file_size - end),
+        });
+    }
+
+    let mut source = File::open(input)?;
+    let mut out = BufWriter::new(File::create(output)?);
+    for b in &layout {
+        if b.size == b.declared {
+            copy_range(&mut source, &mut out, b.offset, b.size)?;
+            continue;
+        }
+        if b.header_len == 8 {
+            let size = u32::try_from(b.size).map_err(|_| {
+                MediaError::UnsupportedFormat(format!(
+                    "{} bytes do not fit the 32-bit size of the box at offset {}",
+                    b.size, b.offset
+                ))
+            })?;
+            out.write_all(&size.to_be_bytes())?;
+            out.write_all(&b.box_type)?;
+        } else {
+            out.write_all(&1u32.to_be_bytes())?;
+            out.write_all(&b.box_type)?;
+            out.write_all(&b.size.to_be_bytes())?;
+        }
+        copy_range(
+            &mut source,
+            &mut out,
+            b.offset + b.header_len,
+            b.size - b.header_len,
+        )?;
+    }
+    out.flush()?;
+    drop(out);
+
+    let patches = match shift_chunk_offsets(output, &mut changes, &mut warnings) {
+        Ok(patches) => patches,
+        Err(e) => {
+            warnings.push(format!("chunk offsets were not checked: {}", e));
+            Vec::new()
+        }
+    };
+    if !patches.is_empty() {
+        let mut file = OpenOptions::new().write(true).open(output)?;
+        for (position, bytes) in patches {
+            file.seek(SeekFrom::Start(position))?;
+            file.write_all(&bytes)?;
+        }
+    }
+
+    if changes.is_empty() {
+        std::fs::remove_file(output)?;
+        return Ok(RepairReport {
+            errors_before: before.as_ref().map_or(0, |r| r.errors),
+            warnings_before: before.as_ref().map_or(0, |r| r.warnings),
+            changes,
+            remaining: before.map(|r| remaining(r.findings)).unwrap_or_default(),
+            bytes_written: 0,
+            warnings,
+        });
+    }
+    let after = validate_file(output)?;
+    Ok(RepairReport {
+        errors_before: before.as_ref().map_or(0, |r| r.errors),
+        warnings_before: before.as_ref().map_or(0, |r| r.warnings),
+        changes,
+        remaining: remaining(after.findings),
+        bytes_written: after.file_size,
+        warnings,
+    })
+}
+
+fn validate_file(path: &Path) -> Result<ValidationReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(path)?;
+    analyzer.set_debug(false);
+    let file_size = analyzer.parser.file_size()?;
+    Ok(validate(analyzer.boxes()?, file_size))
+}
+
+/// End of the last sample the sample tables reference
+fn media_end(path: &Path) -> Result<Option<u64>, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(path)?;
+    analyzer.set_debug(false);
+    let indexes = TrackSampleIndex::build_all(analyzer.boxes()?)?;
+    Ok(indexes
+        .iter()
+        .flat_map(|index| &index.samples)
+        .map(|s| s.offset + s.size as u64)
+        .max())
+}
+
+fn remaining(findings: Vec<Finding>) -> Vec<Finding> {
+    findings
+        .into_iter()
+        .filter(|f| f.severity >= Severity::Warning)
+        .collect()
+}
+
+/// Walks the top-level boxes, correcting sizes that do not end on the next box. Returns the
+/// boxes to keep and where the last one ends.
+fn top_level_layout(
+    window: &mut Window,
+    file_size: u64,
+    media_end: Option<u64>,
+    changes: &mut Vec<RepairChange>,
+) -> Result<(Vec<TopBox>, u64), MediaError> {
+    let mut layout = Vec::new();
+    let mut offset = 0;
+    while let Some(mut b) = header_at(window, offset, file_size)? {
+        let end = offset.saturating_add(b.size);
+        if end <= file_size && ends_on_box(window, end, file_size)? {
+            layout.push(b);
+            offset = end;
+            continue;
+        }
+        if !TOP_LEVEL.contains(&&b.box_type) {
+            // 未知类型且大小对不上, 当作尾部垃圾
+            break;
+        }
+        let name = String::from_utf8_lossy(&b.box_type).into_owned();
+        let fixed = match &b.box_type {
+            b"moov" | b"moof" | b"mfra" | b"udta" | b"meta" => {
+                children_extent(window, &b, file_size)?
+            }
+            _ => next_box(window, &b, file_size)?,
+        };
+        match fixed {
+            Some(size) if size != b.size => {
+                changes.push(RepairChange {
+                    rule: if end > file_size {
+                        "size-overrun"
+                    } else {
+                        "box-size"
+                    },
+                    offset,
+                    description: format!(
+                        "{} size corrected from {} to {} bytes",
+                        name, b.size, size
+                    ),
+                });
+                b.size = size;
+            }
+            _ if end > file_size => {
+                if !matches!(&b.box_type, b"mdat" | b"free" | b"skip" | b"wide") {
+                    return Err(MediaError::InvalidFormat(format!(
+                        "{} box at offset {} is truncated ({} of {} bytes present)",
+                        name,
+                        offset,
+                        file_size - offset,
+                        b.size
+                    )));
+                }
+                changes.push(RepairChange {
+                    rule: "size-overrun",
+                    offset,
+                    description: format!(
+                        "{} declares {} bytes but the file ends after {}; size set to the end of the file",
+                        name,
+                        b.size,
+                        file_size - offset
+                    ),
+                });
+                b.size = file_size - offset;
+            }
+            _ if b.size < b.header_len => {
+                changes.push(RepairChange {
+                    rule: "box-size",
+                    offset,
+                    description: format!(
+                        "{} declares {} bytes, less than its header; size set to {}",
+                        name, b.size, b.header_len
+                    ),
+                });
+                b.size = b.header_len;
+            }
+            // 声明的大小在文件内, 之后的数据不是 box
+            _ => {}
+        }
+        layout.push(b);
+        offset += b.size;
+    }
+    // 最后一个 mdat 声明得太小时, 其后的数据仍被样本表引用, 不是垃圾
+    if let (Some(last), Some(media_end)) = (layout.last_mut(), media_end) {
+        if &last.box_type == b"mdat" && media_end > offset && offset < file_size {
+            let size = media_end.min(file_size) - last.offset;
+            changes.push(RepairChange {
+                rule: "box-size",
+                offset: last.offset,
+                description: format!(
+                    "mdat size corrected from {} to {} bytes to cover every sample",
+                    last.size, size
+                ),
+            });
+            last.size = size;
+            offset = last.offset + size;
+        }
+    }
+    Ok((layout, offset))
+}
+
+/// A plausible box header at `offset`, or None
+fn header_at(
+    window: &mut Window,
+    offset: u64,
+    file_size: u64,
+) -> Result<Option<TopBox>, MediaError> {
+    if offset + 8 > file_size {
+        return Ok(None);
+    }
+    let header = window.get(offset, 16)?;
+    let declared = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+    let box_type = [header[4], header[5], header[6], header[7]];
+    let (header_len, declared) = match declared {
+        0 => (8, 0),
+        1 if header.len() == 16 => (
+            16,
+            u64::from_be_bytes(header[8..16].try_into().unwrap_or_default()),
+        ),
+        1 => return Ok(None),
+        size => (8, size),
+    };
+    let size = if declared == 0 {
+        file_size - offset
+    } else {
+        declared
+    };
+    let printable = box_type
+        .iter()
+        .all(|b| b.is_ascii_graphic() || *b == b' ' || *b == 0xa9);
+    // 已知类型的 box 即使大小小于头部也返回, 由调用方修正
+    if !printable || (size < header_len && !TOP_LEVEL.contains(&&box_type)) {
+        return Ok(None);
+    }
+    Ok(Some(TopBox {
+        offset,
+        header_len,
+        box_type,
+        declared: size,
+        size,
+    }))
+}
+
+/// True when `offset` is the end of the file or the start of a known top-level box
+fn ends_on_box(window: &mut Window, offset: u64, file_size: u64) -> Result<bool, MediaError> {
+    if offset == file_size {
+        return Ok(true);
+    }
+    Ok(header_at(window, offset, file_size)?.is_some_and(|b| TOP_LEVEL.contains(&&b.box_type)))
+}
+
+/// Size of a container derived from its children: the walk stops at the end of the file or
+/// at a box that can only appear at the top level
+fn children_extent(
+    window: &mut Window,
+    container: &TopBox,
+    file_size: u64,
+) -> Result<Option<u64>, MediaError> {
+    let full_box = if &container.box_type == b"meta" { 4 } else { 0 };
+    let mut at = container.offset + container.header_len + full_box;
+    loop {
+        if at == file_size {
+            return Ok(Some(at - container.offset));
+        }
+        let Some(child) = header_at(window, at, file_size)? else {
+            return Ok(None);
+        };
+        if TOP_LEVEL_ONLY.contains(&&child.box_type) {
+            return Ok(Some(at - container.offset));
+        }
+        // 子 box 不能用 0 表示 "到文件结尾"
+        if child.declared == 0 || child.size < child.header_len || at + child.size > file_size {
+            return Ok(None);
+        }
+        at += child.size;
+    }
+}
+
+/// Size of a leaf box derived from the next known top-level box whose own end is also
+/// consistent
+fn next_box(window: &mut Window, b: &TopBox, file_size: u64) -> Result<Option<u64>, MediaError> {
+    let mut at = b.offset + b.header_len;
+    while at + 8 <= file_size {
+        let block = window.get(at, SEARCH_BLOCK + 7)?.to_vec();
+        let positions = block.len().saturating_sub(7);
+        for i in 0..positions {
+            let fourcc = &block[i + 4..i + 8];
+            if !fourcc[0].is_ascii_lowercase() || !TOP_LEVEL.iter().any(|t| t[..] == *fourcc) {
+                continue;
+            }
+            let candidate = at + i as u64;
+            let Some(next) = header_at(window, candidate, file_size)?
+                .filter(|next| next.size >= next.header_len)
+            else {
+                continue;
+            };
+            let next_end = candidate.saturating_add(next.size);
+            if next_end <= file_size && ends_on_box(window, next_end, file_size)? {
+                return Ok(Some(candidate - b.offset));
+            }
+        }
+        at += positions.max(1) as u64;
+    }
+    Ok(None)
+}
+
+/// Chunk layout of one track
+struct ChunkTable<'a> {
+    track_id: u32,
+    chunk_box: &'a Box,
+    offsets: &'a [u64],
+    large: bool,
+    /// Bytes from each chunk offset to the end of its last sample
+    lengths: Vec<u64>,
+    samples: &'a [Sample],
+    /// NAL length prefix size of H.264/H.265 tracks, whose samples can be checked
+    length_size: Option<usize>,
+}
+
+impl ChunkTable<'_> {
+    /// True when every chunk, moved by `delta`, lies inside one mdat and the samples that can
+    /// be checked are well formed
+    fn fits(&self, file: &mut File, mdats: &[(u64, u64)], delta: i64) -> Result<bool, MediaError> {
+        let inside = self
+            .offsets
+            .iter()
+            .zip(&self.lengths)
+            .all(|(&offset, &len)| {
+                let start = offset as i64 + delta;
+                start >= 0
+                    && mdats.iter().any(|&(mdat_start, mdat_end)| {
+                        start as u64 >= mdat_start && start as u64 + len <= mdat_end
+                    })
+            });
+        match self.length_size {
+            Some(length_size) if inside => framed(file, self.samples, delta, length_size),
+            _ => Ok(inside),
+        }
+    }
+}
+
+/// Finds chunk offsets that miss the media data and a single delta that puts them back, as
+/// left behind by an edit that moved the mdat without updating stco. Returns the
+/// (file position, bytes) writes that patch the entries.
+fn shift_chunk_offsets(
+    path: &Path,
+    changes: &mut Vec<RepairChange>,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<(u64, Vec<u8>)>, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(path)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let mdats: Vec<(u64, u64)> = boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "mdat")
+        .map(|b| (b.offset() + b.header_size(), b.offset() + b.size()))
+        .collect();
+    let tracks = Track::all(boxes);
+    let indexes = TrackSampleIndex::build_all(boxes)?;
+    let mut file = File::open(path)?;
+
+    let mut tables = Vec::new();
+    for (track, index) in tracks.iter().zip(&indexes) {
+        let Some(chunk_box) = track
+            .sample_table()
+            .and_then(|stbl| stbl.child("stco").or_else(|| stbl.child("co64")))
+        else {
+            continue;
+        };
+        let BoxData::ChunkOffset(stco) = chunk_box.data() else {
+            continue;
+        };
+        let offsets = stco.offsets();
+        let mut lengths = vec![0u64; offsets.len()];
+        for sample in &index.samples {
+            let Some(chunk) = (sample.chunk_index as usize)
+                .checked_sub(1)
+                .filter(|&c| c < offsets.len())
+            else {
+                continue;
+            };
+            let end = (sample.offset + sample.size as u64).saturating_sub(offsets[chunk]);
+            lengths[chunk] = lengths[chunk].max(end);
+        }
+        let config = track
+            .sample_entry()
+            .and_then(|entry| match entry.entry_type() {
+                "avc1" | "avc3" => entry.child_box("avcC").and_then(NalConfig::from_avcc),
+                "hvc1" | "hev1" => entry.child_box("hvcC").and_then(NalConfig::from_hvcc),
+                _ => None,
+            });
+        tables.push(ChunkTable {
+            track_id: track.track_id(),
+            chunk_box,
+            offsets,
+            large: stco.is_large(),
+            lengths,
+            samples: &index.samples,
+            length_size: config.map(|c| c.length_size),
+        });
+    }
+
+    let mut broken = Vec::new();
+    for table in &tables {
+        broken.push(!table.fits(&mut file, &mdats, 0)?);
+    }
+    if !broken.contains(&true) {
+        return Ok(Vec::new());
+    }
+
+    // 编辑工具移动了 mdat 却没改 stco 时, 最小的 chunk 偏移原本就是 mdat 数据的开头
+    let first_chunk = tables
+        .iter()
+        .filter_map(|t| t.offsets.iter().min())
+        .min()
+        .copied()
+        .unwrap_or(0);
+    let mut delta = None;
+    'candidates: for &(mdat_start, _) in &mdats {
+        let candidate = mdat_start as i64 - first_chunk as i64;
+        if candidate == 0 {
+            continue;
+        }
+        for (table, &broken) in tables.iter().zip(&broken) {
+            if broken && !table.fits(&mut file, &mdats, candidate)? {
+                continue 'candidates;
+            }
+        }
+        delta = Some(candidate);
+        break;
+    }
+    let Some(delta) = delta else {
+        for (table, &broken) in tables.iter().zip(&broken) {
+            if broken {
+                warnings.push(format!(
+                    "chunk offsets of track {} do not match the media data and no single shift fixes them",
+                    table.track_id
+                ));
+            }
+        }
+        return Ok(Vec::new());
+    };
+
+    let mut patches = Vec::new();
+    for (table, &broken) in tables.iter().zip(&broken) {
+        // 样本已验证无误的轨道保持不动, 其余轨道一起平移
+        let verified = !broken && table.length_size.is_some();
+        if verified || !table.fits(&mut file, &mdats, delta)? {
+            continue;
+        }
+        let entry_size = if table.large { 8 } else { 4 };
+        let entries = table.chunk_box.offset() + table.chunk_box.header_size() + 8;
+        for (i, &offset) in table.offsets.iter().enumerate() {
+            let value = (offset as i64 + delta) as u64;
+            let bytes = if table.large {
+                value.to_be_bytes().to_vec()
+            } else {
+                u32::try_from(value)
+                    .map_err(|_| {
+                        MediaError::UnsupportedFormat(format!(
+                            "shifted chunk offset {} of track {} does not fit in stco",
+                            value, table.track_id
+                        ))
+                    })?
+                    .to_be_bytes()
+                    .to_vec()
+            };
+            patches.push((entries + i as u64 * entry_size, bytes));
+        }
+        let outside = !table.offsets.iter().all(|&offset| {
+            mdats
+                .iter()
+                .any(|&(start, end)| offset >= start && offset < end)
+        });
+        changes.push(RepairChange {
+            rule: if outside {
+                "chunk-offset-outside-mdat"
+            } else {
+                "chunk-offset"
+            },
+            offset: table.chunk_box.offset(),
+            description: format!(
+                "shifted {} chunk offsets of track {} by {:+} bytes",
+                table.offsets.len(),
+                table.track_id,
+                delta
+            ),
+        });
+    }
+    Ok(patches)
+}
+
+/// True when the first samples, moved by `delta`, consist of whole length-prefixed NAL units
+fn framed(
+    file: &mut File,
+    samples: &[Sample],
+    delta: i64,
+    length_size: usize,
+) -> Result<bool, MediaError> {
+    for sample in samples.iter().take(FRAMING_CHECKS) {
+        let data = read_bytes(
+            file,
+            (sample.offset as i64 + delta) as u64,
+            sample.size as u64,
+        )?;
+        let mut at = 0;
+        while at < data.len() {
+            let Some(prefix) = data.get(at..at + length_size) else {
+                return Ok(false);
+            };
+            let len = prefix.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+            // NAL 头的 forbidden_zero_bit 必须为 0
+            if len == 0 || data.get(at + length_size).is_none_or(|&h| h & 0x80 != 0) {
+                return Ok(false);
+            }
+            at += length_size + len;
+        }
+        if at != data.len() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
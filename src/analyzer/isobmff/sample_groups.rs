@@ -0,0 +1,191 @@
+use serde::Serialize;
+
+use super::boxes::{
+    grouping_type_name, Box, BoxData, BoxInfo, SampleGroupDescriptionBox, SampleGroupEntry,
+    SampleToGroupBox,
+};
+use crate::analyzer::Property;
+
+/// Membership of one sample in one sample group
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SampleGroupMembership {
+    pub grouping_type: String,
+    pub group_description_index: u32,
+    pub entry: Option<SampleGroupEntry>,
+}
+
+impl std::fmt::Display for SampleGroupMembership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.entry {
+            Some(entry) => write!(f, "{}({})", self.grouping_type.trim_end(), entry),
+            None => write!(
+                f,
+                "{}(#{} missing)",
+                self.grouping_type.trim_end(),
+                self.group_description_index
+            ),
+        }
+    }
+}
+
+/// One sbgp/sgpd pair, matched by grouping type
+struct Grouping<'a> {
+    grouping_type: &'a str,
+    to_group: Option<&'a SampleToGroupBox>,
+    description: Option<&'a SampleGroupDescriptionBox>,
+}
+
+/// Joins the sbgp and sgpd boxes of a sample table (or track fragment)
+pub struct SampleGroups<'a> {
+    groupings: Vec<Grouping<'a>>,
+}
+
+impl<'a> SampleGroups<'a> {
+    /// Collects the sample groups declared directly inside `container` (stbl or traf)
+    pub fn from_container(container: &'a Box) -> Self {
+        let descriptions: Vec<&SampleGroupDescriptionBox> = container
+            .children()
+            .iter()
+            .filter_map(|child| match child.data() {
+                BoxData::SampleGroupDescription(sgpd) => Some(sgpd),
+                _ => None,
+            })
+            .collect();
+
+        let mut groupings: Vec<Grouping> = container
+            .children()
+            .iter()
+            .filter_map(|child| match child.data() {
+                BoxData::SampleToGroup(sbgp) => Some(Grouping {
+                    grouping_type: sbgp.grouping_type(),
+                    to_group: Some(sbgp),
+                    description: descriptions
+                        .iter()
+                        .find(|sgpd| sgpd.grouping_type() == sbgp.grouping_type())
+                        .copied(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        // sgpd version 2 can assign a default group without any sbgp
+        for sgpd in descriptions {
+            let mapped = groupings
+                .iter()
+                .any(|grouping| grouping.grouping_type == sgpd.grouping_type());
+            if !mapped && sgpd.default_sample_description_index().is_some() {
+                groupings.push(Grouping {
+                    grouping_type: sgpd.grouping_type(),
+                    to_group: None,
+                    description: Some(sgpd),
+                });
+            }
+        }
+
+        Self { groupings }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groupings.is_empty()
+    }
+
+    /// Expands the run-length tables into one membership list per sample (0-based)
+    pub fn memberships(&self, sample_count: u32) -> Vec<Vec<SampleGroupMembership>> {
+        let mut samples = vec![Vec::new(); sample_count as usize];
+
+        for grouping in &self.groupings {
+            let default_index = grouping
+                .description
+                .and_then(|sgpd| sgpd.default_sample_description_index())
+                .unwrap_or(0);
+
+            // 先按 sbgp 展开, 未被映射的样本使用默认分组
+            let mut indices = Vec::with_capacity(sample_count as usize);
+            if let Some(sbgp) = grouping.to_group {
+                for entry in sbgp.entries() {
+                    let remaining = sample_count as usize - indices.len();
+                    let run = (entry.sample_count() as usize).min(remaining);
+                    indices.extend(std::iter::repeat_n(entry.group_description_index(), run));
+                }
+            }
+            indices.resize(sample_count as usize, default_index);
+
+            for (sample, index) in indices.into_iter().enumerate() {
+                if index == 0 {
+                    continue;
+                }
+                // Indices above 0x10000 refer to the sgpd local to a track fragment
+                let local_index = if index > 0x10000 {
+                    index - 0x10000
+                } else {
+                    index
+                };
+                samples[sample].push(SampleGroupMembership {
+                    grouping_type: grouping.grouping_type.to_string(),
+                    group_description_index: index,
+                    entry: grouping
+                        .description
+                        .and_then(|sgpd| sgpd.entry(local_index))
+                        .cloned(),
+                });
+            }
+        }
+
+        samples
+    }
+
+    /// Lists runs of consecutive samples sharing the same memberships
+    pub fn fill_properties(&self, sample_count: u32, properties: &mut Vec<Property>) {
+        let grouping_types: Vec<String> = self
+            .groupings
+            .iter()
+            .map(|grouping| {
+                format!(
+                    "{} ({})",
+                    grouping.grouping_type,
+                    grouping_type_name(grouping.grouping_type)
+                )
+            })
+            .collect();
+        properties.push(Property::new(
+            "sample_groups",
+            self.groupings.len(),
+            Some(grouping_types.join(", ")),
+        ));
+
+        // 把相同分组的连续样本合并成区间, 样本编号从 1 开始
+        let memberships = self.memberships(sample_count);
+        let mut runs: Vec<(usize, usize, &Vec<SampleGroupMembership>)> = Vec::new();
+        for (sample, groups) in memberships.iter().enumerate() {
+            match runs.last_mut() {
+                Some((_, last, previous)) if *previous == groups => *last = sample,
+                _ => runs.push((sample, sample, groups)),
+            }
+        }
+
+        for (first, last, groups) in runs.iter().take(5) {
+            let readable = if groups.is_empty() {
+                "none".to_string()
+            } else {
+                groups
+                    .iter()
+                    .map(|group| group.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            properties.push(Property::new(
+                &format!("samples[{}-{}]", first + 1, last + 1),
+                &readable,
+                Some(&readable),
+            ));
+        }
+
+        if runs.len() > 5 {
+            properties.push(Property::new(
+                "...",
+                format!("{} more sample runs", runs.len() - 5),
+                None::<String>,
+            ));
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use serde::Serialize;
+
+use super::boxes::{
+    Box, BoxData, BoxInfo, TFHD_BASE_DATA_OFFSET, TFHD_DEFAULT_BASE_IS_MOOF,
+    TFHD_SAMPLE_DESCRIPTION_INDEX,
+};
+use super::sample_groups::{SampleGroupMembership, SampleGroups};
+use super::track::Track;
+use crate::error::MediaError;
+
+/// sample_is_non_sync_sample bit of the trun/tfhd/trex sample flags
+pub const SAMPLE_FLAG_NON_SYNC: u32 = 0x0001_0000;
+
+/// One media sample, resolved from the sample table boxes
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    /// 1-based sample number, as used by stss/sbgp
+    pub number: u32,
+    /// Absolute file offset of the first byte
+    pub offset: u64,
+    pub size: u32,
+    /// Decoding time in media timescale units
+    pub dts: u64,
+    /// Composition time in media timescale units (DTS + ctts offset)
+    pub cts: i64,
+    pub duration: u32,
+    pub is_sync: bool,
+    /// 1-based chunk number the sample is stored in
+    pub chunk_index: u32,
+    pub sample_description_index: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<SampleGroupMembership>,
+}
+
+/// Every sample of one track, in decoding order
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackSampleIndex {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub timescale: u32,
+    pub samples: Vec<Sample>,
+    /// Inconsistencies between the tables that forced the index to be truncated
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl TrackSampleIndex {
+    /// Builds the sample index of every track in the parsed box tree
+    pub fn build_all(boxes: &[Box]) -> Result<Vec<TrackSampleIndex>, MediaError> {
+        Track::all(boxes).iter().map(Self::build).collect()
+    }
+
+    /// Like `build_all`, followed by the samples of every movie fragment
+    pub fn build_all_with_fragments(boxes: &[Box]) -> Result<Vec<TrackSampleIndex>, MediaError> {
+        let mut indexes = Self::build_all(boxes)?;
+        let mvex = boxes
+            .iter()
+            .find(|b| b.box_type().as_str() == "moov")
+            .and_then(|moov| moov.child("mvex"));
+        Self::append_fragments(&mut indexes, mvex, boxes);
+        Ok(indexes)
+    }
+
+    /// Appends the samples described by the moof boxes among `boxes` (which may come from a
+    /// separate media segment) using the trex defaults of `mvex`; each trun counts as a chunk
+    pub fn append_fragments(indexes: &mut [TrackSampleIndex], mvex: Option<&Box>, boxes: &[Box]) {
+        for moof in boxes.iter().filter(|b| b.box_type().as_str() == "moof") {
+            // 未指定 base_data_offset 时, 第一个 traf 以 moof 起点为基准, 之后的 traf 接着上一个的数据末尾
+            let mut previous_end = moof.offset();
+            let trafs = moof
+                .children()
+                .iter()
+                .filter(|child| child.box_type().as_str() == "traf");
+            for traf in trafs {
+                let Some(BoxData::TrackFragmentHeader(tfhd)) = traf.child("tfhd").map(|b| b.data())
+                else {
+                    continue;
+                };
+                let track_id = tfhd.track_id();
+                let Some(index) = indexes.iter_mut().find(|index| index.track_id == track_id)
+                else {
+                    continue;
+                };
+                let trex = mvex.and_then(|mvex| {
+                    mvex.children().iter().find_map(|child| match child.data() {
+                        BoxData::TrackExtends(trex) if trex.track_id() == track_id => Some(trex),
+                        _ => None,
+                    })
+                });
+                let base = match tfhd.base_data_offset() {
+                    Some(offset) if tfhd.flags() & TFHD_BASE_DATA_OFFSET != 0 => offset,
+                    _ if tfhd.flags() & TFHD_DEFAULT_BASE_IS_MOOF != 0 => moof.offset(),
+                    _ => previous_end,
+                };
+                let description_index = tfhd
+                    .sample_description_index()
+                    .filter(|_| tfhd.flags() & TFHD_SAMPLE_DESCRIPTION_INDEX != 0)
+                    .or(trex.map(|trex| trex.default_sample_description_index()))
+                    .unwrap_or(1);
+                let default_duration = tfhd
+                    .default_sample_duration()
+                    .or(trex.map(|trex| trex.default_sample_duration()))
+                    .unwrap_or(0);
+                let default_size = tfhd
+                    .default_sample_size()
+                    .or(trex.map(|trex| trex.default_sample_size()))
+                    .unwrap_or(0);
+                let default_flags = tfhd
+                    .default_sample_flags()
+                    .or(trex.map(|trex| trex.default_sample_flags()))
+                    .unwrap_or(0);
+                let mut dts = match traf.child("tfdt").map(|b| b.data()) {
+                    Some(BoxData::TrackFragmentDecodeTime(tfdt)) => tfdt.base_media_decode_time(),
+                    _ => index
+                        .samples
+                        .last()
+                        .map_or(0, |last| last.dts + last.duration as u64),
+                };
+
+                let mut position = base;
+                for child in traf.children() {
+                    let BoxData::TrackRun(trun) = child.data() else {
+                        continue;
+                    };
+                    if let Some(data_offset) = trun.data_offset() {
+                        position = base.saturating_add_signed(data_offset as i64);
+                    }
+                    let chunk_index = index.samples.last().map_or(0, |last| last.chunk_index) + 1;
+                    for (i, entry) in trun.entries().iter().enumerate() {
+                        let flags = match (i, trun.first_sample_flags()) {
+                            (0, Some(flags)) => flags,
+                            _ => entry.flags.unwrap_or(default_flags),
+                        };
+                        let size = entry.size.unwrap_or(default_size);
+                        let duration = entry.duration.unwrap_or(default_duration);
+                        index.samples.push(Sample {
+                            number: index.samples.len() as u32 + 1,
+                            offset: position,
+                            size,
+                            dts,
+                            cts: dts as i64 + entry.composition_time_offset.unwrap_or(0),
+                            duration,
+                            is_sync: flags & SAMPLE_FLAG_NON_SYNC == 0,
+                            chunk_index,
+                            sample_description_index: description_index,
+                            groups: Vec::new(),
+                        });
+                        position = position.saturating_add(size as u64);
+                        dts += duration as u64;
+                    }
+                }
+                previous_end = position;
+            }
+        }
+    }
+
+    /// An index without samples for a track whose tables could not be joined, with the
+    /// reason as its warning
+    pub fn unavailable(track: &Track, error: &MediaError) -> TrackSampleIndex {
+        TrackSampleIndex {
+            track_id: track.track_id(),
+            handler_type: track.handler_type(),
+            timescale: track.timescale(),
+            samples: Vec::new(),
+            warnings: vec![format!("sample index unavailable: {}", error)],
+        }
+    }
+
+    /// Joins stts/ctts/stss/stsc/stsz/stco(co64) of one track
+    pub fn build(track: &Track) -> Result<TrackSampleIndex, MediaError> {
+        let track_id = track.track_id();
+        let stbl = track
+            .sample_table()
+            .ok_or_else(|| MediaError::Parse(format!("track {} has no stbl box", track_id)))?;
+
+        let missing =
+            |name: &str| MediaError::Parse(format!("track {} has no {} box", track_id, name));
+
+        let stsz = match stbl.child("stsz").map(|b| b.data()) {
+            Some(BoxData::SampleSize(b)) => b,
+            _ => return Err(missing("stsz")),
+        };
+        let stts = match stbl.child("stts").map(|b| b.data()) {
+            Some(BoxData::TimeToSample(b)) => b,
+            _ => return Err(missing("stts")),
+        };
+        let stsc = match stbl.child("stsc").map(|b| b.data()) {
+            Some(BoxData::SampleToChunk(b)) => b,
+            _ => return Err(missing("stsc")),
+        };
+        let chunk_offsets = match stbl
+            .child("stco")
+            .or_else(|| stbl.child("co64"))
+            .map(|b| b.data())
+        {
+            Some(BoxData::ChunkOffset(b)) => b.offsets(),
+            _ => return Err(missing("stco/co64")),
+        };
+        let ctts = match stbl.child("ctts").map(|b| b.data()) {
+            Some(BoxData::CompositionOffset(b)) => Some(b),
+            _ => None,
+        };
+        let stss = match stbl.child("stss").map(|b| b.data()) {
+            Some(BoxData::SyncSample(b)) => Some(b),
+            _ => None,
+        };
+
+        let sample_count = stsz.sample_count();
+        let mut warnings = Vec::new();
+
+        // stts: 解码时间戳和时长
+        let mut durations = Vec::with_capacity(sample_count as usize);
+        for entry in stts.entries() {
+            let remaining = sample_count as usize - durations.len();
+            let run = (entry.sample_count() as usize).min(remaining);
+            durations.extend(std::iter::repeat_n(entry.sample_delta(), run));
+        }
+        if durations.len() < sample_count as usize {
+            warnings.push(format!(
+                "stts covers {} samples but stsz declares {}",
+                durations.len(),
+                sample_count
+            ));
+        }
+
+        // ctts: 合成时间偏移, 缺失时为 0
+        let mut composition_offsets = Vec::new();
+        if let Some(ctts) = ctts {
+            for entry in ctts.entries() {
+                let remaining = sample_count as usize - composition_offsets.len();
+                let run = (entry.sample_count() as usize).min(remaining);
+                composition_offsets.extend(std::iter::repeat_n(entry.sample_offset(), run));
+            }
+            if composition_offsets.len() < sample_count as usize {
+                warnings.push(format!(
+                    "ctts covers {} samples but stsz declares {}",
+                    composition_offsets.len(),
+                    sample_count
+                ));
+            }
+        }
+
+        // stsc: 按 chunk 展开, 每个 entry 作用到下一个 entry 的 first_chunk 之前
+        let mut chunk_layout = Vec::with_capacity(chunk_offsets.len());
+        let entries = stsc.entries();
+        for (i, entry) in entries.iter().enumerate() {
+            let first = entry.first_chunk().max(1) as usize;
+            let last = entries
+                .get(i + 1)
+                .map(|next| next.first_chunk() as usize)
+                .unwrap_or(chunk_offsets.len() + 1);
+            for _ in first..last.max(first) {
+                chunk_layout.push((entry.samples_per_chunk(), entry.sample_description_index()));
+            }
+        }
+        chunk_layout.truncate(chunk_offsets.len());
+
+        let memberships = SampleGroups::from_container(stbl).memberships(sample_count);
+        let mut memberships = memberships.into_iter();
+
+        let mut samples = Vec::with_capacity(sample_count as usize);
+        let mut dts = 0u64;
+        'chunks: for (chunk, (&chunk_offset, &(per_chunk, description_index))) in
+            chunk_offsets.iter().zip(chunk_layout.iter()).enumerate()
+        {
+            let mut offset = chunk_offset;
+            for _ in 0..per_chunk {
+                let index = samples.len();
+                if index >= sample_count as usize || index >= durations.len() {
+                    break 'chunks;
+                }
+                let size = stsz.sample_size(index).unwrap_or(0);
+                let duration = durations[index];
+                let cts = dts as i64 + composition_offsets.get(index).copied().unwrap_or(0) as i64;
+                let number = index as u32 + 1;
+                let is_sync = stss
+                    .map(|stss| stss.sample_numbers().binary_search(&number).is_ok())
+                    .unwrap_or(true);
+
+                samples.push(Sample {
+                    number,
+                    offset,
+                    size,
+                    dts,
+                    cts,
+                    duration,
+                    is_sync,
+                    chunk_index: chunk as u32 + 1,
+                    sample_description_index: description_index,
+                    groups: memberships.next().unwrap_or_default(),
+                });
+
+                offset += size as u64;
+                dts += duration as u64;
+            }
+        }
+
+        if samples.len() < sample_count as usize && durations.len() >= sample_count as usize {
+            warnings.push(format!(
+                "stsc/stco only reach {} of {} samples",
+                samples.len(),
+                sample_count
+            ));
+        }
+
+        Ok(TrackSampleIndex {
+            track_id,
+            handler_type: track.handler_type(),
+            timescale: track.timescale(),
+            samples,
+            warnings,
+        })
+    }
+}
@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::faststart::read_bytes;
+use super::sample_index::TrackSampleIndex;
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::bitstream::AvcSps;
+use crate::analyzer::extract::NalConfig;
+use crate::analyzer::sei::{parse_sei, SeiMessage};
+use crate::error::MediaError;
+
+/// SEI messages of one video sample
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameSei {
+    pub sample: u32,
+    pub dts: u64,
+    pub cts: i64,
+    pub messages: Vec<SeiMessage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeiMessageCount {
+    pub name: &'static str,
+    pub count: u64,
+}
+
+/// Encoder and options from an x264/x265 user_data_unregistered string
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderSettings {
+    /// e.g. "x264 core 164 r3095 baee400"
+    pub encoder: String,
+    /// key=value pairs after "options:"; flags without a value have an empty value
+    pub options: Vec<(String, String)>,
+    pub first_sample: u32,
+}
+
+/// SEI messages of an H.264/H.265 track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackSei {
+    pub track_id: u32,
+    pub codec: String,
+    pub message_counts: Vec<SeiMessageCount>,
+    /// Samples carrying at least one SEI message
+    pub frames: Vec<FrameSei>,
+    /// Messages stored in avcC/hvcC rather than in the samples
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub declarative: Vec<SeiMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub encoder_settings: Vec<EncoderSettings>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeiReport {
+    pub tracks: Vec<TrackSei>,
+}
+
+/// Decodes the SEI NAL units of every H.264/H.265 sample: HDR static and dynamic metadata,
+/// timecodes, registered and unregistered user data
+pub fn analyze_sei(input: &Path) -> Result<SeiReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let mut source = File::open(input)?;
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        let Some(entry) = track.sample_entry() else {
+            continue;
+        };
+        let codec = entry.entry_type().to_string();
+        let config = match codec.as_str() {
+            "avc1" | "avc3" => entry.child_box("avcC").and_then(NalConfig::from_avcc),
+            "hvc1" | "hev1" => entry.child_box("hvcC").and_then(NalConfig::from_hvcc),
+            _ => continue,
+        };
+        let mut result = TrackSei {
+            track_id: track.track_id(),
+            codec,
+            message_counts: Vec::new(),
+            frames: Vec::new(),
+            declarative: Vec::new(),
+            encoder_settings: Vec::new(),
+            warnings: index.warnings.clone(),
+        };
+        let Some(config) = config else {
+            result.warnings.push(format!(
+                "{} sample entry has no usable configuration",
+                result.codec
+            ));
+            tracks.push(result);
+            continue;
+        };
+
+        let mut sei = SeiDecoder::new(config.hevc);
+        for nal in &config.parameter_sets {
+            result.declarative.extend(sei.nal_unit(nal));
+        }
+        for message in &result.declarative {
+            collect_encoder_settings(&mut result.encoder_settings, message, 0);
+        }
+        for sample in &index.samples {
+            let data = read_bytes(&mut source, sample.offset, sample.size as u64)?;
+            let mut messages = Vec::new();
+            let mut at = 0;
+            while let Some(prefix) = data.get(at..at + config.length_size) {
+                let len = prefix.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+                let Some(nal) = data.get(at + config.length_size..at + config.length_size + len)
+                else {
+                    result.warnings.push(format!(
+                        "sample {}: NAL unit of {} bytes at byte {} exceeds the sample",
+                        sample.number, len, at
+                    ));
+                    break;
+                };
+                at += config.length_size + len;
+                messages.extend(sei.nal_unit(nal));
+            }
+            if messages.is_empty() {
+                continue;
+            }
+            for message in &messages {
+                collect_encoder_settings(&mut result.encoder_settings, message, sample.number);
+                match result
+                    .message_counts
+                    .iter_mut()
+                    .find(|count| count.name == message.name())
+                {
+                    Some(count) => count.count += 1,
+                    None => result.message_counts.push(SeiMessageCount {
+                        name: message.name(),
+                        count: 1,
+                    }),
+                }
+            }
+            result.frames.push(FrameSei {
+                sample: sample.number,
+                dts: sample.dts,
+                cts: sample.cts,
+                messages,
+            });
+        }
+        tracks.push(result);
+    }
+    if tracks.is_empty() {
+        return Err(MediaError::UnsupportedFormat(
+            "the file has no H.264/H.265 track".to_string(),
+        ));
+    }
+    Ok(SeiReport { tracks })
+}
+
+/// Follows the SPS for the H.264 picture timing layout and decodes SEI NAL units
+struct SeiDecoder {
+    hevc: bool,
+    avc_sps: Option<AvcSps>,
+}
+
+impl SeiDecoder {
+    fn new(hevc: bool) -> Self {
+        Self {
+            hevc,
+            avc_sps: None,
+        }
+    }
+
+    fn nal_unit(&mut self, nal: &[u8]) -> Vec<SeiMessage> {
+        let Some(&header) = nal.first() else {
+            return Vec::new();
+        };
+        if self.hevc {
+            match (header >> 1) & 0x3f {
+                39 | 40 => parse_sei(nal, true, None),
+                _ => Vec::new(),
+            }
+        } else {
+            match header & 0x1f {
+                6 => parse_sei(
+                    nal,
+                    false,
+                    self.avc_sps
+                        .as_ref()
+                        .and_then(|sps| sps.pic_timing.as_ref()),
+                ),
+                7 => {
+                    // 通常只有一个 SPS, 以最近出现的为准
+                    if let Some(sps) = AvcSps::parse(nal) {
+                        self.avc_sps = Some(sps);
+                    }
+                    Vec::new()
+                }
+                _ => Vec::new(),
+            }
+        }
+    }
+}
+
+/// Records the settings string of an encoder the first time it is seen
+fn collect_encoder_settings(
+    settings: &mut Vec<EncoderSettings>,
+    message: &SeiMessage,
+    sample: u32,
+) {
+    let SeiMessage::UserDataUnregistered {
+        text: Some(text), ..
+    } = message
+    else {
+        return;
+    };
+    let Some(parsed) = parse_encoder_settings(text, sample) else {
+        return;
+    };
+    if !settings.iter().any(|s| s.encoder == parsed.encoder) {
+        settings.push(parsed);
+    }
+}
+
+/// Splits "x264 - core 164 r3095 baee400 - H.264/MPEG-4 AVC codec - ... - options: cabac=1
+/// ref=3 ..." into the encoder name and its options
+fn parse_encoder_settings(text: &str, sample: u32) -> Option<EncoderSettings> {
+    let (header, options) = text.split_once(" - options: ")?;
+    // 去掉编码器说明, 版权与网址
+    let encoder = header
+        .split(" - ")
+        .filter(|part| !part.contains("codec") && !part.contains("Copy") && !part.contains("http"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let options = options
+        .split_whitespace()
+        .map(|option| match option.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (option.to_string(), String::new()),
+        })
+        .collect();
+    Some(EncoderSettings {
+        encoder,
+        options,
+        first_sample: sample,
+    })
+}
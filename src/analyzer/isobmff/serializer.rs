@@ -0,0 +1,425 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo, RawBytes};
+use super::faststart::{same_file, verify};
+use super::types::BoxType;
+use super::IsobmffAnalyzer;
+use crate::error::MediaError;
+use crate::writer::Writer;
+
+/// Outcome of writing a parsed file back out unchanged
+#[derive(Debug, Clone, Serialize)]
+pub struct RewriteReport {
+    pub boxes_written: u64,
+    pub bytes_written: u64,
+    /// Bytes after the last top-level box the parser could read, copied as is
+    pub tail_bytes: u64,
+    /// The output matches the input byte for byte
+    pub identical: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_difference: Option<u64>,
+}
+
+/// Outcome of writing an edited box tree to a new file
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteStats {
+    pub bytes_written: u64,
+    pub moov_size: u64,
+    pub chunk_offsets_rewritten: u64,
+    /// stco boxes converted to co64 because an offset no longer fits in 32 bits
+    pub upgraded_to_co64: u32,
+    /// Samples whose bytes were compared between input and output
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Writes a box tree back to bytes. Decoded boxes are re-encoded from their fields, sizes are
+/// recomputed, and payloads that were left in the file (mdat, large generic boxes) are copied
+/// from `source`.
+pub struct BoxSerializer<'a> {
+    source: Option<&'a mut File>,
+}
+
+impl<'a> BoxSerializer<'a> {
+    pub fn new(source: Option<&'a mut File>) -> Self {
+        Self { source }
+    }
+
+    /// Serializes one box with its children into memory
+    pub fn encode(&mut self, b: &Box) -> Result<Vec<u8>, MediaError> {
+        let mut writer = Writer::new();
+        self.encode_into(b, &mut writer)?;
+        Ok(writer.into_inner())
+    }
+
+    /// Streams top-level boxes to `out`, copying large payloads straight from the source,
+    /// and returns the number of bytes written
+    pub fn write_boxes(&mut self, boxes: &[Box], out: &mut impl Write) -> Result<u64, MediaError> {
+        let mut written = 0u64;
+        for b in boxes {
+            match b.data().raw_payload() {
+                Some(RawBytes::InFile { offset, len }) if b.children().is_empty() => {
+                    let (offset, len) = (*offset, *len);
+                    let content = len + b.trailing().len();
+                    let header_len = header_size(b, content);
+                    out.write_all(&header_bytes(b, header_len, header_len + content))?;
+                    self.copy(offset, len, out)?;
+                    let mut trailing = Writer::new();
+                    self.write_raw(b.trailing(), &mut trailing)?;
+                    out.write_all(&trailing.into_inner())?;
+                    written += header_len + content;
+                }
+                _ => {
+                    let bytes = self.encode(b)?;
+                    out.write_all(&bytes)?;
+                    written += bytes.len() as u64;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// Size of the box once written
+    pub fn encoded_size(&mut self, b: &Box) -> Result<u64, MediaError> {
+        match b.data().raw_payload() {
+            Some(RawBytes::InFile { len, .. }) if b.children().is_empty() => {
+                let content = len + b.trailing().len();
+                Ok(header_size(b, content) + content)
+            }
+            _ => Ok(self.encode(b)?.len() as u64),
+        }
+    }
+
+    fn encode_into(&mut self, b: &Box, writer: &mut Writer) -> Result<(), MediaError> {
+        let start = writer.position();
+        // 先按原头部大小占位, 写完内容后回填 size
+        let mut header_len = b.header_size();
+        writer.write_bytes(&vec![0; header_len as usize]);
+
+        b.data().write(writer);
+        if let Some(payload) = b.data().raw_payload() {
+            self.write_raw(payload, writer)?;
+        }
+        for child in b.children() {
+            self.encode_into(child, writer)?;
+        }
+        if !b.trailing().is_empty() {
+            self.write_raw(b.trailing(), writer)?;
+        }
+
+        let content = writer.position() - start - header_len;
+        if header_size(b, content) > header_len {
+            // 超出 32 位, 改用 largesize
+            writer.insert(start + header_len, &[0; 8]);
+            header_len = 16;
+        }
+        writer.patch_bytes(start, &header_bytes(b, header_len, header_len + content));
+        Ok(())
+    }
+
+    fn write_raw(&mut self, raw: &RawBytes, writer: &mut Writer) -> Result<(), MediaError> {
+        match raw {
+            RawBytes::Loaded(bytes) => writer.write_bytes(bytes),
+            RawBytes::InFile { offset, len } => {
+                let mut bytes = Vec::with_capacity(*len as usize);
+                self.copy(*offset, *len, &mut bytes)?;
+                writer.write_bytes(&bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, offset: u64, len: u64, out: &mut impl Write) -> Result<(), MediaError> {
+        let source = self.source.as_mut().ok_or_else(|| {
+            MediaError::InvalidFormat(format!(
+                "{} bytes at offset {} are not loaded and no source file was given",
+                len, offset
+            ))
+        })?;
+        source.seek(SeekFrom::Start(offset))?;
+        let copied = io::copy(&mut source.take(len), out)?;
+        if copied != len {
+            return Err(MediaError::UnexpectedEof);
+        }
+        Ok(())
+    }
+}
+
+/// Keeps a 16-byte header where the box had one, and switches to it when the size needs 64 bits
+fn header_size(b: &Box, content: u64) -> u64 {
+    if b.header_size() == 16 || content + 8 > u32::MAX as u64 {
+        16
+    } else {
+        8
+    }
+}
+
+fn header_bytes(b: &Box, header_len: u64, size: u64) -> Vec<u8> {
+    let mut writer = Writer::new();
+    if header_len == 16 {
+        writer.write_u32(1);
+        writer.write_bytes(b.box_type().as_bytes());
+        writer.write_u64(size);
+    } else {
+        writer.write_u32(size as u32);
+        writer.write_bytes(b.box_type().as_bytes());
+    }
+    writer.into_inner()
+}
+
+/// Parses `input`, writes the box tree to `output` and compares the two files
+pub fn rewrite(input: &Path, output: &Path) -> Result<RewriteReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let file_size = analyzer.parser.file_size()?;
+    let boxes = analyzer.boxes()?;
+    let parsed_end = boxes
+        .iter()
+        .map(|b| b.offset() + b.size())
+        .max()
+        .unwrap_or(0);
+
+    let mut source = File::open(input)?;
+    let mut out = BufWriter::new(File::create(output)?);
+    let mut serializer = BoxSerializer::new(Some(&mut source));
+    let mut bytes_written = serializer.write_boxes(boxes, &mut out)?;
+    // 顶层无法解析的部分原样附加
+    let tail_bytes = file_size.saturating_sub(parsed_end);
+    if tail_bytes > 0 {
+        serializer.copy(parsed_end, tail_bytes, &mut out)?;
+        bytes_written += tail_bytes;
+    }
+    out.flush()?;
+    drop(out);
+
+    let first_difference = first_difference(input, output)?;
+    Ok(RewriteReport {
+        boxes_written: boxes.len() as u64,
+        bytes_written,
+        tail_bytes,
+        identical: first_difference.is_none(),
+        first_difference,
+    })
+}
+
+/// Offset of the first byte that differs between two files, or their common length when one
+/// is a prefix of the other
+fn first_difference(a: &Path, b: &Path) -> Result<Option<u64>, MediaError> {
+    let mut a = io::BufReader::new(File::open(a)?);
+    let mut b = io::BufReader::new(File::open(b)?);
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    let mut offset = 0u64;
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        let m = read_full(&mut b, &mut buf_b)?;
+        if let Some(i) = (0..n.min(m)).find(|&i| buf_a[i] != buf_b[i]) {
+            return Ok(Some(offset + i as u64));
+        }
+        if n != m {
+            return Ok(Some(offset + n.min(m) as u64));
+        }
+        if n == 0 {
+            return Ok(None);
+        }
+        offset += n as u64;
+    }
+}
+
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// New file positions of the original top-level boxes
+struct Layout {
+    /// (old offset, old end, new offset)
+    ranges: Vec<(u64, u64, u64)>,
+}
+
+impl Layout {
+    /// Boxes created by an edit have no original range and only shift what follows them
+    fn new(boxes: &[Box], sizes: &[u64], parsed_end: u64, file_size: u64) -> Layout {
+        let mut position = 0u64;
+        let mut ranges = Vec::with_capacity(boxes.len() + 1);
+        for (b, &size) in boxes.iter().zip(sizes) {
+            if b.size() > 0 {
+                ranges.push((b.offset(), b.offset() + b.size(), position));
+            }
+            position += size;
+        }
+        if parsed_end < file_size {
+            ranges.push((parsed_end, file_size, position));
+        }
+        Layout { ranges }
+    }
+
+    /// Maps an old file offset to the new file
+    fn map(&self, offset: u64) -> Option<u64> {
+        self.ranges
+            .iter()
+            .find(|&&(start, end, _)| offset >= start && offset < end)
+            .map(|&(start, _, new_start)| offset - start + new_start)
+    }
+}
+
+fn visit_mut(b: &mut Box, f: &mut impl FnMut(&mut Box)) {
+    f(b);
+    for child in b.children_mut() {
+        visit_mut(child, f);
+    }
+}
+
+/// Writes edited top-level boxes to `output`. Media data keeps its place relative to the box
+/// that holds it, so stco/co64 and explicit tfhd base offsets are moved along with it; the
+/// samples of the new file are then compared with the input.
+pub fn write_file(
+    input: &Path,
+    output: &Path,
+    boxes: &mut [Box],
+) -> Result<WriteStats, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+
+    let mut source = File::open(input)?;
+    let file_size = source.metadata()?.len();
+    // 末尾未解析部分以原始文件为准, 被删除的顶层 box 不能算作尾部
+    let mut original = IsobmffAnalyzer::new(input)?;
+    original.set_debug(false);
+    let parsed_end = original
+        .boxes()?
+        .iter()
+        .map(|b| b.offset() + b.size())
+        .max()
+        .unwrap_or(0);
+
+    // 原始偏移只收集一次, 每轮布局都从原始值重新映射
+    let mut chunk_offsets: Vec<Vec<u64>> = Vec::new();
+    let mut base_offsets: Vec<Option<u64>> = Vec::new();
+    let mut warnings = Vec::new();
+    for b in boxes.iter_mut() {
+        visit_mut(b, &mut |b| match b.data() {
+            BoxData::ChunkOffset(stco) => chunk_offsets.push(stco.offsets().to_vec()),
+            BoxData::TrackFragmentHeader(tfhd) => base_offsets.push(tfhd.base_data_offset()),
+            _ if b.box_type().as_str() == "saio" && warnings.is_empty() => {
+                warnings.push("saio offsets are absolute and were not rewritten".to_string());
+            }
+            _ => {}
+        });
+    }
+
+    let mut serializer = BoxSerializer::new(Some(&mut source));
+    let mut sizes: Vec<u64> = Vec::new();
+    let mut upgraded_to_co64 = 0u32;
+    // co64 的升级会改变 moov 大小, 从而改变偏移, 迭代直到稳定
+    loop {
+        let new_sizes = boxes
+            .iter()
+            .map(|b| serializer.encoded_size(b))
+            .collect::<Result<Vec<_>, _>>()?;
+        if new_sizes == sizes {
+            break;
+        }
+        sizes = new_sizes;
+        let layout = Layout::new(boxes, &sizes, parsed_end, file_size);
+
+        let mut unmapped = None;
+        let mut map = |offset: u64| {
+            layout.map(offset).unwrap_or_else(|| {
+                unmapped = Some(offset);
+                offset
+            })
+        };
+        let (mut next_chunk, mut next_base) = (0, 0);
+        for b in boxes.iter_mut() {
+            visit_mut(b, &mut |b| {
+                let mut upgrade = false;
+                match b.data_mut() {
+                    BoxData::ChunkOffset(stco) => {
+                        let mapped: Vec<u64> =
+                            chunk_offsets[next_chunk].iter().map(|&o| map(o)).collect();
+                        next_chunk += 1;
+                        if !stco.is_large() && mapped.iter().any(|&o| o > u32::MAX as u64) {
+                            stco.set_large(true);
+                            upgrade = true;
+                        }
+                        stco.set_offsets(mapped);
+                    }
+                    BoxData::TrackFragmentHeader(tfhd) => {
+                        if let Some(offset) = base_offsets[next_base] {
+                            tfhd.set_base_data_offset(map(offset));
+                        }
+                        next_base += 1;
+                    }
+                    _ => {}
+                }
+                if upgrade {
+                    b.set_box_type(BoxType::new(*b"co64"));
+                    upgraded_to_co64 += 1;
+                }
+            });
+        }
+        if let Some(offset) = unmapped {
+            return Err(MediaError::InvalidFormat(format!(
+                "offset {} does not point into a box that is kept",
+                offset
+            )));
+        }
+    }
+
+    let mut out = BufWriter::new(File::create(output)?);
+    let mut bytes_written = serializer.write_boxes(boxes, &mut out)?;
+    // 顶层无法解析的部分原样附加
+    if parsed_end < file_size {
+        serializer.copy(parsed_end, file_size - parsed_end, &mut out)?;
+        bytes_written += file_size - parsed_end;
+    }
+    out.flush()?;
+    drop(out);
+
+    let verified_samples = match verify(&mut original, input, output) {
+        Ok(count) => count,
+        Err(e) => {
+            std::fs::remove_file(output).ok();
+            return Err(e);
+        }
+    };
+
+    let moov_size = boxes
+        .iter()
+        .zip(&sizes)
+        .find(|(b, _)| b.box_type().as_str() == "moov")
+        .map(|(_, &size)| size)
+        .unwrap_or(0);
+    Ok(WriteStats {
+        bytes_written,
+        moov_size,
+        chunk_offsets_rewritten: chunk_offsets
+            .iter()
+            .map(|offsets| offsets.len() as u64)
+            .sum(),
+        upgraded_to_co64,
+        verified_samples,
+        warnings,
+    })
+}
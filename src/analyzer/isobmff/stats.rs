@@ -0,0 +1,278 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::nal::{analyze_nal_units, TrackNals};
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::track::Track;
+use super::IsobmffAnalyzer;
+use crate::analyzer::bitstream::SliceType;
+use crate::error::MediaError;
+
+/// Bins of every size histogram
+const HISTOGRAM_BINS: u32 = 24;
+
+/// Distribution of sample sizes, in bytes; percentiles are nearest-rank
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SizeStats {
+    pub count: u32,
+    pub total: u64,
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    pub p50: u32,
+    pub p90: u32,
+    pub p95: u32,
+    pub p99: u32,
+}
+
+/// Size range `[start, end)` of one histogram bar; the last bin includes `end`
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistogramBin {
+    pub start: u32,
+    pub end: u32,
+    pub count: u32,
+}
+
+/// Sizes of the frames of one type
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameTypeStats {
+    /// I, P or B; SI and SP slices count as I and P
+    pub frame_type: char,
+    pub sizes: SizeStats,
+    /// Frames of this type in each bin of the track histogram
+    pub histogram: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DurationCount {
+    pub duration: u32,
+    pub count: u32,
+}
+
+/// Sample durations in media timescale units
+#[derive(Debug, Clone, Serialize)]
+pub struct DurationStats {
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    /// Every distinct duration, most frequent first
+    pub distinct: Vec<DurationCount>,
+    /// Runs of equal durations, i.e. the entries of a minimal stts
+    pub runs: u32,
+    pub longest_run: u32,
+    /// All durations but the last one are equal, or differ by one tick of rounding
+    pub regular: bool,
+}
+
+/// Size, frame type and duration statistics of one track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackStats {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub codec: String,
+    pub timescale: u32,
+    pub sizes: SizeStats,
+    pub size_histogram: Vec<HistogramBin>,
+    /// H.264/H.265 tracks only
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub frame_types: Vec<FrameTypeStats>,
+    pub durations: DurationStats,
+    /// Video tracks: timescale over the mean sample duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<f64>,
+    pub variable_frame_rate: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsReport {
+    pub tracks: Vec<TrackStats>,
+}
+
+/// Sample size distribution, I/P/B frame sizes, and duration regularity of every track;
+/// frame types come from the slice headers of the H.264/H.265 tracks
+pub fn analyze_frame_stats(input: &Path) -> Result<StatsReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    let indexes = TrackSampleIndex::build_all_with_fragments(boxes)?;
+    let nal_tracks = match analyze_nal_units(input) {
+        Ok(report) => report.tracks,
+        Err(MediaError::UnsupportedFormat(_)) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut tracks = Vec::new();
+    for (track, index) in Track::all(boxes).iter().zip(&indexes) {
+        if index.samples.is_empty() {
+            continue;
+        }
+        let handler_type = track.handler_type();
+        let nals = nal_tracks.iter().find(|n| n.track_id == track.track_id());
+        let sizes: Vec<u32> = index.samples.iter().map(|s| s.size).collect();
+        let (min, max) = (
+            sizes.iter().copied().min().unwrap_or(0),
+            sizes.iter().copied().max().unwrap_or(0),
+        );
+        let bin_width = (max - min) / HISTOGRAM_BINS + 1;
+        let bin = |size: u32| ((size - min) / bin_width) as usize;
+        let bins = bin(max) + 1;
+
+        let mut size_histogram: Vec<HistogramBin> = (0..bins as u32)
+            .map(|i| HistogramBin {
+                start: min + i * bin_width,
+                end: (min + (i + 1) * bin_width).min(max),
+                count: 0,
+            })
+            .collect();
+        for &size in &sizes {
+            size_histogram[bin(size)].count += 1;
+        }
+        let frame_types = nals.map_or_else(Vec::new, |nals| frame_type_stats(nals, bin, bins));
+
+        let durations = duration_stats(&index.samples);
+        let video = handler_type == "vide";
+        let frame_rate =
+            (video && durations.mean > 0.0).then(|| index.timescale as f64 / durations.mean);
+        let mut warnings = index.warnings.clone();
+        if let Some(nals) = nals {
+            warnings.extend(nals.warnings.iter().cloned());
+        }
+        if video && !durations.regular {
+            warnings.push(format!(
+                "variable frame rate: {} distinct sample durations in {} stts runs",
+                durations.distinct.len(),
+                durations.runs
+            ));
+        }
+
+        tracks.push(TrackStats {
+            track_id: track.track_id(),
+            codec: track
+                .sample_entry()
+                .map_or(String::new(), |e| e.entry_type().to_string()),
+            handler_type,
+            timescale: index.timescale,
+            sizes: size_stats(sizes),
+            size_histogram,
+            frame_types,
+            frame_rate,
+            variable_frame_rate: video && !durations.regular,
+            durations,
+            warnings,
+        });
+    }
+    if tracks.is_empty() {
+        return Err(MediaError::UnsupportedFormat(
+            "the file has no samples".to_string(),
+        ));
+    }
+    Ok(StatsReport { tracks })
+}
+
+fn size_stats(mut sizes: Vec<u32>) -> SizeStats {
+    sizes.sort_unstable();
+    let count = sizes.len();
+    let total: u64 = sizes.iter().map(|&s| s as u64).sum();
+    let percentile = |p: usize| {
+        if count == 0 {
+            0
+        } else {
+            sizes[(count * p).div_ceil(100).clamp(1, count) - 1]
+        }
+    };
+    SizeStats {
+        count: count as u32,
+        total,
+        min: sizes.first().copied().unwrap_or(0),
+        max: sizes.last().copied().unwrap_or(0),
+        mean: if count == 0 {
+            0.0
+        } else {
+            total as f64 / count as f64
+        },
+        p50: percentile(50),
+        p90: percentile(90),
+        p95: percentile(95),
+        p99: percentile(99),
+    }
+}
+
+fn frame_type_stats(
+    nals: &TrackNals,
+    bin: impl Fn(u32) -> usize,
+    bins: usize,
+) -> Vec<FrameTypeStats> {
+    let mut result = Vec::new();
+    for (frame_type, wanted) in [
+        ('I', &[SliceType::I, SliceType::SI][..]),
+        ('P', &[SliceType::P, SliceType::SP][..]),
+        ('B', &[SliceType::B][..]),
+    ] {
+        let sizes: Vec<u32> = nals
+            .frames
+            .iter()
+            .filter(|f| f.frame_type.is_some_and(|t| wanted.contains(&t)))
+            .map(|f| f.size)
+            .collect();
+        if sizes.is_empty() {
+            continue;
+        }
+        let mut histogram = vec![0; bins];
+        for &size in &sizes {
+            histogram[bin(size)] += 1;
+        }
+        result.push(FrameTypeStats {
+            frame_type,
+            sizes: size_stats(sizes),
+            histogram,
+        });
+    }
+    result
+}
+
+fn duration_stats(samples: &[Sample]) -> DurationStats {
+    let mut distinct: Vec<DurationCount> = Vec::new();
+    let mut runs = 0;
+    let mut longest_run = 0;
+    let mut run = 0;
+    for (i, sample) in samples.iter().enumerate() {
+        match distinct.iter_mut().find(|d| d.duration == sample.duration) {
+            Some(d) => d.count += 1,
+            None => distinct.push(DurationCount {
+                duration: sample.duration,
+                count: 1,
+            }),
+        }
+        if i > 0 && samples[i - 1].duration == sample.duration {
+            run += 1;
+        } else {
+            runs += 1;
+            run = 1;
+        }
+        longest_run = longest_run.max(run);
+    }
+    distinct.sort_by(|a, b| b.count.cmp(&a.count).then(a.duration.cmp(&b.duration)));
+
+    // 最后一个样本的时长常被截断; 相差 1 的时长来自 1001 帧率的取整, 不算可变帧率
+    let body = &samples[..samples.len().saturating_sub(1)];
+    let regular = match (
+        body.iter().map(|s| s.duration).min(),
+        body.iter().map(|s| s.duration).max(),
+    ) {
+        (Some(min), Some(max)) => max - min <= 1,
+        _ => true,
+    };
+    let total: u64 = samples.iter().map(|s| s.duration as u64).sum();
+    DurationStats {
+        min: samples.iter().map(|s| s.duration).min().unwrap_or(0),
+        max: samples.iter().map(|s| s.duration).max().unwrap_or(0),
+        mean: total as f64 / samples.len().max(1) as f64,
+        distinct,
+        runs,
+        longest_run,
+        regular,
+    }
+}
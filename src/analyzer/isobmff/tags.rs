@@ -0,0 +1,349 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::boxes::{
+    Box, BoxData, BoxInfo, HandlerBox, ItemListBox, MetaBox, MetadataItem, MetadataValue,
+    UserDataBox,
+};
+use super::serializer::{self, WriteStats};
+use super::track::movie_item_list;
+use super::types::BoxType;
+use super::IsobmffAnalyzer;
+use crate::error::MediaError;
+
+/// Friendly names accepted for the common iTunes item keys
+pub const TAG_ALIASES: &[(&str, &str)] = &[
+    ("title", "©nam"),
+    ("artist", "©ART"),
+    ("album", "©alb"),
+    ("comment", "©cmt"),
+    ("date", "©day"),
+    ("genre", "©gen"),
+    ("cover", "covr"),
+];
+
+/// Mean used for custom keys that are not a 4CC
+const FREEFORM_MEAN: &str = "com.apple.iTunes";
+
+/// XMP packets stored in a `uuid` box
+const XMP_UUID: [u8; 16] = [
+    0xbe, 0x7a, 0xcf, 0xcb, 0x97, 0xa9, 0x42, 0xe8, 0x9c, 0x71, 0x99, 0x94, 0x91, 0xe3, 0xaf, 0xac,
+];
+
+/// One change to the movie's metadata
+#[derive(Debug, Clone)]
+pub enum TagEdit {
+    /// Sets a text tag; the key is an alias, a 4CC or a custom (freeform) name
+    Set {
+        key: String,
+        value: String,
+    },
+    /// Sets the cover art from JPEG or PNG bytes
+    Cover(Vec<u8>),
+    Delete(String),
+    /// Removes every udta/meta box and XMP packet, before the other edits are applied
+    StripAll,
+}
+
+/// A tag of the movie's ilst
+#[derive(Debug, Clone, Serialize)]
+pub struct Tag {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub value: String,
+    pub data_type: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagEditReport {
+    /// Tags of the written file
+    pub tags: Vec<Tag>,
+    /// Boxes dropped by strip mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_boxes: Vec<String>,
+    #[serde(flatten)]
+    pub write: WriteStats,
+}
+
+/// Lists the iTunes-style tags of the movie (moov/udta/meta/ilst)
+pub fn list_tags(path: &Path) -> Result<Vec<Tag>, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(path)?;
+    analyzer.set_debug(false);
+    Ok(movie_item_list(analyzer.boxes()?)
+        .map(|ilst| ilst.items().iter().map(tag).collect())
+        .unwrap_or_default())
+}
+
+/// Applies the edits and writes the result to `output`, resizing moov and moving every chunk
+/// offset along with the media data
+pub fn edit_tags(
+    input: &Path,
+    output: &Path,
+    edits: &[TagEdit],
+) -> Result<TagEditReport, MediaError> {
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    analyzer.boxes()?;
+    let mut boxes = analyzer.boxes.take().unwrap_or_default();
+
+    let mut removed_boxes = Vec::new();
+    if edits.iter().any(|edit| matches!(edit, TagEdit::StripAll)) {
+        strip_user_metadata(&mut boxes, &mut removed_boxes);
+    }
+
+    let mut warnings = Vec::new();
+    {
+        let moov = boxes
+            .iter_mut()
+            .find(|b| b.box_type().as_str() == "moov")
+            .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+        for edit in edits {
+            match edit {
+                TagEdit::Set { key, value } => {
+                    let (item_type, mean, name) = item_key(key);
+                    set_item(
+                        moov,
+                        MetadataItem::new(
+                            item_type,
+                            mean,
+                            name,
+                            vec![MetadataValue {
+                                version: 0,
+                                data_type: 1,
+                                locale: 0,
+                                data: value.as_bytes().to_vec(),
+                            }],
+                        ),
+                        &mut warnings,
+                    )?;
+                }
+                TagEdit::Cover(image) => {
+                    let data_type = image_type(image).ok_or_else(|| {
+                        MediaError::UnsupportedFormat(
+                            "cover art must be a JPEG or PNG image".to_string(),
+                        )
+                    })?;
+                    set_item(
+                        moov,
+                        MetadataItem::new(
+                            *b"covr",
+                            None,
+                            None,
+                            vec![MetadataValue {
+                                version: 0,
+                                data_type,
+                                locale: 0,
+                                data: image.clone(),
+                            }],
+                        ),
+                        &mut warnings,
+                    )?;
+                }
+                TagEdit::Delete(key) => {
+                    let (item_type, mean, name) = item_key(key);
+                    let key = MetadataItem::new(item_type, mean, name, Vec::new()).key();
+                    let removed = existing_item_list_mut(moov)
+                        .map(|ilst| ilst.remove_item(&key))
+                        .unwrap_or(false);
+                    if !removed {
+                        warnings.push(format!("tag {} not found", key));
+                    }
+                }
+                TagEdit::StripAll => {}
+            }
+        }
+    }
+
+    let mut write = serializer::write_file(input, output, &mut boxes)?;
+    write.warnings.extend(warnings);
+    let tags = movie_item_list(&boxes)
+        .map(|ilst| ilst.items().iter().map(tag).collect())
+        .unwrap_or_default();
+    Ok(TagEditReport {
+        tags,
+        removed_boxes,
+        write,
+    })
+}
+
+/// Resolves a user supplied key to (item type, mean, name)
+fn item_key(key: &str) -> ([u8; 4], Option<String>, Option<String>) {
+    let key = TAG_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(key))
+        .map(|(_, four_cc)| *four_cc)
+        .unwrap_or(key);
+    if let Some(rest) = key.strip_prefix("----:") {
+        let (mean, name) = rest.split_once(':').unwrap_or((FREEFORM_MEAN, rest));
+        return (*b"----", Some(mean.to_string()), Some(name.to_string()));
+    }
+    // 4CC 中的 © 按 Latin-1 存储为单字节
+    let latin1: Vec<u8> = key
+        .chars()
+        .filter_map(|c| u8::try_from(c as u32).ok())
+        .collect();
+    if key.chars().count() == 4 && latin1.len() == 4 {
+        return ([latin1[0], latin1[1], latin1[2], latin1[3]], None, None);
+    }
+    (
+        *b"----",
+        Some(FREEFORM_MEAN.to_string()),
+        Some(key.to_string()),
+    )
+}
+
+fn tag(item: &MetadataItem) -> Tag {
+    let key = item.key();
+    Tag {
+        alias: TAG_ALIASES
+            .iter()
+            .find(|(_, four_cc)| *four_cc == key)
+            .map(|(alias, _)| alias.to_string()),
+        value: item
+            .values()
+            .first()
+            .map(|value| value.to_string())
+            .unwrap_or_default(),
+        data_type: item.values().first().map_or(0, |value| value.data_type),
+        key,
+    }
+}
+
+/// Well-known data type of a cover image: 13 = JPEG, 14 = PNG
+fn image_type(image: &[u8]) -> Option<u32> {
+    if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(13)
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(14)
+    } else {
+        None
+    }
+}
+
+pub(super) fn existing_item_list_mut(moov: &mut Box) -> Option<&mut ItemListBox> {
+    let ilst = moov
+        .child_mut("udta")?
+        .child_mut("meta")?
+        .child_mut("ilst")?;
+    match ilst.data_mut() {
+        BoxData::ItemList(ilst) => Some(ilst),
+        _ => None,
+    }
+}
+
+/// Sets an item of the movie's item list. Refuses when the ilst has bytes after an invalid
+/// item: a new item written behind them would be unreachable
+fn set_item(
+    moov: &mut Box,
+    item: MetadataItem,
+    warnings: &mut Vec<String>,
+) -> Result<(), MediaError> {
+    let ilst = item_list_mut(moov);
+    if !ilst.remainder().is_empty() {
+        return Err(MediaError::InvalidFormat(format!(
+            "ilst has an invalid item followed by {} unparsed bytes; refusing to edit it",
+            ilst.remainder().len()
+        )));
+    }
+    let key = item.key();
+    if ilst
+        .item(&key)
+        .is_some_and(|existing| existing.has_unparsed_children())
+    {
+        warnings.push(format!(
+            "tag {} had child boxes other than data; they were replaced with the new value",
+            key
+        ));
+    }
+    ilst.set_item(item);
+    Ok(())
+}
+
+/// The movie's item list, creating moov/udta/meta/ilst as needed
+fn item_list_mut(moov: &mut Box) -> &mut ItemListBox {
+    if moov.child("udta").is_none() {
+        moov.add_child(new_box(*b"udta", BoxData::UserData(UserDataBox::new())));
+    }
+    let udta = moov.child_mut("udta").expect("udta was just added");
+    if udta.child("meta").is_none() {
+        // iTunes 的写法: 带 mdir 处理器的 ISO meta
+        let mut meta = new_box(*b"meta", BoxData::Meta(MetaBox::new(Some((0, 0)))));
+        meta.add_child(new_box(
+            *b"hdlr",
+            BoxData::Handler(HandlerBox::new(
+                0,
+                0,
+                0,
+                "mdir".to_string(),
+                [u32::from_be_bytes(*b"appl"), 0, 0],
+                vec![0],
+            )),
+        ));
+        udta.add_child(meta);
+    }
+    let meta = udta.child_mut("meta").expect("meta was just added");
+    if meta.child("ilst").is_none() {
+        meta.add_child(new_box(
+            *b"ilst",
+            BoxData::ItemList(ItemListBox::new(Vec::new())),
+        ));
+    }
+    match meta
+        .child_mut("ilst")
+        .expect("ilst was just added")
+        .data_mut()
+    {
+        BoxData::ItemList(ilst) => ilst,
+        _ => unreachable!("ilst is always parsed as an item list"),
+    }
+}
+
+/// A box created by an edit: it has no position in the source file
+fn new_box(box_type: [u8; 4], data: BoxData) -> Box {
+    Box::new(BoxType::new(box_type), 0, 0, 8, data)
+}
+
+/// Drops user data, movie/track meta boxes and XMP packets, recording what was removed
+fn strip_user_metadata(boxes: &mut Vec<Box>, removed: &mut Vec<String>) {
+    boxes.retain(|b| {
+        let xmp = is_xmp(b);
+        if xmp {
+            removed.push("uuid (XMP)".to_string());
+        }
+        !xmp
+    });
+    for moov in boxes.iter_mut().filter(|b| b.box_type().as_str() == "moov") {
+        strip_children(moov, "moov", removed);
+        let mut track = 0;
+        for trak in moov
+            .children_mut()
+            .iter_mut()
+            .filter(|b| b.box_type().as_str() == "trak")
+        {
+            track += 1;
+            strip_children(trak, &format!("moov/trak[{}]", track), removed);
+        }
+    }
+}
+
+fn strip_children(parent: &mut Box, path: &str, removed: &mut Vec<String>) {
+    parent.children_mut().retain(|child| {
+        let drop = matches!(child.box_type().as_str(), "udta" | "meta") || is_xmp(child);
+        if drop {
+            removed.push(format!("{}/{}", path, child.box_type()));
+        }
+        !drop
+    });
+}
+
+fn is_xmp(b: &Box) -> bool {
+    if b.box_type().as_str() != "uuid" {
+        return false;
+    }
+    match b.data().raw_payload() {
+        Some(super::boxes::RawBytes::Loaded(payload)) => payload.starts_with(&XMP_UUID),
+        _ => false,
+    }
+}
@@ -0,0 +1,259 @@
+use serde::Serialize;
+
+use super::sample_index::TrackSampleIndex;
+use super::track::Track;
+
+/// How an edit list entry maps the media onto the presentation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditKind {
+    /// media_time == -1: nothing is presented (a delay)
+    Empty,
+    /// Media is played from media_time at media_rate
+    Normal,
+    /// media_rate == 0: the frame at media_time is held for the whole segment
+    Dwell,
+}
+
+/// One elst entry resolved to seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct EditSegment {
+    pub kind: EditKind,
+    /// Start on the movie (presentation) timeline, in seconds
+    pub presentation_start: f64,
+    /// Segment duration on the movie timeline, in seconds
+    pub duration: f64,
+    /// First media time shown by this segment, in seconds
+    pub media_start: Option<f64>,
+    pub media_rate: f64,
+}
+
+/// Inclusive range of 1-based sample numbers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SampleRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl std::fmt::Display for SampleRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}", self.first)
+        } else {
+            write!(f, "{}-{}", self.first, self.last)
+        }
+    }
+}
+
+/// Collapses ascending sample numbers into ranges
+pub fn collapse_ranges(numbers: impl IntoIterator<Item = u32>) -> Vec<SampleRange> {
+    let mut ranges: Vec<SampleRange> = Vec::new();
+    for number in numbers {
+        match ranges.last_mut() {
+            Some(range) if range.last + 1 == number => range.last = number,
+            _ => ranges.push(SampleRange {
+                first: number,
+                last: number,
+            }),
+        }
+    }
+    ranges
+}
+
+/// Result of applying a track's edit list to its media timeline
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackTimeline {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub movie_timescale: u32,
+    pub media_timescale: u32,
+    pub has_edit_list: bool,
+    pub segments: Vec<EditSegment>,
+    /// Movie time at which the first media of the track is shown (leading empty edits)
+    pub presentation_start: f64,
+    /// Media time shown at `presentation_start`, in seconds
+    pub media_start: f64,
+    /// Total duration of all empty edits, in seconds
+    pub empty_duration: f64,
+    /// Duration of the track on the movie timeline, in seconds
+    pub presentation_duration: f64,
+    /// End of the media on its own timeline (latest CTS + duration), in seconds; not a
+    /// duration when the first CTS is not zero
+    pub media_end: f64,
+    /// Samples whose composition interval is not shown by any edit
+    pub samples_outside: Vec<SampleRange>,
+    pub samples_outside_count: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl TrackTimeline {
+    /// Applies the edit list of `track`; `index` enables the per-sample check
+    pub fn build(
+        track: &Track,
+        index: Option<&TrackSampleIndex>,
+        movie_timescale: u32,
+    ) -> TrackTimeline {
+        let media_timescale = track.timescale();
+        let mut warnings = Vec::new();
+
+        // 媒体时长优先使用样本表 (最大 CTS + duration), 否则回退到 mdhd
+        let media_end = index
+            .and_then(|index| {
+                index
+                    .samples
+                    .iter()
+                    .map(|s| s.cts + s.duration as i64)
+                    .max()
+            })
+            .unwrap_or(track.media_duration() as i64);
+
+        let movie_seconds = |value: i128| -> f64 {
+            if movie_timescale == 0 {
+                0.0
+            } else {
+                value as f64 / movie_timescale as f64
+            }
+        };
+        let media_seconds = |value: i128| -> f64 {
+            if media_timescale == 0 {
+                0.0
+            } else {
+                value as f64 / media_timescale as f64
+            }
+        };
+
+        if media_timescale == 0 {
+            warnings.push("mdhd timescale is 0".to_string());
+        }
+
+        let edits = track.edit_list().map(|elst| elst.entries()).unwrap_or(&[]);
+        if track.edit_list().is_some() && movie_timescale == 0 {
+            warnings.push("mvhd timescale is 0, edit durations cannot be converted".to_string());
+        }
+
+        // Presented media ranges [start, end) in media timescale units
+        let mut presented: Vec<(i128, i128)> = Vec::new();
+        let mut segments = Vec::with_capacity(edits.len());
+        let mut position = 0i128; // movie timescale units
+
+        for (i, edit) in edits.iter().enumerate() {
+            let rate = edit.media_rate as f64 + edit.media_rate_fraction as f64 / 65536.0;
+            let kind = if edit.media_time == -1 {
+                EditKind::Empty
+            } else if edit.media_rate == 0 && edit.media_rate_fraction == 0 {
+                EditKind::Dwell
+            } else {
+                EditKind::Normal
+            };
+
+            if edit.media_time < -1 {
+                warnings.push(format!(
+                    "edit[{}] has invalid media_time {}",
+                    i, edit.media_time
+                ));
+            }
+            if kind == EditKind::Normal && rate != 1.0 {
+                warnings.push(format!("edit[{}] uses media_rate {}", i, rate));
+            }
+            if kind != EditKind::Empty && edit.media_time as i128 >= media_end as i128 {
+                warnings.push(format!(
+                    "edit[{}] starts at media time {} beyond the end of media ({})",
+                    i, edit.media_time, media_end
+                ));
+            }
+
+            // segment_duration 0 means "until the end of the media" (fragmented files)
+            let segment_duration =
+                if edit.segment_duration == 0 && kind == EditKind::Normal && media_timescale != 0 {
+                    let remaining = (media_end as i128 - edit.media_time as i128).max(0);
+                    (remaining as f64 * movie_timescale as f64 / media_timescale as f64 / rate)
+                        as i128
+                } else {
+                    edit.segment_duration as i128
+                };
+
+            match kind {
+                EditKind::Normal if movie_timescale != 0 => {
+                    let media_length = (segment_duration as f64 * media_timescale as f64
+                        / movie_timescale as f64
+                        * rate) as i128;
+                    let start = edit.media_time as i128;
+                    presented.push((start, start + media_length));
+                }
+                EditKind::Dwell => {
+                    let start = edit.media_time as i128;
+                    presented.push((start, start + 1));
+                }
+                _ => {}
+            }
+
+            segments.push(EditSegment {
+                kind,
+                presentation_start: movie_seconds(position),
+                duration: movie_seconds(segment_duration),
+                media_start: (kind != EditKind::Empty)
+                    .then(|| media_seconds(edit.media_time as i128)),
+                media_rate: rate,
+            });
+            position += segment_duration;
+        }
+
+        let has_edit_list = !edits.is_empty();
+        let presentation_start = segments
+            .iter()
+            .take_while(|s| s.kind == EditKind::Empty)
+            .map(|s| s.duration)
+            .fold(0.0, |total, d| total + d);
+        let media_start = segments.iter().find_map(|s| s.media_start).unwrap_or(0.0);
+        let empty_duration = segments
+            .iter()
+            .filter(|s| s.kind == EditKind::Empty)
+            .map(|s| s.duration)
+            .fold(0.0, |total, d| total + d);
+        let presentation_duration = if has_edit_list {
+            movie_seconds(position)
+        } else {
+            media_seconds(media_end as i128)
+        };
+
+        // 没有编辑列表时整个媒体按原样呈现
+        if !has_edit_list {
+            presented.push((i128::MIN, i128::MAX));
+        }
+
+        let outside = index
+            .map(|index| {
+                index
+                    .samples
+                    .iter()
+                    .filter(|s| {
+                        let start = s.cts as i128;
+                        let end = start + (s.duration as i128).max(1);
+                        !presented
+                            .iter()
+                            .any(|&(first, last)| start < last && end > first)
+                    })
+                    .map(|s| s.number)
+                    .collect::<Vec<u32>>()
+            })
+            .unwrap_or_default();
+
+        TrackTimeline {
+            track_id: track.track_id(),
+            handler_type: track.handler_type(),
+            movie_timescale,
+            media_timescale,
+            has_edit_list,
+            segments,
+            presentation_start,
+            media_start,
+            empty_duration,
+            presentation_duration,
+            media_end: media_seconds(media_end as i128),
+            samples_outside_count: outside.len() as u32,
+            samples_outside: collapse_ranges(outside),
+            warnings,
+        }
+    }
+}
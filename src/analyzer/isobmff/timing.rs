@@ -0,0 +1,493 @@
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo};
+use super::sample_index::TrackSampleIndex;
+use super::track::{self, Track};
+
+/// One place where a duration is declared or can be derived from
+#[derive(Debug, Clone, Serialize)]
+pub struct DeclaredDuration {
+    /// "mvhd", "tkhd", "mdhd", "stts", "elst", "mehd" or "fragments"
+    pub source: String,
+    pub value: u64,
+    pub timescale: u32,
+    pub seconds: f64,
+    /// Box version, for boxes that have a 32-bit (version 0) layout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimingIssueKind {
+    DurationMismatch,
+    TimescaleMismatch,
+    ZeroTimescale,
+    Overflow32,
+    UnknownDuration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingIssue {
+    pub kind: TimingIssueKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<u32>,
+    pub message: String,
+}
+
+/// Durations of one track, recomputed in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackTiming {
+    pub track_id: u32,
+    pub handler_type: String,
+    pub durations: Vec<DeclaredDuration>,
+}
+
+/// Cross-check of every duration declared in the file
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingReport {
+    pub movie_timescale: u32,
+    pub movie: Vec<DeclaredDuration>,
+    pub tracks: Vec<TrackTiming>,
+    pub issues: Vec<TimingIssue>,
+}
+
+impl DeclaredDuration {
+    fn new(source: &str, value: u64, timescale: u32, version: Option<u8>) -> Self {
+        Self {
+            source: source.to_string(),
+            value,
+            timescale,
+            seconds: if timescale == 0 {
+                0.0
+            } else {
+                value as f64 / timescale as f64
+            },
+            version,
+        }
+    }
+
+    /// Equal within one tick of either timescale
+    fn matches(&self, other: &DeclaredDuration) -> bool {
+        let tick = |timescale: u32| {
+            if timescale == 0 {
+                0.0
+            } else {
+                1.0 / timescale as f64
+            }
+        };
+        (self.seconds - other.seconds).abs()
+            <= tick(self.timescale) + tick(other.timescale) + f64::EPSILON
+    }
+}
+
+impl TimingReport {
+    pub fn build(boxes: &[Box], indexes: &[TrackSampleIndex]) -> TimingReport {
+        let mut issues = Vec::new();
+        let mvhd = track::movie_header(boxes);
+        let movie_timescale = mvhd.map(|mvhd| mvhd.timescale()).unwrap_or(0);
+        if mvhd.is_some() && movie_timescale == 0 {
+            issues.push(TimingIssue {
+                kind: TimingIssueKind::ZeroTimescale,
+                track_id: None,
+                message: "mvhd timescale is 0".to_string(),
+            });
+        }
+
+        let mut tracks = Vec::new();
+        // 每条轨道按 tkhd 应有的时长, 用于校验 mvhd
+        let mut expected_track_durations = Vec::new();
+        let mut fragment_presentations = Vec::new();
+
+        for track in Track::all(boxes) {
+            let track_id = track.track_id();
+            let media_timescale = track.timescale();
+            let mut issue = |kind, message: String| {
+                issues.push(TimingIssue {
+                    kind,
+                    track_id: Some(track_id),
+                    message,
+                })
+            };
+
+            if track.media_header().is_some() && media_timescale == 0 {
+                issue(
+                    TimingIssueKind::ZeroTimescale,
+                    "mdhd timescale is 0".to_string(),
+                );
+            }
+
+            let tkhd = track.track_header().map(|tkhd| {
+                DeclaredDuration::new(
+                    "tkhd",
+                    tkhd.duration(),
+                    movie_timescale,
+                    Some(tkhd.version()),
+                )
+            });
+            let mdhd = track.media_header().map(|mdhd| {
+                DeclaredDuration::new(
+                    "mdhd",
+                    mdhd.duration(),
+                    media_timescale,
+                    Some(mdhd.version()),
+                )
+            });
+            let stts = track
+                .sample_table()
+                .and_then(|stbl| stbl.child("stts"))
+                .and_then(|stts| match stts.data() {
+                    BoxData::TimeToSample(stts) => Some(stts),
+                    _ => None,
+                })
+                .map(|stts| {
+                    let total = stts
+                        .entries()
+                        .iter()
+                        .map(|e| e.sample_count() as u64 * e.sample_delta() as u64)
+                        .sum();
+                    DeclaredDuration::new("stts", total, media_timescale, None)
+                });
+            let elst = track.edit_list().map(|elst| {
+                let total = elst.entries().iter().map(|e| e.segment_duration).sum();
+                DeclaredDuration::new("elst", total, movie_timescale, None)
+            });
+            let fragments = fragment_end(boxes, track_id)
+                .map(|end| DeclaredDuration::new("fragments", end, media_timescale, None));
+
+            // mdhd 应等于 stts 的总和
+            if let (Some(mdhd), Some(stts)) = (&mdhd, &stts) {
+                if stts.value > 0 && !mdhd.matches(stts) {
+                    let last_delta = track
+                        .sample_table()
+                        .and_then(|stbl| stbl.child("stts"))
+                        .and_then(|b| match b.data() {
+                            BoxData::TimeToSample(stts) => {
+                                stts.entries().last().map(|e| e.sample_delta() as u64)
+                            }
+                            _ => None,
+                        })
+                        .unwrap_or(0);
+                    let hint = if mdhd.value + last_delta == stts.value {
+                        "mdhd excludes the duration of the last sample".to_string()
+                    } else {
+                        timescale_hint(mdhd, stts, movie_timescale)
+                    };
+                    issue(
+                        TimingIssueKind::DurationMismatch,
+                        mismatch(mdhd, stts, &hint),
+                    );
+                }
+                check_overflow(mdhd, stts.value, &mut issue);
+            }
+
+            // tkhd 应等于编辑列表总和, 没有编辑列表时等于换算到影片时间刻度的媒体时长
+            let media_in_movie = stts
+                .as_ref()
+                .filter(|stts| stts.value > 0)
+                .or(mdhd.as_ref())
+                .map(|media| {
+                    let value = rescale(media.value, media.timescale, movie_timescale);
+                    DeclaredDuration::new(
+                        &format!("{} (in movie timescale)", media.source),
+                        value,
+                        movie_timescale,
+                        None,
+                    )
+                });
+            let expected_tkhd = elst.clone().or(media_in_movie.clone());
+            if let (Some(tkhd), Some(expected)) = (&tkhd, &expected_tkhd) {
+                if !tkhd.matches(expected) && (tkhd.value != 0 || fragments.is_none()) {
+                    let hint = match (&elst, &media_in_movie) {
+                        (Some(_), Some(media)) if tkhd.matches(media) => {
+                            "tkhd was computed from the media duration, ignoring the edit list"
+                                .to_string()
+                        }
+                        _ => timescale_hint(tkhd, expected, media_timescale),
+                    };
+                    issue(
+                        TimingIssueKind::DurationMismatch,
+                        mismatch(tkhd, expected, &hint),
+                    );
+                }
+                check_overflow(tkhd, expected.value, &mut issue);
+            }
+            if let Some(expected) = expected_tkhd {
+                expected_track_durations.push(expected);
+            }
+
+            // 音频的媒体时间刻度通常等于采样率
+            if track.handler_type() == "soun" {
+                if let Some(rate) = track.sample_entry().and_then(|e| e.audio_sample_rate()) {
+                    if rate != 0 && media_timescale != 0 && rate != media_timescale {
+                        issue(
+                            TimingIssueKind::TimescaleMismatch,
+                            format!(
+                                "mdhd timescale {} differs from the audio sample rate {}",
+                                media_timescale, rate
+                            ),
+                        );
+                    }
+                }
+            }
+
+            // 视频帧间隔交替相差 1, 说明时间刻度无法精确表示帧率
+            if let Some(index) = indexes.iter().find(|index| index.track_id == track_id) {
+                let min = index.samples.iter().map(|s| s.duration).min();
+                let max = index.samples.iter().map(|s| s.duration).max();
+                if let (Some(min), Some(max)) = (min, max) {
+                    if track.handler_type() == "vide" && min > 0 && max - min == 1 {
+                        issue(
+                            TimingIssueKind::TimescaleMismatch,
+                            format!(
+                                "sample durations alternate between {} and {}: timescale {} cannot represent the frame rate exactly",
+                                min, max, media_timescale
+                            ),
+                        );
+                    }
+                }
+            }
+
+            // mehd 按编辑列表后的呈现时长校验; 分片文件的编辑列表常以 segment_duration 0
+            // 表示一直到媒体结尾, 此时只扣除 media_time
+            if let Some(fragments) = &fragments {
+                let presented = match &elst {
+                    Some(elst) if elst.value > 0 => elst.clone(),
+                    _ => {
+                        let skip = track
+                            .edit_list()
+                            .and_then(|elst| {
+                                elst.entries()
+                                    .iter()
+                                    .find(|entry| entry.media_time >= 0)
+                                    .map(|entry| entry.media_time as u64)
+                            })
+                            .unwrap_or(0);
+                        DeclaredDuration::new(
+                            "fragments (in movie timescale)",
+                            rescale(
+                                fragments.value.saturating_sub(skip),
+                                media_timescale,
+                                movie_timescale,
+                            ),
+                            movie_timescale,
+                            None,
+                        )
+                    }
+                };
+                fragment_presentations.push(presented);
+            }
+
+            tracks.push(TrackTiming {
+                track_id,
+                handler_type: track.handler_type(),
+                durations: [tkhd, elst, mdhd, stts, fragments]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            });
+        }
+
+        let mut movie = Vec::new();
+        let longest = |list: &[DeclaredDuration]| {
+            list.iter()
+                .max_by(|a, b| a.seconds.total_cmp(&b.seconds))
+                .cloned()
+        };
+        let mut issue = |kind, message: String| {
+            issues.push(TimingIssue {
+                kind,
+                track_id: None,
+                message,
+            })
+        };
+
+        let mehd = find_mehd(boxes).map(|(version, duration)| {
+            DeclaredDuration::new("mehd", duration, movie_timescale, Some(version))
+        });
+
+        if let Some(mvhd) = mvhd {
+            let declared = DeclaredDuration::new(
+                "mvhd",
+                mvhd.duration(),
+                movie_timescale,
+                Some(mvhd.version()),
+            );
+            let fragmented = mehd.is_some() || !fragment_presentations.is_empty();
+            if let Some(expected) = longest(&expected_track_durations) {
+                let expected = DeclaredDuration {
+                    source: format!("longest track ({})", expected.source),
+                    ..expected
+                };
+                if expected.value > 0
+                    && !declared.matches(&expected)
+                    && (!fragmented || declared.value != 0)
+                {
+                    let hint = timescale_hint(&declared, &expected, 0);
+                    issue(
+                        TimingIssueKind::DurationMismatch,
+                        mismatch(&declared, &expected, &hint),
+                    );
+                }
+                check_overflow(&declared, expected.value, &mut issue);
+            }
+            if mvhd.version() == 0 && mvhd.duration() == u32::MAX as u64 {
+                issue(
+                    TimingIssueKind::UnknownDuration,
+                    "mvhd duration is 0xFFFFFFFF (unknown)".to_string(),
+                );
+            }
+            movie.push(declared);
+        }
+
+        if let Some(mehd) = mehd {
+            if let Some(end) = longest(&fragment_presentations) {
+                let end = DeclaredDuration {
+                    source: format!("longest track ({})", end.source),
+                    ..end
+                };
+                if !mehd.matches(&end) {
+                    let hint = timescale_hint(&mehd, &end, 0);
+                    issue(
+                        TimingIssueKind::DurationMismatch,
+                        mismatch(&mehd, &end, &hint),
+                    );
+                }
+                check_overflow(
+                    &mehd,
+                    rescale(end.value, end.timescale, movie_timescale),
+                    &mut issue,
+                );
+            }
+            movie.push(mehd);
+        }
+
+        TimingReport {
+            movie_timescale,
+            movie,
+            tracks,
+            issues,
+        }
+    }
+}
+
+fn mismatch(declared: &DeclaredDuration, expected: &DeclaredDuration, hint: &str) -> String {
+    let mut message = format!(
+        "{} duration {:.6}s ({} @ {}) differs from {} {:.6}s ({} @ {}) by {:+.6}s",
+        declared.source,
+        declared.seconds,
+        declared.value,
+        declared.timescale,
+        expected.source,
+        expected.seconds,
+        expected.value,
+        expected.timescale,
+        declared.seconds - expected.seconds
+    );
+    if !hint.is_empty() {
+        message.push_str(": ");
+        message.push_str(hint);
+    }
+    message
+}
+
+/// Explains a mismatch caused by writing a value in another track's timescale
+fn timescale_hint(declared: &DeclaredDuration, expected: &DeclaredDuration, other: u32) -> String {
+    if other != 0 && declared.timescale != 0 {
+        let reinterpreted = rescale(declared.value, other, declared.timescale);
+        if reinterpreted.abs_diff(expected.value) <= 1 {
+            return format!(
+                "the value looks like it was written in timescale {} instead of {}",
+                other, declared.timescale
+            );
+        }
+    }
+    String::new()
+}
+
+/// Flags version 0 durations whose actual value does not fit in 32 bits
+fn check_overflow(
+    declared: &DeclaredDuration,
+    actual: u64,
+    issue: &mut impl FnMut(TimingIssueKind, String),
+) {
+    if declared.version != Some(0) || actual <= u32::MAX as u64 {
+        return;
+    }
+    let wrapped = actual & u32::MAX as u64;
+    let message = if declared.value == wrapped {
+        format!(
+            "{} is version 0 and its duration {} overflowed 32 bits (stored {}); version 1 is required",
+            declared.source, actual, declared.value
+        )
+    } else {
+        format!(
+            "{} is version 0 but the actual duration {} does not fit in 32 bits",
+            declared.source, actual
+        )
+    };
+    issue(TimingIssueKind::Overflow32, message);
+}
+
+fn rescale(value: u64, from: u32, to: u32) -> u64 {
+    if from == 0 {
+        return 0;
+    }
+    (value as u128 * to as u128 / from as u128) as u64
+}
+
+/// (version, fragment_duration) of moov/mvex/mehd
+fn find_mehd(boxes: &[Box]) -> Option<(u8, u64)> {
+    let moov = boxes.iter().find(|b| b.box_type().as_str() == "moov")?;
+    match moov.child("mvex")?.child("mehd")?.data() {
+        BoxData::MovieExtendsHeader(mehd) => Some((mehd.version(), mehd.fragment_duration())),
+        _ => None,
+    }
+}
+
+/// End of the last fragment of a track (tfdt + sample durations), in media timescale units
+fn fragment_end(boxes: &[Box], track_id: u32) -> Option<u64> {
+    let trex_default = boxes
+        .iter()
+        .find(|b| b.box_type().as_str() == "moov")
+        .and_then(|moov| moov.child("mvex"))
+        .and_then(|mvex| {
+            mvex.children().iter().find_map(|child| match child.data() {
+                BoxData::TrackExtends(trex) if trex.track_id() == track_id => {
+                    Some(trex.default_sample_duration())
+                }
+                _ => None,
+            })
+        })
+        .unwrap_or(0);
+
+    let mut end = None;
+    let mut position = 0u64;
+    let trafs = boxes
+        .iter()
+        .filter(|b| b.box_type().as_str() == "moof")
+        .flat_map(|moof| moof.children())
+        .filter(|child| child.box_type().as_str() == "traf");
+    for traf in trafs {
+        let default_duration = match traf.child("tfhd").map(|b| b.data()) {
+            Some(BoxData::TrackFragmentHeader(tfhd)) if tfhd.track_id() == track_id => {
+                tfhd.default_sample_duration().unwrap_or(trex_default)
+            }
+            _ => continue,
+        };
+        if let Some(BoxData::TrackFragmentDecodeTime(tfdt)) = traf.child("tfdt").map(|b| b.data()) {
+            position = tfdt.base_media_decode_time();
+        }
+        for child in traf.children() {
+            if let BoxData::TrackRun(trun) = child.data() {
+                position += trun
+                    .entries()
+                    .iter()
+                    .map(|e| e.duration.unwrap_or(default_duration) as u64)
+                    .sum::<u64>();
+            }
+        }
+        end = Some(position);
+    }
+    end
+}
@@ -0,0 +1,116 @@
+use super::boxes::{
+    Box, BoxData, BoxInfo, EditListBox, ItemListBox, MediaHeaderBox, MovieHeaderBox, SampleEntry,
+    TrackHeaderBox,
+};
+
+/// Read-only view over one `trak` box and the children analyses care about
+pub struct Track<'a> {
+    trak: &'a Box,
+}
+
+impl<'a> Track<'a> {
+    pub fn new(trak: &'a Box) -> Self {
+        Self { trak }
+    }
+
+    /// Collects every track of the first `moov` box
+    pub fn all(boxes: &'a [Box]) -> Vec<Track<'a>> {
+        boxes
+            .iter()
+            .find(|b| b.box_type().as_str() == "moov")
+            .map(|moov| {
+                moov.children()
+                    .iter()
+                    .filter(|child| child.box_type().as_str() == "trak")
+                    .map(Track::new)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn track_id(&self) -> u32 {
+        self.track_header().map(|tkhd| tkhd.track_id()).unwrap_or(0)
+    }
+
+    pub fn track_header(&self) -> Option<&'a TrackHeaderBox> {
+        match self.trak.child("tkhd")?.data() {
+            BoxData::TrackHeader(tkhd) => Some(tkhd),
+            _ => None,
+        }
+    }
+
+    pub fn media_header(&self) -> Option<&'a MediaHeaderBox> {
+        match self.mdia_child("mdhd")?.data() {
+            BoxData::MediaHeader(mdhd) => Some(mdhd),
+            _ => None,
+        }
+    }
+
+    pub fn handler_type(&self) -> String {
+        match self.mdia_child("hdlr").map(|b| b.data()) {
+            Some(BoxData::Handler(hdlr)) => hdlr.handler_type().to_string(),
+            _ => String::new(),
+        }
+    }
+
+    pub fn timescale(&self) -> u32 {
+        self.media_header()
+            .map(|mdhd| mdhd.timescale())
+            .unwrap_or(0)
+    }
+
+    /// Media duration declared in mdhd, in media timescale units
+    pub fn media_duration(&self) -> u64 {
+        self.media_header().map(|mdhd| mdhd.duration()).unwrap_or(0)
+    }
+
+    /// The edit list of this track (edts/elst), if present
+    pub fn edit_list(&self) -> Option<&'a EditListBox> {
+        match self.trak.child("edts")?.child("elst")?.data() {
+            BoxData::EditList(elst) => Some(elst),
+            _ => None,
+        }
+    }
+
+    /// First sample entry of the stsd box (codec 4CC and codec specific data)
+    pub fn sample_entry(&self) -> Option<&'a SampleEntry> {
+        match self.sample_table()?.child("stsd")?.data() {
+            BoxData::SampleDescription(stsd) => stsd.entries().first(),
+            _ => None,
+        }
+    }
+
+    /// The `stbl` box of this track, if present
+    pub fn sample_table(&self) -> Option<&'a Box> {
+        self.mdia_child("minf")?.child("stbl")
+    }
+
+    fn mdia_child(&self, box_type: &str) -> Option<&'a Box> {
+        self.trak.child("mdia")?.child(box_type)
+    }
+}
+
+/// Timescale of the movie (mvhd), which edit lists and tkhd durations use
+pub fn movie_timescale(boxes: &[Box]) -> u32 {
+    movie_header(boxes)
+        .map(|mvhd| mvhd.timescale())
+        .unwrap_or(0)
+}
+
+/// The mvhd box of the first moov
+pub fn movie_header(boxes: &[Box]) -> Option<&MovieHeaderBox> {
+    let moov = boxes.iter().find(|b| b.box_type().as_str() == "moov")?;
+    match moov.child("mvhd")?.data() {
+        BoxData::MovieHeader(mvhd) => Some(mvhd),
+        _ => None,
+    }
+}
+
+/// The iTunes metadata item list of the movie (moov/udta/meta/ilst)
+pub fn movie_item_list(boxes: &[Box]) -> Option<&ItemListBox> {
+    let moov = boxes.iter().find(|b| b.box_type().as_str() == "moov")?;
+    match moov.child("udta")?.child("meta")?.child("ilst")?.data() {
+        BoxData::ItemList(ilst) => Some(ilst),
+        _ => None,
+    }
+}
@@ -0,0 +1,645 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::av_sync::ITunSmpb;
+use super::boxes::{
+    Box, BoxData, BoxInfo, ChunkOffsetBox, CompositionOffsetBox, CompositionOffsetEntry, EditBox,
+    EditListBox, EditListEntry, MetadataItem, MetadataValue, SampleGroupEntry, SampleSizeBox,
+    SampleToChunkBox, SampleToChunkEntry, SyncSampleBox, TimeToSampleBox, TimeToSampleEntry,
+};
+use super::faststart::{copy_range, read_bytes, same_file};
+use super::sample_index::{Sample, TrackSampleIndex};
+use super::serializer::BoxSerializer;
+use super::tags::existing_item_list_mut;
+use super::track::{movie_timescale, Track};
+use super::types::BoxType;
+use super::IsobmffAnalyzer;
+use crate::error::MediaError;
+
+const ITUNSMPB_KEY: &str = "----:com.apple.iTunes:iTunSMPB";
+
+/// Per-sample boxes that are not rebuilt; they are dropped from trimmed tracks
+const DROPPED_SAMPLE_BOXES: &[&str] = &[
+    "sdtp", "subs", "saiz", "saio", "stps", "cslg", "padb", "stdp", "stsh",
+];
+
+/// What was kept of one track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrimmedTrack {
+    pub track_id: u32,
+    pub handler_type: String,
+    /// 1-based numbers of the first and last input samples kept
+    pub first_sample: u32,
+    pub last_sample: u32,
+    pub sample_count: u32,
+    /// Media decoded from the preceding sync sample but hidden by the edit list, in seconds
+    pub hidden_leading: f64,
+    /// Presentation duration of the trimmed track, in seconds
+    pub duration: f64,
+}
+
+/// Outcome of a trim
+#[derive(Debug, Clone, Serialize)]
+pub struct TrimReport {
+    pub start: f64,
+    pub end: f64,
+    pub tracks: Vec<TrimmedTrack>,
+    pub bytes_written: u64,
+    /// Samples whose bytes were compared between input and output
+    pub verified_samples: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Samples kept from one track and the edit that presents them
+struct Cut {
+    /// Index of the first kept sample (a sync sample) and of the last one, inclusive
+    first: usize,
+    last: usize,
+    /// New elst entry: leading empty edit and normal edit in movie units, media_time in the
+    /// new media timeline
+    empty: u64,
+    segment: u64,
+    media_time: i64,
+    /// Presented media, in media timescale units
+    shown: u64,
+    hidden_leading: f64,
+}
+
+/// One chunk of a rewritten file: a run of samples that shared an input chunk
+pub(super) struct Chunk {
+    /// Position of the track among the trak boxes of the output moov
+    pub track: usize,
+    pub source_offset: u64,
+    pub len: u64,
+    pub samples: u32,
+    pub sample_description_index: u32,
+}
+
+/// Writes the part of `input` between `start` and `end` seconds (movie timeline) to `output`
+/// without re-encoding. Every track starts at the sync sample at or before `start`; the edit
+/// list hides the frames before `start`. Audio tracks keep the pre-roll of their 'roll' group.
+pub fn trim(input: &Path, output: &Path, start: f64, end: f64) -> Result<TrimReport, MediaError> {
+    if same_file(input, output) {
+        return Err(MediaError::InvalidFormat(
+            "output must not be the input file".to_string(),
+        ));
+    }
+    if !(start >= 0.0 && end > start) {
+        return Err(MediaError::InvalidFormat(format!(
+            "invalid time range {}-{}",
+            start, end
+        )));
+    }
+
+    let mut analyzer = IsobmffAnalyzer::new(input)?;
+    analyzer.set_debug(false);
+    let boxes = analyzer.boxes()?;
+    if boxes
+        .iter()
+        .any(|b| matches!(b.box_type().as_str(), "moof" | "mfra"))
+    {
+        return Err(MediaError::UnsupportedFormat(
+            "trimming fragmented files is not supported".to_string(),
+        ));
+    }
+    let movie_timescale = movie_timescale(boxes);
+    if movie_timescale == 0 {
+        return Err(MediaError::InvalidFormat(
+            "movie timescale is 0".to_string(),
+        ));
+    }
+    let indexes = TrackSampleIndex::build_all(boxes)?;
+    let cuts: Vec<Option<Cut>> = Track::all(boxes)
+        .iter()
+        .zip(&indexes)
+        .map(|(track, index)| plan(track, index, start, end, movie_timescale))
+        .collect();
+    if cuts.iter().all(Option::is_none) {
+        return Err(MediaError::InvalidFormat(format!(
+            "no samples between {} and {} seconds",
+            start, end
+        )));
+    }
+
+    let mut boxes = analyzer.boxes.take().unwrap_or_default();
+    let mut warnings = Vec::new();
+    // 只保留 ftyp/moov 和不含媒体数据的顶层 box, 新的 mdat 写在最后
+    boxes.retain(|b| !matches!(b.box_type().as_str(), "mdat" | "free" | "skip" | "wide"));
+    let moov = boxes
+        .iter_mut()
+        .find(|b| b.box_type().as_str() == "moov")
+        .ok_or_else(|| MediaError::InvalidFormat("file has no moov box".to_string()))?;
+
+    let mut tracks = Vec::new();
+    let mut chunks = Vec::new();
+    let mut kept_indexes = Vec::new();
+    let mut movie_duration = 0;
+    let mut track_number = 0;
+    // 第一条音轨的新延迟/长度, 用于重写 iTunSMPB; None 表示无法以采样数表示
+    let mut itunsmpb = None;
+    let mut audio_seen = false;
+    moov.children_mut().retain_mut(|trak| {
+        if trak.box_type().as_str() != "trak" {
+            return true;
+        }
+        let (index, cut) = (&indexes[track_number], &cuts[track_number]);
+        track_number += 1;
+        let Some(cut) = cut else {
+            warnings.push(format!(
+                "track {} has no samples in the range and was removed",
+                index.track_id
+            ));
+            return false;
+        };
+        let kept = &index.samples[cut.first..=cut.last];
+        let track = kept_indexes.len();
+        let track_chunks = chunk_runs(track, kept);
+        rebuild_sample_table(trak, kept, cut.first, &track_chunks, &mut warnings);
+        set_edit_list(trak, cut);
+        let media_duration: u64 = kept.iter().map(|s| s.duration as u64).sum();
+        set_durations(trak, cut.empty + cut.segment, media_duration);
+        if index.handler_type == "soun" && !audio_seen {
+            audio_seen = true;
+            let sample_rate = Track::new(trak)
+                .sample_entry()
+                .and_then(|entry| entry.audio_sample_rate());
+            if sample_rate == Some(index.timescale) && cut.media_time >= 0 {
+                itunsmpb = Some(ITunSmpb {
+                    encoder_delay: cut.media_time as u32,
+                    end_padding: media_duration.saturating_sub(cut.media_time as u64 + cut.shown)
+                        as u32,
+                    original_sample_count: cut.shown,
+                });
+            }
+        }
+        movie_duration = movie_duration.max(cut.empty + cut.segment);
+
+        tracks.push(TrimmedTrack {
+            track_id: index.track_id,
+            handler_type: index.handler_type.clone(),
+            first_sample: kept[0].number,
+            last_sample: kept[kept.len() - 1].number,
+            sample_count: kept.len() as u32,
+            hidden_leading: cut.hidden_leading,
+            duration: (cut.empty + cut.segment) as f64 / movie_timescale as f64,
+        });
+        chunks.extend(track_chunks);
+        kept_indexes.push((index.track_id, kept.to_vec()));
+        true
+    });
+    if let Some(BoxData::MovieHeader(mvhd)) = moov.child_mut("mvhd").map(|b| b.data_mut()) {
+        mvhd.set_duration(movie_duration);
+    }
+    // 原 iTunSMPB 描述未剪切的音频, 按新的剪切重写, 无法换算时删除
+    if let Some(ilst) = existing_item_list_mut(moov) {
+        if ilst.item(ITUNSMPB_KEY).is_some() {
+            match itunsmpb {
+                Some(itunsmpb) => ilst.set_item(MetadataItem::new(
+                    *b"----",
+                    Some("com.apple.iTunes".to_string()),
+                    Some("iTunSMPB".to_string()),
+                    vec![MetadataValue {
+                        version: 0,
+                        data_type: 1,
+                        locale: 0,
+                        data: itunsmpb.to_text().into_bytes(),
+                    }],
+                )),
+                None => {
+                    ilst.remove_item(ITUNSMPB_KEY);
+                    warnings.push(
+                        "iTunSMPB was removed: the trimmed audio cannot be described in samples"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    let (bytes_written, verified_samples) =
+        write_progressive(input, output, &mut boxes, chunks, &kept_indexes)?;
+    Ok(TrimReport {
+        start,
+        end,
+        tracks,
+        bytes_written,
+        verified_samples,
+        warnings,
+    })
+}
+
+/// Writes `boxes` followed by one mdat holding `chunks` in input order, fills in the chunk
+/// offsets and checks the result against `expected` (track id, samples); a file that fails the
+/// check is deleted. Returns (bytes written, samples verified).
+pub(super) fn write_progressive(
+    input: &Path,
+    output: &Path,
+    boxes: &mut [Box],
+    mut chunks: Vec<Chunk>,
+    expected: &[(u32, Vec<Sample>)],
+) -> Result<(u64, u64), MediaError> {
+    // 保持输入中的交织顺序
+    chunks.sort_by_key(|chunk| chunk.source_offset);
+    let payload: u64 = chunks.iter().map(|chunk| chunk.len).sum();
+    let mut source = File::open(input)?;
+    let mut serializer = BoxSerializer::new(Some(&mut source));
+    let mut large = false;
+    let (header_len, mdat_header) = loop {
+        set_chunk_offsets(boxes, &chunks, 0, large);
+        let header_len = boxes
+            .iter()
+            .map(|b| serializer.encoded_size(b))
+            .sum::<Result<u64, _>>()?;
+        let mdat_header = if payload + 8 > u32::MAX as u64 { 16 } else { 8 };
+        if large || header_len + mdat_header + payload <= u32::MAX as u64 {
+            break (header_len, mdat_header);
+        }
+        large = true;
+    };
+    set_chunk_offsets(boxes, &chunks, header_len + mdat_header, large);
+
+    let mut out = BufWriter::new(File::create(output)?);
+    let mut bytes_written = serializer.write_boxes(boxes, &mut out)?;
+    if mdat_header == 16 {
+        out.write_all(&1u32.to_be_bytes())?;
+        out.write_all(b"mdat")?;
+        out.write_all(&(payload + 16).to_be_bytes())?;
+    } else {
+        out.write_all(&((payload + 8) as u32).to_be_bytes())?;
+        out.write_all(b"mdat")?;
+    }
+    for chunk in &chunks {
+        copy_range(&mut source, &mut out, chunk.source_offset, chunk.len)?;
+    }
+    bytes_written += mdat_header + payload;
+    out.flush()?;
+    drop(out);
+
+    match verify(&mut source, output, expected) {
+        Ok(verified) => Ok((bytes_written, verified)),
+        Err(e) => {
+            std::fs::remove_file(output).ok();
+            Err(e)
+        }
+    }
+}
+
+/// Chooses the samples of one track and the edit that shows exactly `start..end`
+fn plan(
+    track: &Track,
+    index: &TrackSampleIndex,
+    start: f64,
+    end: f64,
+    movie_timescale: u32,
+) -> Option<Cut> {
+    let timescale = index.timescale as f64;
+    if timescale == 0.0 {
+        return None;
+    }
+    // 现有编辑列表: 前导空编辑 + 第一个正常编辑的 media_time
+    let mut empty_before = 0.0;
+    let mut media_start = 0i64;
+    for entry in track
+        .edit_list()
+        .map(|elst| elst.entries())
+        .unwrap_or_default()
+    {
+        if entry.media_time == -1 {
+            empty_before += entry.segment_duration as f64 / movie_timescale as f64;
+        } else {
+            media_start = entry.media_time;
+            break;
+        }
+    }
+    let to_media = |t: f64| media_start + ((t - empty_before).max(0.0) * timescale).round() as i64;
+    let (start_media, end_media) = (to_media(start), to_media(end));
+
+    let samples = &index.samples;
+    let first = samples
+        .iter()
+        .rposition(|s| s.is_sync && s.cts <= start_media)
+        .or_else(|| samples.iter().position(|s| s.is_sync))?;
+    // 音频 roll 组: 解码器需要前面的 -roll_distance 个样本预热
+    let pre_roll = samples[first]
+        .groups
+        .iter()
+        .find_map(|group| match group.entry {
+            Some(SampleGroupEntry::Roll { roll_distance }) if roll_distance < 0 => {
+                Some(roll_distance.unsigned_abs() as usize)
+            }
+            _ => None,
+        })
+        .unwrap_or(0);
+    let first = first.saturating_sub(pre_roll);
+    let last = samples.iter().rposition(|s| s.cts < end_media)?;
+    if last < first {
+        return None;
+    }
+    let kept = &samples[first..=last];
+    let earliest = kept.iter().map(|s| s.cts).min()?;
+    let latest = kept.iter().map(|s| s.cts + s.duration as i64).max()?;
+    let shown_from = start_media.max(earliest);
+    let shown_to = end_media.min(latest);
+    if shown_to <= shown_from {
+        return None;
+    }
+    let to_movie = |media: i64| (media as f64 / timescale * movie_timescale as f64).round() as u64;
+    Some(Cut {
+        first,
+        last,
+        empty: ((empty_before - start).max(0.0) * movie_timescale as f64).round() as u64,
+        segment: to_movie(shown_to - shown_from),
+        media_time: shown_from - samples[first].dts as i64,
+        shown: (shown_to - shown_from) as u64,
+        hidden_leading: (shown_from - earliest) as f64 / timescale,
+    })
+}
+
+/// Groups samples by the input chunk they were stored in
+pub(super) fn chunk_runs(track: usize, kept: &[Sample]) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut previous: Option<&Sample> = None;
+    for sample in kept {
+        let same_chunk = previous.is_some_and(|p| {
+            p.chunk_index == sample.chunk_index
+                && p.sample_description_index == sample.sample_description_index
+                && p.offset + p.size as u64 == sample.offset
+        });
+        match chunks.last_mut() {
+            Some(chunk) if same_chunk => {
+                chunk.len += sample.size as u64;
+                chunk.samples += 1;
+            }
+            _ => chunks.push(Chunk {
+                track,
+                source_offset: sample.offset,
+                len: sample.size as u64,
+                samples: 1,
+                sample_description_index: sample.sample_description_index,
+            }),
+        }
+        previous = Some(sample);
+    }
+    chunks
+}
+
+/// Replaces stts/ctts/stss/stsz/stsc/stco/sbgp with tables describing only `kept`, which
+/// starts at sample index `first`; stss and ctts are added when the samples need them
+pub(super) fn rebuild_sample_table(
+    trak: &mut Box,
+    kept: &[Sample],
+    first: usize,
+    chunks: &[Chunk],
+    warnings: &mut Vec<String>,
+) {
+    let Some(stbl) = trak
+        .child_mut("mdia")
+        .and_then(|mdia| mdia.child_mut("minf"))
+        .and_then(|minf| minf.child_mut("stbl"))
+    else {
+        return;
+    };
+    stbl.children_mut().retain(|child| {
+        let drop = DROPPED_SAMPLE_BOXES.contains(&child.box_type().as_str());
+        if drop {
+            let warning = format!("{} boxes were dropped", child.box_type());
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+        !drop
+    });
+    // 分片文件的 moov 通常没有 stss/ctts, 重建时按需补上 (放在 stts 之后)
+    let after_stts = stbl
+        .children()
+        .iter()
+        .position(|b| b.box_type().as_str() == "stts")
+        .map_or(0, |i| i + 1);
+    if stbl.child("ctts").is_none() && kept.iter().any(|s| s.cts != s.dts as i64) {
+        stbl.children_mut()
+            .insert(after_stts, new_box(*b"ctts", BoxData::Unknown));
+    }
+    if stbl.child("stss").is_none() && kept.iter().any(|s| !s.is_sync) {
+        stbl.children_mut()
+            .insert(after_stts, new_box(*b"stss", BoxData::Unknown));
+    }
+    for child in stbl.children_mut() {
+        let data = match child.box_type().as_str() {
+            "stts" => BoxData::TimeToSample(TimeToSampleBox::new(
+                0,
+                0,
+                runs(kept.iter().map(|s| s.duration))
+                    .map(|(count, delta)| TimeToSampleEntry::new(count, delta))
+                    .collect(),
+            )),
+            "ctts" => {
+                let offsets: Vec<i64> = kept.iter().map(|s| s.cts - s.dts as i64).collect();
+                let version = u8::from(offsets.iter().any(|&offset| offset < 0));
+                BoxData::CompositionOffset(CompositionOffsetBox::new(
+                    version,
+                    0,
+                    runs(offsets.into_iter().map(|offset| offset as i32))
+                        .map(|(count, offset)| CompositionOffsetEntry::new(count, offset))
+                        .collect(),
+                ))
+            }
+            "stss" => BoxData::SyncSample(SyncSampleBox::new(
+                0,
+                0,
+                (1..)
+                    .zip(kept)
+                    .filter(|(_, s)| s.is_sync)
+                    .map(|(number, _)| number)
+                    .collect(),
+            )),
+            "stsz" => {
+                let sizes: Vec<u32> = kept.iter().map(|s| s.size).collect();
+                let constant = sizes.windows(2).all(|pair| pair[0] == pair[1]);
+                BoxData::SampleSize(if constant {
+                    let size = sizes.first().copied().unwrap_or(0);
+                    SampleSizeBox::new(0, 0, size, sizes.len() as u32, Vec::new())
+                } else {
+                    SampleSizeBox::new(0, 0, 0, sizes.len() as u32, sizes)
+                })
+            }
+            "stsc" => {
+                let mut entries = Vec::new();
+                let mut previous = None;
+                for (number, chunk) in (1..).zip(chunks) {
+                    let layout = (chunk.samples, chunk.sample_description_index);
+                    if previous != Some(layout) {
+                        entries.push(SampleToChunkEntry::new(number, layout.0, layout.1));
+                        previous = Some(layout);
+                    }
+                }
+                BoxData::SampleToChunk(SampleToChunkBox::new(0, 0, entries))
+            }
+            // 偏移在布局确定后再填写
+            "stco" | "co64" => {
+                BoxData::ChunkOffset(ChunkOffsetBox::new(0, 0, vec![0; chunks.len()], false))
+            }
+            "sbgp" => match child.data() {
+                BoxData::SampleToGroup(sbgp) => {
+                    BoxData::SampleToGroup(sbgp.slice(first as u32, kept.len() as u32))
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        *child.data_mut() = data;
+        child.set_trailing(Default::default());
+    }
+}
+
+/// Run-length encodes a sequence as (count, value)
+fn runs<T: PartialEq + Copy>(values: impl Iterator<Item = T>) -> impl Iterator<Item = (u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((count, last)) if *last == value => *count += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs.into_iter()
+}
+
+/// Replaces the edit list with an optional empty edit and one normal edit
+fn set_edit_list(trak: &mut Box, cut: &Cut) {
+    let mut entries = Vec::new();
+    if cut.empty > 0 {
+        entries.push(EditListEntry::new(cut.empty, -1, 1, 0));
+    }
+    entries.push(EditListEntry::new(cut.segment, cut.media_time, 1, 0));
+    let version =
+        u8::from(cut.empty + cut.segment > u32::MAX as u64 || cut.media_time > i32::MAX as i64);
+    let elst = BoxData::EditList(EditListBox::new(version, 0, entries));
+
+    if trak.child("edts").is_none() {
+        let mut edts = new_box(*b"edts", BoxData::Edit(EditBox::new()));
+        edts.add_child(new_box(*b"elst", BoxData::Unknown));
+        // edts 紧跟在 tkhd 之后
+        let at = trak
+            .children()
+            .iter()
+            .position(|b| b.box_type().as_str() == "tkhd")
+            .map_or(0, |i| i + 1);
+        trak.children_mut().insert(at, edts);
+    }
+    let Some(edts) = trak.child_mut("edts") else {
+        return;
+    };
+    edts.children_mut()
+        .retain(|b| b.box_type().as_str() == "elst");
+    if edts.child("elst").is_none() {
+        edts.add_child(new_box(*b"elst", BoxData::Unknown));
+    }
+    if let Some(b) = edts.child_mut("elst") {
+        *b.data_mut() = elst;
+        b.set_trailing(Default::default());
+    }
+}
+
+pub(super) fn set_durations(trak: &mut Box, track_duration: u64, media_duration: u64) {
+    if let Some(BoxData::TrackHeader(tkhd)) = trak.child_mut("tkhd").map(|b| b.data_mut()) {
+        tkhd.set_duration(track_duration);
+    }
+    let mdhd = trak
+        .child_mut("mdia")
+        .and_then(|mdia| mdia.child_mut("mdhd"));
+    if let Some(BoxData::MediaHeader(mdhd)) = mdhd.map(|b| b.data_mut()) {
+        mdhd.set_duration(media_duration);
+    }
+}
+
+/// Fills every stco/co64 with the chunk positions of the new mdat starting at `data_start`
+pub(super) fn set_chunk_offsets(boxes: &mut [Box], chunks: &[Chunk], data_start: u64, large: bool) {
+    let mut offsets: Vec<Vec<u64>> = Vec::new();
+    let mut position = data_start;
+    for chunk in chunks {
+        if offsets.len() <= chunk.track {
+            offsets.resize(chunk.track + 1, Vec::new());
+        }
+        offsets[chunk.track].push(position);
+        position += chunk.len;
+    }
+    let Some(moov) = boxes.iter_mut().find(|b| b.box_type().as_str() == "moov") else {
+        return;
+    };
+    let traks = moov
+        .children_mut()
+        .iter_mut()
+        .filter(|b| b.box_type().as_str() == "trak");
+    for (trak, offsets) in traks.zip(offsets) {
+        let stco = trak
+            .child_mut("mdia")
+            .and_then(|mdia| mdia.child_mut("minf"))
+            .and_then(|minf| minf.child_mut("stbl"))
+            .and_then(|stbl| {
+                stbl.children_mut()
+                    .iter_mut()
+                    .find(|b| matches!(b.box_type().as_str(), "stco" | "co64"))
+            });
+        if let Some(stco) = stco {
+            stco.set_box_type(BoxType::new(if large { *b"co64" } else { *b"stco" }));
+            *stco.data_mut() = BoxData::ChunkOffset(ChunkOffsetBox::new(0, 0, offsets, large));
+        }
+    }
+}
+
+fn new_box(box_type: [u8; 4], data: BoxData) -> Box {
+    Box::new(BoxType::new(box_type), 0, 0, 8, data)
+}
+
+/// Re-parses the output (progressive or fragmented) and compares every expected sample with
+/// the input
+pub(super) fn verify(
+    source: &mut File,
+    output: &Path,
+    expected: &[(u32, Vec<Sample>)],
+) -> Result<u64, MediaError> {
+    let mut written = IsobmffAnalyzer::new(output)?;
+    written.set_debug(false);
+    let after = TrackSampleIndex::build_all_with_fragments(written.boxes()?)?;
+    compare_samples(source, &mut File::open(output)?, expected, &after)
+}
+
+/// Compares the bytes, sync flag, duration and composition offset of the samples of `target`
+/// with the expected samples of `source`, track by track
+pub(super) fn compare_samples(
+    source: &mut File,
+    target: &mut File,
+    expected: &[(u32, Vec<Sample>)],
+    after: &[TrackSampleIndex],
+) -> Result<u64, MediaError> {
+    let mut verified = 0;
+    for ((track_id, before), after) in expected.iter().zip(after) {
+        if *track_id != after.track_id || before.len() != after.samples.len() {
+            return Err(MediaError::InvalidFormat(format!(
+                "verification failed: track {} should have {} samples, the output has {}",
+                track_id,
+                before.len(),
+                after.samples.len()
+            )));
+        }
+        for (a, b) in before.iter().zip(&after.samples) {
+            let expected = read_bytes(source, a.offset, a.size as u64)?;
+            let actual = read_bytes(target, b.offset, b.size as u64)?;
+            if expected != actual
+                || a.is_sync != b.is_sync
+                || a.duration != b.duration
+                || a.cts - a.dts as i64 != b.cts - b.dts as i64
+            {
+                return Err(MediaError::InvalidFormat(format!(
+                    "verification failed: track {} sample {} differs",
+                    track_id, a.number
+                )));
+            }
+            verified += 1;
+        }
+    }
+    Ok(verified)
+}
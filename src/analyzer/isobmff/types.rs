@@ -12,11 +12,17 @@ impl BoxType {
     pub fn as_str(&self) -> &str {
         std::str::from_utf8(&self.0).unwrap_or("????")
     }
+
+    pub fn as_bytes(&self) -> &[u8; 4] {
+        &self.0
+    }
 }
 
 impl fmt::Display for BoxType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
+        // 非 UTF-8 的 4CC (如 ©nam) 按 Latin-1 显示
+        let four_cc: String = self.0.iter().map(|&b| b as char).collect();
+        write!(f, "{}", four_cc)
     }
 }
 
@@ -32,6 +38,11 @@ impl Fixed16_16 {
     pub fn as_f32(&self) -> f32 {
         (self.0 as f32) / 65536.0
     }
+
+    /// The value as stored
+    pub fn raw(&self) -> i32 {
+        self.0
+    }
 }
 
 /// Matrix for video transformation
@@ -55,7 +66,6 @@ impl Mp4DateTime {
         Self(seconds_since_1904)
     }
 
-    #[allow(dead_code)]
     pub fn as_secs(&self) -> u64 {
         self.0
     }
@@ -0,0 +1,526 @@
+use serde::Serialize;
+
+use super::boxes::{Box, BoxData, BoxInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One rule violation
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// Stable rule identifier, e.g. "missing-box"
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// File offset of the offending box
+    pub offset: u64,
+    /// Location in the box tree, e.g. "moov/trak[2]/mdia/minf/stbl/stco"
+    pub path: String,
+    pub message: String,
+}
+
+/// All findings of a validation run
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub file_size: u64,
+    pub findings: Vec<Finding>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl ValidationReport {
+    /// 0 when no finding reaches `fail_on`, 2 otherwise (1 is used for I/O failures)
+    pub fn exit_code(&self, fail_on: Severity) -> i32 {
+        if self.findings.iter().any(|f| f.severity >= fail_on) {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// Runs every rule over the parsed box tree
+pub fn validate(boxes: &[Box], file_size: u64) -> ValidationReport {
+    let mut validator = Validator {
+        findings: Vec::new(),
+    };
+    validator.check_top_level(boxes, file_size);
+    for (b, path) in indexed(boxes, "") {
+        validator.check_box(b, &path);
+    }
+
+    let mut findings = validator.findings;
+    findings.sort_by_key(|f| f.offset);
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Warning)
+        .count();
+    ValidationReport {
+        file_size,
+        findings,
+        errors,
+        warnings,
+    }
+}
+
+struct Validator {
+    findings: Vec<Finding>,
+}
+
+/// Pairs each box with its path; repeated types get a 1-based `[n]` suffix
+pub(super) fn indexed<'a>(boxes: &'a [Box], parent: &str) -> Vec<(&'a Box, String)> {
+    boxes
+        .iter()
+        .map(|b| {
+            let box_type = b.box_type();
+            let same: Vec<_> = boxes
+                .iter()
+                .filter(|other| other.box_type() == box_type)
+                .collect();
+            let mut name = box_type.to_string();
+            if same.len() > 1 {
+                let position = same
+                    .iter()
+                    .position(|other| std::ptr::eq(*other, b))
+                    .unwrap_or(0);
+                name = format!("{}[{}]", name, position + 1);
+            }
+            let path = if parent.is_empty() {
+                name
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            (b, path)
+        })
+        .collect()
+}
+
+impl Validator {
+    fn report(
+        &mut self,
+        rule: &'static str,
+        severity: Severity,
+        b: &Box,
+        path: &str,
+        message: String,
+    ) {
+        self.findings.push(Finding {
+            rule,
+            severity,
+            offset: b.offset(),
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn check_top_level(&mut self, boxes: &[Box], file_size: u64) {
+        let Some(first) = boxes.first() else {
+            self.findings.push(Finding {
+                rule: "no-boxes",
+                severity: Severity::Error,
+                offset: 0,
+                path: String::new(),
+                message: "no box could be parsed".to_string(),
+            });
+            return;
+        };
+
+        // ftyp (或分段文件的 styp) 应为第一个 box
+        let ftyp = boxes
+            .iter()
+            .position(|b| matches!(b.box_type().as_str(), "ftyp" | "styp"));
+        match ftyp {
+            None => self.report(
+                "missing-box",
+                Severity::Warning,
+                first,
+                "",
+                "no ftyp box, readers have to guess the brand".to_string(),
+            ),
+            Some(index) if index > 0 => {
+                let b = &boxes[index];
+                self.report(
+                    "box-order",
+                    Severity::Error,
+                    b,
+                    b.box_type().as_str(),
+                    format!(
+                        "{} must be the first box but is preceded by {}",
+                        b.box_type(),
+                        first.box_type()
+                    ),
+                );
+            }
+            _ => {}
+        }
+
+        let movies: Vec<&Box> = boxes
+            .iter()
+            .filter(|b| b.box_type().as_str() == "moov")
+            .collect();
+        let segment = boxes.iter().any(|b| b.box_type().as_str() == "styp");
+        if movies.is_empty() && !segment {
+            self.report(
+                "missing-box",
+                Severity::Error,
+                first,
+                "",
+                "no moov box".to_string(),
+            );
+        }
+        for extra in movies.iter().skip(1) {
+            self.report(
+                "duplicate-box",
+                Severity::Error,
+                extra,
+                "moov",
+                format!("{} moov boxes, exactly one is allowed", movies.len()),
+            );
+        }
+
+        let mut end = 0;
+        for b in boxes {
+            let box_end = b.offset() + b.size();
+            if box_end > file_size {
+                let message = if b.box_type().as_str() == "mdat" {
+                    format!(
+                        "mdat claims {} bytes but the file ends {} bytes earlier (truncated file?)",
+                        b.size(),
+                        box_end - file_size
+                    )
+                } else {
+                    format!(
+                        "{} ends at {} beyond the end of the file ({})",
+                        b.box_type(),
+                        box_end,
+                        file_size
+                    )
+                };
+                self.report(
+                    "size-overrun",
+                    Severity::Error,
+                    b,
+                    b.box_type().as_str(),
+                    message,
+                );
+            }
+            end = end.max(box_end);
+        }
+        if end < file_size {
+            self.findings.push(Finding {
+                rule: "trailing-data",
+                severity: Severity::Warning,
+                offset: end,
+                path: String::new(),
+                message: format!(
+                    "{} bytes after the last parsable box are not a valid box",
+                    file_size - end
+                ),
+            });
+        }
+
+        if let Some(moov) = movies.first() {
+            let mdats: Vec<(u64, u64)> = boxes
+                .iter()
+                .filter(|b| b.box_type().as_str() == "mdat")
+                .map(|b| (b.offset() + b.header_size(), b.offset() + b.size()))
+                .collect();
+            for (trak, path) in indexed(moov.children(), "moov") {
+                if trak.box_type().as_str() == "trak" {
+                    self.check_chunk_offsets(trak, &path, &mdats);
+                }
+            }
+        }
+    }
+
+    fn check_box(&mut self, b: &Box, path: &str) {
+        let end = b.offset() + b.size();
+        let children = indexed(b.children(), path);
+        for (child, child_path) in &children {
+            if child.offset() + child.size() > end {
+                self.report(
+                    "size-overrun",
+                    Severity::Error,
+                    child,
+                    child_path,
+                    format!(
+                        "{} ends at {} but its parent {} ends at {}",
+                        child.box_type(),
+                        child.offset() + child.size(),
+                        b.box_type(),
+                        end
+                    ),
+                );
+            }
+        }
+
+        if let BoxData::Generic(_) = b.data() {
+            if !matches!(b.box_type().as_str(), "free" | "skip" | "wide" | "mdat") {
+                self.report(
+                    "unchecked-box",
+                    Severity::Info,
+                    b,
+                    path,
+                    format!(
+                        "{} is not parsed, its contents are not validated",
+                        b.box_type()
+                    ),
+                );
+            }
+        }
+
+        self.check_mandatory(b, path);
+        self.check_entry_count(b, path);
+        match b.box_type().as_str() {
+            "stbl" => self.check_sample_table(b, path),
+            "stsc" => self.check_stsc_order(b, path),
+            _ => {}
+        }
+
+        for (child, child_path) in children {
+            self.check_box(child, &child_path);
+        }
+    }
+
+    fn check_mandatory(&mut self, b: &Box, path: &str) {
+        let required: &[&[&str]] = match b.box_type().as_str() {
+            "moov" => &[&["mvhd"]],
+            "trak" => &[&["tkhd"], &["mdia"]],
+            "mdia" => &[&["mdhd"], &["hdlr"], &["minf"]],
+            "minf" => &[&["dinf"], &["stbl"]],
+            "stbl" => &[
+                &["stsd"],
+                &["stts"],
+                &["stsc"],
+                &["stsz", "stz2"],
+                &["stco", "co64"],
+            ],
+            "moof" => &[&["mfhd"]],
+            "traf" => &[&["tfhd"]],
+            _ => &[],
+        };
+        for alternatives in required {
+            if !alternatives.iter().any(|name| b.child(name).is_some()) {
+                self.report(
+                    "missing-box",
+                    Severity::Error,
+                    b,
+                    path,
+                    format!("{} has no {} box", b.box_type(), alternatives.join(" or ")),
+                );
+            }
+        }
+    }
+
+    /// Compares the declared payload size with the size implied by the entries
+    fn check_entry_count(&mut self, b: &Box, path: &str) {
+        let implied = match b.data() {
+            BoxData::TimeToSample(stts) => stts.content_size(),
+            BoxData::SampleToChunk(stsc) => stsc.content_size(),
+            BoxData::SampleSize(stsz) => stsz.content_size(),
+            BoxData::ChunkOffset(stco) => stco.content_size(b.box_type().as_str() == "co64"),
+            BoxData::CompositionOffset(ctts) => ctts.content_size(),
+            BoxData::SyncSample(stss) => stss.content_size(),
+            BoxData::EditList(elst) => elst.content_size(),
+            BoxData::SampleToGroup(sbgp) => sbgp.content_size(),
+            _ => return,
+        };
+        let payload = b.size().saturating_sub(b.header_size());
+        if implied != payload {
+            let severity = if implied > payload {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            self.report(
+                "entry-count",
+                severity,
+                b,
+                path,
+                format!(
+                    "entry count implies a {} byte payload but the box holds {} bytes",
+                    implied, payload
+                ),
+            );
+        }
+    }
+
+    fn check_stsc_order(&mut self, b: &Box, path: &str) {
+        let BoxData::SampleToChunk(stsc) = b.data() else {
+            return;
+        };
+        let entries = stsc.entries();
+        if let Some(first) = entries.first() {
+            if first.first_chunk() != 1 {
+                self.report(
+                    "stsc-order",
+                    Severity::Error,
+                    b,
+                    path,
+                    format!(
+                        "first entry starts at chunk {} instead of 1",
+                        first.first_chunk()
+                    ),
+                );
+            }
+        }
+        for (i, pair) in entries.windows(2).enumerate() {
+            if pair[1].first_chunk() <= pair[0].first_chunk() {
+                self.report(
+                    "stsc-order",
+                    Severity::Error,
+                    b,
+                    path,
+                    format!(
+                        "entry[{}] first_chunk {} is not greater than entry[{}] first_chunk {}",
+                        i + 1,
+                        pair[1].first_chunk(),
+                        i,
+                        pair[0].first_chunk()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_sample_table(&mut self, stbl: &Box, path: &str) {
+        let stsz = match stbl.child("stsz").map(|b| b.data()) {
+            Some(BoxData::SampleSize(stsz)) => stsz.sample_count() as u64,
+            _ => return,
+        };
+
+        if let Some(BoxData::TimeToSample(stts)) = stbl.child("stts").map(|b| b.data()) {
+            let total: u64 = stts.entries().iter().map(|e| e.sample_count() as u64).sum();
+            if total != stsz {
+                self.report(
+                    "sample-count-mismatch",
+                    Severity::Error,
+                    stbl,
+                    path,
+                    format!("stsz declares {} samples but stts covers {}", stsz, total),
+                );
+            }
+        }
+
+        if let Some(BoxData::CompositionOffset(ctts)) = stbl.child("ctts").map(|b| b.data()) {
+            let total: u64 = ctts.entries().iter().map(|e| e.sample_count() as u64).sum();
+            if total != stsz {
+                self.report(
+                    "sample-count-mismatch",
+                    Severity::Warning,
+                    stbl,
+                    path,
+                    format!("stsz declares {} samples but ctts covers {}", stsz, total),
+                );
+            }
+        }
+
+        // stsc 展开到所有 chunk 后的样本数
+        let chunk_count = match stbl
+            .child("stco")
+            .or_else(|| stbl.child("co64"))
+            .map(|b| b.data())
+        {
+            Some(BoxData::ChunkOffset(stco)) => stco.offsets().len() as u64,
+            _ => return,
+        };
+        if let Some(BoxData::SampleToChunk(stsc)) = stbl.child("stsc").map(|b| b.data()) {
+            let entries = stsc.entries();
+            let mut total = 0u64;
+            for (i, entry) in entries.iter().enumerate() {
+                let first = entry.first_chunk() as u64;
+                let next = entries
+                    .get(i + 1)
+                    .map(|e| e.first_chunk() as u64)
+                    .unwrap_or(chunk_count + 1);
+                total += next.saturating_sub(first) * entry.samples_per_chunk() as u64;
+            }
+            if total != stsz {
+                self.report(
+                    "sample-count-mismatch",
+                    Severity::Error,
+                    stbl,
+                    path,
+                    format!(
+                        "stsz declares {} samples but stsc/stco describe {}",
+                        stsz, total
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_chunk_offsets(&mut self, trak: &Box, path: &str, mdats: &[(u64, u64)]) {
+        let Some(stbl) = trak
+            .child("mdia")
+            .and_then(|b| b.child("minf"))
+            .and_then(|b| b.child("stbl"))
+        else {
+            return;
+        };
+        let Some(chunks) = stbl.child("stco").or_else(|| stbl.child("co64")) else {
+            return;
+        };
+        let BoxData::ChunkOffset(stco) = chunks.data() else {
+            return;
+        };
+
+        let outside: Vec<(usize, u64)> = stco
+            .offsets()
+            .iter()
+            .enumerate()
+            .filter(|(_, &offset)| {
+                !mdats
+                    .iter()
+                    .any(|&(start, end)| offset >= start && offset < end)
+            })
+            .map(|(i, &offset)| (i, offset))
+            .collect();
+        if outside.is_empty() {
+            return;
+        }
+
+        // 只列出前几个越界的 chunk
+        let listed: Vec<String> = outside
+            .iter()
+            .take(5)
+            .map(|(i, offset)| format!("chunk {} at {}", i + 1, offset))
+            .collect();
+        let more = if outside.len() > 5 {
+            format!(" and {} more", outside.len() - 5)
+        } else {
+            String::new()
+        };
+        self.report(
+            "chunk-offset-outside-mdat",
+            Severity::Error,
+            chunks,
+            &format!("{}/mdia/minf/stbl/{}", path, chunks.box_type()),
+            format!(
+                "{} of {} chunk offsets point outside every mdat: {}{}",
+                outside.len(),
+                stco.offsets().len(),
+                listed.join(", "),
+                more
+            ),
+        );
+    }
+}
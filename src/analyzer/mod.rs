@@ -4,10 +4,24 @@ use std::error::Error;
 use detector::{DetectionStrategy, FileFormat, FormatDetector};
 use serde::Serialize;
 
+pub mod aac;
+pub mod bitrate;
+pub mod bitstream;
+pub mod captions;
 pub mod detector;
+pub mod elementary;
+pub mod extract;
 pub mod isobmff;
+pub mod privacy;
 pub mod rmff;
-
+pub mod sei;
+
+use isobmff::av_sync::AvSyncReport;
+use isobmff::coverage::CoverageReport;
+use isobmff::interleave::InterleaveReport;
+use isobmff::sample_index::TrackSampleIndex;
+use isobmff::timeline::TrackTimeline;
+use isobmff::timing::TimingReport;
 use isobmff::IsobmffAnalyzer;
 use rmff::RmffAnalyzer;
 
@@ -24,6 +38,25 @@ pub struct MediaInfo {
     pub audio_streams: Vec<AudioStream>,
     pub metadata: HashMap<String, String>,
     pub structure: Vec<ElementInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub samples: Vec<TrackSampleIndex>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timelines: Vec<TrackTimeline>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub av_sync: Option<AvSyncReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<TimingReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interleave: Option<InterleaveReport>,
+}
+
+/// Optional, potentially expensive parts of an analysis
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnalysisOptions {
+    /// Build the per-sample index of every track (ISOBMFF only)
+    pub list_samples: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -106,6 +139,7 @@ pub struct AudioStream {
 pub struct DefaultAnalyzer {
     debug: bool,
     detector: FormatDetector,
+    options: AnalysisOptions,
 }
 
 impl DefaultAnalyzer {
@@ -113,6 +147,7 @@ impl DefaultAnalyzer {
         Self {
             debug,
             detector: FormatDetector::new(DetectionStrategy::Auto),
+            options: AnalysisOptions::default(),
         }
     }
 
@@ -120,8 +155,13 @@ impl DefaultAnalyzer {
         Self {
             debug,
             detector: FormatDetector::new(strategy),
+            options: AnalysisOptions::default(),
         }
     }
+
+    pub fn set_options(&mut self, options: AnalysisOptions) {
+        self.options = options;
+    }
 }
 
 impl MediaAnalyzer for DefaultAnalyzer {
@@ -137,6 +177,7 @@ impl MediaAnalyzer for DefaultAnalyzer {
             FileFormat::Isobmff => {
                 let mut analyzer = IsobmffAnalyzer::new(file_path)?;
                 analyzer.set_debug(self.debug);
+                analyzer.set_options(self.options);
                 Ok(analyzer.analyze()?)
             }
         }
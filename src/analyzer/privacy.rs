@@ -0,0 +1,416 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Kind of personally identifying data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Location,
+    SerialNumber,
+    Owner,
+    Xmp,
+    Exif,
+    Comment,
+    Device,
+}
+
+impl Category {
+    pub fn severity(self) -> Severity {
+        match self {
+            Category::Location | Category::SerialNumber => Severity::High,
+            Category::Owner | Category::Xmp | Category::Exif => Severity::Medium,
+            Category::Comment | Category::Device => Severity::Low,
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            Category::Location => "GPS location",
+            Category::SerialNumber => "device serial number",
+            Category::Owner => "owner or author name",
+            Category::Xmp => "XMP packet",
+            Category::Exif => "Exif block",
+            Category::Comment => "free-text comment",
+            Category::Device => "device make, model or software",
+        }
+    }
+}
+
+/// One piece of identifying data and where it is stored
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacyFinding {
+    pub category: Category,
+    pub severity: Severity,
+    /// File offset of the box or chunk holding the data
+    pub offset: u64,
+    /// Location in the structure, e.g. "moov/udta/meta/ilst/©xyz"
+    pub path: String,
+    /// Tag, key or field name the value was found under
+    pub key: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub value: String,
+    pub message: String,
+}
+
+impl PrivacyFinding {
+    pub fn new(category: Category, offset: u64, path: String, key: &str, value: &str) -> Self {
+        Self {
+            category,
+            severity: category.severity(),
+            offset,
+            path,
+            key: key.to_string(),
+            value: shorten(value.trim_matches(char::from(0)).trim()),
+            message: category.message().to_string(),
+        }
+    }
+}
+
+/// All findings of a privacy audit
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacyReport {
+    pub format: String,
+    pub findings: Vec<PrivacyFinding>,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+}
+
+impl PrivacyReport {
+    pub fn new(format: &str, mut findings: Vec<PrivacyFinding>) -> Self {
+        findings.sort_by_key(|f| f.offset);
+        let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+        Self {
+            format: format.to_string(),
+            high: count(Severity::High),
+            medium: count(Severity::Medium),
+            low: count(Severity::Low),
+            findings,
+        }
+    }
+
+    /// 0 when no finding reaches `fail_on`, 2 otherwise (1 is used for I/O failures)
+    pub fn exit_code(&self, fail_on: Severity) -> i32 {
+        if self.findings.iter().any(|f| f.severity >= fail_on) {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// Values are reported for review, long ones (XMP, comments) only in part
+fn shorten(value: &str) -> String {
+    const MAX_CHARS: usize = 120;
+    if value.chars().count() <= MAX_CHARS {
+        value.to_string()
+    } else {
+        let head: String = value.chars().take(MAX_CHARS).collect();
+        format!("{}...", head)
+    }
+}
+
+/// Classifies a metadata key (a 4CC, a QuickTime `mdta` key or a freeform name)
+pub fn classify_key(key: &str) -> Option<Category> {
+    match key {
+        "©xyz" | "loci" => return Some(Category::Location),
+        "©ART" | "©aut" | "©prf" | "©wrt" | "©cpy" | "aART" | "auth" | "perf" | "cprt" | "ownr" => {
+            return Some(Category::Owner)
+        }
+        "©cmt" | "©des" | "©inf" | "desc" | "ldes" | "dscp" => return Some(Category::Comment),
+        "©mak" | "©mod" | "©swr" => return Some(Category::Device),
+        _ => {}
+    }
+    let key = key.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| key.contains(word));
+    if has(&["location", "gps", "iso6709"]) {
+        Some(Category::Location)
+    } else if has(&["serial", "camera.identifier"]) {
+        Some(Category::SerialNumber)
+    } else if has(&[
+        "author",
+        "artist",
+        "owner",
+        "creator",
+        "performer",
+        "copyright",
+    ]) {
+        Some(Category::Owner)
+    } else if has(&["comment", "description", "information", "notes"]) {
+        Some(Category::Comment)
+    } else if has(&["make", "manufacturer", "model", "software"]) {
+        Some(Category::Device)
+    } else {
+        None
+    }
+}
+
+/// XMP properties worth reporting on their own, besides the packet itself
+const XMP_PROPERTIES: &[(&str, Category)] = &[
+    ("exif:GPSLatitude", Category::Location),
+    ("exif:GPSLongitude", Category::Location),
+    ("aux:SerialNumber", Category::SerialNumber),
+    ("exifEX:BodySerialNumber", Category::SerialNumber),
+    ("exifEX:LensSerialNumber", Category::SerialNumber),
+    ("aux:OwnerName", Category::Owner),
+    ("exifEX:CameraOwnerName", Category::Owner),
+    ("dc:creator", Category::Owner),
+    ("dc:rights", Category::Owner),
+    ("dc:description", Category::Comment),
+    ("exif:UserComment", Category::Comment),
+    ("tiff:Make", Category::Device),
+    ("tiff:Model", Category::Device),
+    ("xmp:CreatorTool", Category::Device),
+];
+
+/// Identifying properties of an XMP packet as (category, property, value)
+pub fn scan_xmp(packet: &[u8]) -> Vec<(Category, String, String)> {
+    let text = String::from_utf8_lossy(packet);
+    let mut found = Vec::new();
+    for &(property, category) in XMP_PROPERTIES {
+        if let Some(position) = text.find(property) {
+            let value = xmp_value(&text[position + property.len()..]);
+            found.push((category, property.to_string(), value));
+        }
+    }
+    found
+}
+
+/// Value after a property name: `name="value"` or `<name>...value</...>`
+fn xmp_value(rest: &str) -> String {
+    let rest = rest.trim_start();
+    if let Some(quoted) = rest.strip_prefix('=') {
+        let quoted = quoted.trim_start();
+        let quote = quoted.chars().next().unwrap_or('"');
+        return quoted[quote.len_utf8().min(quoted.len())..]
+            .split(quote)
+            .next()
+            .unwrap_or_default()
+            .to_string();
+    }
+    let Some(mut rest) = rest.strip_prefix('>') else {
+        return String::new();
+    };
+    // 跳过 rdf:Seq / rdf:Alt / rdf:li 等嵌套标签
+    loop {
+        rest = rest.trim_start();
+        match rest.strip_prefix('<') {
+            Some(tag) if !tag.starts_with('/') => {
+                rest = tag
+                    .split_once('>')
+                    .map(|(_, after)| after)
+                    .unwrap_or_default();
+            }
+            _ => break,
+        }
+    }
+    rest.split('<')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Identifying tags of an Exif block as (category, tag name, value). The block may start with
+/// the HEIF header offset and/or `Exif\0\0` before the TIFF header.
+pub fn scan_exif(data: &[u8]) -> Vec<(Category, String, String)> {
+    let Some(start) = (0..data.len().saturating_sub(4))
+        .take(64)
+        .find(|&i| matches!(&data[i..i + 4], b"II*\0" | b"MM\0*"))
+    else {
+        return Vec::new();
+    };
+    let tiff = Tiff {
+        data: &data[start..],
+        little_endian: data[start] == b'I',
+    };
+    let mut found = Vec::new();
+    let Some(ifd0) = tiff.u32(4) else {
+        return found;
+    };
+    for entry in tiff.entries(ifd0) {
+        match entry.tag {
+            0x010f => found.push((Category::Device, "Make".to_string(), tiff.text(&entry))),
+            0x0110 => found.push((Category::Device, "Model".to_string(), tiff.text(&entry))),
+            0x0131 => found.push((Category::Device, "Software".to_string(), tiff.text(&entry))),
+            0x010e => found.push((
+                Category::Comment,
+                "ImageDescription".to_string(),
+                tiff.text(&entry),
+            )),
+            0x013b => found.push((Category::Owner, "Artist".to_string(), tiff.text(&entry))),
+            0x8298 => found.push((Category::Owner, "Copyright".to_string(), tiff.text(&entry))),
+            0xc62f => found.push((
+                Category::SerialNumber,
+                "CameraSerialNumber".to_string(),
+                tiff.text(&entry),
+            )),
+            // 子 IFD 指针是内联的 LONG 值
+            0x8769 => {
+                let ifd = tiff.u32(entry.value_offset).unwrap_or(0);
+                for entry in tiff.entries(ifd) {
+                    match entry.tag {
+                        0xa430 => found.push((
+                            Category::Owner,
+                            "CameraOwnerName".to_string(),
+                            tiff.text(&entry),
+                        )),
+                        0xa431 => found.push((
+                            Category::SerialNumber,
+                            "BodySerialNumber".to_string(),
+                            tiff.text(&entry),
+                        )),
+                        0xa435 => found.push((
+                            Category::SerialNumber,
+                            "LensSerialNumber".to_string(),
+                            tiff.text(&entry),
+                        )),
+                        0x9286 => found.push((
+                            Category::Comment,
+                            "UserComment".to_string(),
+                            // 前 8 字节是字符集标识
+                            tiff.text(&entry).get(8..).unwrap_or_default().to_string(),
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+            0x8825 => {
+                let ifd = tiff.u32(entry.value_offset).unwrap_or(0);
+                if let Some(position) = tiff.gps_position(ifd) {
+                    found.push((Category::Location, "GPSInfo".to_string(), position));
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+struct IfdEntry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    /// Offset of the value: the entry's own value field when it fits in 4 bytes
+    value_offset: u32,
+}
+
+/// Minimal TIFF reader for the Exif tags above
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn bytes<const N: usize>(&self, offset: u32) -> Option<[u8; N]> {
+        let start = offset as usize;
+        self.data.get(start..start + N)?.try_into().ok()
+    }
+
+    fn u16(&self, offset: u32) -> Option<u16> {
+        let bytes = self.bytes::<2>(offset)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: u32) -> Option<u32> {
+        let bytes = self.bytes::<4>(offset)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn entries(&self, ifd: u32) -> Vec<IfdEntry> {
+        let count = self.u16(ifd).unwrap_or(0) as u32;
+        (0..count)
+            .map_while(|i| {
+                let at = ifd + 2 + i * 12;
+                let field_type = self.u16(at + 2)?;
+                let count = self.u32(at + 4)?;
+                let size = count.saturating_mul(type_size(field_type));
+                Some(IfdEntry {
+                    tag: self.u16(at)?,
+                    field_type,
+                    count,
+                    value_offset: if size <= 4 { at + 8 } else { self.u32(at + 8)? },
+                })
+            })
+            .collect()
+    }
+
+    fn text(&self, entry: &IfdEntry) -> String {
+        let start = entry.value_offset as usize;
+        let end = (start + entry.count as usize).min(self.data.len());
+        self.data
+            .get(start..end)
+            .map(|bytes| {
+                String::from_utf8_lossy(bytes)
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Degrees of a GPS coordinate stored as three rationals (d, m, s)
+    fn degrees(&self, entry: &IfdEntry) -> Option<f64> {
+        if entry.field_type != 5 || entry.count < 3 {
+            return None;
+        }
+        let rational = |i: u32| {
+            let at = entry.value_offset + i * 8;
+            let (num, den) = (self.u32(at)?, self.u32(at + 4)?);
+            Some(if den == 0 {
+                0.0
+            } else {
+                num as f64 / den as f64
+            })
+        };
+        Some(rational(0)? + rational(1)? / 60.0 + rational(2)? / 3600.0)
+    }
+
+    /// "lat, lon" from the GPS IFD, if it holds a position
+    fn gps_position(&self, ifd: u32) -> Option<String> {
+        let entries = self.entries(ifd);
+        let find = |tag: u16| entries.iter().find(|entry| entry.tag == tag);
+        let mut latitude = self.degrees(find(0x0002)?)?;
+        let mut longitude = self.degrees(find(0x0004)?)?;
+        if find(0x0001).map(|entry| self.text(entry)).as_deref() == Some("S") {
+            latitude = -latitude;
+        }
+        if find(0x0003).map(|entry| self.text(entry)).as_deref() == Some("W") {
+            longitude = -longitude;
+        }
+        Some(format!("{:.6}, {:.6}", latitude, longitude))
+    }
+}
+
+fn type_size(field_type: u16) -> u32 {
+    match field_type {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    }
+}
@@ -18,6 +18,18 @@ impl ContChunk {
         }
     }
 
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn copyright(&self) -> &str {
+        &self.copyright
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
     pub fn description(&self) -> &str {
         "Content Description"
     }
@@ -47,6 +47,26 @@ impl MdprChunk {
         }
     }
 
+    pub fn stream_number(&self) -> u16 {
+        self.stream_number
+    }
+
+    pub fn max_bit_rate(&self) -> u32 {
+        self.max_bit_rate
+    }
+
+    pub fn avg_bit_rate(&self) -> u32 {
+        self.avg_bit_rate
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn type_specific_data(&self) -> &[u8] {
+        &self.type_specific_data
+    }
+
     pub fn description(&self) -> &str {
         "Media Properties"
     }
@@ -65,6 +65,10 @@ impl Chunk {
     pub fn add_child(&mut self, child: Chunk) {
         self.children.push(child);
     }
+
+    pub fn data(&self) -> &ChunkData {
+        &self.data
+    }
 }
 
 impl ChunkInfo for Chunk {
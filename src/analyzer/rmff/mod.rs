@@ -1,25 +1,35 @@
-use crate::analyzer::rmff::chunks::ChunkInfo;
+use crate::analyzer::bitrate::{DeclaredBitrate, TimedUnit, TrackBitrate};
+use crate::analyzer::extract::{AudioSpecificConfig, ExtractReport, StreamFormat};
+use crate::analyzer::isobmff::faststart::same_file;
+use crate::analyzer::privacy::{Category, PrivacyFinding};
+use crate::analyzer::rmff::chunks::{ChunkData, ChunkInfo};
 use crate::analyzer::{ElementInfo, MediaInfo};
 use crate::error::MediaError;
 use crate::reader::Reader;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 mod chunk_parser;
 pub mod chunks;
 use chunk_parser::ChunkParser;
 
 pub struct RmffAnalyzer {
+    path: PathBuf,
     parser: ChunkParser,
 }
 
 impl RmffAnalyzer {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, MediaError> {
-        let file = File::open(path)?;
+        let file = File::open(&path)?;
         let reader = Reader::new(file);
         let parser = ChunkParser::new(reader);
 
-        Ok(Self { parser })
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            parser,
+        })
     }
 
     pub fn set_debug(&mut self, debug: bool) {
@@ -36,9 +46,202 @@ impl RmffAnalyzer {
             audio_streams: Vec::new(),
             metadata: Default::default(),
             structure: Self::convert_to_elements(&chunks),
+            samples: Vec::new(),
+            timelines: Vec::new(),
+            av_sync: None,
+            timing: None,
+            coverage: None,
+            interleave: None,
         })
     }
 
+    /// Author, copyright and comment fields of the CONT chunks
+    pub fn privacy_audit(&mut self) -> Result<Vec<PrivacyFinding>, MediaError> {
+        let chunks = self.parser.parse_chunks()?;
+        let mut findings = Vec::new();
+        for chunk in &chunks {
+            let ChunkData::Cont(cont) = chunk.data() else {
+                continue;
+            };
+            let fields = [
+                (Category::Owner, "author", cont.author()),
+                (Category::Owner, "copyright", cont.copyright()),
+                (Category::Comment, "comment", cont.comment()),
+            ];
+            for (category, key, value) in fields {
+                if !value.trim_matches(char::from(0)).trim().is_empty() {
+                    findings.push(PrivacyFinding::new(
+                        category,
+                        chunk.offset(),
+                        chunk.chunk_type().to_string(),
+                        key,
+                        value,
+                    ));
+                }
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Writes the AAC frames of RealAudio stream `stream` to `output` with ADTS headers
+    pub fn extract_stream(
+        &mut self,
+        stream: u16,
+        output: &Path,
+    ) -> Result<ExtractReport, MediaError> {
+        if same_file(&self.path, output) {
+            return Err(MediaError::InvalidFormat(
+                "output must not be the input file".to_string(),
+            ));
+        }
+        let chunks = self.parser.parse_chunks()?;
+        let mdpr = chunks
+            .iter()
+            .find_map(|chunk| match chunk.data() {
+                ChunkData::Mdpr(mdpr) if mdpr.stream_number() == stream => Some(mdpr),
+                _ => None,
+            })
+            .ok_or_else(|| MediaError::InvalidFormat(format!("no stream number {}", stream)))?;
+        let (codec, asc) = realaudio_codec(mdpr.type_specific_data()).ok_or_else(|| {
+            MediaError::UnsupportedFormat(format!(
+                "extracting {} streams is not supported",
+                mdpr.mime_type()
+            ))
+        })?;
+        let config = match (codec.as_str(), asc) {
+            ("raac" | "racp", Some(asc)) => AudioSpecificConfig::parse(asc)
+                .filter(AudioSpecificConfig::fits_adts)
+                .ok_or_else(|| {
+                    MediaError::UnsupportedFormat(
+                        "the AAC configuration cannot be carried in ADTS".to_string(),
+                    )
+                })?,
+            _ => {
+                return Err(MediaError::UnsupportedFormat(format!(
+                    "extracting {} streams is not supported",
+                    codec
+                )))
+            }
+        };
+
+        let mut report = ExtractReport {
+            track_id: stream as u32,
+            codec,
+            format: StreamFormat::Adts,
+            samples: 0,
+            parameter_sets_inserted: 0,
+            bytes_written: 0,
+            warnings: Vec::new(),
+        };
+        let mut source = BufReader::new(File::open(&self.path)?);
+        let mut out = BufWriter::new(File::create(output)?);
+        let result = (|| {
+            for chunk in chunks.iter().filter(|c| c.chunk_type().as_str() == "DATA") {
+                // size(4) + 'DATA' + num_packets(4) + next_data_header(4)
+                let mut at = chunk.offset() + 16;
+                let end = chunk.offset() + chunk.size();
+                while let Some((packet_stream, payload, next)) =
+                    read_packet(&mut source, at, end, &mut report.warnings)?
+                {
+                    at = next;
+                    if packet_stream != stream {
+                        continue;
+                    }
+                    for frame in aac_frames(&payload, &mut report.warnings) {
+                        out.write_all(&config.adts_header(frame.len()))?;
+                        out.write_all(frame)?;
+                        report.samples += 1;
+                        report.bytes_written += 7 + frame.len() as u64;
+                    }
+                }
+            }
+            out.flush()?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(output);
+            return Err(e);
+        }
+        Ok(report)
+    }
+
+    /// Bitrate over time of every stream from its packet sizes and timestamps, with the
+    /// maximum and average bit rates of its MDPR chunk
+    pub fn bitrates(&mut self, windows: &[f64]) -> Result<Vec<TrackBitrate>, MediaError> {
+        let chunks = self.parser.parse_chunks()?;
+        let mut warnings = Vec::new();
+        let mut packets: BTreeMap<u16, Vec<(u32, u64)>> = BTreeMap::new();
+        let mut source = BufReader::new(File::open(&self.path)?);
+        for chunk in chunks.iter().filter(|c| c.chunk_type().as_str() == "DATA") {
+            // size(4) + 'DATA' + num_packets(4) + next_data_header(4)
+            let mut at = chunk.offset() + 16;
+            let end = chunk.offset() + chunk.size();
+            while let Some(header) = read_packet_header(&mut source, at, end, &mut warnings)? {
+                at += header.length;
+                packets
+                    .entry(header.stream)
+                    .or_default()
+                    .push((header.timestamp, header.length - header.header_len));
+            }
+        }
+
+        let streams: Vec<u16> = chunks
+            .iter()
+            .filter_map(|chunk| match chunk.data() {
+                ChunkData::Mdpr(mdpr) => Some(mdpr.stream_number()),
+                _ => None,
+            })
+            .collect();
+        for (stream, list) in packets.iter().filter(|(s, _)| !streams.contains(s)) {
+            warnings.push(format!(
+                "{} packets of stream {} have no MDPR chunk",
+                list.len(),
+                stream
+            ));
+        }
+
+        let mut result = Vec::new();
+        for chunk in &chunks {
+            let ChunkData::Mdpr(mdpr) = chunk.data() else {
+                continue;
+            };
+            let mut stream_packets = packets.remove(&mdpr.stream_number()).unwrap_or_default();
+            stream_packets.sort_by_key(|&(timestamp, _)| timestamp);
+            // 包没有时长, 以到同一流下一个不同时间戳的间隔代替
+            let units = stream_packets
+                .iter()
+                .enumerate()
+                .map(|(i, &(timestamp, bytes))| {
+                    let next = stream_packets[i + 1..]
+                        .iter()
+                        .map(|&(t, _)| t)
+                        .find(|&t| t > timestamp);
+                    TimedUnit {
+                        time: timestamp as f64 / 1000.0,
+                        duration: next.map_or(0.0, |t| (t - timestamp) as f64 / 1000.0),
+                        bytes,
+                    }
+                })
+                .collect();
+            let declared = vec![DeclaredBitrate {
+                source: "MDPR",
+                max: Some(mdpr.max_bit_rate() as u64),
+                average: Some(mdpr.avg_bit_rate() as u64),
+                buffer_size: None,
+            }];
+            let mut stream = TrackBitrate::new(
+                mdpr.stream_number() as u32,
+                mdpr.mime_type().to_string(),
+                units,
+                windows,
+                declared,
+            );
+            stream.warnings.splice(0..0, warnings.iter().cloned());
+            result.push(stream);
+        }
+        Ok(result)
+    }
+
     fn convert_to_elements(chunks: &[chunks::Chunk]) -> Vec<ElementInfo> {
         chunks
             .iter()
@@ -56,3 +259,117 @@ impl RmffAnalyzer {
             .collect()
     }
 }
+
+/// Codec 4CC and, for AAC, the AudioSpecificConfig of a RealAudio (`.ra\xfd`) stream header
+fn realaudio_codec(header: &[u8]) -> Option<(String, Option<&[u8]>)> {
+    if header.get(..4)? != b".ra\xfd" {
+        return None;
+    }
+    let field = |at: usize, len: usize| header.get(at..at + len);
+    match u16::from_be_bytes(field(4, 2)?.try_into().ok()?) {
+        4 => {
+            // 两个 Pascal 字符串: interleaver, codec
+            let interleaver_len = *header.get(56)? as usize;
+            let codec_at = 57 + interleaver_len;
+            let codec_len = *header.get(codec_at)? as usize;
+            let codec = String::from_utf8_lossy(field(codec_at + 1, codec_len)?).to_string();
+            Some((codec, None))
+        }
+        5 => {
+            let codec = String::from_utf8_lossy(field(66, 4)?).to_string();
+            // codecdata 的第一个字节不属于 AudioSpecificConfig
+            let asc = field(74, 4)
+                .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+                .filter(|&len| len > 1)
+                .and_then(|len| field(79, len - 1));
+            Some((codec, asc))
+        }
+        _ => None,
+    }
+}
+
+/// Header of a media packet in a DATA chunk
+struct PacketHeader {
+    stream: u16,
+    /// Milliseconds
+    timestamp: u32,
+    /// Whole packet, header included
+    length: u64,
+    header_len: u64,
+}
+
+/// Reads the header of the media packet at `at`; None at the end of the chunk or on an invalid
+/// header
+fn read_packet_header(
+    source: &mut BufReader<File>,
+    at: u64,
+    end: u64,
+    warnings: &mut Vec<String>,
+) -> Result<Option<PacketHeader>, MediaError> {
+    if at + 12 > end {
+        return Ok(None);
+    }
+    source.seek(SeekFrom::Start(at))?;
+    let mut header = [0u8; 12];
+    source.read_exact(&mut header)?;
+    let version = u16::from_be_bytes([header[0], header[1]]);
+    let length = u16::from_be_bytes([header[2], header[3]]) as u64;
+    let header_len = if version == 0 { 12 } else { 13 };
+    if version > 1 || length < header_len || at + length > end {
+        warnings.push(format!(
+            "invalid packet header at offset {}; the rest of the DATA chunk was skipped",
+            at
+        ));
+        return Ok(None);
+    }
+    Ok(Some(PacketHeader {
+        stream: u16::from_be_bytes([header[4], header[5]]),
+        timestamp: u32::from_be_bytes([header[6], header[7], header[8], header[9]]),
+        length,
+        header_len,
+    }))
+}
+
+/// Reads the media packet at `at`: (stream number, payload, offset of the next packet)
+fn read_packet(
+    source: &mut BufReader<File>,
+    at: u64,
+    end: u64,
+    warnings: &mut Vec<String>,
+) -> Result<Option<(u16, Vec<u8>, u64)>, MediaError> {
+    let Some(header) = read_packet_header(source, at, end, warnings)? else {
+        return Ok(None);
+    };
+    source.seek(SeekFrom::Start(at + header.header_len))?;
+    let mut payload = vec![0u8; (header.length - header.header_len) as usize];
+    source.read_exact(&mut payload)?;
+    Ok(Some((header.stream, payload, at + header.length)))
+}
+
+/// Splits a RealAudio AAC packet: a 16-bit header whose bits 4-7 count the frames, one 16-bit
+/// length per frame, then the frames
+fn aac_frames<'a>(payload: &'a [u8], warnings: &mut Vec<String>) -> Vec<&'a [u8]> {
+    let Some(header) = payload.get(..2) else {
+        return Vec::new();
+    };
+    let count = ((header[1] & 0xf0) >> 4) as usize;
+    let mut at = 2 + 2 * count;
+    let mut frames = Vec::with_capacity(count);
+    for i in 0..count {
+        let len = payload
+            .get(2 + 2 * i..4 + 2 * i)
+            .map_or(0, |len| u16::from_be_bytes([len[0], len[1]]) as usize);
+        match payload.get(at..at + len) {
+            Some(frame) if len > 0 => frames.push(frame),
+            _ => {
+                warnings.push(format!(
+                    "packet lists {} AAC frames but only {} fit its payload",
+                    count, i
+                ));
+                break;
+            }
+        }
+        at += len;
+    }
+    frames
+}
@@ -0,0 +1,420 @@
+use serde::Serialize;
+
+use crate::analyzer::bitstream::{rbsp, AvcPicTimingLayout, BitReader};
+
+/// One decoded SEI message (H.264 annex D, H.265 annex D)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SeiMessage {
+    /// H.264 pic_timing with its clock timestamps
+    PictureTiming {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pic_struct: Option<u8>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        timecodes: Vec<String>,
+    },
+    /// H.265 time_code
+    TimeCode {
+        timecodes: Vec<String>,
+    },
+    RecoveryPoint {
+        recovery_frame_cnt: u32,
+    },
+    /// Chromaticities as CIE 1931 xy, luminance in cd/m²
+    MasteringDisplay {
+        red: [f64; 2],
+        green: [f64; 2],
+        blue: [f64; 2],
+        white_point: [f64; 2],
+        max_luminance: f64,
+        min_luminance: f64,
+    },
+    ContentLightLevel {
+        max_cll: u16,
+        max_fall: u16,
+    },
+    /// SMPTE ST 2094-40 dynamic metadata carried in a T.35 payload
+    Hdr10Plus(Hdr10Plus),
+    /// Other user_data_registered_itu_t_t35 payloads
+    RegisteredT35 {
+        country_code: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        provider_code: Option<u16>,
+        description: &'static str,
+        size: u32,
+    },
+    UserDataUnregistered {
+        uuid: String,
+        /// The payload when it is text, as written by x264 and x265
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        size: u32,
+    },
+    Other {
+        payload_type: u32,
+        name: &'static str,
+        size: u32,
+    },
+}
+
+/// Dynamic tone mapping metadata of the first processing window
+#[derive(Debug, Clone, Serialize)]
+pub struct Hdr10Plus {
+    pub application_version: u8,
+    pub windows: u8,
+    /// cd/m²
+    pub targeted_max_luminance: u32,
+    /// Maximum of each colour component, in units of 0.00001 of 10000 cd/m²
+    pub maxscl: [u32; 3],
+    pub average_maxrgb: u32,
+    /// (percentage, percentile value) pairs of the maxRGB distribution
+    pub distribution: Vec<(u8, u32)>,
+    pub fraction_bright_pixels: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub knee_point: Option<(u16, u16)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bezier_anchors: Vec<u16>,
+}
+
+impl SeiMessage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SeiMessage::PictureTiming { .. } => "pic_timing",
+            SeiMessage::TimeCode { .. } => "time_code",
+            SeiMessage::RecoveryPoint { .. } => "recovery_point",
+            SeiMessage::MasteringDisplay { .. } => "mastering_display_colour_volume",
+            SeiMessage::ContentLightLevel { .. } => "content_light_level_info",
+            SeiMessage::Hdr10Plus(_) => "hdr10plus",
+            SeiMessage::RegisteredT35 { .. } => "user_data_registered_itu_t_t35",
+            SeiMessage::UserDataUnregistered { .. } => "user_data_unregistered",
+            SeiMessage::Other { name, .. } => name,
+        }
+    }
+}
+
+/// Splits an SEI NAL unit (header included) into its raw messages: (payloadType, payload)
+pub fn sei_payloads(nal: &[u8], hevc: bool) -> Vec<(u32, Vec<u8>)> {
+    let header = if hevc { 2 } else { 1 };
+    let Some(body) = nal.get(header..) else {
+        return Vec::new();
+    };
+    let data = rbsp(body);
+    let mut payloads = Vec::new();
+    let mut at = 0;
+    // 末尾只剩 rbsp_trailing_bits (0x80) 时结束
+    while at < data.len() && data[at] != 0x80 {
+        let mut read_value = || {
+            let mut value = 0u32;
+            loop {
+                let byte = *data.get(at)?;
+                at += 1;
+                value += byte as u32;
+                if byte != 0xff {
+                    return Some(value);
+                }
+            }
+        };
+        let (Some(payload_type), Some(size)) = (read_value(), read_value()) else {
+            break;
+        };
+        let Some(payload) = data.get(at..at + size as usize) else {
+            break;
+        };
+        payloads.push((payload_type, payload.to_vec()));
+        at += size as usize;
+    }
+    payloads
+}
+
+/// Decodes the messages of an SEI NAL unit. `pic_timing` describes the H.264 picture timing
+/// fields of the active SPS.
+pub fn parse_sei(
+    nal: &[u8],
+    hevc: bool,
+    pic_timing: Option<&AvcPicTimingLayout>,
+) -> Vec<SeiMessage> {
+    sei_payloads(nal, hevc)
+        .into_iter()
+        .map(|(payload_type, payload)| {
+            let decoded = match payload_type {
+                1 if !hevc => pic_timing.and_then(|layout| avc_picture_timing(&payload, layout)),
+                4 => registered_t35(&payload),
+                5 => user_data_unregistered(&payload),
+                6 => BitReader::new(&payload)
+                    .read_ue()
+                    .map(|recovery_frame_cnt| SeiMessage::RecoveryPoint { recovery_frame_cnt }),
+                136 if hevc => hevc_time_code(&payload),
+                137 => mastering_display(&payload),
+                144 => payload.get(..4).map(|b| SeiMessage::ContentLightLevel {
+                    max_cll: u16::from_be_bytes([b[0], b[1]]),
+                    max_fall: u16::from_be_bytes([b[2], b[3]]),
+                }),
+                _ => None,
+            };
+            decoded.unwrap_or(SeiMessage::Other {
+                payload_type,
+                name: payload_name(payload_type, hevc),
+                size: payload.len() as u32,
+            })
+        })
+        .collect()
+}
+
+fn payload_name(payload_type: u32, hevc: bool) -> &'static str {
+    match (payload_type, hevc) {
+        (0, _) => "buffering_period",
+        (1, _) => "pic_timing",
+        (2, _) => "pan_scan_rect",
+        (3, _) => "filler_payload",
+        (4, _) => "user_data_registered_itu_t_t35",
+        (5, _) => "user_data_unregistered",
+        (6, _) => "recovery_point",
+        (7, false) => "dec_ref_pic_marking_repetition",
+        (19, _) => "film_grain_characteristics",
+        (45, _) => "frame_packing_arrangement",
+        (47, _) => "display_orientation",
+        (128, true) => "structure_of_pictures_info",
+        (129, true) => "active_parameter_sets",
+        (130, true) => "decoding_unit_info",
+        (131, true) => "temporal_sub_layer_zero_index",
+        (132, true) => "decoded_picture_hash",
+        (133, true) => "scalable_nesting",
+        (134, true) => "region_refresh_info",
+        (136, true) => "time_code",
+        (137, _) => "mastering_display_colour_volume",
+        (144, _) => "content_light_level_info",
+        (147, _) => "alternative_transfer_characteristics",
+        (148, _) => "ambient_viewing_environment",
+        _ => "unknown",
+    }
+}
+
+/// pic_timing (D.1.3): the delays are skipped, the clock timestamps become timecodes
+fn avc_picture_timing(payload: &[u8], layout: &AvcPicTimingLayout) -> Option<SeiMessage> {
+    let mut r = BitReader::new(payload);
+    if let Some((cpb_removal_delay_length, dpb_output_delay_length)) = layout.delay_lengths {
+        r.skip((cpb_removal_delay_length + dpb_output_delay_length) as usize)?;
+    }
+    if !layout.pic_struct_present {
+        return Some(SeiMessage::PictureTiming {
+            pic_struct: None,
+            timecodes: Vec::new(),
+        });
+    }
+    let pic_struct = r.read(4)? as u8;
+    // 表 D-1: pic_struct 对应的 NumClockTS
+    let clock_timestamps = match pic_struct {
+        0..=2 => 1,
+        3 | 4 | 7 => 2,
+        5 | 6 | 8 => 3,
+        _ => 0,
+    };
+    let mut timecodes = Vec::new();
+    for _ in 0..clock_timestamps {
+        if !r.read_flag()? {
+            continue;
+        }
+        r.skip(2)?; // ct_type
+        r.read_flag()?; // nuit_field_based_flag
+        r.skip(5)?; // counting_type
+        let timecode = clock_timestamp(&mut r, 8)?;
+        if layout.time_offset_length > 0 {
+            r.skip(layout.time_offset_length as usize)?;
+        }
+        timecodes.push(timecode);
+    }
+    Some(SeiMessage::PictureTiming {
+        pic_struct: Some(pic_struct),
+        timecodes,
+    })
+}
+
+/// time_code (H.265 D.2.27)
+fn hevc_time_code(payload: &[u8]) -> Option<SeiMessage> {
+    let mut r = BitReader::new(payload);
+    let count = r.read(2)?;
+    let mut timecodes = Vec::new();
+    for _ in 0..count {
+        if !r.read_flag()? {
+            continue;
+        }
+        r.read_flag()?; // units_field_based_flag
+        r.skip(5)?; // counting_type
+        let timecode = clock_timestamp(&mut r, 9)?;
+        let time_offset_length = r.read(5)?;
+        r.skip(time_offset_length as usize)?;
+        timecodes.push(timecode);
+    }
+    Some(SeiMessage::TimeCode { timecodes })
+}
+
+/// The shared part of an H.264 clock timestamp and an H.265 time code, from
+/// full_timestamp_flag to hours; units that are not sent are shown as 0
+fn clock_timestamp(r: &mut BitReader, frame_bits: u32) -> Option<String> {
+    let full = r.read_flag()?;
+    r.read_flag()?; // discontinuity_flag
+    let dropped = r.read_flag()?;
+    let frames = r.read(frame_bits)?;
+    let (mut hours, mut minutes, mut seconds) = (0, 0, 0);
+    if full {
+        seconds = r.read(6)?;
+        minutes = r.read(6)?;
+        hours = r.read(5)?;
+    } else if r.read_flag()? {
+        seconds = r.read(6)?;
+        if r.read_flag()? {
+            minutes = r.read(6)?;
+            if r.read_flag()? {
+                hours = r.read(5)?;
+            }
+        }
+    }
+    // 丢帧计数按惯例用分号分隔帧号
+    let separator = if dropped { ';' } else { ':' };
+    Some(format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        hours, minutes, seconds, separator, frames
+    ))
+}
+
+/// mastering_display_colour_volume (D.2.28); the primaries are coded green, blue, red
+fn mastering_display(payload: &[u8]) -> Option<SeiMessage> {
+    let word = |i: usize| -> Option<f64> {
+        Some(u16::from_be_bytes([*payload.get(i)?, *payload.get(i + 1)?]) as f64 * 0.00002)
+    };
+    let luminance = |i: usize| -> Option<f64> {
+        Some(u32::from_be_bytes(payload.get(i..i + 4)?.try_into().ok()?) as f64 * 0.0001)
+    };
+    Some(SeiMessage::MasteringDisplay {
+        green: [word(0)?, word(2)?],
+        blue: [word(4)?, word(6)?],
+        red: [word(8)?, word(10)?],
+        white_point: [word(12)?, word(14)?],
+        max_luminance: luminance(16)?,
+        min_luminance: luminance(20)?,
+    })
+}
+
+fn user_data_unregistered(payload: &[u8]) -> Option<SeiMessage> {
+    let uuid = payload.get(..16)?;
+    let hex: String = uuid.iter().map(|b| format!("{:02x}", b)).collect();
+    let uuid = format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    );
+    let body = &payload[16..];
+    let trimmed = body.strip_suffix(&[0]).unwrap_or(body);
+    let text = (!trimmed.is_empty()
+        && trimmed
+            .iter()
+            .all(|&b| b.is_ascii_graphic() || b == b' ' || b == b'\n' || b == b'\t'))
+    .then(|| String::from_utf8_lossy(trimmed).trim().to_string());
+    Some(SeiMessage::UserDataUnregistered {
+        uuid,
+        text,
+        size: payload.len() as u32,
+    })
+}
+
+/// user_data_registered_itu_t_t35: recognises the ATSC, HDR10+ and Dolby Vision payloads
+fn registered_t35(payload: &[u8]) -> Option<SeiMessage> {
+    let country_code = *payload.first()?;
+    // 0xff 表示后面还有一个扩展字节
+    let at = if country_code == 0xff { 2 } else { 1 };
+    let provider_code = payload
+        .get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let rest = payload.get(at + 2..).unwrap_or_default();
+    let description = match (country_code, provider_code) {
+        (0xb5, Some(0x0031)) => match rest.get(..4) {
+            Some(b"GA94") => "ATSC A/53 closed captions",
+            Some(b"DTG1") => "ATSC active format description",
+            _ => "ATSC user data",
+        },
+        (0xb5, Some(0x003c)) => {
+            if rest.get(..3) == Some(&[0x00, 0x01, 0x04]) {
+                if let Some(metadata) = hdr10plus(&rest[3..]) {
+                    return Some(SeiMessage::Hdr10Plus(metadata));
+                }
+            }
+            "Samsung user data"
+        }
+        (0xb5, Some(0x003b)) => "Dolby Vision metadata",
+        _ => "unknown",
+    };
+    Some(SeiMessage::RegisteredT35 {
+        country_code,
+        provider_code,
+        description,
+        size: payload.len() as u32,
+    })
+}
+
+/// ST 2094-40 / CTA-861-G metadata after application_identifier = 4
+fn hdr10plus(data: &[u8]) -> Option<Hdr10Plus> {
+    let mut r = BitReader::new(data);
+    let application_version = r.read(8)? as u8;
+    let windows = r.read(2)? as u8;
+    // 额外窗口的几何参数: 6 个 16 位坐标, 旋转角, 3 个半轴与 overlap 标志
+    for _ in 1..windows {
+        r.skip(16 * 6 + 8 + 16 * 3 + 1)?;
+    }
+    let targeted_max_luminance = r.read(27)?;
+    if r.read_flag()? {
+        let rows = r.read(5)?;
+        let columns = r.read(5)?;
+        r.skip((rows * columns * 4) as usize)?;
+    }
+    let mut metadata = Hdr10Plus {
+        application_version,
+        windows,
+        targeted_max_luminance,
+        maxscl: [0; 3],
+        average_maxrgb: 0,
+        distribution: Vec::new(),
+        fraction_bright_pixels: 0,
+        knee_point: None,
+        bezier_anchors: Vec::new(),
+    };
+    for window in 0..windows {
+        let maxscl = [r.read(17)?, r.read(17)?, r.read(17)?];
+        let average_maxrgb = r.read(17)?;
+        let mut distribution = Vec::new();
+        for _ in 0..r.read(4)? {
+            distribution.push((r.read(7)? as u8, r.read(17)?));
+        }
+        let fraction_bright_pixels = r.read(10)? as u16;
+        if window == 0 {
+            metadata.maxscl = maxscl;
+            metadata.average_maxrgb = average_maxrgb;
+            metadata.distribution = distribution;
+            metadata.fraction_bright_pixels = fraction_bright_pixels;
+        }
+    }
+    if r.read_flag()? {
+        let rows = r.read(5)?;
+        let columns = r.read(5)?;
+        r.skip((rows * columns * 4) as usize)?;
+    }
+    for window in 0..windows {
+        if r.read_flag()? {
+            let knee_point = (r.read(12)? as u16, r.read(12)? as u16);
+            let mut anchors = Vec::new();
+            for _ in 0..r.read(4)? {
+                anchors.push(r.read(10)? as u16);
+            }
+            if window == 0 {
+                metadata.knee_point = Some(knee_point);
+                metadata.bezier_anchors = anchors;
+            }
+        }
+        if r.read_flag()? {
+            r.skip(6)?; // color_saturation_weight
+        }
+    }
+    Some(metadata)
+}
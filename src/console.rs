@@ -1,7 +1,36 @@
-use crate::analyzer::detector::DetectionStrategy;
-use crate::analyzer::{DefaultAnalyzer, MediaAnalyzer, MediaInfo};
+use crate::analyzer::bitrate::{self, BitrateReport};
+use crate::analyzer::detector::{DetectionStrategy, FileFormat, FormatDetector};
+use crate::analyzer::extract::{ExtractReport, StreamFormat};
+use crate::analyzer::isobmff::aac::{self, AacReport};
+use crate::analyzer::isobmff::av_sync::{AvSyncReport, SyncVerdict};
+use crate::analyzer::isobmff::captions::{self, CaptionFormat, CaptionReport};
+use crate::analyzer::isobmff::coverage::{CoverageKind, CoverageReport, GapContent};
+use crate::analyzer::isobmff::extract;
+use crate::analyzer::isobmff::faststart::{self, FaststartReport};
+use crate::analyzer::isobmff::fragment::{self, DefragmentReport, FragmentReport};
+use crate::analyzer::isobmff::gop::{self, GopReport};
+use crate::analyzer::isobmff::interleave::InterleaveReport;
+use crate::analyzer::isobmff::mux::{self, MuxReport};
+use crate::analyzer::isobmff::nal::{self, NalReport};
+use crate::analyzer::isobmff::recover::{self, RecoverReport};
+use crate::analyzer::isobmff::repair::{self, RepairReport};
+use crate::analyzer::isobmff::sample_index::TrackSampleIndex;
+use crate::analyzer::isobmff::sei::{self, SeiReport};
+use crate::analyzer::isobmff::serializer::{self, RewriteReport};
+use crate::analyzer::isobmff::stats::{self, StatsReport};
+use crate::analyzer::isobmff::tags::{self, Tag, TagEdit, TagEditReport};
+use crate::analyzer::isobmff::timeline::{EditKind, TrackTimeline};
+use crate::analyzer::isobmff::timing::{DeclaredDuration, TimingReport};
+use crate::analyzer::isobmff::trim::{self, TrimReport};
+use crate::analyzer::isobmff::validator::{Finding, Severity, ValidationReport};
+use crate::analyzer::isobmff::IsobmffAnalyzer;
+use crate::analyzer::privacy::{self, PrivacyReport};
+use crate::analyzer::rmff::RmffAnalyzer;
+use crate::analyzer::sei::SeiMessage;
+use crate::analyzer::{AnalysisOptions, DefaultAnalyzer, MediaAnalyzer, MediaInfo};
 use clap::ArgMatches;
 use colored::*;
+use std::path::{Path, PathBuf};
 
 pub fn run_console(matches: &ArgMatches) -> i32 {
     let file_path = matches.get_one::<String>("FILE").unwrap();
@@ -12,7 +41,7 @@ pub fn run_console(matches: &ArgMatches) -> i32 {
         _ => DetectionStrategy::Auto,
     };
 
-    match analyze_file(file_path, strategy) {
+    match analyze_file(file_path, strategy, &AnalysisOptions::default()) {
         Ok(info) => {
             print_tree(&info);
             0
@@ -27,8 +56,10 @@ pub fn run_console(matches: &ArgMatches) -> i32 {
 fn analyze_file(
     file_path: &str,
     strategy: DetectionStrategy,
+    options: &AnalysisOptions,
 ) -> Result<MediaInfo, Box<dyn std::error::Error>> {
-    let analyzer = DefaultAnalyzer::with_strategy(true, strategy);
+    let mut analyzer = DefaultAnalyzer::with_strategy(true, strategy);
+    analyzer.set_options(*options);
     analyzer.analyze(file_path)
 }
 
@@ -36,6 +67,320 @@ pub fn print_tree(info: &crate::analyzer::MediaInfo) {
     println!("Format: {}", info.format.green());
     println!("\nStructure:");
     print_structure(&info.structure, "", 0);
+
+    if !info.timelines.is_empty() {
+        println!("\nTimeline:");
+        print_timelines(&info.timelines);
+    }
+
+    if let Some(report) = &info.av_sync {
+        println!("\nA/V sync:");
+        print_av_sync(report);
+    }
+
+    if let Some(report) = &info.timing {
+        println!("\nTiming:");
+        print_timing(report);
+    }
+
+    if let Some(report) = &info.interleave {
+        println!("\nInterleave:");
+        print_interleave(report);
+    }
+
+    if let Some(report) = &info.coverage {
+        println!("\nmdat coverage:");
+        print_coverage(report);
+    }
+
+    if !info.samples.is_empty() {
+        println!("\nSamples:");
+        print_samples(&info.samples);
+    }
+}
+
+fn print_timelines(timelines: &[TrackTimeline]) {
+    for timeline in timelines {
+        let edits = if timeline.has_edit_list {
+            format!("{} edits", timeline.segments.len())
+        } else {
+            "no edit list".to_string()
+        };
+        println!(
+            "{} ({}): {}",
+            format!("Track {}", timeline.track_id).cyan(),
+            timeline.handler_type,
+            edits
+        );
+        for (i, segment) in timeline.segments.iter().enumerate() {
+            let kind = match segment.kind {
+                EditKind::Empty => "empty".bright_black(),
+                EditKind::Normal => "normal".normal(),
+                EditKind::Dwell => "dwell".yellow(),
+            };
+            let media = segment
+                .media_start
+                .map(|start| format!("media {:.6}s", start))
+                .unwrap_or_default();
+            println!(
+                "    [{}] {:<6} at {:.6}s for {:.6}s  {}  rate {}",
+                i, kind, segment.presentation_start, segment.duration, media, segment.media_rate
+            );
+        }
+        println!(
+            "    presentation start {:.6}s (media {:.6}s), empty {:.6}s, duration {:.6}s (media ends at {:.6}s)",
+            timeline.presentation_start,
+            timeline.media_start,
+            timeline.empty_duration,
+            timeline.presentation_duration,
+            timeline.media_end
+        );
+        if timeline.samples_outside_count > 0 {
+            let ranges: Vec<String> = timeline
+                .samples_outside
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            println!(
+                "    {}: {} samples not presented ({})",
+                "outside".yellow(),
+                timeline.samples_outside_count,
+                ranges.join(", ")
+            );
+        }
+        for warning in &timeline.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+fn print_av_sync(report: &AvSyncReport) {
+    for track in &report.tracks {
+        println!(
+            "{} ({}): first presented {:.6}s",
+            format!("Track {}", track.track_id).cyan(),
+            track.handler_type,
+            track.first_presented
+        );
+        println!(
+            "    first dts {:.6}s ({:?}), first cts {:.6}s, cts shift {:.3} ms",
+            track.first_dts,
+            track.first_dts_source,
+            track.first_cts,
+            track.cts_shift * 1000.0
+        );
+        println!(
+            "    edit delay {:.6}s, edit media start {:.6}s",
+            track.edit_delay, track.edit_media_start
+        );
+        if let Some(priming) = &track.priming {
+            let mut sources = Vec::new();
+            if let Some(skip) = priming.edit_skip {
+                sources.push(format!("elst skips {}", skip));
+            }
+            if let Some(smpb) = priming.itunsmpb {
+                sources.push(format!(
+                    "iTunSMPB delay {} padding {} samples {}",
+                    smpb.encoder_delay, smpb.end_padding, smpb.original_sample_count
+                ));
+            }
+            if let Some(roll) = priming.roll_distance {
+                sources.push(format!("roll distance {}", roll));
+            }
+            if sources.is_empty() {
+                sources.push("not signalled".to_string());
+            }
+            println!("    priming: {}", sources.join(", "));
+        }
+    }
+
+    for pair in &report.pairs {
+        let verdict = match pair.verdict {
+            SyncVerdict::InSync => "in sync".green(),
+            SyncVerdict::Detectable => "detectable".yellow(),
+            SyncVerdict::Unacceptable => "unacceptable".red(),
+        };
+        println!(
+            "Audio {} vs video {}: {:+.1} ms ({})",
+            pair.audio_track_id, pair.video_track_id, pair.offset_ms, verdict
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+}
+
+fn print_timing(report: &TimingReport) {
+    let print_duration = |duration: &DeclaredDuration| {
+        let version = duration
+            .version
+            .map(|v| format!(" v{}", v))
+            .unwrap_or_default();
+        println!(
+            "    {:<10} {:>12.6}s  {} @ {}{}",
+            duration.source,
+            duration.seconds,
+            duration.value,
+            duration.timescale,
+            version.bright_black()
+        );
+    };
+
+    println!("{} (timescale {})", "Movie".cyan(), report.movie_timescale);
+    report.movie.iter().for_each(print_duration);
+    for track in &report.tracks {
+        println!(
+            "{} ({})",
+            format!("Track {}", track.track_id).cyan(),
+            track.handler_type
+        );
+        track.durations.iter().for_each(print_duration);
+    }
+    if report.issues.is_empty() {
+        println!("    {}", "all durations are consistent".green());
+    }
+    for issue in &report.issues {
+        let scope = issue
+            .track_id
+            .map(|id| format!("track {}: ", id))
+            .unwrap_or_default();
+        println!(
+            "    {}: {}{}",
+            format!("{:?}", issue.kind).yellow(),
+            scope,
+            issue.message
+        );
+    }
+}
+
+fn print_interleave(report: &InterleaveReport) {
+    let layout = if report.faststart {
+        "moov before mdat (faststart)".green()
+    } else {
+        "moov after mdat".yellow()
+    };
+    println!(
+        "    {}{}",
+        layout,
+        if report.fragmented {
+            ", fragmented"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "    startup {} bytes, max interleave {:.3}s, max buffer {} bytes ({:.3}s)",
+        report.startup_bytes,
+        report.max_interleave,
+        report.max_buffer_bytes,
+        report.max_buffer_duration
+    );
+    for track in &report.tracks {
+        println!(
+            "{} ({}): {} chunks, duration max {:.3}s avg {:.3}s, largest {} bytes",
+            format!("Track {}", track.track_id).cyan(),
+            track.handler_type,
+            track.chunk_count,
+            track.max_chunk_duration,
+            track.average_chunk_duration,
+            track.max_chunk_bytes
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+}
+
+fn print_coverage(report: &CoverageReport) {
+    for mdat in &report.mdats {
+        let percent = if mdat.payload_size() == 0 {
+            100.0
+        } else {
+            mdat.referenced_bytes as f64 * 100.0 / mdat.payload_size() as f64
+        };
+        println!(
+            "{} payload {}-{} ({} bytes): {:.2}% referenced, {} unreferenced, {} overlapping",
+            format!("mdat @{}", mdat.offset).cyan(),
+            mdat.payload_start,
+            mdat.payload_end,
+            mdat.payload_size(),
+            percent,
+            mdat.unreferenced_bytes,
+            mdat.overlapping_bytes
+        );
+        for range in mdat
+            .ranges
+            .iter()
+            .filter(|r| r.kind != CoverageKind::Referenced)
+        {
+            let label = match (range.kind, range.content) {
+                (CoverageKind::Overlapping, _) => "overlap".red(),
+                (_, Some(GapContent::Zeros)) => "padding".bright_black(),
+                (_, Some(GapContent::Data)) => "data".yellow(),
+                _ => "gap".yellow(),
+            };
+            println!(
+                "    {:<8} {}-{} ({} bytes)",
+                label,
+                range.start,
+                range.end,
+                range.len()
+            );
+        }
+    }
+    for stray in &report.stray_samples {
+        println!(
+            "    {}: track {} sample {} at {} ({} bytes): {:?}",
+            "stray".red(),
+            stray.sample.track_id,
+            stray.sample.number,
+            stray.offset,
+            stray.size,
+            stray.reason
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+}
+
+fn print_samples(tracks: &[TrackSampleIndex]) {
+    for track in tracks {
+        println!(
+            "{} ({}, timescale {}): {} samples",
+            format!("Track {}", track.track_id).cyan(),
+            track.handler_type,
+            track.timescale,
+            track.samples.len()
+        );
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+
+        println!(
+            "{}",
+            format!(
+                "    {:>7} {:>12} {:>8} {:>12} {:>12} {:>7} {:>4} {:>6}  groups",
+                "#", "offset", "size", "dts", "cts", "dur", "sync", "chunk"
+            )
+            .bright_black()
+        );
+        for sample in &track.samples {
+            let groups: Vec<String> = sample.groups.iter().map(|g| g.to_string()).collect();
+            println!(
+                "    {:>7} {:>12} {:>8} {:>12} {:>12} {:>7} {:>4} {:>6}  {}",
+                sample.number,
+                format!("{:#x}", sample.offset),
+                sample.size,
+                sample.dts,
+                sample.cts,
+                sample.duration,
+                if sample.is_sync { "*" } else { "" },
+                sample.chunk_index,
+                groups.join(", ").bright_black()
+            );
+        }
+    }
 }
 
 fn print_structure(items: &[crate::analyzer::ElementInfo], prefix: &str, depth: usize) {
@@ -110,14 +455,14 @@ fn print_structure(items: &[crate::analyzer::ElementInfo], prefix: &str, depth:
     }
 }
 
-pub fn run_console_with_file(file_path: &str, strategy: &str) -> i32 {
+pub fn run_console_with_file(file_path: &str, strategy: &str, options: &AnalysisOptions) -> i32 {
     let strategy = match strategy {
         "extension" => DetectionStrategy::Extension,
         "content" => DetectionStrategy::Content,
         _ => DetectionStrategy::Auto,
     };
 
-    match analyze_file(file_path, strategy) {
+    match analyze_file(file_path, strategy, options) {
         Ok(info) => {
             print_tree(&info);
             0
@@ -129,14 +474,14 @@ pub fn run_console_with_file(file_path: &str, strategy: &str) -> i32 {
     }
 }
 
-pub fn run_console_with_json(file_path: &str, strategy: &str) -> i32 {
+pub fn run_console_with_json(file_path: &str, strategy: &str, options: &AnalysisOptions) -> i32 {
     let strategy = match strategy {
         "extension" => DetectionStrategy::Extension,
         "content" => DetectionStrategy::Content,
         _ => DetectionStrategy::Auto,
     };
 
-    match analyze_file(file_path, strategy) {
+    match analyze_file(file_path, strategy, options) {
         Ok(info) => {
             println!("{}", serde_json::to_string_pretty(&info).unwrap());
             0
@@ -147,3 +492,1344 @@ pub fn run_console_with_json(file_path: &str, strategy: &str) -> i32 {
         }
     }
 }
+
+/// Lint mode: validates the box structure and returns a CI friendly exit code
+pub fn run_lint(file_path: &str, strategy: &str, json: bool, fail_on: Severity) -> i32 {
+    let detector = FormatDetector::new(DetectionStrategy::from(strategy));
+    let report = match detector.detect_format(file_path) {
+        Ok(FileFormat::Isobmff) => IsobmffAnalyzer::new(file_path).and_then(|mut analyzer| {
+            analyzer.set_debug(false);
+            analyzer.validate()
+        }),
+        Ok(FileFormat::RealMedia) => {
+            eprintln!("Error: lint mode only supports ISOBMFF files");
+            return 1;
+        }
+        Err(e) => Err(e),
+    };
+
+    match report {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_validation(&report);
+            }
+            report.exit_code(fail_on)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_validation(report: &ValidationReport) {
+    for finding in &report.findings {
+        print_finding(finding);
+    }
+    let summary = format!("{} errors, {} warnings", report.errors, report.warnings);
+    if report.errors > 0 {
+        println!("{}", summary.red());
+    } else if report.warnings > 0 {
+        println!("{}", summary.yellow());
+    } else {
+        println!("{}", "no problems found".green());
+    }
+}
+
+fn print_finding(finding: &Finding) {
+    let severity = match finding.severity {
+        Severity::Error => "error".red(),
+        Severity::Warning => "warning".yellow(),
+        Severity::Info => "info".normal(),
+    };
+    println!(
+        "{}[{}] {} {}: {}",
+        severity,
+        finding.rule,
+        format!("@{:#x}", finding.offset).bright_black(),
+        finding.path.cyan(),
+        finding.message
+    );
+}
+
+/// NAL unit listing: exits 2 when a sync sample has no IDR/IRAP picture
+pub fn run_nal_units(file_path: &str, json: bool) -> i32 {
+    match nal::analyze_nal_units(Path::new(file_path)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_nal_units(&report);
+            }
+            if report
+                .tracks
+                .iter()
+                .any(|t| !t.sync_without_random_access.is_empty())
+            {
+                2
+            } else {
+                0
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_nal_units(report: &NalReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}, {}-byte NAL lengths): {} samples",
+                track.track_id,
+                track.codec,
+                track.length_size,
+                track.frames.len()
+            )
+            .bold()
+        );
+        let counts: Vec<String> = track
+            .nal_types
+            .iter()
+            .map(|c| format!("{} x{} ({} bytes)", c.name, c.count, c.bytes))
+            .collect();
+        if !counts.is_empty() {
+            println!("    {}", counts.join(", "));
+        }
+        for frame in &track.frames {
+            let frame_type = frame.frame_type.map_or("?".to_string(), |t| t.to_string());
+            let mut line = format!(
+                "    #{:<6} {:<2} dts {:<8} {:>8} bytes",
+                frame.sample, frame_type, frame.dts, frame.size
+            );
+            if let Some(poc) = frame.poc {
+                line.push_str(&format!("  poc {}", poc));
+            }
+            if let Some(frame_num) = frame.frame_num {
+                line.push_str(&format!("  frame_num {}", frame_num));
+            }
+            let units: Vec<String> = frame
+                .nal_units
+                .iter()
+                .map(|u| format!("{}({})", u.name, u.size))
+                .collect();
+            let sync = if frame.is_sync { "sync " } else { "" };
+            println!(
+                "{}  {}{}",
+                line,
+                sync.cyan(),
+                units.join(" ").bright_black()
+            );
+            for problem in &frame.problems {
+                println!("        {}: {}", "warning".yellow(), problem);
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+        if !track.sync_without_random_access.is_empty() {
+            println!(
+                "    {}",
+                format!(
+                    "{} sync samples without an IDR/IRAP picture",
+                    track.sync_without_random_access.len()
+                )
+                .red()
+            );
+        }
+    }
+}
+
+pub fn run_gop(file_path: &str, json: bool) -> i32 {
+    match gop::analyze_gops(Path::new(file_path)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_gop(&report);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_gop(report: &GopReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}): {} GOPs, {} closed, {} open",
+                track.track_id,
+                track.codec,
+                track.gops.len(),
+                track.closed_gops,
+                track.open_gops
+            )
+            .bold()
+        );
+        match (
+            track.keyframe_interval_frames,
+            track.keyframe_interval_seconds,
+        ) {
+            (Some(frames), Some(seconds)) => {
+                let line = format!(
+                    "    keyframe interval: {}-{} frames (mean {:.1}), {:.3}-{:.3}s (mean {:.3}s)",
+                    frames.min, frames.max, frames.mean, seconds.min, seconds.max, seconds.mean
+                );
+                if track.regular {
+                    println!("{}", line);
+                } else {
+                    println!("{} {}", line, "irregular".yellow());
+                }
+            }
+            _ => println!("    keyframe interval: fewer than two keyframes"),
+        }
+        let lengths: Vec<String> = track
+            .length_distribution
+            .iter()
+            .map(|l| format!("{} frames x{}", l.frames, l.count))
+            .collect();
+        println!("    GOP lengths: {}", lengths.join(", "));
+        println!(
+            "    frames: {} I, {} P, {} B; B-pyramid depth {}, max reorder {}",
+            track.i_frames, track.p_frames, track.b_frames, track.pyramid_depth, track.max_reorder
+        );
+        for gop in &track.gops {
+            let pattern = if gop.pattern.chars().count() > 60 {
+                format!("{}...", gop.pattern.chars().take(60).collect::<String>())
+            } else {
+                gop.pattern.clone()
+            };
+            println!(
+                "    #{:<6} {:>5} frames {:>8.3}s  {:<6}  {}",
+                gop.start_sample,
+                gop.frames,
+                gop.duration,
+                if gop.closed { "closed" } else { "open" },
+                pattern.bright_black()
+            );
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+pub fn run_sei(file_path: &str, json: bool) -> i32 {
+    match sei::analyze_sei(Path::new(file_path)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_sei(&report);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_sei(report: &SeiReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}): SEI in {} samples",
+                track.track_id,
+                track.codec,
+                track.frames.len()
+            )
+            .bold()
+        );
+        let counts: Vec<String> = track
+            .message_counts
+            .iter()
+            .map(|c| format!("{} x{}", c.name, c.count))
+            .collect();
+        if !counts.is_empty() {
+            println!("    {}", counts.join(", "));
+        }
+        for settings in &track.encoder_settings {
+            println!(
+                "    {} {} (sample #{}, {} options)",
+                "encoder".cyan(),
+                settings.encoder,
+                settings.first_sample,
+                settings.options.len()
+            );
+            let options: Vec<String> = settings
+                .options
+                .iter()
+                .map(|(key, value)| {
+                    if value.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}={}", key, value)
+                    }
+                })
+                .collect();
+            for line in options.chunks(8) {
+                println!("        {}", line.join(" ").bright_black());
+            }
+        }
+        for message in &track.declarative {
+            println!("    {:<12} {}", "avcC/hvcC", format_sei_message(message));
+        }
+        for frame in &track.frames {
+            for message in &frame.messages {
+                println!(
+                    "    #{:<6} {} {}",
+                    frame.sample,
+                    format!("cts {:<8}", frame.cts).bright_black(),
+                    format_sei_message(message)
+                );
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+fn format_sei_message(message: &SeiMessage) -> String {
+    let details = match message {
+        SeiMessage::PictureTiming {
+            pic_struct,
+            timecodes,
+        } => {
+            let mut parts = Vec::new();
+            if let Some(pic_struct) = pic_struct {
+                parts.push(format!("pic_struct {}", pic_struct));
+            }
+            parts.extend(timecodes.iter().cloned());
+            parts.join(" ")
+        }
+        SeiMessage::TimeCode { timecodes } => timecodes.join(" "),
+        SeiMessage::RecoveryPoint { recovery_frame_cnt } => {
+            format!("recovery_frame_cnt {}", recovery_frame_cnt)
+        }
+        SeiMessage::MasteringDisplay {
+            red,
+            green,
+            blue,
+            white_point,
+            max_luminance,
+            min_luminance,
+        } => format!(
+            "R({:.4},{:.4}) G({:.4},{:.4}) B({:.4},{:.4}) WP({:.4},{:.4}) L({:.4}, {:.4}) cd/m²",
+            red[0],
+            red[1],
+            green[0],
+            green[1],
+            blue[0],
+            blue[1],
+            white_point[0],
+            white_point[1],
+            max_luminance,
+            min_luminance
+        ),
+        SeiMessage::ContentLightLevel { max_cll, max_fall } => {
+            format!("MaxCLL {} MaxFALL {} cd/m²", max_cll, max_fall)
+        }
+        SeiMessage::Hdr10Plus(metadata) => format!(
+            "v{} {} window(s), target {} cd/m², maxscl {:?}, average maxRGB {}, {} percentiles{}",
+            metadata.application_version,
+            metadata.windows,
+            metadata.targeted_max_luminance,
+            metadata.maxscl,
+            metadata.average_maxrgb,
+            metadata.distribution.len(),
+            if metadata.knee_point.is_some() {
+                ", tone mapping curve"
+            } else {
+                ""
+            }
+        ),
+        SeiMessage::RegisteredT35 {
+            country_code,
+            provider_code,
+            description,
+            size,
+        } => format!(
+            "{} (country {:#04x}, provider {}, {} bytes)",
+            description,
+            country_code,
+            provider_code.map_or("-".to_string(), |code| format!("{:#06x}", code)),
+            size
+        ),
+        SeiMessage::UserDataUnregistered { uuid, text, size } => match text {
+            Some(text) if text.chars().count() > 80 => format!(
+                "{} \"{}...\"",
+                uuid,
+                text.chars().take(80).collect::<String>()
+            ),
+            Some(text) => format!("{} \"{}\"", uuid, text),
+            None => format!("{} ({} bytes)", uuid, size),
+        },
+        SeiMessage::Other {
+            payload_type, size, ..
+        } => format!("payload type {} ({} bytes)", payload_type, size),
+    };
+    format!("{} {}", message.name().green(), details)
+}
+
+/// AAC frame listing: exits 2 when a frame has a problem
+pub fn run_aac_frames(file_path: &str, json: bool) -> i32 {
+    match aac::analyze_aac_frames(Path::new(file_path)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_aac_frames(&report);
+            }
+            if report.tracks.iter().any(|t| t.frames_with_problems > 0) {
+                2
+            } else {
+                0
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_aac_frames(report: &AacReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}): {} frames",
+                track.track_id,
+                track.profile,
+                track.frames.len()
+            )
+            .bold()
+        );
+        if let Some(config) = &track.config {
+            let layout: Vec<String> = track
+                .expected_elements
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+            println!(
+                "    {} Hz, channel configuration {} ({}), {} lines per frame, expected duration {}",
+                config.sample_rate,
+                config.channel_config,
+                if layout.is_empty() {
+                    "PCE".to_string()
+                } else {
+                    layout.join(" ")
+                },
+                config.frame_length,
+                track
+                    .expected_duration
+                    .map_or("-".to_string(), |d| d.to_string())
+            );
+        }
+        let windows: Vec<String> = track
+            .window_sequences
+            .iter()
+            .map(|c| format!("{} x{}", c.window_sequence, c.count))
+            .collect();
+        if !windows.is_empty() {
+            println!("    windows: {}", windows.join(", "));
+        }
+        println!(
+            "    SBR in {} frames, ADTS headers in {}, {} stts mismatches",
+            track.sbr_frames,
+            track.adts_frames,
+            track.duration_mismatches.len()
+        );
+        for frame in &track.frames {
+            let mut elements: Vec<String> =
+                frame.block.leading.iter().map(|e| e.to_string()).collect();
+            if let Some(audio) = &frame.block.audio {
+                if let Some(last) = elements.last_mut() {
+                    *last = format!("{}#{}", audio.element, audio.instance_tag);
+                }
+                if let Some(gain) = audio.global_gain {
+                    elements.push(format!("gain {}", gain));
+                }
+                if let (Some(window), Some(shape)) = (audio.window_sequence, audio.window_shape) {
+                    elements.push(format!("{}/{}", window, shape));
+                }
+                if let Some(max_sfb) = audio.max_sfb {
+                    elements.push(format!("sfb {}", max_sfb));
+                }
+            }
+            if let Some(fill) = &frame.block.trailing_fill {
+                elements.push(format!("FIL({} {})", fill.extension, fill.bytes));
+            }
+            println!(
+                "    #{:<6} dts {:<10} {:>6} bytes  {}",
+                frame.sample,
+                frame.dts,
+                frame.size,
+                elements.join(" ").bright_black()
+            );
+            for problem in frame.problems.iter().chain(&frame.block.problems) {
+                println!("        {}: {}", "warning".yellow(), problem);
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+/// Closed caption listing, or export of one channel when `output` is given
+pub fn run_captions(
+    file_path: &str,
+    channel: Option<&str>,
+    output: Option<&str>,
+    json: bool,
+) -> i32 {
+    let Some(output) = output else {
+        return match captions::analyze_captions(Path::new(file_path)) {
+            Ok(report) => {
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+                } else {
+                    print_captions(&report);
+                }
+                0
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        };
+    };
+    let format = if output.to_ascii_lowercase().ends_with(".scc") {
+        CaptionFormat::Scc
+    } else {
+        CaptionFormat::Srt
+    };
+    match captions::export_captions(Path::new(file_path), channel, Path::new(output), format) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let unit = match report.format {
+                    CaptionFormat::Srt => "cues",
+                    CaptionFormat::Scc => "byte pairs",
+                };
+                println!(
+                    "track {} {}: {} {} written to {} ({} bytes)",
+                    report.track_id,
+                    report.channel,
+                    report.entries,
+                    unit,
+                    output,
+                    report.bytes_written
+                );
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_captions(report: &CaptionReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}): cc_data in {} samples, {:.3} fps",
+                track.track_id, track.codec, track.samples_with_captions, track.frame_rate
+            )
+            .bold()
+        );
+        for channel in &track.channels {
+            println!("    {} ({} cues)", channel.name.cyan(), channel.cues.len());
+            for cue in &channel.cues {
+                println!(
+                    "        {} {}",
+                    format!("{:>10.3} - {:<10.3}", cue.start, cue.end).bright_black(),
+                    cue.text.replace('\n', " / ")
+                );
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+pub fn run_frame_stats(file_path: &str, json: bool) -> i32 {
+    match stats::analyze_frame_stats(Path::new(file_path)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_frame_stats(&report);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_frame_stats(report: &StatsReport) {
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}, {}): {} samples, {} bytes",
+                track.track_id,
+                track.handler_type,
+                track.codec,
+                track.sizes.count,
+                track.sizes.total
+            )
+            .bold()
+        );
+        println!(
+            "    {}",
+            format!(
+                "{:<5} {:>7} {:>9} {:>9} {:>11} {:>9} {:>9} {:>9} {:>9}",
+                "type", "count", "min", "max", "mean", "p50", "p90", "p95", "p99"
+            )
+            .cyan()
+        );
+        let rows = std::iter::once(("all".to_string(), &track.sizes)).chain(
+            track
+                .frame_types
+                .iter()
+                .map(|t| (t.frame_type.to_string(), &t.sizes)),
+        );
+        for (label, sizes) in rows {
+            println!(
+                "    {:<5} {:>7} {:>9} {:>9} {:>11.1} {:>9} {:>9} {:>9} {:>9}",
+                label,
+                sizes.count,
+                sizes.min,
+                sizes.max,
+                sizes.mean,
+                sizes.p50,
+                sizes.p90,
+                sizes.p95,
+                sizes.p99
+            );
+        }
+
+        let durations = &track.durations;
+        let distinct: Vec<String> = durations
+            .distinct
+            .iter()
+            .take(8)
+            .map(|d| format!("{} x{}", d.duration, d.count))
+            .collect();
+        let more = if durations.distinct.len() > 8 {
+            ", ..."
+        } else {
+            ""
+        };
+        println!(
+            "    durations: {}-{} (mean {:.2}) at timescale {}; {}{}",
+            durations.min,
+            durations.max,
+            durations.mean,
+            track.timescale,
+            distinct.join(", "),
+            more
+        );
+        println!(
+            "    stts runs: {}, longest {} samples",
+            durations.runs, durations.longest_run
+        );
+        if let Some(frame_rate) = track.frame_rate {
+            if track.variable_frame_rate {
+                println!(
+                    "    frame rate: {:.3} fps average, {}",
+                    frame_rate,
+                    "variable".yellow()
+                );
+            } else {
+                println!("    frame rate: {:.3} fps, constant", frame_rate);
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+/// Bitrate over time per track; with `output`, the series are written there as CSV
+pub fn run_bitrate(
+    file_path: &str,
+    strategy: &str,
+    windows: &[f64],
+    output: Option<&str>,
+    json: bool,
+) -> i32 {
+    let report =
+        match bitrate::analyze_bitrate(file_path, DetectionStrategy::from(strategy), windows) {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 1;
+            }
+        };
+    if let Some(output) = output {
+        if faststart::same_file(Path::new(file_path), Path::new(output)) {
+            eprintln!("Error: output must not be the input file");
+            return 1;
+        }
+        let written = std::fs::File::create(output).and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            bitrate::write_csv(&report, &mut out)?;
+            std::io::Write::flush(&mut out)
+        });
+        if let Err(e) = written {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print_bitrate(&report);
+        if let Some(output) = output {
+            println!("series written to {}", output);
+        }
+    }
+    0
+}
+
+fn print_bitrate(report: &BitrateReport) {
+    println!("{}", format!("{} bitrate", report.format).bold());
+    for track in &report.tracks {
+        println!(
+            "{}",
+            format!(
+                "track {} ({}): {} units, {} bytes over {:.3} s",
+                track.track_id, track.codec, track.units, track.bytes, track.duration
+            )
+            .bold()
+        );
+        println!("    average      {:>10.1} kbit/s", track.average / 1000.0);
+        for peak in &track.peaks {
+            println!(
+                "    peak {:<7} {:>10.1} kbit/s {}",
+                format!("{}s", peak.window),
+                peak.bitrate / 1000.0,
+                format!("at {:.3} s", peak.at).bright_black()
+            );
+        }
+        for declared in &track.declared {
+            let rate = |value: Option<u64>| {
+                value.map_or("-".to_string(), |v| {
+                    format!("{:.1} kbit/s", v as f64 / 1000.0)
+                })
+            };
+            print!(
+                "    {:<12} max {}, average {}",
+                declared.source.cyan(),
+                rate(declared.max),
+                rate(declared.average)
+            );
+            match declared.buffer_size {
+                Some(size) => println!(", buffer {} bytes", size),
+                None => println!(),
+            }
+        }
+        for warning in &track.warnings {
+            println!("    {}: {}", "warning".yellow(), warning);
+        }
+    }
+}
+
+/// Privacy audit: lists identifying metadata, exits 2 when a finding reaches `fail_on`
+pub fn run_privacy(file_path: &str, strategy: &str, json: bool, fail_on: privacy::Severity) -> i32 {
+    let detector = FormatDetector::new(DetectionStrategy::from(strategy));
+    let report = match detector.detect_format(file_path) {
+        Ok(FileFormat::Isobmff) => IsobmffAnalyzer::new(file_path).and_then(|mut analyzer| {
+            analyzer.set_debug(false);
+            Ok(PrivacyReport::new("ISOBMFF", analyzer.privacy_audit()?))
+        }),
+        Ok(FileFormat::RealMedia) => RmffAnalyzer::new(file_path).and_then(|mut analyzer| {
+            analyzer.set_debug(false);
+            Ok(PrivacyReport::new("RealMedia", analyzer.privacy_audit()?))
+        }),
+        Err(e) => Err(e),
+    };
+
+    match report {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_privacy(&report);
+            }
+            report.exit_code(fail_on)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_privacy(report: &PrivacyReport) {
+    for finding in &report.findings {
+        let severity = match finding.severity {
+            privacy::Severity::High => "high".red(),
+            privacy::Severity::Medium => "medium".yellow(),
+            privacy::Severity::Low => "low".normal(),
+        };
+        println!(
+            "{} {} {}: {} ({})",
+            severity,
+            format!("@{:#x}", finding.offset).bright_black(),
+            finding.path.cyan(),
+            finding.message,
+            finding.key
+        );
+        if !finding.value.is_empty() {
+            println!("    {}", finding.value);
+        }
+    }
+    let summary = format!(
+        "{} high, {} medium, {} low",
+        report.high, report.medium, report.low
+    );
+    if report.high > 0 {
+        println!("{}", summary.red());
+    } else if report.medium > 0 {
+        println!("{}", summary.yellow());
+    } else if report.low > 0 {
+        println!("{}", summary);
+    } else {
+        println!("{}", "no identifying metadata found".green());
+    }
+}
+
+/// Faststart mode: writes `output` with moov in front of mdat and verifies it
+pub fn run_faststart(file_path: &str, output: &str, json: bool) -> i32 {
+    match faststart::relocate_moov(Path::new(file_path), Path::new(output)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_faststart(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_faststart(report: &FaststartReport, output: &str) {
+    if report.moov_moved {
+        println!("moov ({} bytes) moved before mdat", report.moov_size);
+    } else {
+        println!("moov already precedes mdat, file copied");
+    }
+    println!(
+        "    {} chunk offsets rewritten, {} stco upgraded to co64",
+        report.chunk_offsets_rewritten, report.upgraded_to_co64
+    );
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} samples verified)",
+        "wrote".green(),
+        output,
+        report.verified_samples
+    );
+}
+
+/// Repair: exits 2 when the written copy still has validation errors
+pub fn run_repair(file_path: &str, output: &str, json: bool) -> i32 {
+    match repair::repair(Path::new(file_path), Path::new(output)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_repair(&report, output);
+            }
+            report.exit_code()
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_repair(report: &RepairReport, output: &str) {
+    println!(
+        "input: {} errors, {} warnings",
+        report.errors_before, report.warnings_before
+    );
+    for change in &report.changes {
+        println!(
+            "    {}[{}] {} {}",
+            "fixed".green(),
+            change.rule,
+            format!("@{:#x}", change.offset).bright_black(),
+            change.description
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    if report.changes.is_empty() {
+        println!("nothing to repair, no file written");
+        return;
+    }
+    for finding in &report.remaining {
+        print_finding(finding);
+    }
+    println!(
+        "{} {} ({} bytes, {} changes)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.changes.len()
+    );
+}
+
+pub fn run_trim(file_path: &str, output: &str, start: f64, end: f64, json: bool) -> i32 {
+    match trim::trim(Path::new(file_path), Path::new(output), start, end) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_trim(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_trim(report: &TrimReport, output: &str) {
+    println!("trimmed to {:.3}s - {:.3}s", report.start, report.end);
+    for track in &report.tracks {
+        println!(
+            "    track {} ({}): samples {}-{} ({} kept), {:.3}s, {:.3}s hidden by edit list",
+            track.track_id,
+            track.handler_type,
+            track.first_sample,
+            track.last_sample,
+            track.sample_count,
+            track.duration,
+            track.hidden_leading
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes, {} samples verified)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.verified_samples
+    );
+}
+
+pub fn run_fragment(
+    file_path: &str,
+    output: &str,
+    duration: f64,
+    segments: bool,
+    json: bool,
+) -> i32 {
+    match fragment::fragment(Path::new(file_path), Path::new(output), duration, segments) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_fragment(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_fragment(report: &FragmentReport, output: &str) {
+    println!(
+        "{} fragments cut at sync samples of track {}",
+        report.fragments.len(),
+        report.reference_track
+    );
+    if let Some(init) = &report.init_segment {
+        println!("    init segment {}", init);
+    }
+    for fragment in &report.fragments {
+        println!(
+            "    #{:<4} {:>10.3}s {:>8.3}s {:>10} bytes{}",
+            fragment.sequence_number,
+            fragment.start,
+            fragment.duration,
+            fragment.size,
+            fragment
+                .file
+                .as_ref()
+                .map(|file| format!("  {}", file))
+                .unwrap_or_default()
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes, {} samples verified)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.verified_samples
+    );
+}
+
+pub fn run_defragment(file_path: &str, output: &str, json: bool) -> i32 {
+    match fragment::defragment(Path::new(file_path), Path::new(output)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_defragment(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_defragment(report: &DefragmentReport, output: &str) {
+    println!("{} fragments flattened into moov", report.fragments);
+    for track in &report.tracks {
+        println!(
+            "    track {} ({}): {} samples, {:.3}s",
+            track.track_id, track.handler_type, track.sample_count, track.duration
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes, {} samples verified)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.verified_samples
+    );
+}
+
+pub fn run_extract(
+    file_path: &str,
+    strategy: &str,
+    track: u32,
+    output: &str,
+    av1_format: StreamFormat,
+    json: bool,
+) -> i32 {
+    let detector = FormatDetector::new(DetectionStrategy::from(strategy));
+    let report = match detector.detect_format(file_path) {
+        Ok(FileFormat::Isobmff) => {
+            extract::extract_track(Path::new(file_path), track, Path::new(output), av1_format)
+        }
+        Ok(FileFormat::RealMedia) => match u16::try_from(track) {
+            Ok(stream) => RmffAnalyzer::new(file_path).and_then(|mut analyzer| {
+                analyzer.set_debug(false);
+                analyzer.extract_stream(stream, Path::new(output))
+            }),
+            Err(_) => {
+                eprintln!("Error: invalid stream number {}", track);
+                return 1;
+            }
+        },
+        Err(e) => Err(e),
+    };
+
+    match report {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_extract(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_extract(report: &ExtractReport, output: &str) {
+    println!(
+        "track {} ({}) as {}: {} samples",
+        report.track_id, report.codec, report.format, report.samples
+    );
+    if report.parameter_sets_inserted > 0 {
+        println!(
+            "    parameter sets inserted at {} keyframes",
+            report.parameter_sets_inserted
+        );
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes)",
+        "wrote".green(),
+        output,
+        report.bytes_written
+    );
+}
+
+pub fn run_mux(inputs: &[&str], output: &str, frame_rate: Option<f64>, json: bool) -> i32 {
+    let inputs: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+    match mux::mux(&inputs, Path::new(output), frame_rate) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_mux(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_mux(report: &MuxReport, output: &str) {
+    for track in &report.tracks {
+        println!(
+            "track {} ({} as {}): {} samples, {:.3}s from {}",
+            track.track_id,
+            track.kind,
+            track.sample_entry,
+            track.samples,
+            track.duration,
+            track.input
+        );
+        if track.hidden_leading > 0.0 {
+            println!(
+                "    edit list hides the first {:.3}s of media",
+                track.hidden_leading
+            );
+        }
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes, {} samples verified)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.verified_samples
+    );
+}
+
+pub fn run_recover(file_path: &str, reference: &str, output: &str, json: bool) -> i32 {
+    match recover::recover(
+        Path::new(file_path),
+        Path::new(reference),
+        Path::new(output),
+    ) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_recover(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_recover(report: &RecoverReport, output: &str) {
+    println!(
+        "{} of {} mdat bytes recovered using {}",
+        report.recovered_bytes, report.media_data, report.reference
+    );
+    for track in &report.tracks {
+        print!(
+            "    track {} ({}, {}): {} samples",
+            track.track_id, track.handler_type, track.codec, track.sample_count
+        );
+        if let Some(sync) = track.sync_samples {
+            print!(", {} keyframes", sync);
+        }
+        println!(", {:.3}s", track.duration);
+    }
+    for warning in &report.warnings {
+        println!("    {}: {}", "warning".yellow(), warning);
+    }
+    println!(
+        "{} {} ({} bytes, {} samples verified)",
+        "wrote".green(),
+        output,
+        report.bytes_written,
+        report.verified_samples
+    );
+}
+
+pub fn run_rewrite(file_path: &str, output: &str, json: bool) -> i32 {
+    match serializer::rewrite(Path::new(file_path), Path::new(output)) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_rewrite(&report, output);
+            }
+            if report.identical {
+                0
+            } else {
+                2
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_rewrite(report: &RewriteReport, output: &str) {
+    println!(
+        "{} {} ({} top-level boxes, {} bytes)",
+        "wrote".green(),
+        output,
+        report.boxes_written,
+        report.bytes_written
+    );
+    if report.tail_bytes > 0 {
+        println!(
+            "    {} unparsed bytes after the last box copied as is",
+            report.tail_bytes
+        );
+    }
+    match report.first_difference {
+        None => println!("    {}", "identical to the input".green()),
+        Some(offset) => println!(
+            "    {} first difference at offset {}",
+            "differs from the input:".red(),
+            offset
+        ),
+    }
+}
+
+pub fn run_tags(file_path: &str, json: bool) -> i32 {
+    match tags::list_tags(Path::new(file_path)) {
+        Ok(tags) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&tags).unwrap());
+            } else {
+                print_tags(&tags);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+pub fn run_edit_tags(file_path: &str, output: &str, edits: &[TagEdit], json: bool) -> i32 {
+    match tags::edit_tags(Path::new(file_path), Path::new(output), edits) {
+        Ok(report) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_tag_edit(&report, output);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn print_tags(tags: &[Tag]) {
+    if tags.is_empty() {
+        println!("{}", "no tags".yellow());
+        return;
+    }
+    for tag in tags {
+        let name = match &tag.alias {
+            Some(alias) => format!("{} ({})", tag.key, alias),
+            None => tag.key.clone(),
+        };
+        println!("{:<32} {}", name.cyan(), tag.value);
+    }
+}
+
+fn print_tag_edit(report: &TagEditReport, output: &str) {
+    println!(
+        "{} {} ({} bytes, moov {} bytes)",
+        "wrote".green(),
+        output,
+        report.write.bytes_written,
+        report.write.moov_size
+    );
+    for path in &report.removed_boxes {
+        println!("    removed {}", path);
+    }
+    println!(
+        "    {} chunk offsets rewritten, {} samples verified",
+        report.write.chunk_offsets_rewritten, report.write.verified_samples
+    );
+    if report.write.upgraded_to_co64 > 0 {
+        println!(
+            "    {} stco boxes upgraded to co64",
+            report.write.upgraded_to_co64
+        );
+    }
+    for warning in &report.write.warnings {
+        println!("    {} {}", "warning:".yellow(), warning);
+    }
+    println!();
+    print_tags(&report.tags);
+}
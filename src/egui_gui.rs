@@ -3,9 +3,73 @@ use egui::{Color32, RichText, ViewportBuilder};
 use rfd::FileDialog;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
+use crate::analyzer::bitrate::{self, BitrateReport};
 use crate::analyzer::detector::DetectionStrategy;
+use crate::analyzer::isobmff::coverage::{CoverageKind, CoverageReport, GapContent};
+use crate::analyzer::isobmff::nal::{self, NalReport};
+use crate::analyzer::isobmff::stats::{self, StatsReport};
+use crate::analyzer::isobmff::tags::{self, TagEdit};
 use crate::analyzer::{DefaultAnalyzer, ElementInfo, MediaAnalyzer, MediaInfo};
 
+/// Draws stacked bars, each a list of (count, color) segments from the bottom up, with the
+/// first and last bin labelled below
+fn draw_histogram(ui: &mut egui::Ui, bars: &[Vec<(u32, Color32)>], first: &str, last: &str) {
+    let (response, painter) = ui.allocate_painter(
+        egui::vec2(ui.available_width(), 160.0),
+        egui::Sense::hover(),
+    );
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+    let top = bars
+        .iter()
+        .map(|bar| bar.iter().map(|(n, _)| n).sum::<u32>())
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    let width = rect.width() / bars.len().max(1) as f32;
+    let chart = rect.shrink2(egui::vec2(0.0, 14.0));
+    for (i, bar) in bars.iter().enumerate() {
+        let left = rect.left() + i as f32 * width;
+        let mut bottom = chart.bottom();
+        for &(count, color) in bar {
+            let height = count as f32 / top * chart.height();
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left + 1.0, bottom - height),
+                    egui::pos2(left + width - 1.0, bottom),
+                ),
+                0.0,
+                color,
+            );
+            bottom -= height;
+        }
+        if let Some(pointer) = response.hover_pos() {
+            if pointer.x >= left && pointer.x < left + width {
+                let total: u32 = bar.iter().map(|(n, _)| n).sum();
+                painter.text(
+                    egui::pos2(left + width / 2.0, rect.top()),
+                    egui::Align2::CENTER_TOP,
+                    total.to_string(),
+                    egui::FontId::monospace(12.0),
+                    Color32::WHITE,
+                );
+            }
+        }
+    }
+    for (text, pos, align) in [
+        (first, rect.left_bottom(), egui::Align2::LEFT_BOTTOM),
+        (last, rect.right_bottom(), egui::Align2::RIGHT_BOTTOM),
+    ] {
+        painter.text(
+            pos,
+            align,
+            text,
+            egui::FontId::monospace(12.0),
+            Color32::GRAY,
+        );
+    }
+}
+
 pub fn run_gui() -> i32 {
     let options = NativeOptions {
         viewport: ViewportBuilder::default()
@@ -126,6 +190,101 @@ struct MediaInspectorApp {
     selected_element: Option<String>,
     detection_strategy: DetectionStrategy,
     current_file: Option<String>,
+    tag_editor: Option<TagEditor>,
+    nal_view: Option<NalView>,
+    bitrate_view: Option<BitrateView>,
+    stats_view: Option<StatsView>,
+}
+
+/// State of the NAL Units window
+struct NalView {
+    report: NalReport,
+    track: usize,
+    /// Index into the frames of the selected track
+    frame: Option<usize>,
+}
+
+/// State of the Bitrate window
+struct BitrateView {
+    report: BitrateReport,
+    track: usize,
+}
+
+/// State of the Frame Statistics window
+struct StatsView {
+    report: StatsReport,
+    track: usize,
+}
+
+/// State of the Tags window
+struct TagEditor {
+    rows: Vec<TagRow>,
+    new_key: String,
+    new_value: String,
+    cover: Option<std::path::PathBuf>,
+    strip_all: bool,
+    status: Option<Result<String, String>>,
+}
+
+struct TagRow {
+    key: String,
+    value: String,
+    /// Value in the file, None for rows added in the editor
+    original: Option<String>,
+    /// Only text values can be edited in place
+    editable: bool,
+    delete: bool,
+}
+
+impl TagEditor {
+    fn load(path: &str) -> Result<Self, String> {
+        let tags = tags::list_tags(std::path::Path::new(path)).map_err(|e| e.to_string())?;
+        Ok(Self {
+            rows: tags
+                .into_iter()
+                .map(|tag| TagRow {
+                    key: tag.key,
+                    original: Some(tag.value.clone()),
+                    value: tag.value,
+                    editable: tag.data_type == 1,
+                    delete: false,
+                })
+                .collect(),
+            new_key: String::new(),
+            new_value: String::new(),
+            cover: None,
+            strip_all: false,
+            status: None,
+        })
+    }
+
+    fn edits(&self) -> Result<Vec<TagEdit>, String> {
+        let mut edits = Vec::new();
+        if self.strip_all {
+            edits.push(TagEdit::StripAll);
+        }
+        for row in &self.rows {
+            if row.delete {
+                if row.original.is_some() && !self.strip_all {
+                    edits.push(TagEdit::Delete(row.key.clone()));
+                }
+            } else if row.original.as_ref() != Some(&row.value) || self.strip_all {
+                // 清除全部后仍保留的文本标签需要重新写入
+                if row.editable {
+                    edits.push(TagEdit::Set {
+                        key: row.key.clone(),
+                        value: row.value.clone(),
+                    });
+                }
+            }
+        }
+        if let Some(cover) = &self.cover {
+            let data = std::fs::read(cover)
+                .map_err(|e| format!("cannot read {}: {}", cover.display(), e))?;
+            edits.push(TagEdit::Cover(data));
+        }
+        Ok(edits)
+    }
 }
 
 impl Default for MediaInspectorApp {
@@ -142,6 +301,10 @@ impl Default for MediaInspectorApp {
             selected_element: None,
             detection_strategy: DetectionStrategy::Auto,
             current_file: None,
+            tag_editor: None,
+            nal_view: None,
+            bitrate_view: None,
+            stats_view: None,
         }
     }
 }
@@ -179,7 +342,81 @@ impl eframe::App for MediaInspectorApp {
                     }
                 });
 
+                ui.menu_button(RichText::new("Edit").size(18.0), |ui| {
+                    let enabled = self.current_file.is_some();
+                    if ui
+                        .add_enabled(
+                            enabled,
+                            egui::Button::new(RichText::new("Tags...").size(18.0)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = &self.current_file {
+                            match TagEditor::load(path) {
+                                Ok(editor) => self.tag_editor = Some(editor),
+                                Err(err) => self.error_message = Some(err),
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                });
+
                 ui.menu_button(RichText::new("View").size(18.0), |ui| {
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new(RichText::new("NAL Units...").size(18.0)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = &self.current_file {
+                            match nal::analyze_nal_units(std::path::Path::new(path)) {
+                                Ok(report) => {
+                                    self.nal_view = Some(NalView {
+                                        report,
+                                        track: 0,
+                                        frame: None,
+                                    })
+                                }
+                                Err(err) => self.error_message = Some(err.to_string()),
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new(RichText::new("Bitrate...").size(18.0)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = &self.current_file {
+                            match bitrate::analyze_bitrate(path, self.detection_strategy, &[1.0]) {
+                                Ok(report) => {
+                                    self.bitrate_view = Some(BitrateView { report, track: 0 })
+                                }
+                                Err(err) => self.error_message = Some(err.to_string()),
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new(RichText::new("Frame Statistics...").size(18.0)),
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = &self.current_file {
+                            match stats::analyze_frame_stats(std::path::Path::new(path)) {
+                                Ok(report) => {
+                                    self.stats_view = Some(StatsView { report, track: 0 })
+                                }
+                                Err(err) => self.error_message = Some(err.to_string()),
+                            }
+                        }
+                        ui.close_menu();
+                    }
                     ui.menu_button(RichText::new("Detection Strategy").size(18.0), |ui| {
                         let strategies = [
                             (DetectionStrategy::Auto, "Auto"),
@@ -247,6 +484,11 @@ impl eframe::App for MediaInspectorApp {
                 });
         }
 
+        self.show_tag_editor(ctx);
+        self.show_nal_view(ctx);
+        self.show_bitrate_view(ctx);
+        self.show_stats_view(ctx);
+
         // 使用 SidePanel 和 CentralPanel 创建双栏布局
         egui::SidePanel::left("tree_panel")
             .resizable(true)
@@ -271,6 +513,10 @@ impl eframe::App for MediaInspectorApp {
                                 .size(22.0)
                                 .color(Color32::LIGHT_BLUE),
                         );
+                        if let Some(coverage) = &info.coverage {
+                            ui.add_space(4.0);
+                            Self::show_coverage(ui, coverage);
+                        }
                         ui.add_space(8.0);
                         ui.separator();
                         ui.add_space(8.0);
@@ -360,6 +606,56 @@ impl MediaInspectorApp {
         Color32::from_rgb(255, 214, 102), // 金黄
     ];
 
+    // mdat 覆盖率条: 绿色为样本引用, 灰色为填充, 橙色为未引用数据, 红色为重叠
+    fn show_coverage(ui: &mut egui::Ui, report: &CoverageReport) {
+        for mdat in &report.mdats {
+            let size = mdat.payload_size().max(1) as f32;
+            let (rect, response) = ui
+                .allocate_exact_size(egui::vec2(ui.available_width(), 12.0), egui::Sense::hover());
+            let painter = ui.painter();
+            painter.rect_filled(rect, 2.0, Color32::from_rgb(60, 60, 65));
+            for range in &mdat.ranges {
+                let color = match (range.kind, range.content) {
+                    (CoverageKind::Referenced, _) => Color32::from_rgb(87, 204, 153),
+                    (CoverageKind::Overlapping, _) => Color32::RED,
+                    (_, Some(GapContent::Zeros)) => Color32::GRAY,
+                    _ => Color32::from_rgb(255, 179, 71),
+                };
+                let left =
+                    rect.left() + rect.width() * (range.start - mdat.payload_start) as f32 / size;
+                let right =
+                    rect.left() + rect.width() * (range.end - mdat.payload_start) as f32 / size;
+                // 很小的问题区间至少画 1 像素宽, 以免被忽略
+                let right = if range.kind == CoverageKind::Referenced {
+                    right
+                } else {
+                    right.max(left + 1.0)
+                };
+                painter.rect_filled(
+                    egui::Rect::from_x_y_ranges(left..=right, rect.y_range()),
+                    0.0,
+                    color,
+                );
+            }
+            let percent = mdat.referenced_bytes as f64 * 100.0 / mdat.payload_size().max(1) as f64;
+            response.on_hover_text(format!(
+                "mdat @{}: {} bytes, {:.2}% referenced\nunreferenced {} bytes, overlapping {} bytes",
+                mdat.offset,
+                mdat.payload_size(),
+                percent,
+                mdat.unreferenced_bytes,
+                mdat.overlapping_bytes
+            ));
+        }
+        for warning in &report.warnings {
+            ui.label(
+                RichText::new(warning)
+                    .size(14.0)
+                    .color(Color32::from_rgb(255, 179, 71)),
+            );
+        }
+    }
+
     // 显示元素树（左侧面板）
     fn show_element_tree(
         ui: &mut egui::Ui,
@@ -860,6 +1156,495 @@ impl MediaInspectorApp {
             .any(|child| self.should_show_element(child))
     }
 
+    // 标签编辑窗口: 修改后另存为新文件, 再打开新文件
+    fn show_tag_editor(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.tag_editor else {
+            return;
+        };
+        let mut open = true;
+        let mut saved = None;
+        egui::Window::new("Tags")
+            .open(&mut open)
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::Grid::new("tag_grid").striped(true).show(ui, |ui| {
+                    for row in &mut editor.rows {
+                        ui.label(RichText::new(&row.key).size(16.0));
+                        if row.editable {
+                            ui.text_edit_singleline(&mut row.value);
+                        } else {
+                            ui.label(&row.value);
+                        }
+                        ui.checkbox(&mut row.delete, "Delete");
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    ui.label("Key");
+                    ui.add(egui::TextEdit::singleline(&mut editor.new_key).desired_width(120.0));
+                    ui.label("Value");
+                    ui.text_edit_singleline(&mut editor.new_value);
+                    if ui.button("Add").clicked() && !editor.new_key.is_empty() {
+                        editor.rows.push(TagRow {
+                            key: std::mem::take(&mut editor.new_key),
+                            value: std::mem::take(&mut editor.new_value),
+                            original: None,
+                            editable: true,
+                            delete: false,
+                        });
+                    }
+                });
+                ui.label(
+                    RichText::new("Keys: title, artist, album, comment, date, genre, a 4CC or any custom name")
+                        .size(13.0)
+                        .color(Color32::GRAY),
+                );
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Cover Art...").clicked() {
+                        editor.cover = FileDialog::new()
+                            .add_filter("Images", &["jpg", "jpeg", "png"])
+                            .pick_file();
+                    }
+                    if let Some(cover) = &editor.cover {
+                        ui.label(cover.display().to_string());
+                    }
+                });
+                ui.checkbox(
+                    &mut editor.strip_all,
+                    "Strip all user metadata (udta, meta, XMP)",
+                );
+                ui.add_space(8.0);
+                match &editor.status {
+                    Some(Ok(message)) => {
+                        ui.colored_label(Color32::from_rgb(87, 204, 153), message);
+                    }
+                    Some(Err(message)) => {
+                        ui.colored_label(Color32::RED, message);
+                    }
+                    None => {}
+                }
+                if ui.button(RichText::new("Save As...").size(18.0)).clicked() {
+                    let Some(input) = self.current_file.clone() else {
+                        return;
+                    };
+                    let Some(output) = FileDialog::new().save_file() else {
+                        return;
+                    };
+                    editor.status = Some(
+                        editor
+                            .edits()
+                            .and_then(|edits| {
+                                tags::edit_tags(std::path::Path::new(&input), &output, &edits)
+                                    .map_err(|e| e.to_string())
+                            })
+                            .map(|report| {
+                                saved = Some(output.clone());
+                                let mut message = format!(
+                                    "wrote {} ({} bytes)",
+                                    output.display(),
+                                    report.write.bytes_written
+                                );
+                                for warning in &report.write.warnings {
+                                    message.push_str(&format!("\nwarning: {}", warning));
+                                }
+                                message
+                            }),
+                    );
+                }
+            });
+        if !open {
+            self.tag_editor = None;
+        }
+        if let Some(output) = saved {
+            let path = output.to_string_lossy().to_string();
+            self.analyze_file(&path);
+            // 继续编辑新文件
+            if let Ok(mut editor) = TagEditor::load(&path) {
+                editor.status = self.tag_editor.take().and_then(|old| old.status);
+                self.tag_editor = Some(editor);
+            }
+        }
+    }
+
+    // 每帧一行, 选中后在下方列出该帧的 NAL 单元
+    fn show_nal_view(&mut self, ctx: &egui::Context) {
+        let Some(view) = &mut self.nal_view else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("NAL Units")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (i, track) in view.report.tracks.iter().enumerate() {
+                        let label = format!("Track {} ({})", track.track_id, track.codec);
+                        if ui.selectable_label(view.track == i, label).clicked() {
+                            view.track = i;
+                            view.frame = None;
+                        }
+                    }
+                });
+                let Some(track) = view.report.tracks.get(view.track) else {
+                    return;
+                };
+                let counts: Vec<String> = track
+                    .nal_types
+                    .iter()
+                    .map(|c| format!("{} x{}", c.name, c.count))
+                    .collect();
+                ui.label(RichText::new(counts.join(", ")).color(Color32::GRAY));
+                for warning in &track.warnings {
+                    ui.colored_label(Color32::YELLOW, warning);
+                }
+                if !track.sync_without_random_access.is_empty() {
+                    ui.colored_label(
+                        Color32::RED,
+                        format!(
+                            "{} sync samples without an IDR/IRAP picture",
+                            track.sync_without_random_access.len()
+                        ),
+                    );
+                }
+                ui.separator();
+
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea::vertical()
+                    .id_source("nal_frames")
+                    .max_height(320.0)
+                    .show_rows(ui, row_height, track.frames.len(), |ui, rows| {
+                        for i in rows {
+                            let frame = &track.frames[i];
+                            let frame_type =
+                                frame.frame_type.map_or("?".to_string(), |t| t.to_string());
+                            let mut text = format!(
+                                "#{:<6} {:<2} {:>8} bytes",
+                                frame.sample, frame_type, frame.size
+                            );
+                            if let Some(poc) = frame.poc {
+                                text.push_str(&format!("  poc {}", poc));
+                            }
+                            if frame.is_sync {
+                                text.push_str("  sync");
+                            }
+                            let color = if !frame.problems.is_empty() {
+                                Color32::RED
+                            } else if frame.random_access {
+                                Color32::LIGHT_BLUE
+                            } else {
+                                Color32::LIGHT_GRAY
+                            };
+                            let label = RichText::new(text).monospace().color(color);
+                            if ui.selectable_label(view.frame == Some(i), label).clicked() {
+                                view.frame = Some(i);
+                            }
+                        }
+                    });
+
+                let Some(frame) = view.frame.and_then(|i| track.frames.get(i)) else {
+                    return;
+                };
+                ui.separator();
+                ui.label(
+                    RichText::new(format!(
+                        "Sample {}: dts {}, cts {}",
+                        frame.sample, frame.dts, frame.cts
+                    ))
+                    .size(16.0),
+                );
+                if let Some(frame_num) = frame.frame_num {
+                    ui.label(format!("frame_num {}", frame_num));
+                }
+                for problem in &frame.problems {
+                    ui.colored_label(Color32::RED, problem);
+                }
+                egui::Grid::new("nal_grid").striped(true).show(ui, |ui| {
+                    for label in ["Offset", "Type", "Name", "Ref/TId", "Size", "Slice"] {
+                        ui.label(RichText::new(label).strong());
+                    }
+                    ui.end_row();
+                    for unit in &frame.nal_units {
+                        ui.label(unit.offset.to_string());
+                        ui.label(unit.nal_type.to_string());
+                        ui.label(unit.name);
+                        ui.label(
+                            unit.ref_idc
+                                .or(unit.temporal_id)
+                                .map_or(String::new(), |v| v.to_string()),
+                        );
+                        ui.label(unit.size.to_string());
+                        ui.label(unit.slice_type.map_or(String::new(), |t| t.to_string()));
+                        ui.end_row();
+                    }
+                });
+            });
+        if !open {
+            self.nal_view = None;
+        }
+    }
+
+    // 1 秒窗口码率曲线, 虚线为容器声明的最大/平均码率
+    fn show_bitrate_view(&mut self, ctx: &egui::Context) {
+        let Some(view) = &mut self.bitrate_view else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Bitrate")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(720.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (i, track) in view.report.tracks.iter().enumerate() {
+                        let label = format!("Track {} ({})", track.track_id, track.codec);
+                        if ui.selectable_label(view.track == i, label).clicked() {
+                            view.track = i;
+                        }
+                    }
+                });
+                let Some(track) = view.report.tracks.get(view.track) else {
+                    return;
+                };
+                let mut summary = format!("average {:.1} kbit/s", track.average / 1000.0);
+                for peak in &track.peaks {
+                    summary.push_str(&format!(
+                        ", {} s peak {:.1} kbit/s at {:.3} s",
+                        peak.window,
+                        peak.bitrate / 1000.0,
+                        peak.at
+                    ));
+                }
+                ui.label(RichText::new(summary).color(Color32::GRAY));
+                for declared in &track.declared {
+                    let rate = |value: Option<u64>| {
+                        value.map_or("-".to_string(), |v| format!("{:.1}", v as f64 / 1000.0))
+                    };
+                    ui.label(
+                        RichText::new(format!(
+                            "{}: max {} kbit/s, average {} kbit/s",
+                            declared.source,
+                            rate(declared.max),
+                            rate(declared.average)
+                        ))
+                        .color(Color32::GRAY),
+                    );
+                }
+                for warning in &track.warnings {
+                    ui.colored_label(Color32::YELLOW, warning);
+                }
+                ui.separator();
+
+                let (response, painter) = ui.allocate_painter(
+                    egui::vec2(ui.available_width(), 280.0),
+                    egui::Sense::hover(),
+                );
+                let rect = response.rect;
+                painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+                let (Some(first), Some(last)) = (track.series.first(), track.series.last()) else {
+                    return;
+                };
+                let declared_max = track
+                    .declared
+                    .iter()
+                    .flat_map(|d| [d.max, d.average])
+                    .flatten()
+                    .fold(0.0, |m: f64, v| m.max(v as f64));
+                let top = track
+                    .series
+                    .iter()
+                    .map(|p| p.windowed)
+                    .fold(declared_max, f64::max)
+                    .max(1.0)
+                    * 1.1;
+                let span = (last.time - first.time).max(f64::EPSILON);
+                let x =
+                    |time: f64| rect.left() + ((time - first.time) / span) as f32 * rect.width();
+                let y = |bitrate: f64| rect.bottom() - (bitrate / top) as f32 * rect.height();
+
+                let points: Vec<egui::Pos2> = track
+                    .series
+                    .iter()
+                    .map(|p| egui::pos2(x(p.time), y(p.windowed)))
+                    .collect();
+                painter.add(egui::Shape::line(
+                    points,
+                    egui::Stroke::new(1.5, Color32::LIGHT_BLUE),
+                ));
+                for declared in &track.declared {
+                    for (value, color) in [
+                        (declared.max, Color32::RED),
+                        (declared.average, Color32::YELLOW),
+                    ] {
+                        let Some(value) = value.filter(|&v| v > 0) else {
+                            continue;
+                        };
+                        let at = y(value as f64);
+                        painter.extend(egui::Shape::dashed_line(
+                            &[egui::pos2(rect.left(), at), egui::pos2(rect.right(), at)],
+                            egui::Stroke::new(1.0, color),
+                            6.0,
+                            4.0,
+                        ));
+                    }
+                }
+                painter.text(
+                    rect.left_top() + egui::vec2(4.0, 4.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("{:.0} kbit/s", top / 1000.0),
+                    egui::FontId::monospace(12.0),
+                    Color32::GRAY,
+                );
+                painter.text(
+                    rect.right_bottom() - egui::vec2(4.0, 4.0),
+                    egui::Align2::RIGHT_BOTTOM,
+                    format!("{:.1} s", last.time),
+                    egui::FontId::monospace(12.0),
+                    Color32::GRAY,
+                );
+
+                if let Some(pointer) = response.hover_pos() {
+                    let time =
+                        first.time + ((pointer.x - rect.left()) / rect.width()) as f64 * span;
+                    let i = track.series.partition_point(|p| p.time < time);
+                    if let Some(point) = track.series.get(i.min(track.series.len() - 1)) {
+                        painter.line_segment(
+                            [
+                                egui::pos2(x(point.time), rect.top()),
+                                egui::pos2(x(point.time), rect.bottom()),
+                            ],
+                            egui::Stroke::new(1.0, Color32::GRAY),
+                        );
+                        painter.text(
+                            egui::pos2(x(point.time) + 4.0, rect.top() + 20.0),
+                            egui::Align2::LEFT_TOP,
+                            format!("{:.3} s\n{:.1} kbit/s", point.time, point.windowed / 1000.0),
+                            egui::FontId::monospace(12.0),
+                            Color32::WHITE,
+                        );
+                    }
+                }
+            });
+        if !open {
+            self.bitrate_view = None;
+        }
+    }
+
+    // 样本大小直方图按 I/P/B 堆叠, 下方为样本时长分布
+    fn show_stats_view(&mut self, ctx: &egui::Context) {
+        let Some(view) = &mut self.stats_view else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Frame Statistics")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(720.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (i, track) in view.report.tracks.iter().enumerate() {
+                        let label = format!("Track {} ({})", track.track_id, track.codec);
+                        if ui.selectable_label(view.track == i, label).clicked() {
+                            view.track = i;
+                        }
+                    }
+                });
+                let Some(track) = view.report.tracks.get(view.track) else {
+                    return;
+                };
+                egui::Grid::new("stats_grid").striped(true).show(ui, |ui| {
+                    for label in ["Type", "Count", "Min", "Max", "Mean", "P50", "P90", "P99"] {
+                        ui.label(RichText::new(label).strong());
+                    }
+                    ui.end_row();
+                    let rows = std::iter::once(("all".to_string(), &track.sizes)).chain(
+                        track
+                            .frame_types
+                            .iter()
+                            .map(|t| (t.frame_type.to_string(), &t.sizes)),
+                    );
+                    for (label, sizes) in rows {
+                        ui.label(label);
+                        ui.label(sizes.count.to_string());
+                        ui.label(sizes.min.to_string());
+                        ui.label(sizes.max.to_string());
+                        ui.label(format!("{:.1}", sizes.mean));
+                        ui.label(sizes.p50.to_string());
+                        ui.label(sizes.p90.to_string());
+                        ui.label(sizes.p99.to_string());
+                        ui.end_row();
+                    }
+                });
+                if let Some(frame_rate) = track.frame_rate {
+                    let text = format!("{:.3} fps", frame_rate);
+                    if track.variable_frame_rate {
+                        ui.colored_label(Color32::YELLOW, format!("{} average, variable", text));
+                    } else {
+                        ui.label(format!("{}, constant", text));
+                    }
+                }
+                for warning in &track.warnings {
+                    ui.colored_label(Color32::YELLOW, warning);
+                }
+                ui.separator();
+
+                // 每个直方图柱: 未识别类型的样本在底部, I/P/B 依次叠在上面
+                let type_color = |frame_type: char| match frame_type {
+                    'I' => Color32::LIGHT_RED,
+                    'P' => Color32::LIGHT_GREEN,
+                    _ => Color32::LIGHT_BLUE,
+                };
+                let bars: Vec<Vec<(u32, Color32)>> = track
+                    .size_histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(i, bin)| {
+                        let typed: Vec<(u32, Color32)> = track
+                            .frame_types
+                            .iter()
+                            .map(|t| (t.histogram[i], type_color(t.frame_type)))
+                            .collect();
+                        let rest = bin
+                            .count
+                            .saturating_sub(typed.iter().map(|(n, _)| n).sum::<u32>());
+                        std::iter::once((rest, Color32::GRAY))
+                            .chain(typed)
+                            .collect()
+                    })
+                    .collect();
+                ui.label("Sample size (bytes)");
+                let (min, max) = (track.sizes.min, track.sizes.max);
+                draw_histogram(ui, &bars, &min.to_string(), &max.to_string());
+                ui.horizontal(|ui| {
+                    for t in &track.frame_types {
+                        ui.colored_label(type_color(t.frame_type), format!("■ {}", t.frame_type));
+                    }
+                });
+
+                ui.label(format!(
+                    "Sample duration (1/{} s), {} stts runs",
+                    track.timescale, track.durations.runs
+                ));
+                let mut distinct = track.durations.distinct.clone();
+                distinct.sort_by_key(|d| d.duration);
+                let bars: Vec<Vec<(u32, Color32)>> = distinct
+                    .iter()
+                    .map(|d| vec![(d.count, Color32::LIGHT_BLUE)])
+                    .collect();
+                draw_histogram(
+                    ui,
+                    &bars,
+                    &track.durations.min.to_string(),
+                    &track.durations.max.to_string(),
+                );
+            });
+        if !open {
+            self.stats_view = None;
+        }
+    }
+
     // 添加辅助方法用于文件分析
     fn analyze_file(&mut self, path: &str) {
         let tx = self.tx.clone();
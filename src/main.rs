@@ -3,8 +3,14 @@ mod console;
 mod egui_gui;
 mod error;
 mod reader;
+mod writer;
 
-use clap::{Arg, Command};
+use analyzer::extract::StreamFormat;
+use analyzer::isobmff::tags::TagEdit;
+use analyzer::isobmff::validator::Severity;
+use analyzer::privacy;
+use analyzer::AnalysisOptions;
+use clap::{Arg, ArgAction, Command};
 
 #[derive(Debug)]
 enum OutputMode {
@@ -44,6 +50,219 @@ fn main() {
                 .default_value("auto")
                 .help("File format detection strategy"),
         )
+        .arg(
+            Arg::new("samples")
+                .long("samples")
+                .action(ArgAction::SetTrue)
+                .help("List every sample of each track (console/json)"),
+        )
+        .arg(
+            Arg::new("nal-units")
+                .long("nal-units")
+                .action(ArgAction::SetTrue)
+                .help("List the NAL units, slice types and POC of every H.264/H.265 sample (console/json)"),
+        )
+        .arg(
+            Arg::new("gop")
+                .long("gop")
+                .action(ArgAction::SetTrue)
+                .help("Report GOP lengths, open/closed GOPs, keyframe interval and B-frame structure (console/json)"),
+        )
+        .arg(
+            Arg::new("sei")
+                .long("sei")
+                .action(ArgAction::SetTrue)
+                .help("Decode the SEI messages of every H.264/H.265 sample: HDR metadata, timecodes, user data (console/json)"),
+        )
+        .arg(
+            Arg::new("aac-frames")
+                .long("aac-frames")
+                .action(ArgAction::SetTrue)
+                .help("Parse the raw_data_block of every AAC frame: elements, global gain, window sequence, SBR; check stts durations (console/json)"),
+        )
+        .arg(
+            Arg::new("captions")
+                .long("captions")
+                .action(ArgAction::SetTrue)
+                .help("Decode CEA-608/708 closed captions from A/53 SEI or c608 tracks (console/json); with --write, export as SRT or, for a .scc OUTPUT, SCC"),
+        )
+        .arg(
+            Arg::new("caption-channel")
+                .long("caption-channel")
+                .value_name("CHANNEL")
+                .help("Caption channel to export with --captions, e.g. CC1 or SERVICE1 (default: the first one with text)"),
+        )
+        .arg(
+            Arg::new("frame-stats")
+                .long("frame-stats")
+                .action(ArgAction::SetTrue)
+                .help("Sample size percentiles, I/P/B frame sizes, duration regularity and VFR detection per track (console/json)"),
+        )
+        .arg(
+            Arg::new("bitrate")
+                .long("bitrate")
+                .action(ArgAction::SetTrue)
+                .help("Instantaneous, windowed and peak bitrate per track against btrt/esds/MDPR (console/json); with --write, the series as CSV"),
+        )
+        .arg(
+            Arg::new("bitrate-window")
+                .long("bitrate-window")
+                .value_name("SECONDS")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(f64))
+                .help("Window for --bitrate peaks, repeatable; the first one is used for the series (default: 1)"),
+        )
+        .arg(
+            Arg::new("lint")
+                .long("lint")
+                .action(ArgAction::SetTrue)
+                .help("Validate the box structure and exit non-zero on problems (console/json)"),
+        )
+        .arg(
+            Arg::new("fail-on")
+                .long("fail-on")
+                .value_parser(["error", "warning", "info"])
+                .default_value("error")
+                .help("Lowest lint severity that makes the exit code non-zero"),
+        )
+        .arg(
+            Arg::new("privacy")
+                .long("privacy")
+                .action(ArgAction::SetTrue)
+                .help("Report personally identifying metadata (console/json)"),
+        )
+        .arg(
+            Arg::new("privacy-fail-on")
+                .long("privacy-fail-on")
+                .value_parser(["high", "medium", "low"])
+                .default_value("high")
+                .help("Lowest privacy finding severity that makes the exit code non-zero"),
+        )
+        .arg(
+            Arg::new("faststart")
+                .long("faststart")
+                .value_name("OUTPUT")
+                .help("Write a copy of the MP4 with moov moved before mdat"),
+        )
+        .arg(
+            Arg::new("repair")
+                .long("repair")
+                .value_name("OUTPUT")
+                .help("Write a copy with wrong top-level box sizes, trailing garbage and shifted chunk offsets fixed"),
+        )
+        .arg(
+            Arg::new("fragment")
+                .long("fragment")
+                .value_name("OUTPUT")
+                .help("Remux the MP4 into moof/mdat fragments cut at keyframes, with a sidx"),
+        )
+        .arg(
+            Arg::new("fragment-duration")
+                .long("fragment-duration")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("2")
+                .help("Target fragment duration for --fragment"),
+        )
+        .arg(
+            Arg::new("segments")
+                .long("segments")
+                .action(ArgAction::SetTrue)
+                .help("With --fragment, write init.mp4 and one segment file per fragment into the OUTPUT directory"),
+        )
+        .arg(
+            Arg::new("defragment")
+                .long("defragment")
+                .value_name("OUTPUT")
+                .help("Flatten a fragmented MP4 into a regular moov + mdat file"),
+        )
+        .arg(
+            Arg::new("rewrite")
+                .long("rewrite")
+                .value_name("OUTPUT")
+                .help("Write the parsed box tree back out and compare it with the input"),
+        )
+        .arg(
+            Arg::new("tags")
+                .long("tags")
+                .action(ArgAction::SetTrue)
+                .help("List the iTunes-style tags of the movie (console/json)"),
+        )
+        .arg(
+            Arg::new("set-tag")
+                .long("set-tag")
+                .value_name("KEY=VALUE")
+                .action(ArgAction::Append)
+                .help(
+                    "Set a tag (title, artist, album, comment, date, genre, a 4CC or a custom key)",
+                ),
+        )
+        .arg(
+            Arg::new("delete-tag")
+                .long("delete-tag")
+                .value_name("KEY")
+                .action(ArgAction::Append)
+                .help("Delete a tag"),
+        )
+        .arg(
+            Arg::new("cover")
+                .long("cover")
+                .value_name("IMAGE")
+                .help("Set the cover art from a JPEG or PNG file"),
+        )
+        .arg(
+            Arg::new("strip-metadata")
+                .long("strip-metadata")
+                .action(ArgAction::SetTrue)
+                .help("Remove all user metadata (udta, meta, XMP) before applying other tag edits"),
+        )
+        .arg(
+            Arg::new("trim")
+                .long("trim")
+                .num_args(2)
+                .value_names(["START", "END"])
+                .help("Cut the MP4 to START..END (seconds or [hh:]mm:ss.fff) without re-encoding"),
+        )
+        .arg(
+            Arg::new("extract")
+                .long("extract")
+                .value_name("TRACK")
+                .value_parser(clap::value_parser!(u32))
+                .help("Write a track (RealMedia: stream number) as a raw elementary stream"),
+        )
+        .arg(
+            Arg::new("extract-format")
+                .long("extract-format")
+                .value_name("FORMAT")
+                .value_parser(["ivf", "obu"])
+                .default_value("ivf")
+                .help("Container for AV1 tracks with --extract"),
+        )
+        .arg(
+            Arg::new("recover")
+                .long("recover")
+                .value_name("REFERENCE")
+                .help("Rebuild a moov-less MP4 using the codec setup of a healthy REFERENCE file from the same device"),
+        )
+        .arg(
+            Arg::new("mux")
+                .long("mux")
+                .value_name("STREAM")
+                .num_args(1..)
+                .help("Build an MP4 from elementary streams (Annex B H.264/H.265, ADTS AAC, AC-3, Ogg Opus)"),
+        )
+        .arg(
+            Arg::new("frame-rate")
+                .long("frame-rate")
+                .value_name("FPS")
+                .value_parser(clap::value_parser!(f64))
+                .help("Frame rate of video streams with --mux (default: from the SPS, else 25)"),
+        )
+        .arg(
+            Arg::new("write").long("write").value_name("OUTPUT").help(
+                "Output file for --trim, --extract, --mux, --recover, --set-tag, --delete-tag, --cover, --strip-metadata, --captions and --bitrate",
+            ),
+        )
         .arg(
             Arg::new("FILE")
                 .help("Input file to analyze")
@@ -60,6 +279,274 @@ fn main() {
 
     let strategy = matches.get_one::<String>("strategy").unwrap();
     let file_path = matches.get_one::<String>("FILE");
+    let options = AnalysisOptions {
+        list_samples: matches.get_flag("samples"),
+    };
+
+    if matches.get_flag("lint") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --lint requires a FILE");
+            std::process::exit(1);
+        };
+        let fail_on = match matches.get_one::<String>("fail-on").map(|s| s.as_str()) {
+            Some("warning") => Severity::Warning,
+            Some("info") => Severity::Info,
+            _ => Severity::Error,
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_lint(path, strategy, json, fail_on));
+    }
+
+    if matches.get_flag("nal-units") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --nal-units requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_nal_units(path, json));
+    }
+
+    if matches.get_flag("gop") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --gop requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_gop(path, json));
+    }
+
+    if matches.get_flag("sei") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --sei requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_sei(path, json));
+    }
+
+    if matches.get_flag("aac-frames") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --aac-frames requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_aac_frames(path, json));
+    }
+
+    if matches.get_flag("captions") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --captions requires a FILE");
+            std::process::exit(1);
+        };
+        let channel = matches
+            .get_one::<String>("caption-channel")
+            .map(|s| s.as_str());
+        let output = matches.get_one::<String>("write").map(|s| s.as_str());
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_captions(path, channel, output, json));
+    }
+
+    if matches.get_flag("frame-stats") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --frame-stats requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_frame_stats(path, json));
+    }
+
+    if matches.get_flag("bitrate") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --bitrate requires a FILE");
+            std::process::exit(1);
+        };
+        let mut windows: Vec<f64> = matches
+            .get_many::<f64>("bitrate-window")
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        if windows.iter().any(|&w| w <= 0.0) {
+            eprintln!("Error: --bitrate-window must be positive");
+            std::process::exit(1);
+        }
+        if windows.is_empty() {
+            windows.push(1.0);
+        }
+        let output = matches.get_one::<String>("write").map(|s| s.as_str());
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_bitrate(path, strategy, &windows, output, json));
+    }
+
+    if matches.get_flag("privacy") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --privacy requires a FILE");
+            std::process::exit(1);
+        };
+        let fail_on = match matches
+            .get_one::<String>("privacy-fail-on")
+            .map(|s| s.as_str())
+        {
+            Some("low") => privacy::Severity::Low,
+            Some("medium") => privacy::Severity::Medium,
+            _ => privacy::Severity::High,
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_privacy(path, strategy, json, fail_on));
+    }
+
+    if let Some(output) = matches.get_one::<String>("faststart") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --faststart requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_faststart(path, output, json));
+    }
+
+    if let Some(output) = matches.get_one::<String>("repair") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --repair requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_repair(path, output, json));
+    }
+
+    if let Some(output) = matches.get_one::<String>("fragment") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --fragment requires a FILE");
+            std::process::exit(1);
+        };
+        let duration = *matches.get_one::<f64>("fragment-duration").unwrap();
+        let segments = matches.get_flag("segments");
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_fragment(
+            path, output, duration, segments, json,
+        ));
+    }
+
+    if let Some(output) = matches.get_one::<String>("defragment") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --defragment requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_defragment(path, output, json));
+    }
+
+    if let Some(output) = matches.get_one::<String>("rewrite") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --rewrite requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_rewrite(path, output, json));
+    }
+
+    if let Some(range) = matches.get_many::<String>("trim") {
+        let range: Vec<&String> = range.collect();
+        let (Some(path), Some(output)) = (file_path, matches.get_one::<String>("write")) else {
+            eprintln!("Error: --trim requires a FILE and --write OUTPUT");
+            std::process::exit(1);
+        };
+        let (Some(start), Some(end)) = (parse_time(range[0]), parse_time(range[1])) else {
+            eprintln!("Error: invalid --trim range {} {}", range[0], range[1]);
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_trim(path, output, start, end, json));
+    }
+
+    if let Some(&track) = matches.get_one::<u32>("extract") {
+        let (Some(path), Some(output)) = (file_path, matches.get_one::<String>("write")) else {
+            eprintln!("Error: --extract requires a FILE and --write OUTPUT");
+            std::process::exit(1);
+        };
+        let av1_format = match matches
+            .get_one::<String>("extract-format")
+            .map(|s| s.as_str())
+        {
+            Some("obu") => StreamFormat::Obu,
+            _ => StreamFormat::Ivf,
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_extract(
+            path, strategy, track, output, av1_format, json,
+        ));
+    }
+
+    if let Some(reference) = matches.get_one::<String>("recover") {
+        let (Some(path), Some(output)) = (file_path, matches.get_one::<String>("write")) else {
+            eprintln!("Error: --recover requires a FILE and --write OUTPUT");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_recover(path, reference, output, json));
+    }
+
+    if let Some(inputs) = matches.get_many::<String>("mux") {
+        let inputs: Vec<&str> = inputs.map(|s| s.as_str()).collect();
+        let Some(output) = matches.get_one::<String>("write") else {
+            eprintln!("Error: --mux requires --write OUTPUT");
+            std::process::exit(1);
+        };
+        let frame_rate = matches.get_one::<f64>("frame-rate").copied();
+        if frame_rate.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+            eprintln!("Error: --frame-rate must be positive");
+            std::process::exit(1);
+        }
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_mux(&inputs, output, frame_rate, json));
+    }
+
+    let mut edits = Vec::new();
+    if matches.get_flag("strip-metadata") {
+        edits.push(TagEdit::StripAll);
+    }
+    for key in matches
+        .get_many::<String>("delete-tag")
+        .into_iter()
+        .flatten()
+    {
+        edits.push(TagEdit::Delete(key.clone()));
+    }
+    for pair in matches.get_many::<String>("set-tag").into_iter().flatten() {
+        let Some((key, value)) = pair.split_once('=') else {
+            eprintln!("Error: --set-tag expects KEY=VALUE, got {}", pair);
+            std::process::exit(1);
+        };
+        edits.push(TagEdit::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    if let Some(image) = matches.get_one::<String>("cover") {
+        match std::fs::read(image) {
+            Ok(data) => edits.push(TagEdit::Cover(data)),
+            Err(e) => {
+                eprintln!("Error: cannot read {}: {}", image, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if !edits.is_empty() {
+        let (Some(path), Some(output)) = (file_path, matches.get_one::<String>("write")) else {
+            eprintln!("Error: tag edits require a FILE and --write OUTPUT");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_edit_tags(path, output, &edits, json));
+    }
+
+    if matches.get_flag("tags") {
+        let Some(path) = file_path else {
+            eprintln!("Error: --tags requires a FILE");
+            std::process::exit(1);
+        };
+        let json = matches!(output_mode, OutputMode::Json);
+        std::process::exit(console::run_tags(path, json));
+    }
 
     match (output_mode, file_path) {
         // 无文件参数，启动 GUI
@@ -72,11 +559,24 @@ fn main() {
         }
         // 控制台模式且有文件
         (OutputMode::Console, Some(path)) => {
-            std::process::exit(console::run_console_with_file(path, strategy));
+            std::process::exit(console::run_console_with_file(path, strategy, &options));
         }
         // JSON 模式且有文件
         (OutputMode::Json, Some(path)) => {
-            std::process::exit(console::run_console_with_json(path, strategy));
+            std::process::exit(console::run_console_with_json(path, strategy, &options));
+        }
+    }
+}
+
+/// Parses seconds ("12.5") or a clock time ("1:02.5", "01:00:02.500")
+fn parse_time(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let part: f64 = part.parse().ok()?;
+        if !part.is_finite() || part < 0.0 {
+            return None;
         }
+        seconds = seconds * 60.0 + part;
     }
+    Some(seconds)
 }
@@ -80,7 +80,6 @@ impl Reader {
         Ok(i16::from_be_bytes(buf))
     }
 
-    #[allow(dead_code)]
     pub fn get_file_size(&mut self) -> io::Result<u64> {
         let current_pos = self.position();
         let size = self.file.seek(SeekFrom::End(0))?;
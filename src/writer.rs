@@ -0,0 +1,85 @@
+/// Big-endian byte buffer, the counterpart of `Reader` for serializing boxes
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    pub fn write_u24(&mut self, value: u32) {
+        self.write_bytes(&value.to_be_bytes()[1..]);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    pub fn write_i16(&mut self, value: i16) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    /// Writes exactly `len` bytes of `value`, padded with NULs or truncated
+    pub fn write_fixed_string(&mut self, value: &str, len: usize) {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(len, 0);
+        self.write_bytes(&bytes);
+    }
+
+    /// Writes version (8 bits) and flags (24 bits) of a full box
+    pub fn write_version_flags(&mut self, version: u8, flags: u32) {
+        self.write_u8(version);
+        self.write_u24(flags);
+    }
+
+    pub fn position(&self) -> u64 {
+        self.buf.len() as u64
+    }
+
+    /// Overwrites 4 bytes at an earlier position, used to patch box sizes
+    pub fn patch_u32(&mut self, position: u64, value: u32) {
+        let at = position as usize;
+        self.buf[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn patch_bytes(&mut self, position: u64, bytes: &[u8]) {
+        let at = position as usize;
+        self.buf[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Inserts bytes at an earlier position, shifting everything after it
+    pub fn insert(&mut self, position: u64, bytes: &[u8]) {
+        let at = position as usize;
+        self.buf.splice(at..at, bytes.iter().copied());
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}